@@ -1044,6 +1044,48 @@ impl PartialEq<InnerBlock> for Block {
     }
 }
 
+/// A text field inside of a block that can be searched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SearchField {
+    /// The main content of Text and Uncertain blocks
+    Content,
+    /// The surface form of an Abbreviation
+    Surface,
+    /// The expanded form of an Abbreviation
+    Expansion,
+    /// The content of the n-th version of a Correction
+    Version(usize),
+}
+impl SearchField {
+    /// The DOM id of the input element showing this field for the block with `id`
+    pub(super) fn element_id(&self, id: usize) -> String {
+        match self {
+            // the expansion of an abbreviation is the primary input of that block
+            Self::Content | Self::Expansion => format!("block-input-{id}"),
+            Self::Surface => format!("block-input-{id}-surface"),
+            Self::Version(n) => format!("block-input-{id}-v-{n}"),
+        }
+    }
+}
+
+/// Set `field` in `block` to `new_value`
+///
+/// Fields that do not exist in this block type are ignored.
+pub(super) fn set_block_field(block: &mut Block, field: SearchField, new_value: String) {
+    match (block, field) {
+        (Block::Text(x), SearchField::Content) => x.content = new_value,
+        (Block::Uncertain(x), SearchField::Content) => x.content = new_value,
+        (Block::Abbreviation(x), SearchField::Surface) => x.surface = new_value,
+        (Block::Abbreviation(x), SearchField::Expansion) => x.expansion = new_value,
+        (Block::Correction(x), SearchField::Version(idx)) => {
+            if let Some(version) = x.versions.get_mut(idx) {
+                version.content = new_value;
+            }
+        }
+        _ => {}
+    };
+}
+
 /// Block type with data
 ///
 /// This is the same as [`critic_format::streamed::Block`], but all data is wrapped in an [`RwSignal`]
@@ -1182,6 +1224,31 @@ impl InnerBlock {
         }
     }
 
+    /// All text fields in this block that find/replace should look at, in display order
+    pub(super) fn searchable_fields(&self) -> Vec<(SearchField, String)> {
+        match self {
+            InnerBlock::Text(x) => vec![(SearchField::Content, x.read_untracked().content.clone())],
+            InnerBlock::Uncertain(x) => {
+                vec![(SearchField::Content, x.read_untracked().content.clone())]
+            }
+            InnerBlock::Abbreviation(x) => vec![
+                (SearchField::Surface, x.read_untracked().surface.clone()),
+                (SearchField::Expansion, x.read_untracked().expansion.clone()),
+            ],
+            InnerBlock::Correction(x) => x
+                .read_untracked()
+                .versions
+                .iter()
+                .enumerate()
+                .map(|(idx, v)| (SearchField::Version(idx), v.content.clone()))
+                .collect(),
+            InnerBlock::Break(_)
+            | InnerBlock::Lacuna(_)
+            | InnerBlock::Space(_)
+            | InnerBlock::Anchor(_) => vec![],
+        }
+    }
+
     /// The primary language for this block if applicable
     fn lang(&self) -> Option<String> {
         match self {
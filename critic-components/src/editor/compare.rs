@@ -0,0 +1,364 @@
+//! Compare the draft side by side with another user's published transcription of the same page
+//!
+//! The blocks of both transcriptions are aligned with a longest common subsequence. Blocks
+//! without a partner are paired with the next unmatched block of the same type in the other
+//! transcription, and the text of such pairs is compared character by character. Every block of
+//! the other transcription can be copied into the draft as a single undoable step.
+
+use critic_format::streamed::Block;
+use critic_shared::diff::{block_text, block_type_name, lcs_matches};
+use leptos::prelude::*;
+
+use super::blocks::{EditorBlock, InnerBlock};
+use super::undo::{UnReStack, UnReStep};
+
+/// The users who published a transcription of this page
+#[server]
+pub(super) async fn get_published_usernames(
+    msname: String,
+    pagename: String,
+) -> Result<Vec<String>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    let page = critic_server::db::get_page(&config.db, &msname, &pagename)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(critic_server::db::get_transcriptions(&config.db, page.id)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?
+        .into_iter()
+        .filter(|t| t.published)
+        .map(|t| t.username)
+        .collect())
+}
+
+/// The blocks of the published transcription of this page by `username`
+#[server]
+pub(super) async fn get_published_transcription(
+    msname: String,
+    pagename: String,
+    username: String,
+) -> Result<Vec<Block>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    let page = critic_server::db::get_page(&config.db, &msname, &pagename)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    let published = critic_server::db::get_transcriptions(&config.db, page.id)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?
+        .into_iter()
+        .any(|t| t.published && t.username == username);
+    if !published {
+        return Err(ServerFnError::new(
+            "This user has not published a transcription of this page.",
+        ));
+    };
+    critic_server::tei::read_transcription(&config.data_directory, &msname, &pagename, &username)
+        .map(|ms| ms.content)
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// One row of the side-by-side comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Row {
+    /// the blocks at these indices are equal
+    Same(usize, usize),
+    /// blocks that differ, with the block of the draft first
+    ///
+    /// At least one of them is present.
+    Different(Option<usize>, Option<usize>),
+}
+
+/// Align the blocks of the draft with the blocks of the other transcription
+fn align(own: &[InnerBlock], other: &[Block]) -> Vec<Row> {
+    let (n, m) = (own.len(), other.len());
+    let matches = lcs_matches(own, other, |own, other| own == other);
+
+    let mut res = vec![];
+    let (mut last_own, mut last_other) = (0, 0);
+    for (match_own, match_other) in matches.into_iter().chain(std::iter::once((n, m))) {
+        let mut only_other = (last_other..match_other).collect::<Vec<_>>();
+        for own_idx in last_own..match_own {
+            let own_type = block_type_name(&own[own_idx].clone().into());
+            // pair the block with the first unmatched block of the same type
+            match only_other
+                .iter()
+                .position(|other_idx| block_type_name(&other[*other_idx]) == own_type)
+            {
+                Some(pos) => {
+                    // blocks of the other transcription skipped by this pairing come first
+                    for other_idx in only_other.drain(..pos) {
+                        res.push(Row::Different(None, Some(other_idx)));
+                    }
+                    res.push(Row::Different(Some(own_idx), Some(only_other.remove(0))));
+                }
+                None => res.push(Row::Different(Some(own_idx), None)),
+            };
+        }
+        for other_idx in only_other {
+            res.push(Row::Different(None, Some(other_idx)));
+        }
+        if match_own < n {
+            res.push(Row::Same(match_own, match_other));
+        };
+        (last_own, last_other) = (match_own + 1, match_other + 1);
+    }
+    res
+}
+
+/// Split two texts into pieces, marking the pieces that are not part of their longest common
+/// subsequence of characters
+fn char_diff(a: &str, b: &str) -> (Vec<(String, bool)>, Vec<(String, bool)>) {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut changed_a = vec![true; a.len()];
+    let mut changed_b = vec![true; b.len()];
+    for (i, j) in lcs_matches(&a, &b, |a, b| a == b) {
+        changed_a[i] = false;
+        changed_b[j] = false;
+    }
+    // merge neighbouring characters with the same marking
+    let pieces = |chars: &[char], changed: &[bool]| {
+        let mut res: Vec<(String, bool)> = vec![];
+        for (c, changed) in chars.iter().zip(changed) {
+            match res.last_mut() {
+                Some((text, last)) if last == changed => text.push(*c),
+                _ => res.push((c.to_string(), *changed)),
+            }
+        }
+        res
+    };
+    (pieces(&a, &changed_a), pieces(&b, &changed_b))
+}
+
+/// The text of a block with the differences to its partner highlighted
+fn highlighted(pieces: Vec<(String, bool)>) -> impl IntoView {
+    pieces
+        .into_iter()
+        .map(|(text, changed)| {
+            let class = if changed {
+                "bg-amber-600 text-white"
+            } else {
+                ""
+            };
+            view! { <span class=class>{text}</span> }
+        })
+        .collect_view()
+}
+
+/// A cell of the comparison showing one block
+#[component]
+fn BlockCell(
+    block: Option<Block>,
+    /// the differences to the block in the other column, if the blocks were paired
+    pieces: Option<Vec<(String, bool)>>,
+    /// are the blocks in this row equal?
+    same: bool,
+) -> impl IntoView {
+    let Some(block) = block else {
+        return view! { <td class="p-1 bg-slate-700"></td> }.into_any();
+    };
+    let class = if same {
+        "p-1"
+    } else if pieces.is_some() {
+        "p-1 bg-amber-900"
+    } else {
+        "p-1 bg-red-900"
+    };
+    let type_name = block_type_name(&block);
+    let text = match pieces {
+        Some(pieces) => highlighted(pieces).into_any(),
+        None => block_text(&block).into_any(),
+    };
+    view! {
+        <td class=class dir="auto">
+            <span class="mr-2 font-light text-xs">{type_name}</span>
+            {text}
+        </td>
+    }
+    .into_any()
+}
+
+/// Make the current closure react to changes inside this block
+fn track_inner(inner: &InnerBlock) {
+    match inner {
+        InnerBlock::Break(x) => x.track(),
+        InnerBlock::Lacuna(x) => x.track(),
+        InnerBlock::Anchor(x) => x.track(),
+        InnerBlock::Text(x) => x.track(),
+        InnerBlock::Correction(x) => x.track(),
+        InnerBlock::Uncertain(x) => x.track(),
+        InnerBlock::Abbreviation(x) => x.track(),
+        InnerBlock::Space(x) => x.track(),
+    }
+}
+
+/// Lets the user compare the draft with a published transcription of another user and copy
+/// blocks from it
+#[component]
+pub(super) fn ComparePanel(
+    msname: String,
+    pagename: String,
+    blocks: ReadSignal<Vec<EditorBlock>>,
+    set_blocks: WriteSignal<Vec<EditorBlock>>,
+    next_id: RwSignal<usize>,
+    undo_stack: RwSignal<UnReStack>,
+) -> impl IntoView {
+    let open = RwSignal::new(false);
+    let usernames = OnceResource::new(get_published_usernames(msname.clone(), pagename.clone()));
+    let load_action = Action::new(move |username: &String| {
+        let (msname, pagename, username) = (msname.clone(), pagename.clone(), username.clone());
+        async move { get_published_transcription(msname, pagename, username).await }
+    });
+
+    // copy a block of the other transcription into the draft, replacing the block at `own_idx`
+    // or inserted at `insert_at`
+    let copy_block = move |other: Block, own_idx: Option<usize>, insert_at: usize| {
+        let id = next_id.get_untracked();
+        *next_id.write() += 1;
+        let new_block = EditorBlock {
+            id,
+            inner: other.into(),
+            focus_on_load: false,
+        };
+        match own_idx {
+            Some(idx) => {
+                let Some(old_block) = blocks.read_untracked().get(idx).cloned() else {
+                    return;
+                };
+                set_blocks.write()[idx] = new_block.clone();
+                undo_stack.write().push_undo(UnReStep::new_block_change(
+                    idx,
+                    vec![old_block],
+                    vec![new_block],
+                ));
+            }
+            None => {
+                set_blocks.write().insert(insert_at, new_block.clone());
+                undo_stack
+                    .write()
+                    .push_undo(UnReStep::new_insertion(insert_at, new_block));
+            }
+        };
+    };
+
+    let comparison = move || {
+        let Some(result) = load_action.value().get() else {
+            return None;
+        };
+        let other = match result {
+            Ok(other) => other,
+            Err(e) => {
+                return Some(view! { <p class="text-red-500">{e.to_string()}</p> }.into_any())
+            }
+        };
+        let own = blocks
+            .get()
+            .into_iter()
+            .map(|b| b.inner)
+            .collect::<Vec<_>>();
+        own.iter().for_each(track_inner);
+        let rows = align(&own, &other);
+        let own = own.into_iter().map(Block::from).collect::<Vec<_>>();
+        let same = rows.iter().filter(|r| matches!(r, Row::Same(..))).count();
+        // the number of draft blocks before each row, i.e. where blocks are inserted
+        let mut own_position = 0;
+        let rows = rows
+            .into_iter()
+            .map(|row| {
+                let insert_at = own_position;
+                let (own_idx, other_idx, is_same) = match row {
+                    Row::Same(a, b) => (Some(a), Some(b), true),
+                    Row::Different(a, b) => (a, b, false),
+                };
+                if own_idx.is_some() {
+                    own_position += 1;
+                };
+                let own_block = own_idx.map(|idx| own[idx].clone());
+                let other_block = other_idx.map(|idx| other[idx].clone());
+                let (own_pieces, other_pieces) = match (&own_block, &other_block) {
+                    (Some(a), Some(b)) if !is_same => {
+                        let (a, b) = char_diff(&block_text(a), &block_text(b));
+                        (Some(a), Some(b))
+                    }
+                    _ => (None, None),
+                };
+                let copy_button = other_block.clone().filter(|_| !is_same).map(|block| {
+                    let label = if own_idx.is_some() {
+                        "Replace"
+                    } else {
+                        "Insert"
+                    };
+                    view! {
+                        <button class="text-xs underline" on:click=move |_| {
+                            copy_block(block.clone(), own_idx, insert_at);
+                        }>{label}</button>
+                    }
+                });
+                view! {
+                    <tr class="border-b border-slate-600">
+                        <BlockCell block=own_block pieces=own_pieces same=is_same/>
+                        <td class="p-1">{copy_button}</td>
+                        <BlockCell block=other_block pieces=other_pieces same=is_same/>
+                    </tr>
+                }
+            })
+            .collect_view();
+        Some(
+            view! {
+                <p class="text-xs">
+                    {format!("{same} of {} blocks in the draft and {} blocks in the other transcription agree.", own.len(), other.len())}
+                </p>
+                <table class="w-full table-fixed text-sm">
+                    <thead>
+                        <tr>
+                            <th class="w-5/12">"Your draft"</th>
+                            <th class="w-2/12"></th>
+                            <th class="w-5/12">"Published transcription"</th>
+                        </tr>
+                    </thead>
+                    <tbody>{rows}</tbody>
+                </table>
+            }
+            .into_any(),
+        )
+    };
+
+    view! {
+        <div class="flex flex-col gap-2 p-2">
+            <div class="flex flex-row justify-start gap-2">
+                <button class="font-light text-xs" on:click=move |_| open.update(|o| *o = !*o)>
+                    {move || if open.get() { "Hide comparison" } else { "Compare with a published transcription" }}
+                </button>
+                <Show when=move || open.get()>
+                    <Suspense fallback=|| view! { <span class="text-xs">"Loading published transcriptions..."</span> }>
+                    {move || Suspend::new(async move {
+                        usernames.await.map(|usernames| view! {
+                            <select class="text-sm"
+                                on:change:target=move |ev| {
+                                    let username = ev.target().value();
+                                    if !username.is_empty() {
+                                        load_action.dispatch(username);
+                                    };
+                                }
+                            >
+                                <option value="">"(choose a user)"</option>
+                                {usernames.into_iter().map(|username| view! {
+                                    <option value=username.clone()>{username.clone()}</option>
+                                }).collect::<Vec<_>>()}
+                            </select>
+                        })
+                    })}
+                    </Suspense>
+                    <span class="text-xs">
+                        {move || load_action.pending().get().then_some("Loading transcription...")}
+                    </span>
+                </Show>
+            </div>
+            <Show when=move || open.get()>
+                {comparison}
+            </Show>
+        </div>
+    }
+}
@@ -0,0 +1,384 @@
+//! Find and replace across the content of all blocks in the editor
+//!
+//! Matching can optionally use regular expressions and can ignore combining marks (Hebrew points,
+//! accents, ...). In that case, both the pattern and the searched text are decomposed and stripped
+//! of all combining marks before matching, and the hits are mapped back onto the original text.
+
+use leptos::{logging::log, prelude::*};
+use regex::Regex;
+use unicode_normalization::char::{decompose_canonical, is_combining_mark};
+use web_sys::{wasm_bindgen::JsCast, HtmlTextAreaElement};
+
+use super::blocks::{set_block_field, EditorBlock, SearchField};
+use super::undo::{UnReStack, UnReStep};
+
+/// How a search pattern is interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(super) struct SearchOptions {
+    /// interpret the pattern as a regular expression
+    pub regex: bool,
+    /// ignore combining marks (points, accents, cantillation) in both pattern and text
+    pub ignore_marks: bool,
+}
+
+/// A single hit of the search pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct SearchHit {
+    /// logical id of the block containing this hit
+    pub block_id: usize,
+    /// the field inside the block containing this hit
+    pub field: SearchField,
+    /// start of the hit in UTF-16 code units, as used for selections in text inputs
+    pub start: usize,
+    /// end of the hit in UTF-16 code units
+    pub end: usize,
+}
+
+/// A haystack with combining marks (optionally) stripped
+///
+/// Keeps track of the original byte offset of each char in the stripped string.
+struct Stripped {
+    text: String,
+    /// (byte offset in `text`, byte offset in the original) for every char in `text`
+    offsets: Vec<(usize, usize)>,
+    /// length of the original in bytes
+    original_len: usize,
+}
+impl Stripped {
+    fn new(original: &str, ignore_marks: bool) -> Self {
+        let mut text = String::with_capacity(original.len());
+        let mut offsets = Vec::with_capacity(original.len());
+        for (orig_idx, c) in original.char_indices() {
+            if !ignore_marks {
+                offsets.push((text.len(), orig_idx));
+                text.push(c);
+                continue;
+            }
+            decompose_canonical(c, |d| {
+                if !is_combining_mark(d) {
+                    offsets.push((text.len(), orig_idx));
+                    text.push(d);
+                }
+            });
+        }
+        Self {
+            text,
+            offsets,
+            original_len: original.len(),
+        }
+    }
+
+    /// Map the byte offset where a match starts in the stripped text back to the original text
+    ///
+    /// An offset inside a char that decomposed into several base chars maps to the start of that
+    /// char.
+    fn original_start(&self, stripped_idx: usize) -> usize {
+        match self
+            .offsets
+            .binary_search_by_key(&stripped_idx, |(s, _)| *s)
+        {
+            Ok(pos) => self.offsets[pos].1,
+            Err(_) => self.original_len,
+        }
+    }
+
+    /// Map the byte offset where a match ends in the stripped text back to the original text
+    ///
+    /// An offset at the end of a stripped char maps to the start of the next base char in the
+    /// original, so trailing marks are kept with the char they belong to. An offset inside a char
+    /// that decomposed into several base chars maps to the end of that char.
+    fn original_end(&self, stripped_idx: usize) -> usize {
+        let Ok(pos) = self
+            .offsets
+            .binary_search_by_key(&stripped_idx, |(s, _)| *s)
+        else {
+            return self.original_len;
+        };
+        let orig_idx = self.offsets[pos].1;
+        if pos == 0 || self.offsets[pos - 1].1 != orig_idx {
+            return orig_idx;
+        };
+        self.offsets[pos..]
+            .iter()
+            .map(|(_, o)| *o)
+            .find(|o| *o != orig_idx)
+            .unwrap_or(self.original_len)
+    }
+
+    /// The byte ranges in the original text of the non-empty matches of `re`
+    ///
+    /// Matches starting inside a char already covered by the previous match are left out.
+    fn original_matches<'a>(
+        &'a self,
+        re: &'a Regex,
+    ) -> impl Iterator<Item = (usize, usize, regex::Captures<'a>)> + 'a {
+        let mut last_end = 0;
+        re.captures_iter(&self.text).filter_map(move |caps| {
+            let m = caps.get(0).expect("capture group 0 always exists");
+            let start = self.original_start(m.start());
+            if m.is_empty() || start < last_end {
+                return None;
+            };
+            last_end = self.original_end(m.end());
+            Some((start, last_end, caps))
+        })
+    }
+}
+
+/// Strip combining marks from a pattern if required
+fn prepare_pattern(pattern: &str, options: SearchOptions) -> String {
+    let stripped = Stripped::new(pattern, options.ignore_marks).text;
+    if options.regex {
+        stripped
+    } else {
+        regex::escape(&stripped)
+    }
+}
+
+/// Compile the pattern with the given options
+pub(super) fn compile(pattern: &str, options: SearchOptions) -> Result<Regex, regex::Error> {
+    Regex::new(&prepare_pattern(pattern, options))
+}
+
+/// Convert a byte offset in `s` to an offset in UTF-16 code units
+fn utf16_offset(s: &str, byte_offset: usize) -> usize {
+    s[..byte_offset].encode_utf16().count()
+}
+
+/// Find all (non-empty) hits of `re` in `haystack`
+///
+/// The hits are given as ranges of UTF-16 code units into `haystack`, which is how text inputs
+/// count their selection.
+pub(super) fn find_in(re: &Regex, haystack: &str, options: SearchOptions) -> Vec<(usize, usize)> {
+    let stripped = Stripped::new(haystack, options.ignore_marks);
+    stripped
+        .original_matches(re)
+        .map(|(start, end, _)| (utf16_offset(haystack, start), utf16_offset(haystack, end)))
+        .collect()
+}
+
+/// Replace all hits of `re` in `haystack`
+///
+/// When using regex, `replacement` may reference capture groups (`$1`, `${name}`).
+/// Returns None if nothing was replaced.
+pub(super) fn replace_in(
+    re: &Regex,
+    haystack: &str,
+    replacement: &str,
+    options: SearchOptions,
+) -> Option<String> {
+    let stripped = Stripped::new(haystack, options.ignore_marks);
+    let mut res = String::with_capacity(haystack.len());
+    let mut last_end = 0;
+    let mut replaced_any = false;
+    for (start, end, caps) in stripped.original_matches(re) {
+        res.push_str(&haystack[last_end..start]);
+        if options.regex {
+            caps.expand(replacement, &mut res);
+        } else {
+            res.push_str(replacement);
+        };
+        last_end = end;
+        replaced_any = true;
+    }
+    res.push_str(&haystack[last_end..]);
+    replaced_any.then_some(res)
+}
+
+/// All hits in the blocks, in the order they appear in the editor
+pub(super) fn find_all(
+    re: &Regex,
+    blocks: &[EditorBlock],
+    options: SearchOptions,
+) -> Vec<SearchHit> {
+    blocks
+        .iter()
+        .flat_map(|block| {
+            block
+                .inner
+                .searchable_fields()
+                .into_iter()
+                .flat_map(move |(field, content)| {
+                    find_in(re, &content, options)
+                        .into_iter()
+                        .map(move |(start, end)| SearchHit {
+                            block_id: block.id(),
+                            field,
+                            start,
+                            end,
+                        })
+                })
+        })
+        .collect()
+}
+
+/// Replace all hits in all blocks, returning an undo step if anything was changed
+///
+/// All changes end up in a single undo step.
+pub(super) fn replace_all(
+    re: &Regex,
+    replacement: &str,
+    blocks: &mut [EditorBlock],
+    options: SearchOptions,
+) -> Option<UnReStep> {
+    let mut changes = vec![];
+    for block in blocks.iter_mut() {
+        let old_inner: critic_format::streamed::Block = block.inner.clone().into();
+        let mut new_inner = old_inner.clone();
+        let mut changed = false;
+        for (field, content) in block.inner.searchable_fields() {
+            if let Some(new_content) = replace_in(re, &content, replacement, options) {
+                set_block_field(&mut new_inner, field, new_content);
+                changed = true;
+            };
+        }
+        if changed && block.overwrite_inner(&old_inner, &new_inner).is_some() {
+            changes.push((block.id(), old_inner, new_inner));
+        };
+    }
+    (!changes.is_empty()).then(|| UnReStep::new_multi_data_change(changes))
+}
+
+/// Put the focus on a hit, selecting its text
+fn focus_hit(hit: &SearchHit) {
+    let Some(element) = document().get_element_by_id(&hit.field.element_id(hit.block_id)) else {
+        log!(
+            "Unable to find the input element for block {}",
+            hit.block_id
+        );
+        return;
+    };
+    let Ok(textarea) = element.dyn_into::<HtmlTextAreaElement>() else {
+        return;
+    };
+    let _ = textarea.focus();
+    let _ = textarea.set_selection_range(hit.start as u32, hit.end as u32);
+}
+
+/// The panel for find and replace
+#[component]
+pub(super) fn FindReplace(
+    blocks: ReadSignal<Vec<EditorBlock>>,
+    set_blocks: WriteSignal<Vec<EditorBlock>>,
+    undo_stack: RwSignal<UnReStack>,
+) -> impl IntoView {
+    let pattern = RwSignal::new(String::default());
+    let replacement = RwSignal::new(String::default());
+    let use_regex = RwSignal::new(false);
+    let ignore_marks = RwSignal::new(false);
+    // index of the hit that was focused last and the number of hits at that point
+    let current_hit = RwSignal::new(None::<(usize, usize)>);
+
+    let options = move || SearchOptions {
+        regex: use_regex.get_untracked(),
+        ignore_marks: ignore_marks.get_untracked(),
+    };
+    let compiled = Memo::new(move |_| {
+        let pattern = pattern.get();
+        let options = SearchOptions {
+            regex: use_regex.get(),
+            ignore_marks: ignore_marks.get(),
+        };
+        if pattern.is_empty() {
+            return None;
+        };
+        Some(compile(&pattern, options).map_err(|e| e.to_string()))
+    });
+
+    let find_next = move |_| {
+        let Some(Ok(re)) = compiled.get_untracked() else {
+            return;
+        };
+        // blocks may have changed since the last search, so always search again
+        let hits = find_all(&re, &blocks.read_untracked(), options());
+        if hits.is_empty() {
+            current_hit.set(Some((0, 0)));
+            return;
+        };
+        let next = current_hit
+            .get_untracked()
+            .map_or(0, |(idx, _)| (idx + 1) % hits.len());
+        focus_hit(&hits[next]);
+        current_hit.set(Some((next, hits.len())));
+    };
+
+    let do_replace_all = move |_| {
+        let Some(Ok(re)) = compiled.get_untracked() else {
+            return;
+        };
+        let step = replace_all(
+            &re,
+            &replacement.get_untracked(),
+            &mut set_blocks.write(),
+            options(),
+        );
+        if let Some(step) = step {
+            undo_stack.write().push_undo(step);
+        };
+        current_hit.set(None);
+    };
+
+    view! {
+        <div class="flex flex-row justify-start gap-2 p-2 border border-slate-500 rounded-md">
+            <input
+                class="text-sm text-black"
+                placeholder="find"
+                autocomplete="false"
+                spellcheck="false"
+                id="find-replace-pattern"
+                prop:value=move || pattern.get()
+                on:input:target=move |ev| {
+                    pattern.set(ev.target().value());
+                    current_hit.set(None);
+                }
+            />
+            <input
+                class="text-sm text-black"
+                placeholder="replace with"
+                autocomplete="false"
+                spellcheck="false"
+                id="find-replace-replacement"
+                prop:value=move || replacement.get()
+                on:input:target=move |ev| {
+                    replacement.set(ev.target().value());
+                }
+            />
+            <label class="text-xs">
+                <input type="checkbox"
+                    prop:checked=move || use_regex.get()
+                    on:change:target=move |ev| {
+                        use_regex.set(ev.target().checked());
+                        current_hit.set(None);
+                    }
+                />
+                "Regex"
+            </label>
+            <label class="text-xs">
+                <input type="checkbox"
+                    prop:checked=move || ignore_marks.get()
+                    on:change:target=move |ev| {
+                        ignore_marks.set(ev.target().checked());
+                        current_hit.set(None);
+                    }
+                />
+                "Ignore points and accents"
+            </label>
+            <button on:click=find_next>"Find next"</button>
+            <button on:click=do_replace_all>"Replace all"</button>
+            <span class="text-xs">
+            {move || match compiled.get() {
+                Some(Err(e)) => format!("Invalid pattern: {e}"),
+                Some(Ok(_)) => match current_hit.get() {
+                    Some((_, 0)) => "No hits".to_string(),
+                    Some((idx, total)) => format!("Hit {} of {total}", idx + 1),
+                    None => String::default(),
+                },
+                None => String::default(),
+            }}
+            </span>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,201 @@
+//! Tests for find and replace
+
+use critic_format::streamed::{Block, BreakType, Paragraph};
+
+use super::{compile, find_in, replace_all, replace_in, SearchOptions, Stripped};
+use crate::editor::{blocks::EditorBlock, undo::UnReStack};
+
+const PLAIN: SearchOptions = SearchOptions {
+    regex: false,
+    ignore_marks: false,
+};
+const IGNORE_MARKS: SearchOptions = SearchOptions {
+    regex: false,
+    ignore_marks: true,
+};
+const REGEX: SearchOptions = SearchOptions {
+    regex: true,
+    ignore_marks: false,
+};
+
+fn find(pattern: &str, haystack: &str, options: SearchOptions) -> Vec<(usize, usize)> {
+    find_in(&compile(pattern, options).unwrap(), haystack, options)
+}
+
+fn replace(
+    pattern: &str,
+    haystack: &str,
+    replacement: &str,
+    options: SearchOptions,
+) -> Option<String> {
+    replace_in(
+        &compile(pattern, options).unwrap(),
+        haystack,
+        replacement,
+        options,
+    )
+}
+
+fn text(content: &str) -> Block {
+    Block::Text(Paragraph {
+        lang: "hbo-Hebr".to_string(),
+        content: content.to_string(),
+    })
+}
+
+fn editor_blocks(blocks: Vec<Block>) -> Vec<EditorBlock> {
+    blocks
+        .into_iter()
+        .enumerate()
+        .map(|(id, block)| EditorBlock {
+            id,
+            inner: block.into(),
+            focus_on_load: false,
+        })
+        .collect()
+}
+
+fn plain_blocks(blocks: &[EditorBlock]) -> Vec<Block> {
+    blocks
+        .iter()
+        .map(|block| block.inner.clone().into())
+        .collect()
+}
+
+#[test]
+fn stripping_removes_marks_and_maps_back() {
+    // בָּרָא: bet, dagesh, qamats, resh, qamats, alef
+    let stripped = Stripped::new("בָּרָא", true);
+    assert_eq!(stripped.text, "ברא");
+    assert_eq!(stripped.original_start(0), 0);
+    // resh starts after bet, dagesh and qamats
+    assert_eq!(stripped.original_start(2), 6);
+    // the end of bet maps past its marks
+    assert_eq!(stripped.original_end(2), 6);
+    assert_eq!(stripped.original_end(6), "בָּרָא".len());
+
+    let unchanged = Stripped::new("בָּרָא", false);
+    assert_eq!(unchanged.text, "בָּרָא");
+}
+
+#[test]
+fn matches_inside_decomposed_chars_cover_the_whole_char() {
+    // 가 decomposes canonically into the two base chars ᄀ and ᅡ
+    let stripped = Stripped::new("가나", true);
+    assert_eq!(stripped.text, "\u{1100}\u{1161}\u{1102}\u{1161}");
+    assert_eq!(stripped.original_start(3), 0);
+    assert_eq!(stripped.original_end(3), 3);
+    assert_eq!(stripped.original_end(9), 6);
+
+    assert_eq!(find("\u{1100}", "가나", IGNORE_MARKS), vec![(0, 1)]);
+    assert_eq!(find("\u{1161}", "가나", IGNORE_MARKS), vec![(0, 1), (1, 2)]);
+    assert_eq!(
+        replace("\u{1100}", "가나", "X", IGNORE_MARKS),
+        Some("X나".to_string())
+    );
+}
+
+#[test]
+fn matches_within_an_already_matched_char_are_skipped() {
+    let options = SearchOptions {
+        regex: true,
+        ignore_marks: true,
+    };
+    assert_eq!(find("\u{1100}|\u{1161}", "가", options), vec![(0, 1)]);
+    assert_eq!(
+        replace("\u{1100}|\u{1161}", "가", "X", options),
+        Some("X".to_string())
+    );
+}
+
+#[test]
+fn hits_are_counted_in_utf16_code_units() {
+    assert_eq!(find("ab", "ab ab", PLAIN), vec![(0, 2), (3, 5)]);
+    // 😀 and 𐤀 are outside the basic multilingual plane and take two code units each
+    assert_eq!(find("ab", "😀 ab", PLAIN), vec![(3, 5)]);
+    assert_eq!(find("𐤀", "a𐤀𐤀", PLAIN), vec![(1, 3), (3, 5)]);
+}
+
+#[test]
+fn marks_are_ignored_only_when_asked() {
+    assert!(find("ברא", "בָּרָא אֱלֹהִים", PLAIN).is_empty());
+    assert_eq!(find("ברא", "בָּרָא אֱלֹהִים", IGNORE_MARKS), vec![(0, 6)]);
+    // marks in the pattern are ignored as well
+    assert_eq!(
+        find("אֱלֹהִים", "בראשית ברא אלהים", IGNORE_MARKS),
+        vec![(11, 16)]
+    );
+}
+
+#[test]
+fn patterns_are_literal_unless_regex() {
+    assert_eq!(find("a.c", "abc a.c", PLAIN), vec![(4, 7)]);
+    assert_eq!(find("a.c", "abc a.c", REGEX), vec![(0, 3), (4, 7)]);
+    // empty matches are no hits
+    assert!(find("x*", "abc", REGEX).is_empty());
+}
+
+#[test]
+fn replacing_keeps_the_text_around_hits() {
+    assert_eq!(
+        replace("ברא", "בָּרָא אֱלֹהִים", "עשה", IGNORE_MARKS),
+        Some("עשה אֱלֹהִים".to_string())
+    );
+    assert_eq!(replace("ברא", "בָּרָא אֱלֹהִים", "עשה", PLAIN), None);
+    assert_eq!(
+        replace("a", "banana", "o", PLAIN),
+        Some("bonono".to_string())
+    );
+}
+
+#[test]
+fn regex_replacements_expand_capture_groups() {
+    assert_eq!(
+        replace(r"(\w+) (\w+)", "ab cd", "$2 $1", REGEX),
+        Some("cd ab".to_string())
+    );
+    // without regex, the replacement is taken literally
+    assert_eq!(replace("ab", "ab", "$1", PLAIN), Some("$1".to_string()));
+}
+
+#[test]
+fn replace_all_changes_every_block_in_one_undo_step() {
+    let mut blocks = editor_blocks(vec![
+        text("בָּרָא"),
+        Block::Break(BreakType::Line),
+        text("אלהים"),
+        text("ברא ברא"),
+    ]);
+    let re = compile("ברא", IGNORE_MARKS).unwrap();
+    let step = replace_all(&re, "X", &mut blocks, IGNORE_MARKS).unwrap();
+    assert_eq!(
+        plain_blocks(&blocks),
+        vec![
+            text("X"),
+            Block::Break(BreakType::Line),
+            text("אלהים"),
+            text("X X"),
+        ]
+    );
+
+    let mut undo_stack = UnReStack::new();
+    undo_stack.push_undo(step);
+    undo_stack.undo(&mut blocks).unwrap();
+    assert_eq!(
+        plain_blocks(&blocks),
+        vec![
+            text("בָּרָא"),
+            Block::Break(BreakType::Line),
+            text("אלהים"),
+            text("ברא ברא"),
+        ]
+    );
+}
+
+#[test]
+fn replace_all_without_hits_has_nothing_to_undo() {
+    let mut blocks = editor_blocks(vec![text("אלהים")]);
+    let re = compile("ברא", PLAIN).unwrap();
+    assert!(replace_all(&re, "X", &mut blocks, PLAIN).is_none());
+    assert_eq!(plain_blocks(&blocks), vec![text("אלהים")]);
+}
@@ -0,0 +1,127 @@
+//! Start a transcription from the output of handwritten text recognition (PAGE XML or ALTO)
+//!
+//! The file is converted into blocks on the server and opened in the editor as a draft; nothing
+//! is saved until the transcription is saved.
+
+use critic_format::streamed::Block;
+use leptos::{html::Input, prelude::*};
+use send_wrapper::SendWrapper;
+use web_sys::FormData;
+
+use super::blocks::EditorBlock;
+use super::seed::replace_all_blocks;
+use super::undo::UnReStack;
+
+/// Send the HTR/OCR output to the server and get the blocks back
+async fn convert_htr(
+    file: web_sys::File,
+    lang: String,
+    min_confidence: f64,
+) -> Result<Vec<Block>, String> {
+    let form_data = FormData::new().map_err(|_| "Unable to create form data.".to_string())?;
+    form_data
+        .append_with_blob_and_filename("file", &file, file.name().as_str())
+        .map_err(|_| "Unable to add the file to the form data.".to_string())?;
+    let res = reqwasm::http::Request::post(&format!(
+        "{}{}?lang={}&min_confidence={min_confidence}",
+        critic_shared::urls::UPLOAD_BASE_URL,
+        critic_shared::urls::HTR_IMPORT_API_ENDPOINT,
+        urlencoding::encode(&lang),
+    ))
+    .body(form_data)
+    .send()
+    .await
+    .map_err(|e| format!("There was a problem sending the POST request: {e}."))?;
+    if res.ok() {
+        res.json::<Vec<Block>>()
+            .await
+            .map_err(|e| format!("There was a problem deserializing response: {e}."))
+    } else {
+        Err(res.text().await.unwrap_or_else(|e| e.to_string()))
+    }
+}
+
+/// Lets the user fill the editor with the text recognised in a PAGE XML or ALTO file
+#[component]
+pub(super) fn ImportHtr(
+    default_language: String,
+    blocks: ReadSignal<Vec<EditorBlock>>,
+    set_blocks: WriteSignal<Vec<EditorBlock>>,
+    next_id: RwSignal<usize>,
+    undo_stack: RwSignal<UnReStack>,
+) -> impl IntoView {
+    let file_input = NodeRef::<Input>::new();
+    let lang = RwSignal::new(default_language);
+    let min_confidence = RwSignal::new(critic_shared::HTR_DEFAULT_MIN_CONFIDENCE);
+
+    let import_action = Action::new_local(
+        move |(file, lang, min_confidence): &(SendWrapper<web_sys::File>, String, f64)| {
+            let (file, lang, min_confidence) = (file.clone().take(), lang.clone(), *min_confidence);
+            async move { convert_htr(file, lang, min_confidence).await }
+        },
+    );
+
+    // replace the editor content as soon as the recognised text arrives
+    Effect::new(move |_| {
+        if let Some(Ok(new_blocks)) = import_action.value().get() {
+            replace_all_blocks(new_blocks, set_blocks, next_id, undo_stack);
+        };
+    });
+
+    view! {
+        <div class="flex flex-row justify-start gap-2 p-2">
+            <span class="font-light text-xs">"Start from HTR/OCR output (PAGE XML or ALTO): "</span>
+            <input type="file" accept=".xml" class="text-xs" node_ref=file_input/>
+            <input
+                class="text-sm text-black w-24"
+                placeholder="language"
+                autocomplete="false"
+                spellcheck="false"
+                prop:value=move || lang.get()
+                on:change:target=move |ev| lang.set(ev.target().value())
+            />
+            <label class="text-xs">
+                "Mark words as uncertain below confidence "
+                <input type="number" min="0" max="1" step="0.05" class="text-sm text-black w-16"
+                    prop:value=move || min_confidence.get()
+                    on:change:target=move |ev| {
+                        if let Ok(x) = ev.target().value().parse::<f64>() {
+                            min_confidence.set(x);
+                        };
+                    }
+                />
+            </label>
+            <button
+                disabled=move || import_action.pending().get()
+                on:click=move |_| {
+                    let Some(file) = file_input
+                        .get_untracked()
+                        .and_then(|input| input.files())
+                        .and_then(|files| files.get(0))
+                    else {
+                        return;
+                    };
+                    if !blocks.read_untracked().is_empty()
+                        && !window()
+                            .confirm_with_message("This replaces the current transcription. Continue?")
+                            .unwrap_or(false)
+                    {
+                        return;
+                    };
+                    import_action.dispatch_local((
+                        SendWrapper::new(file),
+                        lang.get_untracked(),
+                        min_confidence.get_untracked(),
+                    ));
+                }
+            >"Import"</button>
+            <span class="text-xs">
+            {move || import_action.pending().get().then_some("Converting recognised text...")}
+            {move || match import_action.value().get() {
+                Some(Err(e)) => Some(e),
+                _ => None,
+            }}
+            </span>
+        </div>
+    }
+}
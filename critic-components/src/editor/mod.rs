@@ -5,7 +5,7 @@
 use critic_format::streamed::BlockType;
 use critic_shared::ShowHelp;
 use leptos::{
-    ev::keydown,
+    ev::{keydown, paste},
     logging::log,
     prelude::{Action, *},
 };
@@ -17,11 +17,61 @@ use web_sys::{wasm_bindgen::JsCast, HtmlTextAreaElement};
 mod blocks;
 use blocks::*;
 
+mod compare;
+use compare::ComparePanel;
+
+mod find_replace;
+use find_replace::FindReplace;
+
+mod htr;
+use htr::ImportHtr;
+
+mod paste;
+use paste::{smart_paste, PasteMode, PasteSettings};
+
 mod undo;
 
 mod save;
 
-mod versification_scheme;
+mod seed;
+use seed::SeedFromReference;
+
+pub mod versification_scheme;
+
+/// Convert a selection to byte offsets into `value`
+///
+/// Selections in textareas are given as character indices.
+fn selection_to_byte_offsets(value: &str, start: u32, end: u32) -> (usize, usize) {
+    let mut indices = value.char_indices().map(|(i, _)| i);
+    let start_utf8 = match indices.nth(start as usize) {
+        Some(el) => el,
+        None => value.len(),
+    };
+    let end_utf8 = if start == end {
+        start_utf8
+    } else {
+        indices
+            .nth((end - start - 1) as usize)
+            .unwrap_or(value.len())
+    };
+    (start_utf8, end_utf8)
+}
+
+/// The textarea that currently has focus and the logical id of the block it belongs to
+///
+/// Returns None if the focus is not in the primary input of a block.
+fn focused_block_input() -> Option<(HtmlTextAreaElement, usize)> {
+    let primary_input = use_document()
+        .active_element()?
+        .dyn_into::<HtmlTextAreaElement>()
+        .ok()?;
+    let id = primary_input
+        .id()
+        .strip_prefix("block-input-")?
+        .parse::<usize>()
+        .ok()?;
+    Some((primary_input, id))
+}
 
 /// Add a new Block to the editor
 ///
@@ -42,30 +92,10 @@ fn new_node(
     default_language: &str,
 ) {
     // first find out the id of the block currently selected
-    let active_element = match use_document().active_element() {
-        Some(el) => el,
-        None => {
-            return;
-        }
-    };
-    let primary_input = match active_element.dyn_into::<HtmlTextAreaElement>() {
-        Ok(el) => el,
-        Err(_) => {
-            return;
-        }
-    };
-    // get the block index we are in right now
-    // break if this is an ID which we do not know
-    if !primary_input.id().starts_with("block-input-") {
+    // break if this is an element or ID which we do not know
+    let Some((primary_input, id)) = focused_block_input() else {
         return;
     };
-    let id_stripped = &primary_input.id()[12..];
-    let id = match id_stripped.parse::<usize>() {
-        Ok(el) => el,
-        Err(_) => {
-            return;
-        }
-    };
 
     // If text is currently selected, the block should be created with the selected text as its
     // content
@@ -78,19 +108,7 @@ fn new_node(
         // after-selection
         match (current_select_start, current_select_end) {
             (Some(x), Some(y)) => {
-                // convert indices to byte offsets (they are given as utf-8 character indices)
-                let mut indices = complete_value.char_indices().map(|(i, _)| i);
-                let start_utf8 = match indices.nth(x as usize) {
-                    Some(el) => el,
-                    None => complete_value.len(),
-                };
-                let end_utf8 = if x == y {
-                    start_utf8
-                } else {
-                    indices
-                        .nth((y - x - 1) as usize)
-                        .unwrap_or(complete_value.len())
-                };
+                let (start_utf8, end_utf8) = selection_to_byte_offsets(&complete_value, x, y);
                 let new_blocks = match blocks.read().get(physical_index) {
                     Some(el) => {
                         let res = el.split_at_selection(
@@ -146,6 +164,7 @@ const SHORTCUT_DESCRIPTIONS: &[(&str, &str, &str)] = &[
     ),
     ("z", "Undo", "Undo your last action"),
     ("r", "Redo", "Redo the action you just undid"),
+    ("f", "Find", "Show or hide find and replace"),
     ("t", "Text", "Add a new block of text without markup"),
     (
         "a",
@@ -208,7 +227,15 @@ fn HelpOverlay(active: RwSignal<ShowHelp>) -> impl IntoView {
 }
 
 #[component]
-pub fn Editor(default_language: String) -> impl IntoView {
+pub fn Editor(
+    default_language: String,
+    /// The manuscript this transcription is for, if known
+    #[prop(optional_no_strip)]
+    msname: Option<String>,
+    /// The page this transcription is for, if known
+    #[prop(optional_no_strip)]
+    pagename: Option<String>,
+) -> impl IntoView {
     let undo_stack = RwSignal::new(UnReStack::new());
 
     // logical ID of blocks, 1-based
@@ -281,12 +308,64 @@ pub fn Editor(default_language: String) -> impl IntoView {
         }
     };
 
-    let save_state_action = Action::new(|blocks: &Vec<EditorBlock>| {
-        let blocks_dehydrated = blocks.iter().map(|b| b.inner.clone().into()).collect();
-        async move { save_editor_state(blocks_dehydrated).await }
+    let save_state_action = Action::new({
+        let (msname, pagename) = (msname.clone(), pagename.clone());
+        move |blocks: &Vec<EditorBlock>| {
+            let blocks_dehydrated = blocks.iter().map(|b| b.inner.clone().into()).collect();
+            let (msname, pagename) = (msname.clone(), pagename.clone());
+            async move { save_editor_state(msname, pagename, blocks_dehydrated).await }
+        }
     });
     let pending_save = save_state_action.pending();
 
+    let find_replace_open = RwSignal::new(false);
+    let paste_mode = RwSignal::new(PasteMode::default());
+
+    // intercept pastes into the raw text of Text blocks when smart paste is enabled
+    let _paste_cleanup = use_event_listener(use_document(), paste, move |evt| {
+        if !paste_mode.read_untracked().smart {
+            return;
+        };
+        let Some((primary_input, id)) = focused_block_input() else {
+            return;
+        };
+        let Some(text) = evt
+            .clipboard_data()
+            .and_then(|data| data.get_data("text").ok())
+        else {
+            return;
+        };
+        let Some(physical_index) = physical_index_maybe(id) else {
+            return;
+        };
+        let value = primary_input.value();
+        let (start, end) = match (
+            primary_input.selection_start().unwrap_or(None),
+            primary_input.selection_end().unwrap_or(None),
+        ) {
+            (Some(x), Some(y)) => selection_to_byte_offsets(&value, x, y),
+            _ => (value.len(), value.len()),
+        };
+        let paste_mode = paste_mode.read_untracked();
+        let step = smart_paste(
+            &mut set_blocks.write(),
+            physical_index,
+            start,
+            end,
+            &text,
+            paste_mode.verses(),
+            &mut next_id.write(),
+        );
+        if let Some(step) = step {
+            // the text is already in the new blocks
+            evt.prevent_default();
+            undo_stack.write().push_undo(step);
+        };
+    });
+
+    // the keyboard-shortcut listener takes ownership of default_language
+    let htr_language = default_language.clone();
+
     // the keyboard-shortcut listener
     let _cleanup = use_event_listener(use_document(), keydown, move |evt| {
         log!("Pressed: {}", evt.key_code());
@@ -310,6 +389,9 @@ pub fn Editor(default_language: String) -> impl IntoView {
                     log!("{e}");
                 }
             };
+        // <ctrl>-<alt>-F - find and replace
+        } else if evt.alt_key() && evt.ctrl_key() && evt.key_code() == 70 {
+            find_replace_open.update(|x| *x ^= true);
         // <ctrl>-<alt>-T (new Text)
         } else if evt.alt_key() && evt.ctrl_key() && evt.key_code() == 84 {
             new_node(
@@ -405,11 +487,11 @@ pub fn Editor(default_language: String) -> impl IntoView {
     // around
     provide_context(undo_stack);
 
+    let load_state = load_editor_state(msname.clone(), pagename.clone());
     let load_state_resource = OnceResource::<Vec<EditorBlock>>::new(async move {
-        match load_editor_state().await {
-            Ok(streamed) => {
-                let blocks: Vec<EditorBlock> = streamed
-                    .content
+        match load_state.await {
+            Ok(content) => {
+                let blocks: Vec<EditorBlock> = content
                     .into_iter()
                     .enumerate()
                     .map(|(idx, x)| EditorBlock {
@@ -443,6 +525,38 @@ pub fn Editor(default_language: String) -> impl IntoView {
             </button>
             <HelpOverlay active=help_active/>
             <p>{move || pending_save.get().then_some("Saving state...")}</p>
+            {
+                if let (Some(msname), Some(pagename)) = (msname, pagename) {
+                    Some(view! {
+                        <SeedFromReference
+                            msname=msname.clone()
+                            pagename=pagename.clone()
+                            blocks=blocks
+                            set_blocks=set_blocks
+                            next_id=next_id
+                            undo_stack=undo_stack/>
+                        <ComparePanel
+                            msname=msname
+                            pagename=pagename
+                            blocks=blocks
+                            set_blocks=set_blocks
+                            next_id=next_id
+                            undo_stack=undo_stack/>
+                    })
+                } else {
+                    None
+                }
+            }
+            <ImportHtr
+                default_language=htr_language
+                blocks=blocks
+                set_blocks=set_blocks
+                next_id=next_id
+                undo_stack=undo_stack/>
+            <PasteSettings paste_mode=paste_mode/>
+            <Show when=move || find_replace_open.get()>
+                <FindReplace blocks=blocks set_blocks=set_blocks undo_stack=undo_stack/>
+            </Show>
             <br/>
             <Suspense fallback=|| { view!{ <p>"Loading editor state from the server..."</p> } }>
             {move || Suspend::new(async move {
@@ -0,0 +1,239 @@
+//! Smart paste: turn multi-line text into Text, Break and Anchor blocks
+//!
+//! When smart paste is enabled, pasting text with line breaks or verse markers (e.g. `1:3`) into
+//! the raw text of a Text block splits the text into blocks instead of pasting one huge paragraph.
+//! Verse markers only carry chapter and verse, so the book of the pasted text is a paste setting.
+
+use critic_format::streamed::{Block, BreakType, Paragraph};
+use critic_shared::{anchor::verse_anchor, VersificationScheme};
+use leptos::prelude::*;
+use regex::Regex;
+
+use super::blocks::{EditorBlock, InnerBlock};
+use super::undo::UnReStep;
+
+/// The settings for pasting into the editor
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(super) struct PasteMode {
+    /// split pasted text into blocks
+    pub smart: bool,
+    /// create anchors for verse markers in this versification scheme
+    ///
+    /// If this is None, verse markers are kept as text.
+    pub scheme: Option<VersificationScheme>,
+    /// the book verse markers are in, as in the verse map (e.g. `Gen`)
+    ///
+    /// If this is empty, verse markers are kept as text.
+    pub book: String,
+}
+impl PasteMode {
+    /// The scheme and book to create verse anchors in, None if verse markers are kept as text
+    pub fn verses(&self) -> Option<(&VersificationScheme, &str)> {
+        let book = self.book.trim();
+        match &self.scheme {
+            Some(scheme) if !book.is_empty() => Some((scheme, book)),
+            _ => None,
+        }
+    }
+}
+
+/// Matches verse markers like `1:3` that stand on their own
+fn verse_marker_regex() -> Regex {
+    Regex::new(r"(^|\s)(\d+:\d+)(\s|$)").expect("static regex")
+}
+
+/// A new Text block
+fn text_block(lang: &str, content: &str) -> Block {
+    Block::Text(Paragraph {
+        lang: lang.to_string(),
+        content: content.to_string(),
+    })
+}
+
+/// Split a single line into Text and Anchor blocks
+///
+/// Verse markers become anchors for verses of `book` in `scheme`, e.g. `1:3` in Genesis becomes
+/// `A_V_P_Gen 1:3`.
+fn split_line(line: &str, lang: &str, verses: Option<(&VersificationScheme, &str)>) -> Vec<Block> {
+    let Some((scheme, book)) = verses else {
+        return if line.trim().is_empty() {
+            vec![]
+        } else {
+            vec![text_block(lang, line.trim())]
+        };
+    };
+    let mut res = vec![];
+    let mut last_end = 0;
+    let re = verse_marker_regex();
+    // markers share the surrounding whitespace, so we cannot use find_iter directly: the
+    // whitespace after one marker is needed as the start of the next one
+    let mut search_start = 0;
+    while let Some(caps) = re.captures_at(line, search_start) {
+        let marker = caps.get(2).expect("group 2 always participates");
+        let before = line[last_end..marker.start()].trim();
+        if !before.is_empty() {
+            res.push(text_block(lang, before));
+        };
+        res.push(verse_anchor(scheme, &format!("{book} {}", marker.as_str())));
+        last_end = marker.end();
+        search_start = marker.end();
+    }
+    let rest = line[last_end..].trim();
+    if !rest.is_empty() {
+        res.push(text_block(lang, rest));
+    };
+    res
+}
+
+/// Split pasted text into blocks
+///
+/// Lines become Text blocks separated by line breaks, verse markers become Anchors.
+pub(super) fn split_pasted_text(
+    text: &str,
+    lang: &str,
+    verses: Option<(&VersificationScheme, &str)>,
+) -> Vec<Block> {
+    let mut res = vec![];
+    for (idx, line) in text.lines().enumerate() {
+        if idx != 0 {
+            res.push(Block::Break(BreakType::Line));
+        };
+        res.extend(split_line(line, lang, verses));
+    }
+    res
+}
+
+/// Paste `text` into the Text block at `physical_index`, replacing the byte range `start..end` of
+/// its content.
+///
+/// Returns None if the paste does not need any new blocks, i.e. a normal paste will do.
+/// Otherwise the blocks are replaced and the returned step undoes the complete paste.
+pub(super) fn smart_paste(
+    blocks: &mut Vec<EditorBlock>,
+    physical_index: usize,
+    start: usize,
+    end: usize,
+    text: &str,
+    verses: Option<(&VersificationScheme, &str)>,
+    next_id: &mut usize,
+) -> Option<UnReStep> {
+    let InnerBlock::Text(paragraph) = &blocks.get(physical_index)?.inner else {
+        return None;
+    };
+    let Paragraph { lang, content } = paragraph.get_untracked();
+    let mut pasted = split_pasted_text(text, &lang, verses);
+    if pasted.len() <= 1 && pasted.iter().all(|b| matches!(b, Block::Text(_))) {
+        return None;
+    };
+
+    // glue the text around the selection to the pasted text
+    let before = &content[..start];
+    let after = &content[end..];
+    match pasted.first_mut() {
+        Some(Block::Text(first)) => first.content = format!("{before}{}", first.content),
+        _ => {
+            if !before.is_empty() {
+                pasted.insert(0, text_block(&lang, before));
+            }
+        }
+    };
+    match pasted.last_mut() {
+        Some(Block::Text(last)) => last.content.push_str(after),
+        _ => {
+            if !after.is_empty() {
+                pasted.push(text_block(&lang, after));
+            }
+        }
+    };
+
+    let last_idx = pasted.len() - 1;
+    let new_blocks: Vec<EditorBlock> = pasted
+        .into_iter()
+        .enumerate()
+        .map(|(idx, block)| {
+            let new_block = EditorBlock {
+                id: *next_id,
+                inner: block.into(),
+                // continue typing at the end of the pasted text
+                focus_on_load: idx == last_idx,
+            };
+            *next_id += 1;
+            new_block
+        })
+        .collect();
+    let removed = blocks
+        .splice(physical_index..physical_index + 1, new_blocks.clone())
+        .collect();
+    Some(UnReStep::new_block_change(
+        physical_index,
+        removed,
+        new_blocks,
+    ))
+}
+
+/// Settings for smart paste, shown above the editor
+#[component]
+pub(super) fn PasteSettings(paste_mode: RwSignal<PasteMode>) -> impl IntoView {
+    let versification_schemes =
+        use_context::<OnceResource<Result<Vec<VersificationScheme>, ServerFnError>>>()
+            .expect("Editor provides versification schemes");
+
+    view! {
+        <div class="flex flex-row justify-start gap-2 p-2">
+            <label class="text-xs">
+                <input type="checkbox"
+                    prop:checked=move || paste_mode.read().smart
+                    on:change:target=move |ev| {
+                        paste_mode.write().smart = ev.target().checked();
+                    }
+                />
+                "Split pasted text into lines and verses"
+            </label>
+            <span class="font-light text-xs">"Verse markers are in: "</span>
+            <Suspense fallback=|| view! { <span class="text-xs">"Loading versification schemes..."</span> }>
+            {move || Suspend::new(async move {
+                match versification_schemes.await {
+                    Ok(schemes) => {
+                        let schemes_for_change = schemes.clone();
+                        leptos::either::Either::Left(view! {
+                            <select
+                                class="text-sm"
+                                prop:value=move || paste_mode.read().scheme.as_ref().map(|s| s.full_name.clone()).unwrap_or_default()
+                                on:change:target=move |ev| {
+                                    let selected = ev.target().value();
+                                    paste_mode.write().scheme = schemes_for_change
+                                        .iter()
+                                        .find(|s| s.full_name == selected)
+                                        .cloned();
+                                }
+                            >
+                                <option value="">"(keep verse markers as text)"</option>
+                                {schemes.into_iter().map(|scheme| view! {
+                                    <option value=scheme.full_name.clone()>{scheme.full_name.clone()}</option>
+                                }).collect::<Vec<_>>()}
+                            </select>
+                        })
+                    }
+                    Err(e) => leptos::either::Either::Right(view! {
+                        <span class="text-xs">"Unable to get versification schemes from the server: "{e.to_string()}</span>
+                    }),
+                }
+            })}
+            </Suspense>
+            <label class="font-light text-xs">"Book: "
+                <input type="text" class="text-sm w-16" placeholder="Gen"
+                    prop:value=move || paste_mode.read().book.clone()
+                    on:input:target=move |ev| {
+                        paste_mode.write().book = ev.target().value();
+                    }
+                />
+            </label>
+            <Show when=move || { let mode = paste_mode.read(); mode.scheme.is_some() && mode.book.trim().is_empty() }>
+                <span class="text-xs text-red-600">"Set the book to turn verse markers into anchors"</span>
+            </Show>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod test;
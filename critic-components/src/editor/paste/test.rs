@@ -0,0 +1,202 @@
+//! Tests for smart paste
+
+use critic_format::streamed::{Block, BreakType, Paragraph};
+use critic_shared::{anchor::verse_anchor, VersificationScheme};
+use leptos::prelude::*;
+
+use super::{smart_paste, split_line, split_pasted_text, PasteMode};
+use crate::editor::{blocks::EditorBlock, undo::UnReStack};
+
+fn scheme() -> VersificationScheme {
+    VersificationScheme {
+        id: 1,
+        full_name: "Present".to_string(),
+        shorthand: "P".to_string(),
+    }
+}
+
+fn text(content: &str) -> Block {
+    Block::Text(Paragraph {
+        lang: "hbo-Hebr".to_string(),
+        content: content.to_string(),
+    })
+}
+
+fn anchor(verse_nr: &str) -> Block {
+    verse_anchor(&scheme(), verse_nr)
+}
+
+fn editor_blocks(blocks: Vec<Block>) -> Vec<EditorBlock> {
+    blocks
+        .into_iter()
+        .enumerate()
+        .map(|(id, block)| EditorBlock {
+            id,
+            inner: block.into(),
+            focus_on_load: false,
+        })
+        .collect()
+}
+
+fn plain_blocks(blocks: &[EditorBlock]) -> Vec<Block> {
+    blocks
+        .iter()
+        .map(|block| block.inner.clone().into())
+        .collect()
+}
+
+#[test]
+fn verse_markers_become_anchors_in_the_book() {
+    let scheme = scheme();
+    assert_eq!(
+        split_line(
+            "1:1 בראשית ברא 1:2 והארץ",
+            "hbo-Hebr",
+            Some((&scheme, "Gen"))
+        ),
+        vec![
+            anchor("Gen 1:1"),
+            text("בראשית ברא"),
+            anchor("Gen 1:2"),
+            text("והארץ"),
+        ]
+    );
+    let Block::Anchor(first) = &split_line("1:3", "hbo-Hebr", Some((&scheme, "Gen")))[0] else {
+        panic!("expected an anchor");
+    };
+    assert_eq!(first.anchor_id, "A_V_P_Gen 1:3");
+}
+
+#[test]
+fn adjacent_verse_markers_share_their_whitespace() {
+    let scheme = scheme();
+    assert_eq!(
+        split_line("1:1 1:2", "hbo-Hebr", Some((&scheme, "Gen"))),
+        vec![anchor("Gen 1:1"), anchor("Gen 1:2")]
+    );
+}
+
+#[test]
+fn verse_markers_stay_text_without_scheme_and_book() {
+    assert_eq!(
+        split_line(" 1:1 בראשית ", "hbo-Hebr", None),
+        vec![text("1:1 בראשית")]
+    );
+    assert!(split_line("   ", "hbo-Hebr", None).is_empty());
+    // markers inside words are not verse markers
+    let scheme = scheme();
+    assert_eq!(
+        split_line("a1:1 b", "hbo-Hebr", Some((&scheme, "Gen"))),
+        vec![text("a1:1 b")]
+    );
+}
+
+#[test]
+fn paste_mode_needs_scheme_and_book_for_anchors() {
+    let mut mode = PasteMode {
+        smart: true,
+        scheme: Some(scheme()),
+        book: " ".to_string(),
+    };
+    assert!(mode.verses().is_none());
+    mode.book = "Gen ".to_string();
+    assert_eq!(mode.verses(), Some((&scheme(), "Gen")));
+    mode.scheme = None;
+    assert!(mode.verses().is_none());
+}
+
+#[test]
+fn lines_are_separated_by_line_breaks() {
+    let scheme = scheme();
+    assert_eq!(
+        split_pasted_text(
+            "1:1 בראשית\nברא\n\nאלהים",
+            "hbo-Hebr",
+            Some((&scheme, "Gen"))
+        ),
+        vec![
+            anchor("Gen 1:1"),
+            text("בראשית"),
+            Block::Break(BreakType::Line),
+            text("ברא"),
+            Block::Break(BreakType::Line),
+            Block::Break(BreakType::Line),
+            text("אלהים"),
+        ]
+    );
+}
+
+#[test]
+fn single_lines_are_pasted_normally() {
+    let mut blocks = editor_blocks(vec![text("בראשית")]);
+    let mut next_id = 1;
+    assert!(smart_paste(&mut blocks, 0, 0, 0, "ברא אלהים", None, &mut next_id).is_none());
+    assert_eq!(next_id, 1);
+    assert_eq!(plain_blocks(&blocks), vec![text("בראשית")]);
+}
+
+#[test]
+fn pastes_replace_the_selection_and_undo_in_one_step() {
+    let scheme = scheme();
+    let mut blocks = editor_blocks(vec![text("abcdef"), Block::Break(BreakType::Column)]);
+    let mut next_id = 2;
+    let step = smart_paste(
+        &mut blocks,
+        0,
+        2,
+        4,
+        "X\n1:2 Y",
+        Some((&scheme, "Gen")),
+        &mut next_id,
+    )
+    .expect("the paste needs new blocks");
+    assert_eq!(
+        plain_blocks(&blocks),
+        vec![
+            text("abX"),
+            Block::Break(BreakType::Line),
+            anchor("Gen 1:2"),
+            text("Yef"),
+            Block::Break(BreakType::Column),
+        ]
+    );
+    assert_eq!(next_id, 6);
+    assert!(blocks[3].focus_on_load);
+
+    let mut undo_stack = UnReStack::new();
+    undo_stack.push_undo(step);
+    undo_stack.undo(&mut blocks).unwrap();
+    assert_eq!(
+        plain_blocks(&blocks),
+        vec![text("abcdef"), Block::Break(BreakType::Column)]
+    );
+}
+
+#[test]
+fn text_around_anchors_at_the_edges_is_kept() {
+    let scheme = scheme();
+    let mut blocks = editor_blocks(vec![text("ab")]);
+    let mut next_id = 1;
+    smart_paste(
+        &mut blocks,
+        0,
+        1,
+        1,
+        "1:1",
+        Some((&scheme, "Gen")),
+        &mut next_id,
+    )
+    .expect("anchors need new blocks");
+    assert_eq!(
+        plain_blocks(&blocks),
+        vec![text("a"), anchor("Gen 1:1"), text("b")]
+    );
+}
+
+#[test]
+fn only_text_blocks_take_smart_pastes() {
+    let mut blocks = editor_blocks(vec![Block::Break(BreakType::Line)]);
+    let mut next_id = 1;
+    assert!(smart_paste(&mut blocks, 0, 0, 0, "a\nb", None, &mut next_id).is_none());
+    assert!(smart_paste(&mut blocks, 3, 0, 0, "a\nb", None, &mut next_id).is_none());
+}
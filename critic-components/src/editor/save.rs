@@ -1,66 +1,129 @@
 //! Functions for saving (and loading) the state from the server.
+//!
+//! The editor of a page keeps its state in the transcription of the logged-in user for that page.
+//! Without a page (the playground at /editor) there is no transcription to keep it in, so the
+//! state goes into the scratch file `tmp/data`, which is shared by everyone using the playground.
 
+use critic_format::streamed::Block;
 use leptos::prelude::*;
 
-#[server]
-pub(super) async fn load_editor_state() -> Result<critic_format::streamed::Manuscript, ServerFnError>
-{
-    use std::path::Path;
+/// Where the playground keeps its state
+#[cfg(feature = "ssr")]
+const SCRATCH_PATH: &str = "tmp/data";
 
-    let path = Path::new("tmp/data");
-    let file = std::fs::File::open(path).map_err(|e| ServerFnError::new(e.to_string()))?;
-    let buf_reader = std::io::BufReader::new(file);
-    let ds: critic_format::schema::Tei =
-        quick_xml::de::from_reader(buf_reader).map_err(|e| ServerFnError::new(e.to_string()))?;
-    let normalized: critic_format::normalized::Manuscript =
-        ds.try_into()
-            .map_err(|e: critic_format::denorm::NormalizationError| {
-                ServerFnError::new(e.to_string())
-            })?;
-    let streamed: critic_format::streamed::Manuscript = normalized
-        .try_into()
-        .map_err(|e: critic_format::destream::StreamError| ServerFnError::new(e.to_string()))?;
+/// The logged-in user, who owns the transcriptions the editor loads and saves
+#[cfg(feature = "ssr")]
+async fn transcribing_user() -> Result<critic_server::auth::AuthenticatedUser, ServerFnError> {
+    use critic_server::auth::AuthSession;
+    use leptos_axum::extract;
 
-    Ok(streamed)
+    let auth_session = extract::<AuthSession>()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Failed to get AuthSession: {e}")))?;
+    auth_session.user.ok_or(ServerFnError::new(
+        "Unauthorized: Need to be logged in to transcribe.",
+    ))
 }
 
+/// Load the transcription of the logged-in user for a page, or the playground without a page
+///
+/// A page the user has not transcribed yet starts out empty.
+#[server]
+pub(super) async fn load_editor_state(
+    msname: Option<String>,
+    pagename: Option<String>,
+) -> Result<Vec<Block>, ServerFnError> {
+    let path = match (msname, pagename) {
+        (Some(msname), Some(pagename)) => {
+            let config: std::sync::Arc<critic_server::config::Config> =
+                use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+            let user = transcribing_user().await?;
+            critic_server::tei::transcription_path(
+                &config.data_directory,
+                &msname,
+                &pagename,
+                &user.username,
+            )
+        }
+        _ => SCRATCH_PATH.to_string(),
+    };
+    let content = match std::fs::read_to_string(&path) {
+        Ok(x) => x,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    critic_server::tei::parse_tei(&content)
+        .map(|streamed| streamed.content)
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Save the transcription of the logged-in user for a page, or the playground without a page
+///
+/// The header of a transcription is taken from the manuscript. Saving does not change whether the
+/// transcription is published.
+///
 /// We take streamed blocks because they have no Signals and so can properly (de-)serialize
 #[server]
 pub(super) async fn save_editor_state(
-    blocks: Vec<critic_format::streamed::Block>,
+    msname: Option<String>,
+    pagename: Option<String>,
+    blocks: Vec<Block>,
 ) -> Result<(), ServerFnError> {
-    use std::io::Write;
-    use std::path::Path;
+    let (Some(msname), Some(pagename)) = (msname, pagename) else {
+        let ms = critic_format::streamed::Manuscript {
+            meta: critic_format::normalized::Meta {
+                name: "playground".to_string(),
+                page_nr: "1".to_string(),
+                title: "playground".to_string(),
+                institution: None,
+                collection: None,
+                hand_desc: None,
+                script_desc: None,
+            },
+            content: blocks,
+        };
+        let tei =
+            critic_server::tei::write_tei(ms).map_err(|e| ServerFnError::new(e.to_string()))?;
+        return std::fs::write(SCRATCH_PATH, tei).map_err(|e| ServerFnError::new(e.to_string()));
+    };
 
-    let path = Path::new("tmp/data");
-    let mut file = std::fs::File::create(path).map_err(|e| ServerFnError::new(e.to_string()))?;
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    let user = transcribing_user().await?;
+    let manuscript = critic_server::db::get_manuscript(&config.db, &msname)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    let page = manuscript
+        .pages
+        .iter()
+        .find(|page| page.name == pagename)
+        .ok_or(ServerFnError::new(format!(
+            "The page {pagename} does not exist in manuscript {msname}."
+        )))?;
 
-    // todo fill actual data
     let ms = critic_format::streamed::Manuscript {
-        meta: critic_format::normalized::Meta {
-            name: "name".to_string(),
-            page_nr: "pgnr".to_string(),
-            title: "title".to_string(),
-            institution: None,
-            collection: None,
-            hand_desc: None,
-            script_desc: None,
-        },
+        meta: critic_server::tei::page_meta(&manuscript.meta, &pagename),
         content: blocks,
     };
+    let tei = critic_server::tei::write_tei(ms).map_err(|e| ServerFnError::new(e.to_string()))?;
 
-    let destreamed: critic_format::normalized::Manuscript = ms
-        .try_into()
-        .map_err(|e: critic_format::destream::StreamError| ServerFnError::new(e.to_string()))?;
-    let denormed: critic_format::schema::Tei =
-        destreamed
-            .try_into()
-            .map_err(|e: critic_format::denorm::NormalizationError| {
-                ServerFnError::new(e.to_string())
-            })?;
-    let sr = quick_xml::se::to_string_with_root("TEI", &denormed)
-        .map_err(|e| ServerFnError::new(e.to_string()))?;
-    file.write(sr.as_bytes())
+    critic_server::db::ensure_transcription(&config.db, page.id, &user.username)
+        .await
         .map_err(|e| ServerFnError::new(e.to_string()))?;
-    Ok(())
+    std::fs::create_dir_all(critic_server::tei::transcription_directory(
+        &config.data_directory,
+        &msname,
+        &pagename,
+    ))
+    .map_err(|e| ServerFnError::new(e.to_string()))?;
+    std::fs::write(
+        critic_server::tei::transcription_path(
+            &config.data_directory,
+            &msname,
+            &pagename,
+            &user.username,
+        ),
+        tei,
+    )
+    .map_err(|e| ServerFnError::new(e.to_string()))
 }
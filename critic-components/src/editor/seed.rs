@@ -0,0 +1,184 @@
+//! Seed a new transcription with the text of a reference edition
+//!
+//! The verses from `verse_start` to `verse_end` of the page are fetched from the reference
+//! edition and turned into Anchor and Text blocks.
+
+use critic_format::streamed::Block;
+use critic_shared::{ReferenceEdition, VersificationScheme};
+use leptos::prelude::*;
+
+use super::blocks::EditorBlock;
+use super::undo::{UnReStack, UnReStep};
+
+#[server]
+pub(super) async fn get_reference_editions() -> Result<Vec<ReferenceEdition>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    critic_server::db::get_reference_editions(&config.db)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Get the blocks for the verses on a page from a reference edition
+#[server]
+pub(super) async fn get_reference_blocks(
+    msname: String,
+    pagename: String,
+    edition_id: i64,
+    scheme_id: i64,
+) -> Result<Vec<Block>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+
+    let page = critic_server::db::get_page(&config.db, &msname, &pagename)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    let (Some(verse_start), Some(verse_end)) = (page.verse_start, page.verse_end) else {
+        return Err(ServerFnError::new(
+            "The verse range of this page is not known yet.",
+        ));
+    };
+    let edition = critic_server::db::get_reference_editions(&config.db)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?
+        .into_iter()
+        .find(|e| e.id == edition_id)
+        .ok_or(ServerFnError::new("This reference edition does not exist."))?;
+    let scheme = critic_server::db::get_versification_schemes(&config.db)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?
+        .into_iter()
+        .find(|s| s.id == scheme_id)
+        .ok_or(ServerFnError::new(
+            "This versification scheme does not exist.",
+        ))?;
+
+    let verses = critic_server::db::get_reference_verses(
+        &config.db,
+        edition_id,
+        scheme_id,
+        verse_start,
+        verse_end,
+    )
+    .await
+    .map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(critic_server::reference::reference_blocks(
+        verses,
+        &scheme,
+        &edition.lang,
+    ))
+}
+
+/// Replace all blocks in the editor with `new_blocks` in a single undoable step
+pub(super) fn replace_all_blocks(
+    new_blocks: Vec<Block>,
+    set_blocks: WriteSignal<Vec<EditorBlock>>,
+    next_id: RwSignal<usize>,
+    undo_stack: RwSignal<UnReStack>,
+) {
+    let new_blocks: Vec<EditorBlock> = new_blocks
+        .into_iter()
+        .map(|block| {
+            let id = next_id.get_untracked();
+            *next_id.write() += 1;
+            EditorBlock {
+                id,
+                inner: block.into(),
+                focus_on_load: false,
+            }
+        })
+        .collect();
+    let removed = set_blocks.write().splice(.., new_blocks.clone()).collect();
+    undo_stack
+        .write()
+        .push_undo(UnReStep::new_block_change(0, removed, new_blocks));
+}
+
+/// Lets the user fill the editor with the text of a reference edition
+#[component]
+pub(super) fn SeedFromReference(
+    msname: String,
+    pagename: String,
+    blocks: ReadSignal<Vec<EditorBlock>>,
+    set_blocks: WriteSignal<Vec<EditorBlock>>,
+    next_id: RwSignal<usize>,
+    undo_stack: RwSignal<UnReStack>,
+) -> impl IntoView {
+    let editions = OnceResource::new(get_reference_editions());
+    let versification_schemes =
+        use_context::<OnceResource<Result<Vec<VersificationScheme>, ServerFnError>>>()
+            .expect("Editor provides versification schemes");
+    let selected_edition = RwSignal::new(None::<i64>);
+    let selected_scheme = RwSignal::new(None::<i64>);
+
+    let seed_action = Action::new(move |(edition_id, scheme_id): &(i64, i64)| {
+        let (msname, pagename, edition_id, scheme_id) =
+            (msname.clone(), pagename.clone(), *edition_id, *scheme_id);
+        async move { get_reference_blocks(msname, pagename, edition_id, scheme_id).await }
+    });
+
+    // replace the editor content as soon as the reference text arrives
+    Effect::new(move |_| {
+        if let Some(Ok(new_blocks)) = seed_action.value().get() {
+            replace_all_blocks(new_blocks, set_blocks, next_id, undo_stack);
+        };
+    });
+
+    let parse_id = |value: String| value.parse::<i64>().ok();
+
+    view! {
+        <div class="flex flex-row justify-start gap-2 p-2">
+            <span class="font-light text-xs">"Start from reference edition: "</span>
+            <Suspense fallback=|| view! { <span class="text-xs">"Loading reference editions..."</span> }>
+            {move || Suspend::new(async move {
+                editions.await.map(|editions| view! {
+                    <select class="text-sm"
+                        on:change:target=move |ev| selected_edition.set(parse_id(ev.target().value()))
+                    >
+                        <option value="">"(choose an edition)"</option>
+                        {editions.into_iter().map(|edition| view! {
+                            <option value=edition.id>{edition.name}</option>
+                        }).collect::<Vec<_>>()}
+                    </select>
+                })
+            })}
+            </Suspense>
+            <Suspense fallback=|| view! { <span class="text-xs">"Loading versification schemes..."</span> }>
+            {move || Suspend::new(async move {
+                versification_schemes.await.map(|schemes| view! {
+                    <select class="text-sm"
+                        on:change:target=move |ev| selected_scheme.set(parse_id(ev.target().value()))
+                    >
+                        <option value="">"(choose a versification scheme)"</option>
+                        {schemes.into_iter().map(|scheme| view! {
+                            <option value=scheme.id>{scheme.full_name}</option>
+                        }).collect::<Vec<_>>()}
+                    </select>
+                })
+            })}
+            </Suspense>
+            <button
+                disabled=move || selected_edition.get().is_none() || selected_scheme.get().is_none()
+                on:click=move |_| {
+                    if !blocks.read_untracked().is_empty()
+                        && !window()
+                            .confirm_with_message("This replaces the current transcription. Continue?")
+                            .unwrap_or(false)
+                    {
+                        return;
+                    };
+                    if let (Some(edition_id), Some(scheme_id)) = (selected_edition.get_untracked(), selected_scheme.get_untracked()) {
+                        seed_action.dispatch((edition_id, scheme_id));
+                    };
+                }
+            >"Fill in"</button>
+            <span class="text-xs">
+            {move || seed_action.pending().get().then_some("Loading reference text...")}
+            {move || match seed_action.value().get() {
+                Some(Err(e)) => Some(e.to_string()),
+                _ => None,
+            }}
+            </span>
+        </div>
+    }
+}
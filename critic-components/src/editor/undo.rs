@@ -87,6 +87,8 @@ pub(super) enum UnReStep {
     /// - a block was split into multiple blocks
     /// - two blocks were merged
     BlockChange(BlockChange),
+    /// Data inside several blocks has changed at once (e.g. a replace-all)
+    MultiDataChange(MultiDataChange),
 }
 impl UnReStep {
     pub fn new_data_change(
@@ -120,6 +122,15 @@ impl UnReStep {
     pub fn new_swap(physical_index_1: usize, physical_index_2: usize) -> Self {
         Self::BlockSwap(BlockSwap::new(physical_index_1, physical_index_2))
     }
+    /// Changes are given as (logical_index, old_inner_block, new_inner_block)
+    pub fn new_multi_data_change(changes: Vec<(usize, Block, Block)>) -> Self {
+        Self::MultiDataChange(MultiDataChange::new(
+            changes
+                .into_iter()
+                .map(|(id, old, new)| DataChange::new(id, old, new))
+                .collect(),
+        ))
+    }
 }
 impl Replay for UnReStep {
     fn replay(&self, blocks: &mut Vec<EditorBlock>) -> Result<(), ReplayError> {
@@ -127,6 +138,7 @@ impl Replay for UnReStep {
             Self::DataChange(x) => x.replay(blocks),
             Self::BlockSwap(x) => x.replay(blocks),
             Self::BlockChange(x) => x.replay(blocks),
+            Self::MultiDataChange(x) => x.replay(blocks),
         }
     }
 }
@@ -136,6 +148,7 @@ impl Invert for UnReStep {
             Self::DataChange(x) => Self::DataChange(x.invert()),
             Self::BlockSwap(x) => Self::BlockSwap(x.invert()),
             Self::BlockChange(x) => Self::BlockChange(x.invert()),
+            Self::MultiDataChange(x) => Self::MultiDataChange(x.invert()),
         }
     }
 }
@@ -240,6 +253,45 @@ impl Replay for DataChange {
 }
 impl UnRe for DataChange {}
 
+/// Several [`DataChange`]s that are undone and redone together
+#[derive(Debug, Clone)]
+pub(super) struct MultiDataChange {
+    /// The individual changes in the order they were applied
+    changes: Vec<DataChange>,
+}
+impl MultiDataChange {
+    pub fn new(changes: Vec<DataChange>) -> Self {
+        Self { changes }
+    }
+}
+impl Invert for MultiDataChange {
+    fn invert(self) -> Self {
+        // undo the changes in reverse order
+        Self {
+            changes: self.changes.into_iter().rev().map(Invert::invert).collect(),
+        }
+    }
+}
+impl Replay for MultiDataChange {
+    fn replay(&self, blocks: &mut Vec<EditorBlock>) -> Result<(), ReplayError> {
+        // check all changes first, so that we never apply only a part of them
+        for change in &self.changes {
+            let block = blocks
+                .iter()
+                .find(|blck| blck.id() == change.id)
+                .ok_or(ReplayError::BlockNotFound(change.id))?;
+            if change.old_inner != block.inner {
+                return Err(ReplayError::OldStateInconsistent);
+            };
+        }
+        for change in &self.changes {
+            change.replay(blocks)?;
+        }
+        Ok(())
+    }
+}
+impl UnRe for MultiDataChange {}
+
 /// The two blocks given by their logical IDs were swapped.
 #[derive(Debug, Clone)]
 pub(super) struct BlockSwap {
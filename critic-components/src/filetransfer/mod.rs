@@ -15,8 +15,11 @@ use components::{
     messages::{TransferComplete, TransferFailed},
 };
 
+/// Upload files with a POST request to `target_url`
+///
+/// The url is read when the upload starts, so it may depend on other inputs of the form.
 #[component]
-pub fn TransferPage(msname: String) -> impl IntoView {
+pub fn TransferPage(#[prop(into)] target_url: Signal<String>) -> impl IntoView {
     let files = RwSignal::new(Vec::<SendWrapper<File>>::new());
 
     let transfer_action = Action::new_local(move |files: &Vec<SendWrapper<File>>| {
@@ -24,8 +27,8 @@ pub fn TransferPage(msname: String) -> impl IntoView {
             .iter()
             .map(|wrapped| wrapped.clone().take())
             .collect::<Vec<_>>();
-        let name = msname.clone();
-        async move { services::transfer_files(&selected_files, &name).await }
+        let url = target_url.get_untracked();
+        async move { services::transfer_files(&selected_files, &url).await }
     });
     let transfer_pending = transfer_action.pending();
     let transfer_reply = transfer_action.value();
@@ -3,7 +3,7 @@
 use critic_shared::{FileTransferResponse, MAX_BODY_SIZE};
 use web_sys::FormData;
 
-pub async fn transfer_batch(files: &[web_sys::File], msname: &str) -> FileTransferResponse {
+pub async fn transfer_batch(files: &[web_sys::File], target_url: &str) -> FileTransferResponse {
     let form_data = FormData::new().unwrap();
     for file in files.iter() {
         form_data
@@ -13,15 +13,10 @@ pub async fn transfer_batch(files: &[web_sys::File], msname: &str) -> FileTransf
 
     let mut this_batch_response = FileTransferResponse::new();
 
-    match reqwasm::http::Request::post(&format!(
-        "{}{}/{}",
-        critic_shared::urls::UPLOAD_BASE_URL,
-        critic_shared::urls::PAGE_UPLOAD_API_ENDPOINT,
-        msname
-    ))
-    .body(form_data)
-    .send()
-    .await
+    match reqwasm::http::Request::post(target_url)
+        .body(form_data)
+        .send()
+        .await
     {
         Ok(res) => match res.json::<FileTransferResponse>().await {
             Ok(x) => {
@@ -44,8 +39,8 @@ pub async fn transfer_batch(files: &[web_sys::File], msname: &str) -> FileTransf
     this_batch_response
 }
 
-/// Transfer files to the api endpoint on the server with a POST request
-pub async fn transfer_files(files: &[web_sys::File], msname: &str) -> FileTransferResponse {
+/// Transfer files to the api endpoint `target_url` on the server with a POST request
+pub async fn transfer_files(files: &[web_sys::File], target_url: &str) -> FileTransferResponse {
     let mut response = FileTransferResponse::new();
     // loop; take as many files as possible until the upload limit is reached
     // send a batch, update the response with the results
@@ -61,7 +56,7 @@ pub async fn transfer_files(files: &[web_sys::File], msname: &str) -> FileTransf
         } else {
             // send this batch
             response.extend(
-                transfer_batch(&files[batch_start..batch_end], msname)
+                transfer_batch(&files[batch_start..batch_end], target_url)
                     .await
                     .err
                     .into_iter(),
@@ -82,7 +77,7 @@ pub async fn transfer_files(files: &[web_sys::File], msname: &str) -> FileTransf
     }
     // send the final batch
     response.extend(
-        transfer_batch(&files[batch_start..batch_end], msname)
+        transfer_batch(&files[batch_start..batch_end], target_url)
             .await
             .err
             .into_iter(),
@@ -0,0 +1,2 @@
+DROP TABLE reference_verse;
+DROP TABLE reference_edition;
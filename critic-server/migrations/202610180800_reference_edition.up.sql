@@ -0,0 +1,19 @@
+--- Reference editions used as base text for new transcriptions (e.g. a public-domain WLC import)
+CREATE TABLE reference_edition (
+	id BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	--- short name of the edition (e.g. WLC)
+	name TEXT NOT NULL UNIQUE,
+	--- language of the text in this edition (e.g. hbo-Hebr)
+	lang TEXT NOT NULL
+);
+--- The text of reference editions, verse by verse
+CREATE TABLE reference_verse (
+	--- the edition this verse belongs to
+	edition BIGINT NOT NULL REFERENCES reference_edition(id) ON DELETE CASCADE,
+	--- the (non-semantic) verse id
+	verse_id BIGINT NOT NULL REFERENCES verse(id),
+	--- the plain text of this verse in this edition
+	content TEXT NOT NULL,
+	--- each edition contains each verse at most once
+	UNIQUE(edition, verse_id)
+);
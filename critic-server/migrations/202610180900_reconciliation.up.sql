@@ -0,0 +1,17 @@
+--- Reconciliations of the published transcriptions of a page into one accepted transcription
+--- the reconciled file lives on the branch `rec/<manuscript>/<page>/<user>` in gitlab until it is merged
+CREATE TABLE reconciliation (
+	id BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+	--- the page that is reconciled
+	page BIGINT NOT NULL REFERENCES page(id),
+	--- the user working on this reconciliation
+	username TEXT NOT NULL REFERENCES user_session(username),
+	--- the branch holding the reconciled file
+	branch TEXT NOT NULL,
+	--- the iid of the merge request in gitlab, if one was opened already
+	merge_request BIGINT,
+	--- open: in progress or in review, accepted: merged, closed: abandoned
+	status TEXT NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'accepted', 'closed')),
+	--- only one reconciliation per page and user is active on a branch
+	UNIQUE(page, username, branch)
+);
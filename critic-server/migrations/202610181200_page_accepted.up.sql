@@ -0,0 +1,2 @@
+--- is there an accepted (reconciled) transcription of this page?
+ALTER TABLE page ADD COLUMN accepted BOOL NOT NULL DEFAULT false;
@@ -0,0 +1,3 @@
+DROP SEQUENCE external_user_id;
+ALTER TABLE user_session DROP COLUMN role;
+ALTER TABLE user_session DROP COLUMN login;
@@ -0,0 +1,6 @@
+--- which login backend created this session: forge, oidc or dev
+ALTER TABLE user_session ADD COLUMN login TEXT NOT NULL DEFAULT 'forge';
+--- the role (as gitlab access level) given at login, NULL if the role is looked up in the forge
+ALTER TABLE user_session ADD COLUMN role INT;
+--- users not known to the forge get negative ids, so that they never clash with ids from the forge
+CREATE SEQUENCE external_user_id START -1 INCREMENT -1 MAXVALUE -1;
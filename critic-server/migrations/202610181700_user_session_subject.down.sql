@@ -0,0 +1,3 @@
+ALTER TABLE user_session DROP CONSTRAINT user_session_issuer_subject;
+ALTER TABLE user_session DROP COLUMN subject;
+ALTER TABLE user_session DROP COLUMN issuer;
@@ -0,0 +1,4 @@
+--- users logging in with OpenID Connect are known by issuer and subject, NULL for other logins
+ALTER TABLE user_session ADD COLUMN issuer TEXT;
+ALTER TABLE user_session ADD COLUMN subject TEXT;
+ALTER TABLE user_session ADD CONSTRAINT user_session_issuer_subject UNIQUE (issuer, subject);
@@ -0,0 +1,383 @@
+//! Agreement between independent transcriptions of the same page
+//!
+//! Two transcriptions are compared on two levels: their blocks are aligned with a longest common
+//! subsequence (block agreement), and their plain text is compared with the edit distance
+//! (character agreement). Blocks that differ are reported as disagreements, grouped by type.
+//!
+//! Comparing transcriptions is expensive, so the agreement of a page is kept in the
+//! [`AgreementCache`] until one of its published transcriptions changes.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use critic_format::streamed::Block;
+use critic_shared::{
+    agreement::{AccuracyPoint, Disagreement, PageAgreement},
+    diff::{block_text, block_type_name, lcs_matches},
+};
+
+use crate::{
+    config::Config,
+    db::{get_manuscript, get_page, get_published_transcriptions_of_user, get_transcriptions},
+    export::{
+        text::{plain_text, TextExportOptions},
+        ExportError,
+    },
+    tei::{read_tei_file, read_transcription, reconciled_path, transcription_path},
+};
+
+/// The published transcriptions an agreement is computed from, with the modification time and
+/// size of their files
+type AgreementSources = Vec<(String, Option<(SystemTime, u64)>)>;
+
+/// The agreements computed so far, by manuscript and page name
+///
+/// An agreement is reused as long as the published transcriptions of its page are the same files
+/// as when it was computed.
+#[derive(Debug, Default)]
+pub struct AgreementCache(Mutex<HashMap<(String, String), (AgreementSources, PageAgreement)>>);
+impl AgreementCache {
+    fn get(
+        &self,
+        msname: &str,
+        pagename: &str,
+        sources: &AgreementSources,
+    ) -> Option<PageAgreement> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&(msname.to_string(), pagename.to_string()))
+            .filter(|(cached, _)| cached == sources)
+            .map(|(_, agreement)| agreement.clone())
+    }
+
+    fn insert(&self, sources: AgreementSources, agreement: PageAgreement) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                (agreement.msname.clone(), agreement.pagename.clone()),
+                (sources, agreement),
+            );
+    }
+}
+
+/// A short description of a block for disagreements with missing blocks
+fn describe_block(block: &Block) -> String {
+    match block {
+        Block::Text(_) | Block::Uncertain(_) => format!("\"{}\"", block_text(block)),
+        _ => block_text(block),
+    }
+}
+
+/// `name: a / b` if `a` and `b` differ
+fn field<T: PartialEq + core::fmt::Debug>(name: &str, a: &T, b: &T) -> Option<String> {
+    (a != b).then(|| format!("{name}: {a:?} / {b:?}"))
+}
+
+/// What differs between two blocks of the same type
+fn describe_difference(a: &Block, b: &Block) -> String {
+    let fields = match (a, b) {
+        (Block::Lacuna(a), Block::Lacuna(b)) => vec![
+            (a.n != b.n || a.unit != b.unit).then(|| {
+                format!(
+                    "extent: {} {} / {} {}",
+                    a.n,
+                    a.unit.name(),
+                    b.n,
+                    b.unit.name()
+                )
+            }),
+            field("reason", &a.reason, &b.reason),
+            field("certainty", &a.cert, &b.cert),
+        ],
+        (Block::Uncertain(a), Block::Uncertain(b)) => vec![
+            field("text", &a.content, &b.content),
+            field("certainty", &a.cert, &b.cert),
+            field("agent", &a.agent, &b.agent),
+        ],
+        (Block::Correction(a), Block::Correction(b)) => {
+            let hands = |c: &critic_format::streamed::Correction| {
+                c.versions
+                    .iter()
+                    .map(|v| v.hand.clone())
+                    .collect::<Vec<_>>()
+            };
+            let contents = |c: &critic_format::streamed::Correction| {
+                c.versions
+                    .iter()
+                    .map(|v| v.content.clone())
+                    .collect::<Vec<_>>()
+            };
+            vec![
+                field("hands", &hands(a), &hands(b)),
+                field("versions", &contents(a), &contents(b)),
+            ]
+        }
+        (Block::Abbreviation(a), Block::Abbreviation(b)) => vec![
+            field("surface", &a.surface, &b.surface),
+            field("expansion", &a.expansion, &b.expansion),
+        ],
+        (Block::Space(a), Block::Space(b)) => vec![(a.quantity != b.quantity || a.unit != b.unit)
+            .then(|| {
+                format!(
+                    "extent: {} {} / {} {}",
+                    a.quantity,
+                    a.unit.name(),
+                    b.quantity,
+                    b.unit.name()
+                )
+            })],
+        (Block::Text(a), Block::Text(b)) => vec![field("text", &a.content, &b.content)],
+        (Block::Anchor(a), Block::Anchor(b)) => vec![field("anchor", &a.anchor_id, &b.anchor_id)],
+        _ => vec![],
+    };
+    let fields = fields.into_iter().flatten().collect::<Vec<_>>();
+    if fields.is_empty() {
+        // only attributes we do not describe (e.g. the language) differ
+        format!("{} / {}", describe_block(a), describe_block(b))
+    } else {
+        fields.join(", ")
+    }
+}
+
+/// The pairs of indices of equal blocks in a longest common subsequence
+fn block_matches(a: &[Block], b: &[Block]) -> Vec<(usize, usize)> {
+    lcs_matches(a, b, |a, b| a == b)
+}
+
+/// The share of blocks two transcriptions agree on (Dice coefficient of the aligned blocks)
+pub fn block_agreement(a: &[Block], b: &[Block]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    };
+    2.0 * block_matches(a, b).len() as f64 / (a.len() + b.len()) as f64
+}
+
+/// The similarity of two texts based on their edit distance (0 to 1)
+pub fn character_agreement(a: &str, b: &str) -> f64 {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    };
+    // levenshtein distance, keeping only the last row
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.iter().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    1.0 - row[b.len()] as f64 / longest as f64
+}
+
+/// The disagreements between the transcriptions of `users.0` (`a`) and `users.1` (`b`), by type
+fn disagreements(
+    a: &[Block],
+    b: &[Block],
+    users: &(String, String),
+    res: &mut BTreeMap<String, Vec<Disagreement>>,
+) {
+    let mut push = |block_type: &str, description: String| {
+        res.entry(block_type.to_string())
+            .or_default()
+            .push(Disagreement {
+                users: users.clone(),
+                description,
+            });
+    };
+    let end = (a.len(), b.len());
+    let (mut last_a, mut last_b) = (0, 0);
+    for (match_a, match_b) in block_matches(a, b).into_iter().chain(std::iter::once(end)) {
+        let mut only_b = b[last_b..match_b].iter().collect::<Vec<_>>();
+        for block_a in &a[last_a..match_a] {
+            // pair a block with the first unmatched block of the same type in the other one
+            match only_b
+                .iter()
+                .position(|block_b| block_type_name(block_b) == block_type_name(block_a))
+            {
+                Some(idx) => {
+                    let block_b = only_b.remove(idx);
+                    push(
+                        block_type_name(block_a),
+                        describe_difference(block_a, block_b),
+                    );
+                }
+                None => push(
+                    block_type_name(block_a),
+                    format!("only {}: {}", users.0, describe_block(block_a)),
+                ),
+            };
+        }
+        for block_b in only_b {
+            push(
+                block_type_name(block_b),
+                format!("only {}: {}", users.1, describe_block(block_b)),
+            );
+        }
+        (last_a, last_b) = (match_a + 1, match_b + 1);
+    }
+}
+
+/// The agreement between all published transcriptions of a page, from the cache if none of
+/// them changed since it was last computed
+pub async fn page_agreement(
+    config: &Config,
+    msname: &str,
+    pagename: &str,
+) -> Result<PageAgreement, ExportError> {
+    let page = get_page(&config.db, msname, pagename)
+        .await
+        .map_err(ExportError::DB)?;
+    let usernames = get_transcriptions(&config.db, page.id)
+        .await
+        .map_err(ExportError::DB)?
+        .into_iter()
+        .filter(|t| t.published)
+        .map(|t| t.username)
+        .collect::<Vec<_>>();
+    let sources = usernames
+        .iter()
+        .map(|username| {
+            let path = transcription_path(&config.data_directory, msname, pagename, username);
+            let changed = std::fs::metadata(path)
+                .and_then(|meta| Ok((meta.modified()?, meta.len())))
+                .ok();
+            (username.clone(), changed)
+        })
+        .collect::<AgreementSources>();
+    if let Some(agreement) = config.agreement_cache.get(msname, pagename, &sources) {
+        return Ok(agreement);
+    };
+
+    let mut transcriptions = vec![];
+    for username in usernames {
+        // one broken transcription should not hide the agreement of the others
+        let blocks = match read_transcription(&config.data_directory, msname, pagename, &username) {
+            Ok(tei) => tei.content,
+            Err(e) => {
+                tracing::warn!(
+                    "Leaving the transcription of {msname} {pagename} by {username} out of the agreement: {e}"
+                );
+                continue;
+            }
+        };
+        let text = plain_text(&blocks, &TextExportOptions::default());
+        transcriptions.push((username, blocks, text));
+    }
+
+    let (mut block_sum, mut character_sum, mut pairs) = (0.0, 0.0, 0);
+    let mut grouped = BTreeMap::new();
+    for (i, (user_a, blocks_a, text_a)) in transcriptions.iter().enumerate() {
+        for (user_b, blocks_b, text_b) in &transcriptions[i + 1..] {
+            block_sum += block_agreement(blocks_a, blocks_b);
+            character_sum += character_agreement(text_a, text_b);
+            pairs += 1;
+            disagreements(
+                blocks_a,
+                blocks_b,
+                &(user_a.to_string(), user_b.to_string()),
+                &mut grouped,
+            );
+        }
+    }
+    let agreement = PageAgreement {
+        msname: msname.to_string(),
+        pagename: pagename.to_string(),
+        usernames: transcriptions
+            .into_iter()
+            .map(|(username, _, _)| username)
+            .collect(),
+        block_agreement: (pairs != 0).then(|| block_sum / pairs as f64),
+        character_agreement: (pairs != 0).then(|| character_sum / pairs as f64),
+        disagreements: grouped,
+    };
+    config.agreement_cache.insert(sources, agreement.clone());
+    Ok(agreement)
+}
+
+/// The accuracy of the published transcriptions of a user against the reconciled transcriptions,
+/// in the order the user started them
+///
+/// Pages that have not been reconciled yet are left out.
+pub async fn user_accuracy(
+    config: &Config,
+    username: &str,
+) -> Result<Vec<AccuracyPoint>, ExportError> {
+    let mut res = vec![];
+    for transcription in get_published_transcriptions_of_user(&config.db, username)
+        .await
+        .map_err(ExportError::DB)?
+    {
+        let path = reconciled_path(
+            &config.data_directory,
+            &transcription.msname,
+            &transcription.pagename,
+        );
+        if !std::path::Path::new(&path).exists() {
+            continue;
+        };
+        let read = read_tei_file(&path).and_then(|reconciled| {
+            read_transcription(
+                &config.data_directory,
+                &transcription.msname,
+                &transcription.pagename,
+                username,
+            )
+            .map(|own| (own.content, reconciled.content))
+        });
+        let (own, reconciled) = match read {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::warn!(
+                    "Leaving {} {} out of the accuracy of {username}: {e}",
+                    transcription.msname,
+                    transcription.pagename
+                );
+                continue;
+            }
+        };
+        let options = TextExportOptions::default();
+        res.push(AccuracyPoint {
+            block_accuracy: block_agreement(&own, &reconciled),
+            character_accuracy: character_agreement(
+                &plain_text(&own, &options),
+                &plain_text(&reconciled, &options),
+            ),
+            msname: transcription.msname,
+            pagename: transcription.pagename,
+        });
+    }
+    Ok(res)
+}
+
+/// The agreement for every page of a manuscript with at least two published transcriptions
+pub async fn manuscript_agreement(
+    config: &Config,
+    msname: &str,
+) -> Result<Vec<PageAgreement>, ExportError> {
+    let mut pages = get_manuscript(&config.db, msname)
+        .await
+        .map_err(ExportError::DB)?
+        .pages;
+    pages.sort_by_key(|page| page.id);
+    let mut res = vec![];
+    for page in pages {
+        let agreement = page_agreement(config, msname, &page.name).await?;
+        if agreement.usernames.len() >= 2 {
+            res.push(agreement);
+        };
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod test;
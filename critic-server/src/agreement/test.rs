@@ -0,0 +1,115 @@
+//! Tests for comparing transcriptions
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+
+use critic_format::streamed::{Block, BreakType, Paragraph};
+use critic_shared::agreement::PageAgreement;
+
+use super::{block_agreement, character_agreement, disagreements, AgreementCache};
+
+fn text(content: &str) -> Block {
+    Block::Text(Paragraph {
+        lang: "hbo-Hebr".to_string(),
+        content: content.to_string(),
+    })
+}
+
+fn assert_close(a: f64, b: f64) {
+    assert!((a - b).abs() < 1e-9, "{a} != {b}");
+}
+
+#[test]
+fn character_agreement_of_equal_texts() {
+    assert_close(character_agreement("בראשית ברא", "בראשית ברא"), 1.0);
+    assert_close(character_agreement("", ""), 1.0);
+}
+
+#[test]
+fn character_agreement_of_different_texts() {
+    assert_close(character_agreement("abc", ""), 0.0);
+    assert_close(character_agreement("abc", "xyz"), 0.0);
+    // one substitution in four characters
+    assert_close(character_agreement("abcd", "abxd"), 0.75);
+    // one insertion, measured against the longer text
+    assert_close(character_agreement("abcd", "abcde"), 0.8);
+}
+
+#[test]
+fn character_agreement_counts_characters_not_bytes() {
+    // ש and ת are two bytes each
+    assert_close(character_agreement("בראשית", "בראשיט"), 5.0 / 6.0);
+}
+
+#[test]
+fn character_agreement_is_symmetric() {
+    assert_close(
+        character_agreement("kitten", "sitting"),
+        character_agreement("sitting", "kitten"),
+    );
+    assert_close(character_agreement("kitten", "sitting"), 1.0 - 3.0 / 7.0);
+}
+
+#[test]
+fn block_agreement_of_equal_transcriptions() {
+    let blocks = vec![text("בראשית"), Block::Break(BreakType::Line), text("ברא")];
+    assert_close(block_agreement(&blocks, &blocks), 1.0);
+    assert_close(block_agreement(&[], &[]), 1.0);
+}
+
+#[test]
+fn block_agreement_of_different_transcriptions() {
+    let a = vec![text("בראשית"), Block::Break(BreakType::Line), text("ברא")];
+    let b = vec![text("בראשית"), text("ברא")];
+    // two of five blocks agree on each side
+    assert_close(block_agreement(&a, &b), 0.8);
+    assert_close(block_agreement(&a, &[]), 0.0);
+    assert_close(block_agreement(&[text("a")], &[text("b")]), 0.0);
+}
+
+#[test]
+fn disagreements_pair_blocks_of_the_same_type() {
+    let a = vec![text("בראשית"), Block::Break(BreakType::Line), text("ברא")];
+    let b = vec![text("בראשית"), text("בדא")];
+    let mut res = BTreeMap::new();
+    disagreements(&a, &b, &("alice".to_string(), "bob".to_string()), &mut res);
+    assert_eq!(res.len(), 2);
+    assert_eq!(res["Break"].len(), 1);
+    assert_eq!(res["Break"][0].description, "only alice: line break");
+    assert_eq!(res["Text"].len(), 1);
+    assert_eq!(
+        res["Text"][0].description,
+        "text: \"ברא\" / \"בדא\"".to_string()
+    );
+}
+
+#[test]
+fn cached_agreements_are_dropped_when_a_transcription_changes() {
+    let agreement = PageAgreement {
+        msname: "Leningradensis".to_string(),
+        pagename: "1r".to_string(),
+        usernames: vec!["alice".to_string(), "bob".to_string()],
+        block_agreement: Some(1.0),
+        character_agreement: Some(0.9),
+        disagreements: BTreeMap::new(),
+    };
+    let changed = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let sources = vec![
+        ("alice".to_string(), Some((changed, 100))),
+        ("bob".to_string(), Some((changed, 120))),
+    ];
+    let cache = AgreementCache::default();
+    assert_eq!(cache.get("Leningradensis", "1r", &sources), None);
+    cache.insert(sources.clone(), agreement.clone());
+    assert_eq!(cache.get("Leningradensis", "1r", &sources), Some(agreement));
+    assert_eq!(cache.get("Leningradensis", "1v", &sources), None);
+
+    let mut edited = sources.clone();
+    edited[1].1 = Some((changed + Duration::from_secs(1), 120));
+    assert_eq!(cache.get("Leningradensis", "1r", &edited), None);
+    let mut another_user = sources.clone();
+    another_user.push(("carol".to_string(), Some((changed, 90))));
+    assert_eq!(cache.get("Leningradensis", "1r", &another_user), None);
+}
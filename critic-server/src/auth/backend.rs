@@ -1,28 +1,47 @@
 //! The backend endpoints for authentication and session handling for the oauth flow
+//!
+//! Users log in with the forge, with the OpenID provider or (during development) by picking a
+//! configured user; `/login` lets them choose when several ways are configured.
 
 /// keys used in the session store
 const CSRF_STATE_KEY: &str = "oauth.csrf-state";
 const PKCE_VERIFIER_KEY: &str = "oauth.pkce_verifier";
 const NEXT_URL_KEY: &str = "oauth.next";
+const NONCE_KEY: &str = "oidc.nonce";
 
-use axum::{extract::Query, response::IntoResponse, Router};
+use axum::{
+    extract::{Form, Query},
+    response::{Html, IntoResponse, Response},
+    Router,
+};
 use axum_login::tower_sessions::Session;
-use oauth2::CsrfToken;
+use oauth2::{CsrfToken, PkceCodeVerifier};
 use reqwest::StatusCode;
 use serde::Deserialize;
 use tracing::{error, warn};
 
-use super::{AuthSession, Credentials};
+use super::{AuthSession, Credentials, OauthCredentials};
 
 pub fn auth_router() -> Router {
     Router::new()
-        // redirect to the oauth endpoint on gitlab
+        // choose how to log in, or redirect if there is only one way
         .route("/login", axum::routing::get(login_get_endpoint))
-        // the endpoint that gitlab will redirect into after successful login there
+        // redirect to the oauth endpoint of the forge
+        .route("/login/forge", axum::routing::get(forge_login_get_endpoint))
+        // redirect to the OpenID provider
+        .route("/login/oidc", axum::routing::get(oidc_login_get_endpoint))
+        // pick a configured user, without any identity provider
+        .route(
+            "/login/dev",
+            axum::routing::get(dev_login_get_endpoint).post(dev_login_post_endpoint),
+        )
+        // the endpoint that the forge will redirect into after successful login there
         .route(
             "/oauth/redirect",
             axum::routing::get(oauth_redirect_endpoint),
         )
+        // the endpoint that the OpenID provider will redirect into after successful login there
+        .route("/oidc/redirect", axum::routing::get(oidc_redirect_endpoint))
         // logout an existing session
         .route("/logout", axum::routing::post(logout_post_endpoint))
 }
@@ -37,8 +56,80 @@ pub async fn login_get_endpoint(
     session: Session,
     Query(next): Query<LoginQueryNext>,
 ) -> impl IntoResponse {
-    let (auth_url, csrf_state, pkce_verifier) = auth_session.backend.authorize_url();
+    if let Some(next_location) = next.next {
+        session
+            .insert(NEXT_URL_KEY, next_location)
+            .await
+            .expect("String serialization is infallible.");
+    }
+
+    let backend = &auth_session.backend;
+    let mut options = vec![];
+    if backend.has_forge_login() {
+        options.push(("/login/forge", "Forge account (maintainers)".to_string()));
+    };
+    if let Some(oidc) = &backend.oidc {
+        options.push(("/login/oidc", oidc.label().to_string()));
+    };
+    if backend.dev_login.is_some() {
+        options.push(("/login/dev", "Development login".to_string()));
+    };
+
+    match options.as_slice() {
+        [] => {
+            error!("Cannot log in a user: no way of logging in is configured.");
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Logging in is not configured for this critic instance.",
+            )
+                .into_response()
+        }
+        [(location, _)] => axum::response::Redirect::to(location).into_response(),
+        options => login_page(
+            &options
+                .iter()
+                .map(|(location, label)| {
+                    format!(
+                        r#"<li><a href="{location}">{}</a></li>"#,
+                        html_escape(label)
+                    )
+                })
+                .collect::<String>(),
+        )
+        .into_response(),
+    }
+}
 
+/// A minimal page with `list_items` for users to choose from
+fn login_page(list_items: &str) -> Html<String> {
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><title>critic - log in</title></head>
+<body>
+<h1>Log in to critic</h1>
+<ul>
+{list_items}
+</ul>
+</body>
+</html>"#
+    ))
+}
+
+/// Escape text to put into html
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Remember the secrets of a login flow in the session
+async fn store_flow_secrets(
+    session: &Session,
+    csrf_state: CsrfToken,
+    pkce_verifier: PkceCodeVerifier,
+) {
     session
         .insert(CSRF_STATE_KEY, csrf_state.secret())
         .await
@@ -47,34 +138,110 @@ pub async fn login_get_endpoint(
         .insert(PKCE_VERIFIER_KEY, pkce_verifier.secret())
         .await
         .expect("String serialization is infallible.");
-    if let Some(next_location) = next.next {
-        session
-            .insert(NEXT_URL_KEY, next_location)
-            .await
-            .expect("String serialization is infallible.");
-    }
+}
 
-    // insert next location in to the user session??
+pub async fn forge_login_get_endpoint(
+    auth_session: AuthSession,
+    session: Session,
+) -> impl IntoResponse {
+    let Some((auth_url, csrf_state, pkce_verifier)) = auth_session.backend.authorize_url() else {
+        error!("Cannot log in a user: oauth is not configured.");
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Logging in with the forge is not configured for this critic instance.",
+        )
+            .into_response();
+    };
+    store_flow_secrets(&session, csrf_state, pkce_verifier).await;
     axum::response::Redirect::to(auth_url.as_str()).into_response()
 }
 
+pub async fn oidc_login_get_endpoint(
+    auth_session: AuthSession,
+    session: Session,
+) -> impl IntoResponse {
+    let Some(oidc) = &auth_session.backend.oidc else {
+        error!("Cannot log in a user: OpenID Connect is not configured.");
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Logging in with single sign-on is not configured for this critic instance.",
+        )
+            .into_response();
+    };
+    let (auth_url, csrf_state, pkce_verifier, nonce) = match oidc.authorize_url().await {
+        Ok(x) => x,
+        Err(e) => {
+            error!("Cannot log in a user with OpenID Connect: {e}");
+            return (
+                StatusCode::BAD_GATEWAY,
+                "The single sign-on provider cannot be reached.",
+            )
+                .into_response();
+        }
+    };
+    store_flow_secrets(&session, csrf_state, pkce_verifier).await;
+    session
+        .insert(NONCE_KEY, nonce)
+        .await
+        .expect("String serialization is infallible.");
+    axum::response::Redirect::to(auth_url.as_str()).into_response()
+}
+
+pub async fn dev_login_get_endpoint(auth_session: AuthSession) -> impl IntoResponse {
+    let Some(dev_login) = &auth_session.backend.dev_login else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    login_page(
+        &dev_login
+            .users
+            .iter()
+            .map(|user| {
+                let username = html_escape(&user.username);
+                format!(
+                    r#"<li><form method="post" action="/login/dev"><input type="hidden" name="username" value="{username}"><button type="submit">{username} ({:?})</button></form></li>"#,
+                    user.role
+                )
+            })
+            .collect::<String>(),
+    )
+    .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DevLoginForm {
+    username: String,
+}
+
+pub async fn dev_login_post_endpoint(
+    auth_session: AuthSession,
+    session: Session,
+    Form(DevLoginForm { username }): Form<DevLoginForm>,
+) -> impl IntoResponse {
+    if auth_session.backend.dev_login.is_none() {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    finish_login(auth_session, session, Credentials::Dev { username }).await
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct AuthzResp {
     code: String,
     state: CsrfToken,
 }
 
-pub async fn oauth_redirect_endpoint(
-    mut auth_session: AuthSession,
-    session: Session,
-    Query(AuthzResp {
+/// Get the secrets of the login flow back from the session
+///
+/// On failure, the response to send is returned.
+async fn oauth_credentials(
+    session: &Session,
+    AuthzResp {
         code,
         state: csrf_state,
-    }): Query<AuthzResp>,
-) -> impl IntoResponse {
+    }: AuthzResp,
+) -> Result<OauthCredentials, Response> {
     let Ok(Some(known_csrf_state)) = session.get(CSRF_STATE_KEY).await else {
         warn!("oauth redirect called with inconsistent csrf state");
-        return StatusCode::BAD_REQUEST.into_response();
+        return Err(StatusCode::BAD_REQUEST.into_response());
     };
 
     let pkce_verifier = match session.get(PKCE_VERIFIER_KEY).await {
@@ -83,38 +250,45 @@ pub async fn oauth_redirect_endpoint(
         // the pkce verifier was not set
         Ok(None) => {
             error!("The PKCE verifier was missing while dealing with an oauth redirect with valid CSRF state");
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
         }
         // unable to get data from the session
         Err(e) => {
             error!("Unable to get Session data while dealing with an oauth redirect with valid CSRF state");
             error!("Original error: {e}");
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
         }
     };
 
-    let creds = Credentials {
+    Ok(OauthCredentials {
         code,
         csrf_state,
         known_csrf_state,
         pkce_verifier,
-    };
+    })
+}
 
+/// Authenticate and log in the user, then redirect to where they wanted to go
+async fn finish_login(
+    mut auth_session: AuthSession,
+    session: Session,
+    creds: Credentials,
+) -> Response {
     let user = match auth_session.authenticate(creds).await {
         Ok(Some(user)) => user,
         Ok(None) => {
-            warn!("Got Oauth Redirect but csrf state was invalid.");
+            warn!("Got a login, but csrf state was invalid or the user has no role.");
             return StatusCode::UNAUTHORIZED.into_response();
         }
         Err(e) => {
-            error!("Tried to authenticate user in the oauth redirect, but got the following error: {e}");
+            error!("Tried to authenticate user in the login redirect, but got the following error: {e}");
             return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
     };
 
     if let Err(e) = auth_session.login(&user).await {
         error!(
-            "Unable to login user {} after getting correct oauth redirect: {e}",
+            "Unable to login user {} after getting correct login redirect: {e}",
             user.username
         );
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
@@ -127,6 +301,34 @@ pub async fn oauth_redirect_endpoint(
     }
 }
 
+pub async fn oauth_redirect_endpoint(
+    auth_session: AuthSession,
+    session: Session,
+    Query(authz): Query<AuthzResp>,
+) -> impl IntoResponse {
+    let creds = match oauth_credentials(&session, authz).await {
+        Ok(x) => x,
+        Err(response) => return response,
+    };
+    finish_login(auth_session, session, Credentials::Forge(creds)).await
+}
+
+pub async fn oidc_redirect_endpoint(
+    auth_session: AuthSession,
+    session: Session,
+    Query(authz): Query<AuthzResp>,
+) -> impl IntoResponse {
+    let oauth = match oauth_credentials(&session, authz).await {
+        Ok(x) => x,
+        Err(response) => return response,
+    };
+    let Ok(Some(nonce)) = session.remove::<String>(NONCE_KEY).await else {
+        warn!("OpenID Connect redirect called without a nonce in the session");
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    finish_login(auth_session, session, Credentials::Oidc { oauth, nonce }).await
+}
+
 pub async fn logout_post_endpoint(mut auth_session: AuthSession) -> impl IntoResponse {
     match auth_session.logout().await {
         Ok(_) => axum::response::Redirect::to("/login").into_response(),
@@ -0,0 +1,52 @@
+//! Logging in without any identity provider, for development and end-to-end tests
+//!
+//! The users and their roles are listed in the `[dev_login]` section of the config, anyone who can
+//! reach critic can log in as any of them by picking them from a form. This is therefore refused
+//! in release builds unless `allow_in_release` is set.
+//!
+//! Together with the local forge (with the same users), critic runs without gitlab at all.
+
+use serde::Deserialize;
+
+use super::NormalizedTokenResponse;
+use crate::gitlab::GitlabUserRole;
+
+/// A user who can log in with the development login
+#[derive(Deserialize, Debug, Clone)]
+pub struct DevUser {
+    pub username: String,
+    pub role: GitlabUserRole,
+}
+
+/// Config for the development login
+#[derive(Deserialize, Debug, Clone)]
+pub struct DevLoginConfig {
+    /// Allow the development login in a release build
+    ///
+    /// Only set this for test deployments that are not reachable from the internet.
+    #[serde(default)]
+    pub allow_in_release: bool,
+    #[serde(default)]
+    pub users: Vec<DevUser>,
+}
+impl DevLoginConfig {
+    /// Is the development login allowed in this build?
+    pub fn is_allowed(&self) -> bool {
+        cfg!(debug_assertions) || self.allow_in_release
+    }
+
+    pub fn user(&self, username: &str) -> Option<&DevUser> {
+        self.users.iter().find(|user| user.username == username)
+    }
+}
+
+/// Fresh tokens for a session of a development user
+///
+/// The access token is random, so that logging in again ends older sessions of the same user.
+pub fn dev_tokens() -> NormalizedTokenResponse {
+    NormalizedTokenResponse {
+        access_token: oauth2::CsrfToken::new_random().into_secret(),
+        refresh_token: String::default(),
+        expires_at: time::OffsetDateTime::now_utc() + time::Duration::days(1),
+    }
+}
@@ -1,6 +1,7 @@
 //! All types and endpoints for authenticating users
 
-use axum::http::header::{AUTHORIZATION, USER_AGENT};
+use std::sync::Arc;
+
 use axum_login::{AuthUser, AuthnBackend, UserId};
 use oauth2::{
     url::Url, AuthorizationCode, CsrfToken, PkceCodeChallenge, PkceCodeVerifier, Scope,
@@ -11,15 +12,16 @@ use serde::Deserialize;
 use crate::{
     config::Config,
     db::{self, DBError},
+    forge::{Forge, ForgeError},
 };
 
 // some basic types used across the app
-/// The JSON object returned from gitlabs get-user endpoint
+/// A user as known to the forge (this is the JSON object returned from gitlabs get-user endpoint)
 #[derive(Debug, Deserialize)]
 pub struct UserInfo {
-    /// ID of the user in gitlab - we use the same ID in the internal DB here
+    /// ID of the user in the forge - we use the same ID in the internal DB here
     pub id: i32,
-    /// username of the user in gitlab - we use the same here
+    /// username of the user in the forge - we use the same here
     pub username: String,
 }
 impl From<AuthenticatedUser> for UserInfo {
@@ -38,7 +40,16 @@ pub struct AuthenticatedUser {
     pub username: String,
     pub access_token: String,
     pub refresh_token: String,
+    /// when the access token expires, the session itself ends on inactivity
     pub expires_at: time::OffsetDateTime,
+    /// the login backend the user logged in with (`forge`, `oidc` or `dev`)
+    pub login: String,
+    /// the role given at login (as gitlab access level), None if the forge knows the role
+    pub role: Option<i32>,
+    /// the OpenID provider of the user, None for other logins
+    pub issuer: Option<String>,
+    /// the identifier of the user at the OpenID provider, None for other logins
+    pub subject: Option<String>,
 }
 impl std::fmt::Debug for AuthenticatedUser {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -48,6 +59,10 @@ impl std::fmt::Debug for AuthenticatedUser {
             .field("access_token", &"[redacted]")
             .field("refresh_token", &"[redacted]")
             .field("expires_at", &self.expires_at)
+            .field("login", &self.login)
+            .field("role", &self.role)
+            .field("issuer", &self.issuer)
+            .field("subject", &self.subject)
             .finish()
     }
 }
@@ -107,6 +122,8 @@ impl
 
 /// has all the backend APIs for auth flows
 pub mod backend;
+pub mod dev;
+pub mod oidc;
 
 impl AuthUser for AuthenticatedUser {
     type Id = i32;
@@ -122,7 +139,7 @@ impl AuthUser for AuthenticatedUser {
 
 /// Known secrets for this Oauth2 Flow before getting authorization_token
 #[derive(Debug, Clone, Deserialize)]
-pub struct Credentials {
+pub struct OauthCredentials {
     /// the authorization code returned from the oauth server
     code: String,
     /// the CSRF state returned from the oauth server
@@ -133,16 +150,38 @@ pub struct Credentials {
     pkce_verifier: String,
 }
 
+/// What a user presents to log in, depending on the login backend
+#[derive(Debug, Clone, Deserialize)]
+pub enum Credentials {
+    /// the oauth2 flow of the forge
+    Forge(OauthCredentials),
+    /// the OpenID Connect flow, with the nonce sent in the authorization request
+    Oidc {
+        oauth: OauthCredentials,
+        nonce: String,
+    },
+    /// the development login, with the username picked from the form
+    Dev { username: String },
+}
+
 /// The types of Problems that can occur while doing an oauth2 flow
 #[derive(Debug)]
 pub enum BackendError {
     /// failure while talking to our postgres
     DB(DBError),
-    /// failure while calling the /oauth/token endpoint in gitlab - could not get token
+    /// failure while calling the /oauth/token endpoint in the forge - could not get token
     TokenExchange(String),
-    Reqwest(reqwest::Error),
-    Gitlab(reqwest::Error),
+    /// failure while asking the forge who the user is
+    Forge(ForgeError),
     TokenResponse(NormalizeTokenResponseError),
+    /// users cannot log in with oauth, because it is not configured
+    OauthNotConfigured,
+    /// failure while logging in with the OpenID provider
+    Oidc(oidc::OidcError),
+    /// users cannot log in with OpenID Connect, because it is not configured
+    OidcNotConfigured,
+    /// users cannot log in with the development login, because it is not configured
+    DevLoginNotConfigured,
 }
 impl core::fmt::Display for BackendError {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
@@ -156,74 +195,106 @@ impl core::fmt::Display for BackendError {
                     "Failure while exchanging authorization code for access token: {e}"
                 )
             }
-            Self::Reqwest(e) => {
-                write!(f, "Failure sending http request: {e}")
-            }
-            Self::Gitlab(e) => {
-                write!(f, "Failure to parse response JSON from gitlab API: {e}")
+            Self::Forge(e) => {
+                write!(f, "Failure getting the user from the forge: {e}")
             }
             Self::TokenResponse(e) => {
                 write!(
                     f,
-                    "Token response from gitlabs api was not as expected: {e}"
+                    "Token response from the forges api was not as expected: {e}"
                 )
             }
+            Self::OauthNotConfigured => {
+                write!(f, "Logging in with oauth is not configured")
+            }
+            Self::Oidc(e) => {
+                write!(f, "{e}")
+            }
+            Self::OidcNotConfigured => {
+                write!(f, "Logging in with OpenID Connect is not configured")
+            }
+            Self::DevLoginNotConfigured => {
+                write!(f, "The development login is not configured")
+            }
         }
     }
 }
 impl std::error::Error for BackendError {}
 
+/// Log in users with the oauth2 flow of the forge or with an OpenID provider
 #[derive(Debug, Clone)]
-pub struct GitlabOauthBackend {
+pub struct LoginBackend {
     db: sqlx::Pool<sqlx::Postgres>,
-    client: crate::config::OauthClient,
+    /// None if oauth is not configured
+    client: Option<crate::config::OauthClient>,
+    forge: Arc<dyn Forge>,
+    /// None if OpenID Connect is not configured
+    pub oidc: Option<Arc<oidc::OidcProvider>>,
+    /// None if the development login is not enabled
+    pub dev_login: Option<Arc<dev::DevLoginConfig>>,
 }
 
-impl GitlabOauthBackend {
-    pub fn new(config: std::sync::Arc<Config>) -> Self {
+impl LoginBackend {
+    pub fn new(config: Arc<Config>) -> Self {
         let db = config.db.clone();
         let client = config.oauth_client.clone();
-        Self { db, client }
+        let forge = config.forge.clone();
+        let oidc = config.oidc.clone();
+        let dev_login = config.dev_login.clone();
+        Self {
+            db,
+            client,
+            forge,
+            oidc,
+            dev_login,
+        }
+    }
+
+    /// Can users log in with the forge?
+    pub fn has_forge_login(&self) -> bool {
+        self.client.is_some()
     }
 
     /// URL to show to the user to start the oauth flow
     /// RETURNS
     ///     the url to show
     ///     the CsrfToken in use
-    pub fn authorize_url(&self) -> (Url, CsrfToken, PkceCodeVerifier) {
+    /// or None if oauth is not configured
+    pub fn authorize_url(&self) -> Option<(Url, CsrfToken, PkceCodeVerifier)> {
+        let client = self.client.as_ref()?;
         let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
-        let (url, csrf_token) = self
-            .client
+        let scopes = self
+            .forge
+            .oauth_endpoints()
+            .map(|endpoints| endpoints.scopes)
+            .unwrap_or_default();
+        let (url, csrf_token) = client
             .authorize_url(CsrfToken::new_random)
-            .add_scope(Scope::new("api".to_string()))
+            .add_scopes(scopes.into_iter().map(Scope::new))
             .set_pkce_challenge(pkce_challenge)
             .url();
-        (url, csrf_token, pkce_verifier)
+        Some((url, csrf_token, pkce_verifier))
     }
-}
-
-#[async_trait::async_trait]
-impl AuthnBackend for GitlabOauthBackend {
-    type User = AuthenticatedUser;
-    type Credentials = Credentials;
-    type Error = BackendError;
 
-    async fn authenticate(
+    /// Log in a user with the oauth2 flow of the forge
+    async fn authenticate_forge(
         &self,
-        creds: Self::Credentials,
-    ) -> Result<Option<Self::User>, Self::Error> {
+        creds: OauthCredentials,
+    ) -> Result<Option<AuthenticatedUser>, BackendError> {
         // Ensure the CSRF state has not been tampered with.
         if creds.known_csrf_state.secret() != creds.csrf_state.secret() {
             return Ok(None);
         };
+        let Some(oauth_client) = &self.client else {
+            return Err(BackendError::OauthNotConfigured);
+        };
 
         // Process authorization code, expecting a token response back.
         let client = reqwest::ClientBuilder::new()
             .redirect(reqwest::redirect::Policy::none())
             .build()
             .expect("static client");
-        let token_res = self
-            .client
+        let token_res = oauth_client
             // authorization code is known from session
             .exchange_code(AuthorizationCode::new(creds.code))
             // PKCE code verifier is known from session
@@ -233,20 +304,11 @@ impl AuthnBackend for GitlabOauthBackend {
             .map_err(|e| BackendError::TokenExchange(e.to_string()))?;
 
         // Use access token to request user info.
-        let user_info = client
-            .get("https://gitlab.tanakhcc.org/api/v4/user")
-            .header(USER_AGENT.as_str(), "axum-login") // See: https://docs.github.com/en/rest/overview/resources-in-the-rest-api?apiVersion=2022-11-28#user-agent-required
-            .header(
-                AUTHORIZATION.as_str(),
-                format!("Bearer {}", token_res.access_token().secret()),
-            )
-            .send()
+        let user_info = self
+            .forge
+            .user_info(token_res.access_token().secret())
             .await
-            .map_err(Self::Error::Reqwest)?;
-        let user_info = user_info
-            .json::<UserInfo>()
-            .await
-            .map_err(Self::Error::Gitlab)?;
+            .map_err(BackendError::Forge)?;
 
         // Persist user in our database so we can use `get_user`.
         let user = db::insert_or_update_user_session(
@@ -259,6 +321,75 @@ impl AuthnBackend for GitlabOauthBackend {
         Ok(Some(user))
     }
 
+    /// Log in a user with the OpenID provider
+    async fn authenticate_oidc(
+        &self,
+        creds: OauthCredentials,
+        nonce: String,
+    ) -> Result<Option<AuthenticatedUser>, BackendError> {
+        if creds.known_csrf_state.secret() != creds.csrf_state.secret() {
+            return Ok(None);
+        };
+        let Some(provider) = &self.oidc else {
+            return Err(BackendError::OidcNotConfigured);
+        };
+        let Some(oidc_user) = provider
+            .authenticate(creds.code, creds.pkce_verifier, &nonce)
+            .await
+            .map_err(BackendError::Oidc)?
+        else {
+            tracing::warn!("A user without a role in critic tried to log in with OpenID Connect.");
+            return Ok(None);
+        };
+        let user = db::insert_or_update_oidc_user_session(&self.db, oidc_user)
+            .await
+            .map_err(BackendError::DB)?;
+        Ok(Some(user))
+    }
+
+    /// Log in a user with the development login
+    ///
+    /// Returns None if the user is not configured.
+    async fn authenticate_dev(
+        &self,
+        username: String,
+    ) -> Result<Option<AuthenticatedUser>, BackendError> {
+        let Some(dev_login) = &self.dev_login else {
+            return Err(BackendError::DevLoginNotConfigured);
+        };
+        let Some(dev_user) = dev_login.user(&username) else {
+            return Ok(None);
+        };
+        let user = db::insert_or_update_external_user_session(
+            &self.db,
+            "dev",
+            &dev_user.username,
+            &dev_user.role,
+            dev::dev_tokens(),
+        )
+        .await
+        .map_err(BackendError::DB)?;
+        Ok(Some(user))
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthnBackend for LoginBackend {
+    type User = AuthenticatedUser;
+    type Credentials = Credentials;
+    type Error = BackendError;
+
+    async fn authenticate(
+        &self,
+        creds: Self::Credentials,
+    ) -> Result<Option<Self::User>, Self::Error> {
+        match creds {
+            Credentials::Forge(creds) => self.authenticate_forge(creds).await,
+            Credentials::Oidc { oauth, nonce } => self.authenticate_oidc(oauth, nonce).await,
+            Credentials::Dev { username } => self.authenticate_dev(username).await,
+        }
+    }
+
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
         sqlx::query_as!(
             AuthenticatedUser,
@@ -274,4 +405,4 @@ impl AuthnBackend for GitlabOauthBackend {
 // We use a type alias for convenience.
 //
 // Note that we've supplied our concrete backend here.
-pub type AuthSession = axum_login::AuthSession<GitlabOauthBackend>;
+pub type AuthSession = axum_login::AuthSession<LoginBackend>;
@@ -0,0 +1,431 @@
+//! Logging in with a generic OpenID Connect provider (e.g. keycloak or a university SSO)
+//!
+//! This is meant for users that do not need an account in the forge: readers and transcribers.
+//! Their role is taken from a claim in the ID token (usually the groups of the user) and is capped
+//! at [`GitlabUserRole::Developer`], maintainers always log in with the forge.
+//! They are known by the issuer and the `sub` claim of their ID token, and get the username
+//! `oidc:<sub>`, so that they never take the username of a forge user and keep their transcriptions
+//! when they change their name at the provider.
+//!
+//! The discovery document and the signing keys of the provider are fetched on every login, so
+//! that a provider rotating its keys or starting late does not need a restart of critic.
+
+use std::collections::HashMap;
+
+use oauth2::{
+    url::Url, AuthorizationCode, CsrfToken, PkceCodeChallenge, PkceCodeVerifier, Scope,
+    TokenResponse,
+};
+use serde::{Deserialize, Serialize};
+
+use super::NormalizedTokenResponse;
+use crate::gitlab::GitlabUserRole;
+
+#[derive(Debug)]
+pub enum OidcError {
+    /// Failure talking to the provider
+    Reqwest(reqwest::Error),
+    /// The discovery document belongs to another issuer
+    IssuerMismatch { expected: String, found: String },
+    /// An url of the provider or the redirect url is invalid
+    Url(oauth2::url::ParseError),
+    /// Failure while exchanging the authorization code for tokens
+    TokenExchange(String),
+    /// The token response did not contain an ID token
+    NoIdToken,
+    /// The ID token is invalid (signature, audience, issuer, expiry)
+    IdToken(jsonwebtoken::errors::Error),
+    /// The ID token is signed with a key the provider does not publish
+    UnknownKey(Option<String>),
+    /// The ID token is not signed with an asymmetric algorithm
+    UnsupportedAlgorithm(jsonwebtoken::Algorithm),
+    /// The nonce in the ID token is not the one sent with the authorization request
+    NonceMismatch,
+    /// A claim that is needed is missing from the ID token or has the wrong type
+    MissingClaim(String),
+}
+impl core::fmt::Display for OidcError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Reqwest(e) => {
+                write!(f, "Failure talking to the OpenID provider: {e}")
+            }
+            Self::IssuerMismatch { expected, found } => {
+                write!(
+                    f,
+                    "The OpenID provider claims to be {found}, but {expected} is configured"
+                )
+            }
+            Self::Url(e) => {
+                write!(f, "Invalid url for OpenID Connect: {e}")
+            }
+            Self::TokenExchange(e) => {
+                write!(
+                    f,
+                    "Failure while exchanging authorization code for tokens: {e}"
+                )
+            }
+            Self::NoIdToken => {
+                write!(f, "The OpenID provider did not return an ID token")
+            }
+            Self::IdToken(e) => {
+                write!(f, "The ID token is invalid: {e}")
+            }
+            Self::UnknownKey(kid) => {
+                write!(
+                    f,
+                    "The ID token is signed with an unknown key ({})",
+                    kid.as_deref().unwrap_or("no key id")
+                )
+            }
+            Self::UnsupportedAlgorithm(alg) => {
+                write!(
+                    f,
+                    "The ID token is signed with unsupported algorithm {alg:?}"
+                )
+            }
+            Self::NonceMismatch => {
+                write!(f, "The nonce in the ID token does not match the request")
+            }
+            Self::MissingClaim(claim) => {
+                write!(f, "The ID token has no usable claim {claim}")
+            }
+        }
+    }
+}
+impl core::error::Error for OidcError {}
+impl From<reqwest::Error> for OidcError {
+    fn from(value: reqwest::Error) -> Self {
+        Self::Reqwest(value)
+    }
+}
+
+/// Config for logging in with an OpenID Connect provider
+#[derive(Deserialize, Debug, Clone)]
+pub struct OidcConfig {
+    /// The issuer, e.g. `https://sso.example.org/realms/critic`
+    ///
+    /// The discovery document is expected at `<issuer>/.well-known/openid-configuration`. Plain
+    /// http is allowed, so that a local stand-in can be used for testing.
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Shown on the login page, e.g. `University account`
+    #[serde(default = "default_label")]
+    pub label: String,
+    /// Scopes to request in addition to `openid`, e.g. `profile` or `groups`
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// The claim holding the roles or groups of the user (a string or a list of strings)
+    #[serde(default = "default_role_claim")]
+    pub role_claim: String,
+    /// The role given to users with a value of the role claim
+    ///
+    /// A user with several values gets the highest role. Roles above developer are not allowed.
+    #[serde(default)]
+    pub roles: HashMap<String, GitlabUserRole>,
+    /// The role of users without a mapped value, users cannot log in if this is not set
+    pub default_role: Option<GitlabUserRole>,
+    /// Where the provider redirects to after logging in
+    ///
+    /// Defaults to `https://<public_addr>/oidc/redirect`
+    pub redirect_url: Option<String>,
+}
+fn default_label() -> String {
+    "Single sign-on".to_string()
+}
+fn default_role_claim() -> String {
+    "groups".to_string()
+}
+
+/// The parts of the discovery document critic needs
+#[derive(Deserialize, Debug)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+/// The token response of an OpenID provider contains the ID token next to the access token
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct IdTokenFields {
+    id_token: Option<String>,
+}
+impl oauth2::ExtraTokenFields for IdTokenFields {}
+
+type IdTokenResponse = oauth2::StandardTokenResponse<IdTokenFields, oauth2::basic::BasicTokenType>;
+
+type OidcClient = oauth2::Client<
+    oauth2::StandardErrorResponse<oauth2::basic::BasicErrorResponseType>,
+    IdTokenResponse,
+    oauth2::StandardTokenIntrospectionResponse<
+        oauth2::EmptyExtraTokenFields,
+        oauth2::basic::BasicTokenType,
+    >,
+    oauth2::StandardRevocableToken,
+    oauth2::StandardErrorResponse<oauth2::RevocationErrorResponseType>,
+    oauth2::EndpointSet,
+    oauth2::EndpointNotSet,
+    oauth2::EndpointNotSet,
+    oauth2::EndpointNotSet,
+    oauth2::EndpointSet,
+>;
+
+/// A user who logged in with the OpenID provider
+#[derive(Debug)]
+pub struct OidcUser {
+    /// The issuer of the ID token
+    pub issuer: String,
+    /// The `sub` claim of the ID token, unique and stable for the issuer
+    pub subject: String,
+    pub role: GitlabUserRole,
+    pub tokens: NormalizedTokenResponse,
+}
+impl OidcUser {
+    /// The username in critic: `oidc:<sub>`
+    ///
+    /// The subject is percent-encoded, as the username ends up in file names.
+    pub fn username(&self) -> String {
+        format!("oidc:{}", urlencoding::encode(&self.subject))
+    }
+}
+
+/// When the access token of a login expires, as stored with the session
+///
+/// As for forge logins, this is the expiry of the access token, not of the session: the session
+/// ends after a day of inactivity like any other. Providers that do not say when the access token
+/// expires get the expiry of the ID token.
+fn token_expiry(
+    now: time::OffsetDateTime,
+    expires_in: Option<std::time::Duration>,
+    claims: &HashMap<String, serde_json::Value>,
+) -> Result<time::OffsetDateTime, OidcError> {
+    if let Some(expires_in) = expires_in {
+        return Ok(now + expires_in);
+    };
+    claims
+        .get("exp")
+        .and_then(|exp| exp.as_i64())
+        .and_then(|exp| time::OffsetDateTime::from_unix_timestamp(exp).ok())
+        .ok_or(OidcError::MissingClaim("exp".to_string()))
+}
+
+/// An OpenID provider users can log in with
+#[derive(Debug)]
+pub struct OidcProvider {
+    config: OidcConfig,
+    redirect_url: oauth2::RedirectUrl,
+    http: reqwest::Client,
+}
+impl OidcProvider {
+    pub fn new(config: OidcConfig, public_addr: &str) -> Result<Self, OidcError> {
+        let redirect_url = oauth2::RedirectUrl::new(
+            config
+                .redirect_url
+                .clone()
+                .unwrap_or(format!("https://{public_addr}/oidc/redirect")),
+        )
+        .map_err(OidcError::Url)?;
+        let http = reqwest::ClientBuilder::new()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("static client");
+        Ok(Self {
+            config,
+            redirect_url,
+            http,
+        })
+    }
+
+    /// Shown on the login page
+    pub fn label(&self) -> &str {
+        &self.config.label
+    }
+
+    /// Get the discovery document of the issuer
+    async fn metadata(&self) -> Result<ProviderMetadata, OidcError> {
+        let issuer = self.config.issuer.trim_end_matches('/');
+        let metadata: ProviderMetadata = self
+            .http
+            .get(format!("{issuer}/.well-known/openid-configuration"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if metadata.issuer.trim_end_matches('/') != issuer {
+            return Err(OidcError::IssuerMismatch {
+                expected: self.config.issuer.clone(),
+                found: metadata.issuer,
+            });
+        };
+        Ok(metadata)
+    }
+
+    fn client(&self, metadata: &ProviderMetadata) -> Result<OidcClient, OidcError> {
+        Ok(
+            oauth2::Client::new(oauth2::ClientId::new(self.config.client_id.clone()))
+                .set_client_secret(oauth2::ClientSecret::new(self.config.client_secret.clone()))
+                .set_auth_uri(
+                    oauth2::AuthUrl::new(metadata.authorization_endpoint.clone())
+                        .map_err(OidcError::Url)?,
+                )
+                .set_token_uri(
+                    oauth2::TokenUrl::new(metadata.token_endpoint.clone())
+                        .map_err(OidcError::Url)?,
+                )
+                .set_redirect_uri(self.redirect_url.clone()),
+        )
+    }
+
+    /// URL to show to the user to start logging in
+    /// RETURNS
+    ///     the url to show
+    ///     the CsrfToken in use
+    ///     the pkce verifier
+    ///     the nonce the ID token has to contain
+    pub async fn authorize_url(
+        &self,
+    ) -> Result<(Url, CsrfToken, PkceCodeVerifier, String), OidcError> {
+        let client = self.client(&self.metadata().await?)?;
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let nonce = CsrfToken::new_random().into_secret();
+        let (url, csrf_token) = client
+            .authorize_url(CsrfToken::new_random)
+            .add_scope(Scope::new("openid".to_string()))
+            .add_scopes(
+                self.config
+                    .scopes
+                    .iter()
+                    .filter(|scope| *scope != "openid")
+                    .cloned()
+                    .map(Scope::new),
+            )
+            .add_extra_param("nonce", &nonce)
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+        Ok((url, csrf_token, pkce_verifier, nonce))
+    }
+
+    /// Exchange the authorization code, validate the ID token and map the user to a role
+    ///
+    /// Returns None if the user has no role in critic.
+    pub async fn authenticate(
+        &self,
+        code: String,
+        pkce_verifier: String,
+        nonce: &str,
+    ) -> Result<Option<OidcUser>, OidcError> {
+        let metadata = self.metadata().await?;
+        let token_res = self
+            .client(&metadata)?
+            .exchange_code(AuthorizationCode::new(code))
+            .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier))
+            .request_async(&self.http)
+            .await
+            .map_err(|e| OidcError::TokenExchange(e.to_string()))?;
+        let id_token = token_res
+            .extra_fields()
+            .id_token
+            .as_deref()
+            .ok_or(OidcError::NoIdToken)?;
+        let claims = self.validate(&metadata, id_token, nonce).await?;
+
+        let subject = claims
+            .get("sub")
+            .and_then(|sub| sub.as_str())
+            .filter(|sub| !sub.is_empty())
+            .ok_or(OidcError::MissingClaim("sub".to_string()))?
+            .to_string();
+        let Some(role) = self.role(&claims) else {
+            return Ok(None);
+        };
+        let expires_at = token_expiry(
+            time::OffsetDateTime::now_utc(),
+            token_res.expires_in(),
+            &claims,
+        )?;
+        Ok(Some(OidcUser {
+            issuer: metadata.issuer.trim_end_matches('/').to_string(),
+            subject,
+            role,
+            tokens: NormalizedTokenResponse {
+                access_token: token_res.access_token().secret().clone(),
+                refresh_token: token_res
+                    .refresh_token()
+                    .map(|token| token.secret().clone())
+                    .unwrap_or_default(),
+                expires_at,
+            },
+        }))
+    }
+
+    /// Check signature, audience, issuer, expiry and nonce of the ID token and return its claims
+    async fn validate(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<HashMap<String, serde_json::Value>, OidcError> {
+        let header = jsonwebtoken::decode_header(id_token).map_err(OidcError::IdToken)?;
+        // symmetric algorithms would accept tokens signed with the client secret
+        if matches!(
+            header.alg,
+            jsonwebtoken::Algorithm::HS256
+                | jsonwebtoken::Algorithm::HS384
+                | jsonwebtoken::Algorithm::HS512
+        ) {
+            return Err(OidcError::UnsupportedAlgorithm(header.alg));
+        };
+        let jwks: jsonwebtoken::jwk::JwkSet = self
+            .http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            // without a key id, the only key of the provider is used
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or(OidcError::UnknownKey(header.kid.clone()))?;
+        let key = jsonwebtoken::DecodingKey::from_jwk(jwk).map_err(OidcError::IdToken)?;
+
+        let mut validation = jsonwebtoken::Validation::new(header.alg);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_issuer(&[&metadata.issuer]);
+        let claims =
+            jsonwebtoken::decode::<HashMap<String, serde_json::Value>>(id_token, &key, &validation)
+                .map_err(OidcError::IdToken)?
+                .claims;
+
+        if claims.get("nonce").and_then(|n| n.as_str()) != Some(nonce) {
+            return Err(OidcError::NonceMismatch);
+        };
+        Ok(claims)
+    }
+
+    /// The highest role mapped from the role claim, capped at developer
+    fn role(&self, claims: &HashMap<String, serde_json::Value>) -> Option<GitlabUserRole> {
+        let values = match claims.get(&self.config.role_claim) {
+            Some(serde_json::Value::String(value)) => vec![value.as_str()],
+            Some(serde_json::Value::Array(values)) => {
+                values.iter().filter_map(|value| value.as_str()).collect()
+            }
+            _ => vec![],
+        };
+        values
+            .into_iter()
+            .filter_map(|value| self.config.roles.get(value).copied())
+            .max()
+            .or(self.config.default_role)
+            .map(|role| role.min(GitlabUserRole::Developer))
+    }
+}
+
+#[cfg(test)]
+mod test;
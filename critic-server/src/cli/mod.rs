@@ -0,0 +1,100 @@
+//! Maintenance commands run from the command line instead of starting the server
+//!
+//! `critic <command> [arguments]` runs a single command with the normal config and exits.
+
+use std::path::PathBuf;
+
+use crate::{
+    config::Config, consistency::check_and_report, forge::RepositorySource,
+    gitlab::webhook::replay_payloads, rebuild::rebuild,
+};
+
+/// The usage shown when the arguments cannot be parsed
+pub const USAGE: &str = "Usage:
+  critic                                          start the server
+  critic replay-webhooks <payload dir> [<clone>]  replay recorded gitlab webhook payloads
+  critic rebuild-db [--dry-run] [<clone>]         rebuild the database from the repository
+  critic check-consistency                        check transcriptions, metadata, anchors and images
+
+<clone> is a local clone of the repository to read files from instead of the forge.";
+
+/// A command given on the command line
+#[derive(Debug, Clone)]
+pub enum Command {
+    ReplayWebhooks {
+        payload_dir: PathBuf,
+        source: RepositorySource,
+    },
+    RebuildDb {
+        source: RepositorySource,
+        dry_run: bool,
+    },
+    CheckConsistency,
+}
+impl Command {
+    /// Parse the command from the arguments (without the program name)
+    ///
+    /// None if no command is given, i.e. the server should be started.
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, String> {
+        let source = |clone: Option<&String>| {
+            clone.map_or(RepositorySource::Forge, |dir| {
+                RepositorySource::LocalClone(dir.into())
+            })
+        };
+        match args {
+            [] => Ok(None),
+            [command, payload_dir, rest @ ..]
+                if command == "replay-webhooks" && rest.len() <= 1 =>
+            {
+                Ok(Some(Self::ReplayWebhooks {
+                    payload_dir: payload_dir.into(),
+                    source: source(rest.first()),
+                }))
+            }
+            [command, rest @ ..] if command == "rebuild-db" => {
+                let dry_run = rest.iter().any(|arg| arg == "--dry-run");
+                let clones = rest
+                    .iter()
+                    .filter(|arg| *arg != "--dry-run")
+                    .collect::<Vec<_>>();
+                match clones[..] {
+                    [] => Ok(Some(Self::RebuildDb {
+                        source: RepositorySource::Forge,
+                        dry_run,
+                    })),
+                    [clone] => Ok(Some(Self::RebuildDb {
+                        source: source(Some(clone)),
+                        dry_run,
+                    })),
+                    _ => Err(USAGE.to_string()),
+                }
+            }
+            [command] if command == "check-consistency" => Ok(Some(Self::CheckConsistency)),
+            _ => Err(USAGE.to_string()),
+        }
+    }
+
+    /// Run the command, returning what should be reported to the user
+    pub async fn run(self, config: &Config) -> Result<String, String> {
+        match self {
+            Self::ReplayWebhooks {
+                payload_dir,
+                source,
+            } => replay_payloads(config, &source, &payload_dir)
+                .await
+                .map(|n| format!("Replayed {n} webhook payloads."))
+                .map_err(|e| format!("Failed to replay webhook payloads: {e}")),
+            Self::RebuildDb { source, dry_run } => rebuild(config, &source, dry_run)
+                .await
+                .map(|report| report.to_string())
+                .map_err(|e| format!("Failed to rebuild the database: {e}")),
+            Self::CheckConsistency => check_and_report(config)
+                .await
+                .map(|report| report.to_string())
+                .map_err(|e| format!("Failed to check consistency: {e}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,103 @@
+//! Tests for parsing the command line
+
+use std::path::PathBuf;
+
+use super::{Command, USAGE};
+use crate::forge::RepositorySource;
+
+fn parse(args: &[&str]) -> Result<Option<Command>, String> {
+    Command::from_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+}
+
+fn is_clone(source: &RepositorySource, dir: &str) -> bool {
+    matches!(source, RepositorySource::LocalClone(clone) if *clone == PathBuf::from(dir))
+}
+
+#[test]
+fn no_command_starts_the_server() {
+    assert!(parse(&[]).unwrap().is_none());
+}
+
+#[test]
+fn rebuild_db() {
+    assert!(matches!(
+        parse(&["rebuild-db"]),
+        Ok(Some(Command::RebuildDb {
+            source: RepositorySource::Forge,
+            dry_run: false
+        }))
+    ));
+    assert!(matches!(
+        parse(&["rebuild-db", "--dry-run"]),
+        Ok(Some(Command::RebuildDb {
+            source: RepositorySource::Forge,
+            dry_run: true
+        }))
+    ));
+}
+
+#[test]
+fn rebuild_db_from_a_clone() {
+    for args in [
+        &["rebuild-db", "/srv/clone"][..],
+        &["rebuild-db", "--dry-run", "/srv/clone"],
+        &["rebuild-db", "/srv/clone", "--dry-run"],
+    ] {
+        match parse(args) {
+            Ok(Some(Command::RebuildDb { source, dry_run })) => {
+                assert!(is_clone(&source, "/srv/clone"), "{args:?}");
+                assert_eq!(dry_run, args.contains(&"--dry-run"), "{args:?}");
+            }
+            other => panic!("{args:?} parsed as {other:?}"),
+        };
+    }
+}
+
+#[test]
+fn rebuild_db_takes_at_most_one_clone() {
+    assert_eq!(
+        parse(&["rebuild-db", "/srv/a", "/srv/b"]).unwrap_err(),
+        USAGE
+    );
+}
+
+#[test]
+fn replay_webhooks() {
+    match parse(&["replay-webhooks", "/srv/payloads"]) {
+        Ok(Some(Command::ReplayWebhooks {
+            payload_dir,
+            source: RepositorySource::Forge,
+        })) => assert_eq!(payload_dir, PathBuf::from("/srv/payloads")),
+        other => panic!("parsed as {other:?}"),
+    };
+    match parse(&["replay-webhooks", "/srv/payloads", "/srv/clone"]) {
+        Ok(Some(Command::ReplayWebhooks {
+            payload_dir,
+            source,
+        })) => {
+            assert_eq!(payload_dir, PathBuf::from("/srv/payloads"));
+            assert!(is_clone(&source, "/srv/clone"));
+        }
+        other => panic!("parsed as {other:?}"),
+    };
+}
+
+#[test]
+fn check_consistency() {
+    assert!(matches!(
+        parse(&["check-consistency"]),
+        Ok(Some(Command::CheckConsistency))
+    ));
+}
+
+#[test]
+fn unknown_or_incomplete_commands_show_the_usage() {
+    for args in [
+        &["serve"][..],
+        &["replay-webhooks"],
+        &["replay-webhooks", "/srv/payloads", "/srv/clone", "more"],
+        &["check-consistency", "now"],
+    ] {
+        assert_eq!(parse(args).unwrap_err(), USAGE, "{args:?}");
+    }
+}
@@ -0,0 +1,251 @@
+//! Distances between manuscripts and a neighbor-joining tree for stemmatic work
+//!
+//! Two manuscripts are compared at every variant location where both are extant, i.e. the verse
+//! is present in both and neither reading contains a lacuna. Their distance is the share of those
+//! locations where they have different readings.
+
+use critic_shared::collation::{DistanceMatrix, Orthography, Token, TokenKind, VariantTable};
+
+/// Is `c` a combining mark or punctuation ignored when comparing orthography?
+fn is_orthographic_mark(c: char) -> bool {
+    matches!(c,
+        // combining diacritical marks
+        '\u{0300}'..='\u{036F}'
+        // hebrew accents, points, maqaf, paseq and sof pasuq
+        | '\u{0591}'..='\u{05C7}'
+        // arabic vowel signs
+        | '\u{0610}'..='\u{061A}' | '\u{064B}'..='\u{065F}' | '\u{0670}'
+        // syriac points
+        | '\u{0730}'..='\u{074A}'
+    ) || (!c.is_alphanumeric() && !c.is_whitespace())
+}
+
+/// The spelling of a word with vocalization, accents, punctuation and case removed
+pub fn orthographic_form(word: &str) -> String {
+    word.chars()
+        .filter(|c| !is_orthographic_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The [`orthographic_form`] of a word without Hebrew matres lectionis
+///
+/// Every ו and י inside the word is dropped, so that plene and defective spellings agree. Whether
+/// a ו or י is a vowel letter cannot be told from unvocalized text, so consonantal ones are
+/// dropped as well and words like עיר and ער agree, too.
+pub fn defective_form(word: &str) -> String {
+    let letters = orthographic_form(word).chars().collect::<Vec<_>>();
+    let last = letters.len().saturating_sub(1);
+    letters
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, c)| *idx == 0 || *idx == last || !matches!(c, 'ו' | 'י'))
+        .map(|(_, c)| c)
+        .collect()
+}
+
+/// The form of a reading used for comparison, None if it contains a lacuna
+fn comparable_reading(tokens: &[Token], orthography: Orthography) -> Option<Vec<String>> {
+    tokens
+        .iter()
+        .map(|token| match (&token.kind, orthography) {
+            (TokenKind::Lacuna, _) => None,
+            (_, Orthography::Exact) => Some(token.text.clone()),
+            (_, Orthography::IgnoreMarks) => Some(orthographic_form(&token.text)),
+            (_, Orthography::IgnoreMatresLectionis) => Some(defective_form(&token.text)),
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|words| words.into_iter().filter(|w| !w.is_empty()).collect())
+}
+
+/// Compute the distances between all witnesses of a variant table
+pub fn distance_matrix(table: &VariantTable, orthography: Orthography) -> DistanceMatrix {
+    let sigla = std::iter::once(&table.base)
+        .chain(&table.witnesses)
+        .cloned()
+        .collect::<Vec<_>>();
+    let n = sigla.len();
+    let mut compared = vec![vec![0; n]; n];
+    let mut agreements = vec![vec![0; n]; n];
+    for verse in &table.verses {
+        for location in &verse.locations {
+            // the comparable reading of every witness at this location
+            let mut readings: Vec<Option<Vec<String>>> = vec![None; n];
+            for reading in &location.readings {
+                let form = comparable_reading(&reading.tokens, orthography);
+                for siglum in &reading.sigla {
+                    if let Some(idx) = sigla.iter().position(|s| s == siglum) {
+                        readings[idx] = form.clone();
+                    };
+                }
+            }
+            for i in 0..n {
+                for j in 0..n {
+                    if let (Some(a), Some(b)) = (&readings[i], &readings[j]) {
+                        compared[i][j] += 1;
+                        if a == b {
+                            agreements[i][j] += 1;
+                        };
+                    };
+                }
+            }
+        }
+    }
+    DistanceMatrix {
+        sigla,
+        compared,
+        agreements,
+    }
+}
+
+/// The distance matrix as CSV, with the sigla as first row and column
+///
+/// Pairs that could not be compared have an empty cell.
+pub fn distance_csv(matrix: &DistanceMatrix) -> String {
+    let escape = |s: &str| {
+        if s.contains([',', '"', '\n']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    };
+    let mut res = String::default();
+    for siglum in &matrix.sigla {
+        res.push(',');
+        res.push_str(&escape(siglum));
+    }
+    res.push('\n');
+    for (i, siglum) in matrix.sigla.iter().enumerate() {
+        res.push_str(&escape(siglum));
+        for j in 0..matrix.sigla.len() {
+            res.push(',');
+            if let Some(distance) = matrix.distance(i, j) {
+                res.push_str(&format!("{distance:.4}"));
+            };
+        }
+        res.push('\n');
+    }
+    res
+}
+
+/// A (sub)tree built by neighbor joining
+enum Tree {
+    Leaf(String),
+    /// children with the lengths of their branches
+    Node(Vec<(Tree, f64)>),
+}
+impl Tree {
+    fn newick(&self) -> String {
+        match self {
+            // quote labels, since sigla may contain characters with special meaning
+            Self::Leaf(label) => format!("'{}'", label.replace('\'', "''")),
+            Self::Node(children) => format!(
+                "({})",
+                children
+                    .iter()
+                    .map(|(tree, length)| format!("{}:{length:.4}", tree.newick()))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+}
+
+/// The distances neighbor joining starts from
+///
+/// Every manuscript has a distance of 0 to itself. Pairs that could not be compared get a
+/// distance of 0 as well, so that missing text does not separate manuscripts in the tree.
+fn tree_distances(matrix: &DistanceMatrix) -> Vec<Vec<f64>> {
+    let n = matrix.sigla.len();
+    (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| {
+                    if i == j {
+                        0.0
+                    } else {
+                        matrix.distance(i, j).unwrap_or(0.0)
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// An unrooted neighbor-joining tree of the manuscripts in Newick format
+///
+/// Pairs that could not be compared are not told apart, see [`tree_distances`].
+pub fn neighbor_joining(matrix: &DistanceMatrix) -> String {
+    let mut distances = tree_distances(matrix);
+    let mut trees = matrix
+        .sigla
+        .iter()
+        .map(|s| Tree::Leaf(s.clone()))
+        .collect::<Vec<_>>();
+
+    while trees.len() > 3 {
+        let r = trees.len();
+        let sums = distances
+            .iter()
+            .map(|row| row.iter().sum::<f64>())
+            .collect::<Vec<_>>();
+        // the pair minimizing the Q-criterion
+        let mut best = (0, 1, f64::INFINITY);
+        for i in 0..r {
+            for j in (i + 1)..r {
+                let q = (r as f64 - 2.0) * distances[i][j] - sums[i] - sums[j];
+                if q < best.2 {
+                    best = (i, j, q);
+                };
+            }
+        }
+        let (i, j, _) = best;
+        let d = distances[i][j];
+        let length_i = (0.5 * d + (sums[i] - sums[j]) / (2.0 * (r as f64 - 2.0))).max(0.0);
+        let length_j = (d - length_i).max(0.0);
+        let new_distances = (0..r)
+            .filter(|k| *k != i && *k != j)
+            .map(|k| 0.5 * (distances[i][k] + distances[j][k] - d))
+            .collect::<Vec<_>>();
+
+        // j > i, so remove j first to keep i valid
+        let tree_j = trees.remove(j);
+        let tree_i = trees.remove(i);
+        distances.remove(j);
+        distances.remove(i);
+        for row in distances.iter_mut() {
+            row.remove(j);
+            row.remove(i);
+        }
+        for (row, new) in distances.iter_mut().zip(&new_distances) {
+            row.push(*new);
+        }
+        let mut new_row = new_distances;
+        new_row.push(0.0);
+        distances.push(new_row);
+        trees.push(Tree::Node(vec![(tree_i, length_i), (tree_j, length_j)]));
+    }
+
+    let root = match trees.len() {
+        0 => return ";".to_string(),
+        1 => return format!("{};", trees.remove(0).newick()),
+        2 => {
+            let d = distances[0][1];
+            Tree::Node(trees.into_iter().map(|t| (t, d / 2.0)).collect())
+        }
+        // the last three nodes are joined in the center
+        _ => {
+            let (a, b, c) = (distances[0][1], distances[0][2], distances[1][2]);
+            let lengths = [
+                (0.5 * (a + b - c)).max(0.0),
+                (0.5 * (a + c - b)).max(0.0),
+                (0.5 * (b + c - a)).max(0.0),
+            ];
+            Tree::Node(trees.into_iter().zip(lengths).collect())
+        }
+    };
+    format!("{};", root.newick())
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,138 @@
+//! Tests for distances between manuscripts
+
+use critic_shared::collation::{
+    DistanceMatrix, Orthography, Token, TokenKind, VariantLocation, VariantReading, VariantTable,
+    VariantVerse,
+};
+
+use super::{defective_form, distance_matrix, neighbor_joining, orthographic_form, tree_distances};
+
+#[test]
+fn orthographic_form_removes_vocalization_and_punctuation() {
+    assert_eq!(orthographic_form("בְּרֵאשִׁ֖ית"), "בראשית");
+    assert_eq!(orthographic_form("הָאָֽרֶץ׃"), "הארץ");
+    assert_eq!(orthographic_form("Word,"), "word");
+}
+
+#[test]
+fn orthographic_form_keeps_consonantal_vav_and_yod() {
+    assert_ne!(orthographic_form("עִיר"), orthographic_form("עַר"));
+    assert_ne!(orthographic_form("שִׁיר"), orthographic_form("שַׂר"));
+    assert_ne!(orthographic_form("הָיָה"), orthographic_form("הֵה"));
+    assert_eq!(orthographic_form("דָּוִיד"), "דויד");
+}
+
+#[test]
+fn defective_form_drops_matres_lectionis_inside_words() {
+    assert_eq!(defective_form("דָּוִיד"), "דד");
+    assert_eq!(defective_form("דָּוִד"), defective_form("דָּוִיד"));
+    assert_eq!(defective_form("קוֹל"), defective_form("קֹל"));
+}
+
+#[test]
+fn defective_form_keeps_word_initial_and_final_letters() {
+    assert_eq!(defective_form("וַיֹּאמֶר"), "ואמר");
+    assert_eq!(defective_form("אָבִי"), "אבי");
+    assert_ne!(defective_form("אָבִי"), defective_form("אָב"));
+    assert_eq!(defective_form("לוֹ"), "לו");
+    assert_eq!(defective_form("ו"), "ו");
+    assert_eq!(defective_form(""), "");
+}
+
+/// A table where `A` reads `a` and `B` reads `b` at a single location
+fn table(a: &str, b: &str) -> VariantTable {
+    let reading = |text: &str, siglum: &str| VariantReading {
+        tokens: vec![Token {
+            text: text.to_string(),
+            kind: TokenKind::Word,
+        }],
+        sigla: vec![siglum.to_string()],
+    };
+    VariantTable {
+        base: "A".to_string(),
+        witnesses: vec!["B".to_string()],
+        verses: vec![VariantVerse {
+            verse_nr: "Gen 1:1".to_string(),
+            base: reading(a, "A").tokens,
+            locations: vec![VariantLocation {
+                start: 0,
+                end: 1,
+                readings: vec![reading(a, "A"), reading(b, "B")],
+            }],
+            missing: vec![],
+        }],
+    }
+}
+
+#[test]
+fn only_the_chosen_spelling_differences_are_ignored() {
+    let distance =
+        |a: &str, b: &str, orthography| distance_matrix(&table(a, b), orthography).distance(0, 1);
+    assert_eq!(distance("עִיר", "עיר", Orthography::Exact), Some(1.0));
+    assert_eq!(distance("עִיר", "עיר", Orthography::IgnoreMarks), Some(0.0));
+    assert_eq!(distance("עִיר", "עַר", Orthography::IgnoreMarks), Some(1.0));
+    assert_eq!(distance("דָּוִד", "דָּוִיד", Orthography::IgnoreMarks), Some(1.0));
+    assert_eq!(
+        distance("דָּוִד", "דָּוִיד", Orthography::IgnoreMatresLectionis),
+        Some(0.0)
+    );
+}
+
+/// A matrix of three manuscripts where `C` shares no extant location with the others
+fn matrix_with_gap() -> DistanceMatrix {
+    DistanceMatrix {
+        sigla: vec!["A".to_string(), "B".to_string(), "C".to_string()],
+        compared: vec![vec![4, 4, 0], vec![4, 4, 0], vec![0, 0, 0]],
+        agreements: vec![vec![4, 3, 0], vec![3, 4, 0], vec![0, 0, 0]],
+    }
+}
+
+#[test]
+fn tree_distances_are_zero_on_the_diagonal() {
+    let distances = tree_distances(&matrix_with_gap());
+    for (i, row) in distances.iter().enumerate() {
+        assert_eq!(row[i], 0.0);
+    }
+}
+
+#[test]
+fn tree_distances_are_zero_for_pairs_without_comparison() {
+    let distances = tree_distances(&matrix_with_gap());
+    assert_eq!(distances[0][1], 0.25);
+    assert_eq!(distances[1][0], 0.25);
+    assert_eq!(distances[0][2], 0.0);
+    assert_eq!(distances[2][1], 0.0);
+}
+
+#[test]
+fn neighbor_joining_of_small_matrices() {
+    let empty = DistanceMatrix {
+        sigla: vec![],
+        compared: vec![],
+        agreements: vec![],
+    };
+    assert_eq!(neighbor_joining(&empty), ";");
+    assert_eq!(
+        neighbor_joining(&matrix_with_gap()),
+        "('A':0.1250,'B':0.1250,'C':0.0000);"
+    );
+}
+
+#[test]
+fn neighbor_joining_joins_the_closest_manuscripts() {
+    // A and B agree everywhere, C and D differ from them and each other at half the locations
+    let sigla = ["A", "B", "C", "D"].map(String::from).to_vec();
+    let agreements = vec![
+        vec![8, 8, 4, 4],
+        vec![8, 8, 4, 4],
+        vec![4, 4, 8, 4],
+        vec![4, 4, 4, 8],
+    ];
+    let matrix = DistanceMatrix {
+        sigla,
+        compared: vec![vec![8; 4]; 4],
+        agreements,
+    };
+    let newick = neighbor_joining(&matrix);
+    assert!(newick.contains("('A':0.0000,'B':0.0000)"), "{newick}");
+}
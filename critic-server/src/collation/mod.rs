@@ -0,0 +1,578 @@
+//! Verse-aligned collation of manuscripts
+//!
+//! The accepted text of every manuscript is split into verses at its verse anchors. The words of
+//! each verse are then aligned against a base witness, and the differences are grouped into
+//! variant units.
+
+pub mod distance;
+
+use std::collections::{BTreeMap, HashMap};
+
+use critic_format::streamed::Block;
+pub use critic_shared::collation::{Token, TokenKind};
+use critic_shared::{
+    anchor::anchor_verse,
+    collation::{VariantLocation, VariantReading, VariantTable, VariantVerse},
+    diff::lcs_matches,
+    PageMeta,
+};
+
+use crate::{
+    config::Config,
+    db::{get_manuscript, get_manuscripts_by_name, get_verse_map, DBError, VerseMapEntry},
+    export::{
+        load_page_transcription, manuscript_transcriptions, ExportError, TranscriptionChoice,
+    },
+};
+
+/// A single word with the given kind
+fn word(text: &str, kind: TokenKind) -> Token {
+    Token {
+        text: text.to_string(),
+        kind,
+    }
+}
+
+/// Split blocks into tokens for collation
+///
+/// Abbreviations are collated in their expanded form, corrections in their last version. Breaks,
+/// spaces and anchors are ignored.
+pub fn tokenize(blocks: &[Block]) -> Vec<Token> {
+    let mut res = vec![];
+    for block in blocks {
+        match block {
+            Block::Text(paragraph) => res.extend(
+                paragraph
+                    .content
+                    .split_whitespace()
+                    .map(|w| word(w, TokenKind::Word)),
+            ),
+            Block::Abbreviation(abbreviation) => res.extend(
+                abbreviation
+                    .expansion
+                    .split_whitespace()
+                    .map(|w| word(w, TokenKind::Word)),
+            ),
+            Block::Uncertain(uncertain) => res.extend(
+                uncertain
+                    .content
+                    .split_whitespace()
+                    .map(|w| word(w, TokenKind::Uncertain)),
+            ),
+            Block::Correction(correction) => {
+                let original = correction
+                    .versions
+                    .first()
+                    .map(|v| v.content.clone())
+                    .unwrap_or_default();
+                if let Some(last) = correction.versions.last() {
+                    res.extend(last.content.split_whitespace().map(|w| {
+                        word(
+                            w,
+                            TokenKind::Corrected {
+                                original: original.clone(),
+                            },
+                        )
+                    }));
+                };
+            }
+            Block::Lacuna(_) => res.push(word("", TokenKind::Lacuna)),
+            Block::Break(_) | Block::Space(_) | Block::Anchor(_) => {}
+        }
+    }
+    res
+}
+
+/// Maps verse anchors (scheme shorthand, verse number) to non-semantic verse ids
+#[derive(Debug, Clone, Default)]
+pub struct AnchorMap {
+    ids: HashMap<(String, String), i64>,
+    /// verse numbers by verse id and scheme shorthand
+    numbers: HashMap<(i64, String), String>,
+}
+impl AnchorMap {
+    pub fn new(entries: Vec<VerseMapEntry>) -> Self {
+        let mut res = Self::default();
+        for entry in entries {
+            res.ids.insert(
+                (entry.shorthand.clone(), entry.verse_nr.clone()),
+                entry.verse_id,
+            );
+            res.numbers
+                .insert((entry.verse_id, entry.shorthand), entry.verse_nr);
+        }
+        res
+    }
+
+    /// The verse id an anchor id refers to, if it is a known verse anchor
+    pub fn verse_id(&self, anchor_id: &str) -> Option<i64> {
+        let (shorthand, verse_nr) = anchor_verse(anchor_id)?;
+        self.ids
+            .get(&(shorthand.to_string(), verse_nr.to_string()))
+            .copied()
+    }
+
+    /// The verse id of a verse number in the scheme `shorthand`
+    pub fn verse_id_by_number(&self, shorthand: &str, verse_nr: &str) -> Option<i64> {
+        self.ids
+            .get(&(shorthand.to_string(), verse_nr.to_string()))
+            .copied()
+    }
+
+    /// The verse number of a verse in the scheme `shorthand`
+    pub fn verse_nr(&self, verse_id: i64, shorthand: &str) -> Option<&str> {
+        self.numbers
+            .get(&(verse_id, shorthand.to_string()))
+            .map(String::as_str)
+    }
+}
+
+/// Load the verse map from the db
+pub async fn load_anchor_map(config: &Config) -> Result<AnchorMap, ExportError> {
+    Ok(AnchorMap::new(
+        get_verse_map(&config.db).await.map_err(ExportError::DB)?,
+    ))
+}
+
+/// Split blocks into verses at their verse anchors
+///
+/// Blocks before the first known verse anchor are dropped.
+pub fn split_at_verses(
+    blocks: impl IntoIterator<Item = Block>,
+    anchors: &AnchorMap,
+) -> BTreeMap<i64, Vec<Block>> {
+    let mut res: BTreeMap<i64, Vec<Block>> = BTreeMap::new();
+    let mut current = None;
+    for block in blocks {
+        if let Block::Anchor(anchor) = &block {
+            if let Some(verse_id) = anchors.verse_id(&anchor.anchor_id) {
+                current = Some(verse_id);
+                continue;
+            };
+        };
+        if let Some(verse_id) = current {
+            res.entry(verse_id).or_default().push(block);
+        };
+    }
+    res
+}
+
+/// The text of a manuscript, split into verses
+#[derive(Debug, Clone)]
+pub struct Witness {
+    /// the name of the manuscript
+    pub siglum: String,
+    /// the blocks of every verse found in the manuscript
+    pub verses: BTreeMap<i64, Vec<Block>>,
+}
+impl Witness {
+    /// The tokens of a verse, None if the verse is not present in this witness
+    pub fn tokens(&self, verse_id: i64) -> Option<Vec<Token>> {
+        self.verses.get(&verse_id).map(|blocks| tokenize(blocks))
+    }
+}
+
+/// Load the accepted text of a manuscript as a witness
+///
+/// The pages are joined in order, so verses may continue across pages.
+pub async fn load_witness(
+    config: &Config,
+    msname: &str,
+    anchors: &AnchorMap,
+) -> Result<Witness, ExportError> {
+    let (meta, pages) =
+        manuscript_transcriptions(config, msname, &TranscriptionChoice::default()).await?;
+    Ok(Witness {
+        siglum: meta.title,
+        verses: split_at_verses(pages.into_iter().flat_map(|(_, ms)| ms.content), anchors),
+    })
+}
+
+/// The pages (in page order) that may contain text of the verses `verse_start..=verse_end`
+///
+/// Pages whose verses are unknown are kept, as is the page after a page in the range, since the
+/// last verse may continue on it.
+pub fn pages_in_range(pages: &[PageMeta], verse_start: i64, verse_end: i64) -> Vec<&PageMeta> {
+    let overlaps = |page: &PageMeta| match (page.verse_start, page.verse_end) {
+        (Some(first), Some(last)) => first <= verse_end && last >= verse_start,
+        _ => true,
+    };
+    pages
+        .iter()
+        .enumerate()
+        .filter(|&(idx, page)| overlaps(page) || (idx > 0 && overlaps(&pages[idx - 1])))
+        .map(|(_, page)| page)
+        .collect()
+}
+
+/// Load the accepted text of the pages of a manuscript covering `verse_start..=verse_end` as a
+/// witness
+///
+/// Pages that have neither an accepted transcription nor a single published one are left out, as
+/// are pages whose transcription cannot be read.
+pub async fn load_witness_range(
+    config: &Config,
+    msname: &str,
+    anchors: &AnchorMap,
+    verse_start: i64,
+    verse_end: i64,
+) -> Result<Witness, ExportError> {
+    let manuscript = get_manuscript(&config.db, msname)
+        .await
+        .map_err(ExportError::DB)?;
+    let mut pages = manuscript.pages;
+    // pages do not have an explicit position, they are ordered by upload
+    pages.sort_by_key(|page| page.id);
+    let mut blocks = vec![];
+    for page in pages_in_range(&pages, verse_start, verse_end) {
+        match load_page_transcription(config, msname, page, &TranscriptionChoice::default()).await {
+            Ok(Some(transcription)) => blocks.extend(transcription.content),
+            Ok(None) => {}
+            Err(ExportError::AmbiguousTranscription(msname, pagename, usernames)) => {
+                tracing::info!(
+                    "Leaving {msname} {pagename} out of the collation: it has not been reconciled and has several published transcriptions ({}).",
+                    usernames.join(", ")
+                );
+            }
+            Err(ExportError::Tei(e)) => {
+                tracing::warn!(
+                    "Leaving {msname} {} out of the collation: its transcription cannot be read: {e}",
+                    page.name
+                );
+            }
+            Err(e) => return Err(e),
+        };
+    }
+    Ok(Witness {
+        siglum: manuscript.meta.title,
+        verses: split_at_verses(blocks, anchors),
+    })
+}
+
+/// The alignment of one witness against the base
+struct Alignment<'a> {
+    tokens: &'a [Token],
+    matches: Vec<(usize, usize)>,
+}
+impl Alignment<'_> {
+    /// The base ranges in which this witness differs from the base
+    ///
+    /// Insertions are given as empty ranges before the base token they precede.
+    fn differences(&self, base_len: usize) -> Vec<(usize, usize)> {
+        let mut res = vec![];
+        let (mut last_base, mut last_witness) = (0, 0);
+        let end = (base_len, self.tokens.len());
+        for &(b, w) in self.matches.iter().chain(std::iter::once(&end)) {
+            if b != last_base || w != last_witness {
+                res.push((last_base, b));
+            };
+            (last_base, last_witness) = (b + 1, w + 1);
+        }
+        res
+    }
+
+    /// The tokens of this witness corresponding to the base range `start..end`
+    fn reading(&self, start: usize, end: usize) -> Vec<Token> {
+        let witness_start = self
+            .matches
+            .iter()
+            .rev()
+            .find(|(b, _)| *b < start)
+            .map_or(0, |(_, w)| w + 1);
+        let witness_end = self
+            .matches
+            .iter()
+            .find(|(b, _)| *b >= end)
+            .map_or(self.tokens.len(), |(_, w)| *w);
+        self.tokens[witness_start..witness_end.max(witness_start)].to_vec()
+    }
+}
+
+/// A reading shared by some witnesses
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading {
+    /// the tokens of this reading, empty for omissions
+    pub tokens: Vec<Token>,
+    /// the witnesses with this reading
+    pub sigla: Vec<String>,
+}
+
+/// A place where at least one witness differs from the base
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantUnit {
+    /// first base token of the lemma
+    pub start: usize,
+    /// end (exclusive) of the lemma in the base tokens
+    pub end: usize,
+    /// the readings differing from the base
+    pub readings: Vec<Reading>,
+}
+
+/// A verse collated against the base witness
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollatedVerse {
+    pub verse_id: i64,
+    /// the tokens of the base witness
+    pub base: Vec<Token>,
+    pub units: Vec<VariantUnit>,
+    /// the witnesses that do not contain this verse at all
+    pub missing: Vec<String>,
+}
+
+/// Merge overlapping and adjacent ranges, extending insertions to the preceding base token
+///
+/// Adjacent ranges are merged as well, because an insertion at the border of two ranges would
+/// otherwise show up in both.
+fn merge_ranges(mut ranges: Vec<(usize, usize)>, base_len: usize) -> Vec<(usize, usize)> {
+    for range in ranges.iter_mut() {
+        // an insertion needs a lemma: use the word before (or after at the start of the verse)
+        if range.0 == range.1 && base_len > 0 {
+            if range.0 > 0 {
+                range.0 -= 1;
+            } else {
+                range.1 = 1;
+            }
+        };
+    }
+    ranges.sort();
+    let mut res: Vec<(usize, usize)> = vec![];
+    for (start, end) in ranges {
+        match res.last_mut() {
+            Some(last) if start <= last.1 => {
+                last.1 = last.1.max(end);
+            }
+            _ => res.push((start, end)),
+        };
+    }
+    res
+}
+
+/// Collate the witnesses of a single verse against the base
+///
+/// `witnesses` are given as (siglum, tokens), None if the verse is not present in a witness.
+pub fn collate_verse(
+    verse_id: i64,
+    base: Vec<Token>,
+    witnesses: &[(String, Option<Vec<Token>>)],
+) -> CollatedVerse {
+    let mut missing = vec![];
+    let mut alignments = vec![];
+    for (siglum, tokens) in witnesses {
+        match tokens {
+            Some(tokens) => alignments.push((
+                siglum,
+                Alignment {
+                    tokens,
+                    matches: lcs_matches(&base, tokens, Token::agrees_with),
+                },
+            )),
+            None => missing.push(siglum.clone()),
+        };
+    }
+
+    let ranges = merge_ranges(
+        alignments
+            .iter()
+            .flat_map(|(_, a)| a.differences(base.len()))
+            .collect(),
+        base.len(),
+    );
+    let units = ranges
+        .into_iter()
+        .filter_map(|(start, end)| {
+            let lemma = &base[start..end];
+            let mut readings: Vec<Reading> = vec![];
+            for (siglum, alignment) in &alignments {
+                let tokens = alignment.reading(start, end);
+                if tokens.len() == lemma.len()
+                    && tokens.iter().zip(lemma).all(|(t, l)| t.agrees_with(l))
+                {
+                    continue;
+                };
+                match readings.iter_mut().find(|r| r.tokens == tokens) {
+                    Some(reading) => reading.sigla.push(siglum.to_string()),
+                    None => readings.push(Reading {
+                        tokens,
+                        sigla: vec![siglum.to_string()],
+                    }),
+                };
+            }
+            (!readings.is_empty()).then_some(VariantUnit {
+                start,
+                end,
+                readings,
+            })
+        })
+        .collect();
+    CollatedVerse {
+        verse_id,
+        base,
+        units,
+        missing,
+    }
+}
+
+/// Collate the verses `verse_start..=verse_end` of `witnesses` against `base`
+///
+/// Verses missing in the base witness are collated against an empty base.
+pub fn collate(
+    base: &Witness,
+    witnesses: &[Witness],
+    verse_start: i64,
+    verse_end: i64,
+) -> Vec<CollatedVerse> {
+    // all verses in the range present in any witness
+    let mut verse_ids = base
+        .verses
+        .range(verse_start..=verse_end)
+        .map(|(id, _)| *id)
+        .chain(
+            witnesses
+                .iter()
+                .flat_map(|w| w.verses.range(verse_start..=verse_end).map(|(id, _)| *id)),
+        )
+        .collect::<Vec<_>>();
+    verse_ids.sort();
+    verse_ids.dedup();
+
+    verse_ids
+        .into_iter()
+        .map(|verse_id| {
+            let others = witnesses
+                .iter()
+                .map(|w| (w.siglum.clone(), w.tokens(verse_id)))
+                .collect::<Vec<_>>();
+            collate_verse(verse_id, base.tokens(verse_id).unwrap_or_default(), &others)
+        })
+        .collect()
+}
+
+/// Turn collated verses into a variant table
+///
+/// Every location lists the reading of the base first, supported by the base and all witnesses
+/// agreeing with it. Verses are labeled with their number in the scheme `shorthand`.
+pub fn variant_table(
+    base: &Witness,
+    witnesses: &[Witness],
+    verses: Vec<CollatedVerse>,
+    anchors: &AnchorMap,
+    shorthand: &str,
+) -> VariantTable {
+    let verses = verses
+        .into_iter()
+        .map(|verse| {
+            let present = witnesses
+                .iter()
+                .map(|w| &w.siglum)
+                .filter(|siglum| !verse.missing.contains(siglum))
+                .collect::<Vec<_>>();
+            let locations = verse
+                .units
+                .into_iter()
+                .map(|unit| {
+                    let mut sigla = vec![base.siglum.clone()];
+                    sigla.extend(
+                        present
+                            .iter()
+                            .filter(|siglum| {
+                                !unit.readings.iter().any(|r| r.sigla.contains(siglum))
+                            })
+                            .map(|siglum| siglum.to_string()),
+                    );
+                    let mut readings = vec![VariantReading {
+                        tokens: verse.base[unit.start..unit.end].to_vec(),
+                        sigla,
+                    }];
+                    readings.extend(unit.readings.into_iter().map(|r| VariantReading {
+                        tokens: r.tokens,
+                        sigla: r.sigla,
+                    }));
+                    VariantLocation {
+                        start: unit.start,
+                        end: unit.end,
+                        readings,
+                    }
+                })
+                .collect();
+            VariantVerse {
+                verse_nr: anchors
+                    .verse_nr(verse.verse_id, shorthand)
+                    .map_or(verse.verse_id.to_string(), str::to_string),
+                base: verse.base,
+                locations,
+                missing: verse.missing,
+            }
+        })
+        .collect();
+    VariantTable {
+        base: base.siglum.clone(),
+        witnesses: witnesses.iter().map(|w| w.siglum.clone()).collect(),
+        verses,
+    }
+}
+
+/// Collate all manuscripts covering the verses `start..=end` (verse numbers in the scheme
+/// `shorthand`)
+///
+/// The manuscript `base` is used as base witness, if it is None the first manuscript covering the
+/// range is. Only the pages covering the range are loaded, see [`load_witness_range`].
+pub async fn collate_range(
+    config: &Config,
+    shorthand: &str,
+    start: &str,
+    end: &str,
+    base: Option<String>,
+) -> Result<VariantTable, ExportError> {
+    let anchors = load_anchor_map(config).await?;
+    let verse_id = |verse_nr: &str| {
+        anchors
+            .verse_id_by_number(shorthand, verse_nr)
+            .ok_or(ExportError::DB(DBError::VerseDoesNotExist(
+                verse_nr.to_string(),
+            )))
+    };
+    let (verse_start, verse_end) = (verse_id(start)?, verse_id(end)?);
+
+    let mut witnesses = vec![];
+    for manuscript in get_manuscripts_by_name(&config.db, None)
+        .await
+        .map_err(ExportError::DB)?
+    {
+        let witness =
+            load_witness_range(config, &manuscript.title, &anchors, verse_start, verse_end).await?;
+        if witness
+            .verses
+            .range(verse_start..=verse_end)
+            .next()
+            .is_some()
+            || base.as_ref() == Some(&witness.siglum)
+        {
+            witnesses.push(witness);
+        };
+    }
+
+    let base_idx = match &base {
+        Some(base) => witnesses
+            .iter()
+            .position(|w| &w.siglum == base)
+            .ok_or(ExportError::DB(DBError::ManuscriptDoesNotExist(
+                base.clone(),
+            )))?,
+        None => 0,
+    };
+    if witnesses.is_empty() {
+        return Ok(VariantTable {
+            base: String::default(),
+            witnesses: vec![],
+            verses: vec![],
+        });
+    };
+    let base = witnesses.remove(base_idx);
+    let collated = collate(&base, &witnesses, verse_start, verse_end);
+    Ok(variant_table(
+        &base, &witnesses, collated, &anchors, shorthand,
+    ))
+}
+
+#[cfg(test)]
+mod test;
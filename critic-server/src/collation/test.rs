@@ -0,0 +1,320 @@
+//! Tests for collating manuscripts
+
+use critic_format::streamed::{
+    Abbreviation, Block, BlockType, BreakType, Correction, FromTypeLangAndContent, Paragraph,
+    Version,
+};
+use critic_shared::{
+    anchor::verse_anchor,
+    collation::{tokens_text, Token, TokenKind, VariantLocation},
+    diff::lcs_matches,
+    PageMeta, VersificationScheme,
+};
+
+use super::{
+    collate, collate_verse, merge_ranges, pages_in_range, split_at_verses, tokenize, variant_table,
+    Alignment, AnchorMap, Reading, VariantUnit, Witness,
+};
+use crate::db::VerseMapEntry;
+
+fn page(id: i64, verses: Option<(i64, i64)>) -> PageMeta {
+    PageMeta {
+        id,
+        manuscript_id: 1,
+        name: format!("{id}r"),
+        verse_start: verses.map(|(first, _)| first),
+        verse_end: verses.map(|(_, last)| last),
+    }
+}
+
+fn ids(pages: Vec<&PageMeta>) -> Vec<i64> {
+    pages.into_iter().map(|page| page.id).collect()
+}
+
+#[test]
+fn pages_outside_the_range_are_left_out() {
+    let pages = vec![
+        page(1, Some((1, 10))),
+        page(2, Some((11, 20))),
+        page(3, Some((21, 30))),
+        page(4, Some((31, 40))),
+        page(5, Some((41, 50))),
+    ];
+    assert_eq!(ids(pages_in_range(&pages, 22, 25)), vec![3, 4]);
+    assert_eq!(ids(pages_in_range(&pages, 45, 60)), vec![5]);
+    assert!(pages_in_range(&pages, 60, 70).is_empty());
+}
+
+#[test]
+fn ranges_across_pages_keep_every_page_they_touch() {
+    let pages = vec![
+        page(1, Some((1, 10))),
+        page(2, Some((10, 20))),
+        page(3, Some((21, 30))),
+        page(4, Some((31, 40))),
+    ];
+    // verse 10 starts on page 1 and continues on page 2
+    assert_eq!(ids(pages_in_range(&pages, 10, 10)), vec![1, 2, 3]);
+    assert_eq!(ids(pages_in_range(&pages, 15, 25)), vec![2, 3, 4]);
+}
+
+#[test]
+fn pages_with_unknown_verses_are_kept() {
+    let pages = vec![
+        page(1, Some((1, 10))),
+        page(2, None),
+        page(3, Some((21, 30))),
+    ];
+    assert_eq!(ids(pages_in_range(&pages, 25, 25)), vec![2, 3]);
+}
+
+fn words(text: &str) -> Vec<Token> {
+    text.split_whitespace()
+        .map(|w| Token {
+            text: w.to_string(),
+            kind: TokenKind::Word,
+        })
+        .collect()
+}
+
+fn text(content: &str) -> Block {
+    Block::Text(Paragraph {
+        lang: "hbo-Hebr".to_string(),
+        content: content.to_string(),
+    })
+}
+
+fn scheme() -> VersificationScheme {
+    VersificationScheme {
+        id: 1,
+        full_name: "Present".to_string(),
+        shorthand: "P".to_string(),
+    }
+}
+
+/// Genesis 1:1 and 1:2 as verses 1 and 2
+fn anchors() -> AnchorMap {
+    AnchorMap::new(
+        [(1, "Gen 1:1"), (2, "Gen 1:2")]
+            .into_iter()
+            .map(|(verse_id, verse_nr)| VerseMapEntry {
+                verse_id,
+                shorthand: "P".to_string(),
+                verse_nr: verse_nr.to_string(),
+            })
+            .collect(),
+    )
+}
+
+fn witness(siglum: &str, verses: &[(i64, &str)]) -> Witness {
+    Witness {
+        siglum: siglum.to_string(),
+        verses: verses
+            .iter()
+            .map(|(verse_id, content)| (*verse_id, vec![text(content)]))
+            .collect(),
+    }
+}
+
+#[test]
+fn tokens_of_every_kind_of_block() {
+    let abbreviation = Block::Abbreviation(Abbreviation {
+        surface_lang: "hbo-Hebr".to_string(),
+        expansion_lang: "hbo-Hebr".to_string(),
+        surface: "אל'".to_string(),
+        expansion: "אלהים".to_string(),
+    });
+    let correction = Block::Correction(Correction {
+        versions: vec![
+            Version {
+                lang: "hbo-Hebr".to_string(),
+                hand: None,
+                content: "השמם".to_string(),
+            },
+            Version {
+                lang: "hbo-Hebr".to_string(),
+                hand: None,
+                content: "השמים ואת".to_string(),
+            },
+        ],
+    });
+    let uncertain = Block::from_type_lang_and_content(
+        BlockType::Uncertain,
+        "hbo-Hebr".to_string(),
+        "את".to_string(),
+    );
+    let lacuna =
+        Block::from_type_lang_and_content(BlockType::Lacuna, String::default(), String::default());
+    let blocks = vec![
+        verse_anchor(&scheme(), "Gen 1:1"),
+        text("בראשית  ברא"),
+        abbreviation,
+        Block::Break(BreakType::Line),
+        uncertain,
+        correction,
+        lacuna,
+    ];
+    let corrected = |text: &str| Token {
+        text: text.to_string(),
+        kind: TokenKind::Corrected {
+            original: "השמם".to_string(),
+        },
+    };
+    let mut expected = words("בראשית ברא אלהים");
+    expected.push(Token {
+        text: "את".to_string(),
+        kind: TokenKind::Uncertain,
+    });
+    expected.push(corrected("השמים"));
+    expected.push(corrected("ואת"));
+    expected.push(Token {
+        text: String::default(),
+        kind: TokenKind::Lacuna,
+    });
+    assert_eq!(tokenize(&blocks), expected);
+}
+
+#[test]
+fn blocks_are_split_at_known_verse_anchors() {
+    let unknown = verse_anchor(&scheme(), "Gen 9:9");
+    let blocks = vec![
+        text("title"),
+        verse_anchor(&scheme(), "Gen 1:1"),
+        text("a"),
+        unknown.clone(),
+        text("b"),
+        verse_anchor(&scheme(), "Gen 1:2"),
+        text("c"),
+    ];
+    let verses = split_at_verses(blocks, &anchors());
+    assert_eq!(verses.len(), 2);
+    assert_eq!(verses[&1], vec![text("a"), unknown, text("b")]);
+    assert_eq!(verses[&2], vec![text("c")]);
+}
+
+#[test]
+fn insertions_get_a_lemma_and_touching_ranges_merge() {
+    // an insertion takes the word before it, or the first word at the start of the verse
+    assert_eq!(merge_ranges(vec![(2, 2)], 5), vec![(1, 2)]);
+    assert_eq!(merge_ranges(vec![(0, 0)], 5), vec![(0, 1)]);
+    // nothing to take in an empty base
+    assert_eq!(merge_ranges(vec![(0, 0)], 0), vec![(0, 0)]);
+    assert_eq!(
+        merge_ranges(vec![(3, 4), (1, 3), (2, 3), (6, 7)], 8),
+        vec![(1, 4), (6, 7)]
+    );
+}
+
+#[test]
+fn alignments_find_differences_and_readings() {
+    let base = words("a b c d");
+    let tokens = words("a x c d e");
+    let alignment = Alignment {
+        tokens: &tokens,
+        matches: lcs_matches(&base, &tokens, Token::agrees_with),
+    };
+    // x replaces b, e is inserted at the end
+    assert_eq!(alignment.differences(base.len()), vec![(1, 2), (4, 4)]);
+    assert_eq!(alignment.reading(1, 2), words("x"));
+    assert_eq!(alignment.reading(3, 4), words("d e"));
+}
+
+#[test]
+fn witnesses_with_the_same_reading_share_it() {
+    let witnesses = vec![
+        ("A".to_string(), Some(words("a x c d e"))),
+        ("B".to_string(), Some(words("a b c d"))),
+        ("C".to_string(), None),
+        ("D".to_string(), Some(words("a x c d"))),
+    ];
+    let verse = collate_verse(1, words("a b c d"), &witnesses);
+    assert_eq!(verse.missing, vec!["C"]);
+    assert_eq!(
+        verse.units,
+        vec![
+            VariantUnit {
+                start: 1,
+                end: 2,
+                readings: vec![Reading {
+                    tokens: words("x"),
+                    sigla: vec!["A".to_string(), "D".to_string()],
+                }],
+            },
+            VariantUnit {
+                start: 3,
+                end: 4,
+                readings: vec![Reading {
+                    tokens: words("d e"),
+                    sigla: vec!["A".to_string()],
+                }],
+            },
+        ]
+    );
+}
+
+#[test]
+fn lacunae_never_agree() {
+    let mut tokens = words("a");
+    tokens.push(Token {
+        text: String::default(),
+        kind: TokenKind::Lacuna,
+    });
+    let verse = collate_verse(1, words("a b"), &[("A".to_string(), Some(tokens.clone()))]);
+    assert_eq!(verse.units.len(), 1);
+    assert_eq!(verse.units[0].readings[0].tokens, tokens[1..].to_vec());
+}
+
+#[test]
+fn variant_tables_list_the_base_reading_first() {
+    let base = witness("L", &[(1, "a b c d")]);
+    let witnesses = vec![
+        witness("A", &[(1, "a x c d e"), (2, "y")]),
+        witness("B", &[(1, "a b c d")]),
+        witness("C", &[]),
+        witness("D", &[(1, "a x c d")]),
+    ];
+    let collated = collate(&base, &witnesses, 1, 2);
+    let table = variant_table(&base, &witnesses, collated, &anchors(), "P");
+    assert_eq!(table.base, "L");
+    assert_eq!(table.witnesses, vec!["A", "B", "C", "D"]);
+    assert_eq!(table.verses.len(), 2);
+
+    let first = &table.verses[0];
+    assert_eq!(first.verse_nr, "Gen 1:1");
+    assert_eq!(first.base, words("a b c d"));
+    assert_eq!(first.missing, vec!["C"]);
+    let readings = |location: &VariantLocation| {
+        location
+            .readings
+            .iter()
+            .map(|r| (tokens_text(&r.tokens), r.sigla.join(" ")))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        readings(&first.locations[0]),
+        vec![
+            ("b".to_string(), "L B".to_string()),
+            ("x".to_string(), "A D".to_string())
+        ]
+    );
+    assert_eq!(
+        readings(&first.locations[1]),
+        vec![
+            ("d".to_string(), "L B D".to_string()),
+            ("d e".to_string(), "A".to_string())
+        ]
+    );
+
+    // the base does not have verse 2, so it is collated against an empty base
+    let second = &table.verses[1];
+    assert_eq!(second.verse_nr, "Gen 1:2");
+    assert!(second.base.is_empty());
+    assert_eq!(second.missing, vec!["B", "C", "D"]);
+    assert_eq!(
+        readings(&second.locations[0]),
+        vec![
+            (String::default(), "L".to_string()),
+            ("y".to_string(), "A".to_string())
+        ]
+    );
+}
@@ -1,6 +1,13 @@
 //! Parse Config from config file
+//!
+//! Some settings can change while critic is running, see [`Config::reload`].
 
-use std::{fs::read_to_string, path::Path, str::FromStr};
+use std::{
+    fs::read_to_string,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, OnceLock, PoisonError, RwLock},
+};
 
 use leptos::config::LeptosOptions;
 use serde::Deserialize;
@@ -8,14 +15,51 @@ use sqlx::{Pool, Postgres};
 use tracing::{error, level_filters::LevelFilter};
 use tracing_subscriber::filter::LevelParseError;
 
+use crate::{
+    agreement::AgreementCache,
+    auth::{
+        dev::DevLoginConfig,
+        oidc::{OidcConfig, OidcError, OidcProvider},
+    },
+    forge::{
+        forgejo::{ForgejoConfig, ForgejoForge},
+        local::{LocalForge, LocalForgeConfig},
+        Forge, OauthEndpoints, ReloadableForge,
+    },
+    gitlab::GitlabForge,
+    session::{CriticSessionStore, SessionConfig},
+    tls::TlsConfig,
+};
+
+mod reload;
+pub use reload::{LogLevelSetter, ReloadReport};
+
+#[cfg(test)]
+mod test;
+
+/// Where the config is read from
+const CONFIG_PATH: &str = "/etc/critic/config.toml";
+
 #[derive(Debug)]
 pub enum ConfigError {
     TomlParse(toml::de::Error),
     ConfigFileRead(std::io::Error),
     PoolCreate(sqlx::Error),
     LogLevel(LevelParseError),
-    GitlabAddrParse(oauth2::url::ParseError),
+    ForgeAddrParse(oauth2::url::ParseError),
     PublicAddrParse(oauth2::url::ParseError),
+    /// The section configuring the selected forge is missing
+    MissingForgeConfig(&'static str),
+    /// oauth is configured, but users cannot log in with the selected forge
+    OauthNotSupported,
+    Oidc(OidcError),
+    /// the development login is configured in a release build without allowing it
+    DevLoginInRelease,
+    SessionStore(sqlx::Error),
+    /// consistency_check_interval_hours is less than one hour
+    ConsistencyCheckInterval,
+    /// accepted_branch and gitlab.accepted_branch are set to different branches
+    AcceptedBranchConflict,
 }
 impl core::fmt::Display for ConfigError {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
@@ -32,16 +76,46 @@ impl core::fmt::Display for ConfigError {
             Self::LogLevel(e) => {
                 write!(f, "Unable to parse log_level: {e}")
             }
-            Self::GitlabAddrParse(e) => {
+            Self::ForgeAddrParse(e) => {
+                write!(f, "Unable to build the oauth urls of the forge: {e}")
+            }
+            Self::PublicAddrParse(e) => {
                 write!(
                     f,
-                    "Unable to interpret gitlab_addr as addr while using it to build a url: {e}"
+                    "Unable to interpret public_addr as addr while using it to build a url: {e}"
                 )
             }
-            Self::PublicAddrParse(e) => {
+            Self::MissingForgeConfig(section) => {
                 write!(
                     f,
-                    "Unable to interpret public_addr as addr while using it to build a url: {e}"
+                    "The selected forge needs to be configured in the [{section}] section"
+                )
+            }
+            Self::OauthNotSupported => {
+                write!(
+                    f,
+                    "oauth is configured, but the selected forge does not support logging in with oauth"
+                )
+            }
+            Self::Oidc(e) => {
+                write!(f, "Unable to configure OpenID Connect: {e}")
+            }
+            Self::SessionStore(e) => {
+                write!(f, "Unable to open the session store: {e}")
+            }
+            Self::DevLoginInRelease => {
+                write!(
+                    f,
+                    "The development login is configured, but this is a release build. Set allow_in_release in [dev_login] to use it anyway."
+                )
+            }
+            Self::ConsistencyCheckInterval => {
+                write!(f, "consistency_check_interval_hours must be at least 1")
+            }
+            Self::AcceptedBranchConflict => {
+                write!(
+                    f,
+                    "accepted_branch and gitlab.accepted_branch name different branches. Only set the top-level accepted_branch."
                 )
             }
         }
@@ -75,6 +149,10 @@ struct WebConfigData {
     /// Where is this website called from on the internet (including any reverse-proxies, NAT etc.)
     /// Gitlab must be able to communicate with critic via this FQDN, using https
     public_addr: String,
+    /// The scheme public_addr is reached with in links to critic (e.g. in exports), https if not set
+    public_scheme: Option<String>,
+    /// Serve https directly instead of http behind a reverse proxy
+    tls: Option<TlsConfig>,
 }
 
 #[derive(Deserialize)]
@@ -86,9 +164,9 @@ struct OauthConfigData {
 /// The OauthConfig that will be usable to create clients on the server side
 #[derive(Deserialize)]
 struct OauthConfig {
-    /// the client ID we use to authenticate to gitlab
+    /// the client ID we use to authenticate to the forge
     client_id: oauth2::ClientId,
-    /// the client secret we use to authenticate to gitlab
+    /// the client secret we use to authenticate to the forge
     client_secret: oauth2::ClientSecret,
     auth_url: oauth2::AuthUrl,
     token_url: oauth2::TokenUrl,
@@ -97,16 +175,16 @@ struct OauthConfig {
 impl OauthConfig {
     fn try_from_config_data(
         value: OauthConfigData,
-        gitlab_addr: &str,
+        endpoints: OauthEndpoints,
         public_addr: &str,
     ) -> Result<Self, ConfigError> {
         Ok(Self {
             client_id: oauth2::ClientId::new(value.client_id),
             client_secret: oauth2::ClientSecret::new(value.client_secret),
-            auth_url: oauth2::AuthUrl::new(format!("https://{gitlab_addr}/oauth/authorize"))
-                .map_err(ConfigError::GitlabAddrParse)?,
-            token_url: oauth2::TokenUrl::new(format!("https://{gitlab_addr}/oauth/token"))
-                .map_err(ConfigError::GitlabAddrParse)?,
+            auth_url: oauth2::AuthUrl::new(endpoints.auth_url)
+                .map_err(ConfigError::ForgeAddrParse)?,
+            token_url: oauth2::TokenUrl::new(endpoints.token_url)
+                .map_err(ConfigError::ForgeAddrParse)?,
             redirect_url: oauth2::RedirectUrl::new(format!("https://{public_addr}/oauth/redirect"))
                 .map_err(ConfigError::PublicAddrParse)?,
         })
@@ -138,7 +216,7 @@ impl From<OauthConfig> for OauthClient {
 }
 
 /// Config partaining to the gitlab instance
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct GitlabConfig {
     /// the address where we can talk to gitlab
     pub addr: String,
@@ -150,6 +228,32 @@ pub struct GitlabConfig {
     ///
     /// This project MUST live in the namespace given by group_name
     pub project_name: String,
+    /// A project access token with read_repository scope
+    ///
+    /// Used whenever critic talks to gitlab without a logged-in user, e.g. to re-import the
+    /// files of merged reconciliations.
+    pub access_token: Option<String>,
+    /// The secret gitlab sends in the X-Gitlab-Token header of its webhooks
+    ///
+    /// All webhooks are rejected while this is not set.
+    pub webhook_secret: Option<String>,
+    /// Where the accepted branch used to be configured, use the top-level `accepted_branch`
+    ///
+    /// Still read if the top-level key is not set.
+    pub accepted_branch: Option<String>,
+}
+
+/// The forges critic can work with
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ForgeKind {
+    /// gitlab, configured in the [gitlab] section
+    #[default]
+    Gitlab,
+    /// forgejo or gitea, configured in the [forgejo] section
+    Forgejo,
+    /// a bare git repository on the local disk, configured in the [local_forge] section
+    Local,
 }
 
 /// The config data as it is present in (a well-formed) toml config file
@@ -158,9 +262,24 @@ struct ConfigData {
     db: DbConfigData,
     web: WebConfigData,
     log_level: Option<String>,
-    oauth: OauthConfigData,
+    /// the oauth application in the forge, if users log in with the forge
+    oauth: Option<OauthConfigData>,
+    /// the OpenID provider, if readers and transcribers log in with it
+    oidc: Option<OidcConfig>,
+    /// users to log in as without any identity provider, for development only
+    dev_login: Option<DevLoginConfig>,
+    /// where sessions are stored (postgres unless configured otherwise)
+    #[serde(default)]
+    sessions: SessionConfig,
+    /// which forge hosts the repository
+    #[serde(default)]
+    forge: ForgeKind,
     /// used as server part for determining where to communicate to gitlab
-    gitlab: GitlabConfig,
+    gitlab: Option<GitlabConfig>,
+    forgejo: Option<ForgejoConfig>,
+    local_forge: Option<LocalForgeConfig>,
+    /// The branch accepted reconciliations are merged into
+    accepted_branch: Option<String>,
     /// The directory where xml and image files should live
     ///
     /// critic will create the required substructure there
@@ -172,10 +291,48 @@ struct ConfigData {
     /// from time to time
     #[serde(default = "default_worker_threads")]
     worker_threads: u8,
+    /// how many hours should pass between two runs of the consistency checker? (at least 1)
+    #[serde(default = "default_consistency_check_interval_hours")]
+    consistency_check_interval_hours: u64,
+}
+fn default_accepted_branch() -> String {
+    "critic/accepted".to_string()
+}
+
+/// The accepted branch, also read from where it used to be configured (`gitlab.accepted_branch`)
+fn accepted_branch(
+    accepted_branch: Option<String>,
+    gitlab: Option<&GitlabConfig>,
+) -> Result<String, ConfigError> {
+    match (
+        accepted_branch,
+        gitlab.and_then(|gitlab| gitlab.accepted_branch.clone()),
+    ) {
+        (Some(branch), Some(old)) if branch != old => Err(ConfigError::AcceptedBranchConflict),
+        (Some(branch), _) | (None, Some(branch)) => Ok(branch),
+        (None, None) => Ok(default_accepted_branch()),
+    }
 }
 fn default_worker_threads() -> u8 {
     4
 }
+fn default_consistency_check_interval_hours() -> u64 {
+    24
+}
+
+/// The settings that can change while critic is running
+#[derive(Debug, Clone)]
+pub struct RuntimeSettings {
+    pub log_level: LevelFilter,
+    /// how many worker threads new minification batches use
+    pub worker_threads: u8,
+    /// used as server part for determining where to communicate to gitlab
+    ///
+    /// None unless gitlab is the forge.
+    pub gitlab: Option<GitlabConfig>,
+    /// None if critic serves plain http (usually behind a reverse proxy doing TLS)
+    pub tls: Option<TlsConfig>,
+}
 
 /// The main config object that will be available across the Serverside application
 #[derive(Debug)]
@@ -183,15 +340,39 @@ pub struct Config {
     // DB pool to use
     pub db: Pool<Postgres>,
     pub leptos_options: LeptosOptions,
-    pub log_level: LevelFilter,
-    pub oauth_client: OauthClient,
-    /// used as server part for determining where to communicate to gitlab
-    pub gitlab: GitlabConfig,
+    /// settings that are changed by [`Config::reload`], read them with [`Config::runtime`]
+    runtime: RwLock<RuntimeSettings>,
+    /// applies a changed log level to the tracing subscriber
+    log_level_setter: OnceLock<LogLevelSetter>,
+    /// is notified when the TLS certificate should be reloaded
+    pub tls_reload: tokio::sync::Notify,
+    /// the config file as it was read at startup, to find changes that need a restart
+    loaded: toml::Table,
+    /// None if users do not log in with the forge
+    pub oauth_client: Option<OauthClient>,
+    /// None if users do not log in with an OpenID provider
+    pub oidc: Option<Arc<OidcProvider>>,
+    /// None if the development login is not enabled
+    pub dev_login: Option<Arc<DevLoginConfig>>,
+    pub session_store: CriticSessionStore,
+    pub forge_kind: ForgeKind,
+    /// replaced when the gitlab settings are reloaded
+    pub forge: Arc<ReloadableForge>,
+    /// The branch accepted reconciliations are merged into
+    pub accepted_branch: String,
     pub data_directory: String,
-    pub worker_threads: u8,
+    /// the agreements between transcriptions computed so far
+    pub agreement_cache: AgreementCache,
+    /// how many hours should pass between two runs of the consistency checker
+    pub consistency_check_interval_hours: u64,
+    /// Where is this website called from on the internet, with scheme (e.g. https://example.org)
+    pub public_url: String,
 }
 impl Config {
-    async fn try_from_config_data(value: ConfigData) -> Result<Self, ConfigError> {
+    async fn try_from_config_data(
+        value: ConfigData,
+        loaded: toml::Table,
+    ) -> Result<Self, ConfigError> {
         // postgres settings
         let url = format!(
             "postgres://{}:{}@{}:{}/{}",
@@ -209,6 +390,10 @@ impl Config {
             }
         };
 
+        let session_store = CriticSessionStore::try_new(&value.sessions, &db)
+            .await
+            .map_err(ConfigError::SessionStore)?;
+
         let addr = std::net::SocketAddr::from_str(&value.web.site_addr)
             .expect("Should be able to parse socket addr");
 
@@ -218,30 +403,120 @@ impl Config {
             .site_pkg_dir("pkg")
             .site_addr(addr)
             .build();
-        let log_level = tracing_subscriber::filter::LevelFilter::from_str(
-            &value.log_level.unwrap_or("INFO".to_string()),
-        )?;
+        let log_level = parse_log_level(value.log_level)?;
+        let accepted_branch = accepted_branch(value.accepted_branch, value.gitlab.as_ref())?;
+
+        let (forge, gitlab): (Arc<dyn Forge>, _) = match value.forge {
+            ForgeKind::Gitlab => {
+                let gitlab = value
+                    .gitlab
+                    .ok_or(ConfigError::MissingForgeConfig("gitlab"))?;
+                (Arc::new(GitlabForge::new(&gitlab)), Some(gitlab))
+            }
+            ForgeKind::Forgejo => (
+                Arc::new(ForgejoForge::new(
+                    &value
+                        .forgejo
+                        .ok_or(ConfigError::MissingForgeConfig("forgejo"))?,
+                )),
+                None,
+            ),
+            ForgeKind::Local => (
+                Arc::new(LocalForge::new(
+                    value
+                        .local_forge
+                        .ok_or(ConfigError::MissingForgeConfig("local_forge"))?,
+                )),
+                None,
+            ),
+        };
+        let oauth_client = match (value.oauth, forge.oauth_endpoints()) {
+            (None, _) => None,
+            (Some(oauth), Some(endpoints)) => Some(
+                OauthConfig::try_from_config_data(oauth, endpoints, &value.web.public_addr)?.into(),
+            ),
+            (Some(_), None) => return Err(ConfigError::OauthNotSupported),
+        };
+        let oidc = value
+            .oidc
+            .map(|oidc| OidcProvider::new(oidc, &value.web.public_addr).map(Arc::new))
+            .transpose()
+            .map_err(ConfigError::Oidc)?;
+        if let Some(dev_login) = &value.dev_login {
+            if !dev_login.is_allowed() {
+                return Err(ConfigError::DevLoginInRelease);
+            };
+        };
+        if value.consistency_check_interval_hours < 1 {
+            return Err(ConfigError::ConsistencyCheckInterval);
+        };
 
         Ok(Self {
             db,
             leptos_options,
-            log_level,
-            oauth_client: OauthConfig::try_from_config_data(
-                value.oauth,
-                &value.gitlab.addr,
-                &value.web.public_addr,
-            )?
-            .into(),
-            gitlab: value.gitlab,
+            runtime: RwLock::new(RuntimeSettings {
+                log_level,
+                worker_threads: value.worker_threads,
+                gitlab,
+                tls: value.web.tls,
+            }),
+            log_level_setter: OnceLock::new(),
+            tls_reload: tokio::sync::Notify::new(),
+            loaded,
+            oauth_client,
+            oidc,
+            dev_login: value.dev_login.map(Arc::new),
+            session_store,
+            forge_kind: value.forge,
+            forge: Arc::new(ReloadableForge::new(forge)),
+            accepted_branch,
             data_directory: value.data_directory,
-            worker_threads: value.worker_threads,
+            agreement_cache: AgreementCache::default(),
+            consistency_check_interval_hours: value.consistency_check_interval_hours,
+            public_url: format!(
+                "{}://{}",
+                value.web.public_scheme.as_deref().unwrap_or("https"),
+                value.web.public_addr
+            ),
         })
     }
 
     pub async fn try_create() -> Result<Self, ConfigError> {
-        let path = Path::new("/etc/critic/config.toml");
-        let content = read_to_string(path).map_err(ConfigError::ConfigFileRead)?;
-        let config_data: ConfigData = toml::from_str(&content).map_err(ConfigError::TomlParse)?;
-        Self::try_from_config_data(config_data).await
+        let (config_data, loaded) = read_config_file()?;
+        Self::try_from_config_data(config_data, loaded).await
     }
+
+    /// The current settings that can change at runtime
+    pub fn runtime(&self) -> RuntimeSettings {
+        self.runtime
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Set how a reloaded log level is applied, can only be set once
+    pub fn set_log_level_setter(&self, setter: LogLevelSetter) {
+        if self.log_level_setter.set(setter).is_err() {
+            tracing::warn!("The log level setter was already set, ignoring the new one.");
+        };
+    }
+}
+
+/// Read and parse the config file, both as config and as plain toml
+fn read_config_file() -> Result<(ConfigData, toml::Table), ConfigError> {
+    let content = read_to_string(Path::new(CONFIG_PATH)).map_err(ConfigError::ConfigFileRead)?;
+    parse_config(&content)
+}
+
+/// Parse the content of a config file, both into its settings and as plain toml
+fn parse_config(content: &str) -> Result<(ConfigData, toml::Table), ConfigError> {
+    let config_data: ConfigData = toml::from_str(content).map_err(ConfigError::TomlParse)?;
+    let table: toml::Table = toml::from_str(content).map_err(ConfigError::TomlParse)?;
+    Ok((config_data, table))
+}
+
+fn parse_log_level(log_level: Option<String>) -> Result<LevelFilter, ConfigError> {
+    Ok(LevelFilter::from_str(
+        &log_level.unwrap_or("INFO".to_string()),
+    )?)
 }
@@ -0,0 +1,237 @@
+//! Re-reading the config file while critic is running (on SIGHUP)
+//!
+//! Only some settings can be applied at runtime: the log level, the worker threads of new
+//! minification batches, the gitlab settings and the TLS certificate. Changes to all other
+//! settings are detected by comparing the file with the one read at startup and reported, they
+//! need a restart.
+
+use std::sync::{Arc, PoisonError};
+
+use tracing::level_filters::LevelFilter;
+
+use super::{
+    parse_log_level, read_config_file, Config, ConfigData, ConfigError, ForgeKind, GitlabConfig,
+    RuntimeSettings,
+};
+use crate::gitlab::GitlabForge;
+
+/// Top-level keys that are applied at runtime
+const RELOADABLE_KEYS: [&str; 3] = ["log_level", "worker_threads", "gitlab"];
+/// Keys in the [web] section that are applied at runtime
+const RELOADABLE_WEB_KEYS: [&str; 1] = ["tls"];
+
+/// Applies a log level to the running tracing subscriber
+pub struct LogLevelSetter(Box<dyn Fn(LevelFilter) -> Result<(), String> + Send + Sync>);
+impl LogLevelSetter {
+    pub fn new(setter: impl Fn(LevelFilter) -> Result<(), String> + Send + Sync + 'static) -> Self {
+        Self(Box::new(setter))
+    }
+}
+impl core::fmt::Debug for LogLevelSetter {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("LogLevelSetter").finish_non_exhaustive()
+    }
+}
+
+/// Which changed settings were applied and which need a restart
+#[derive(Debug, Default)]
+pub struct ReloadReport {
+    pub applied: Vec<String>,
+    pub restart_required: Vec<String>,
+}
+impl core::fmt::Display for ReloadReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        if self.applied.is_empty() {
+            write!(f, "No changed setting was applied.")?;
+        } else {
+            write!(f, "Applied changed settings: {}.", self.applied.join(", "))?;
+        };
+        if !self.restart_required.is_empty() {
+            write!(
+                f,
+                " These changed settings need a restart: {}.",
+                self.restart_required.join(", ")
+            )?;
+        };
+        Ok(())
+    }
+}
+
+/// The keys that differ between `old` and `new`, except those in `skip`
+fn changed_keys(old: &toml::Table, new: &toml::Table, prefix: &str, skip: &[&str]) -> Vec<String> {
+    let mut keys = old
+        .keys()
+        .chain(new.keys())
+        .filter(|key| !skip.contains(&key.as_str()))
+        .filter(|key| old.get(*key) != new.get(*key))
+        .map(|key| format!("{prefix}{key}"))
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// The [web] section of a config file
+fn web_section(table: &toml::Table) -> toml::Table {
+    table
+        .get("web")
+        .and_then(|web| web.as_table())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// What reloading changes, worked out without applying anything yet
+#[derive(Debug)]
+struct ReloadPlan {
+    report: ReloadReport,
+    /// the settings once the reload is applied
+    runtime: RuntimeSettings,
+    /// the log level to hand to the tracing subscriber, if it changed
+    new_log_level: Option<LevelFilter>,
+    /// the gitlab settings to build a new forge with, if they changed
+    new_gitlab: Option<GitlabConfig>,
+}
+
+/// Compare a newly read config file with the running settings
+///
+/// `loaded` is the file read at startup, `gitlab_login` tells whether users log in with gitlab.
+fn plan_reload(
+    loaded: &toml::Table,
+    table: &toml::Table,
+    value: ConfigData,
+    old: &RuntimeSettings,
+    forge_kind: ForgeKind,
+    gitlab_login: bool,
+) -> Result<ReloadPlan, ConfigError> {
+    let log_level = parse_log_level(value.log_level)?;
+
+    let mut skip = RELOADABLE_KEYS.to_vec();
+    skip.push("web");
+    let mut report = ReloadReport {
+        applied: vec![],
+        restart_required: changed_keys(loaded, table, "", &skip),
+    };
+    report.restart_required.extend(changed_keys(
+        &web_section(loaded),
+        &web_section(table),
+        "web.",
+        &RELOADABLE_WEB_KEYS,
+    ));
+
+    // the log level
+    let new_log_level = if log_level == old.log_level {
+        None
+    } else {
+        report.applied.push("log_level".to_string());
+        Some(log_level)
+    };
+
+    // the worker threads, picked up by the next minification batch
+    if value.worker_threads != old.worker_threads {
+        report.applied.push("worker_threads".to_string());
+    };
+
+    // the gitlab settings, only used if gitlab is the forge
+    let mut new_gitlab = None;
+    let gitlab = match (forge_kind, value.gitlab) {
+        (ForgeKind::Gitlab, None) => {
+            report.restart_required.push("gitlab".to_string());
+            old.gitlab.clone()
+        }
+        (ForgeKind::Gitlab, Some(gitlab)) => {
+            if old.gitlab.as_ref() != Some(&gitlab) {
+                report.applied.push("gitlab".to_string());
+                // the oauth client was built with the old address
+                if gitlab_login && old.gitlab.as_ref().map(|old| &old.addr) != Some(&gitlab.addr) {
+                    report
+                        .restart_required
+                        .push("gitlab.addr (for logging in)".to_string());
+                };
+                // the accepted branch only used to be configured here
+                if old
+                    .gitlab
+                    .as_ref()
+                    .and_then(|old| old.accepted_branch.as_ref())
+                    != gitlab.accepted_branch.as_ref()
+                {
+                    report
+                        .restart_required
+                        .push("gitlab.accepted_branch".to_string());
+                };
+                new_gitlab = Some(gitlab.clone());
+            };
+            Some(gitlab)
+        }
+        (_, _) => None,
+    };
+
+    // the TLS certificate, switching TLS on or off changes how critic listens
+    let tls = if old.tls.is_some() == value.web.tls.is_some() {
+        if value.web.tls.is_some() {
+            report
+                .applied
+                .push("web.tls (certificate reloaded)".to_string());
+        };
+        value.web.tls
+    } else {
+        report.restart_required.push("web.tls".to_string());
+        old.tls.clone()
+    };
+
+    Ok(ReloadPlan {
+        report,
+        runtime: RuntimeSettings {
+            log_level: new_log_level.unwrap_or(old.log_level),
+            worker_threads: value.worker_threads,
+            gitlab,
+            tls,
+        },
+        new_log_level,
+        new_gitlab,
+    })
+}
+
+impl Config {
+    /// Re-read the config file and apply the settings that can change at runtime
+    ///
+    /// Nothing is changed if the config file cannot be read or parsed.
+    pub fn reload(&self) -> Result<ReloadReport, ConfigError> {
+        let (value, table) = read_config_file()?;
+        let old = self.runtime();
+        let mut plan = plan_reload(
+            &self.loaded,
+            &table,
+            value,
+            &old,
+            self.forge_kind,
+            self.oauth_client.is_some(),
+        )?;
+
+        if let Some(log_level) = plan.new_log_level {
+            let changed = match self.log_level_setter.get() {
+                Some(setter) => (setter.0)(log_level)
+                    .map_err(|e| tracing::warn!("Failed to change the log level: {e}"))
+                    .is_ok(),
+                None => false,
+            };
+            if !changed {
+                plan.report.applied.retain(|key| key != "log_level");
+                plan.report.restart_required.push("log_level".to_string());
+                plan.runtime.log_level = old.log_level;
+            };
+        };
+        if let Some(gitlab) = &plan.new_gitlab {
+            self.forge.replace(Arc::new(GitlabForge::new(gitlab)));
+        };
+
+        *self.runtime.write().unwrap_or_else(PoisonError::into_inner) = plan.runtime;
+        // only notify once the new certificate paths are visible
+        if self.runtime().tls.is_some() {
+            self.tls_reload.notify_one();
+        };
+        Ok(plan.report)
+    }
+}
+
+#[cfg(test)]
+mod test;
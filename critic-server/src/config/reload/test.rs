@@ -0,0 +1,284 @@
+//! Tests for reloading the config
+
+use tracing::level_filters::LevelFilter;
+
+use super::{changed_keys, plan_reload, ReloadPlan, ReloadReport};
+use crate::{
+    config::{parse_config, ForgeKind, GitlabConfig, RuntimeSettings},
+    tls::TlsConfig,
+};
+
+/// A config file using gitlab, with `extra` appended
+fn config_file(extra: &str) -> String {
+    format!(
+        r#"
+data_directory = "/var/lib/critic"
+{extra}
+
+[db]
+user = "critic"
+password = "secret"
+host = "localhost"
+database = "critic"
+
+[web]
+site_addr = "127.0.0.1:8080"
+public_addr = "critic.example.org"
+
+[gitlab]
+addr = "gitlab.example.org"
+group_name = "bible/critic"
+project_name = "manuscripts"
+"#
+    )
+}
+
+fn gitlab() -> GitlabConfig {
+    GitlabConfig {
+        addr: "gitlab.example.org".to_string(),
+        group_name: "bible/critic".to_string(),
+        project_name: "manuscripts".to_string(),
+        access_token: None,
+        webhook_secret: None,
+        accepted_branch: None,
+    }
+}
+
+/// The settings critic runs with after starting with `config_file("")`
+fn running() -> RuntimeSettings {
+    RuntimeSettings {
+        log_level: LevelFilter::INFO,
+        worker_threads: 4,
+        gitlab: Some(gitlab()),
+        tls: None,
+    }
+}
+
+/// Plan reloading `new` while running with `loaded` and `old`
+fn plan_for(loaded: &str, new: &str, old: &RuntimeSettings, forge_kind: ForgeKind) -> ReloadPlan {
+    let (_, loaded) = parse_config(loaded).unwrap();
+    let (value, table) = parse_config(new).unwrap();
+    plan_reload(&loaded, &table, value, old, forge_kind, true).unwrap()
+}
+
+fn table(content: &str) -> toml::Table {
+    toml::from_str(content).unwrap()
+}
+
+#[test]
+fn changed_keys_lists_added_removed_and_changed_keys() {
+    let old = table("a = 1\nb = 2\nc = 3\nskipped = 1");
+    let new = table("a = 1\nb = 5\nd = 4\nskipped = 2");
+    assert_eq!(
+        changed_keys(&old, &new, "web.", &["skipped"]),
+        vec!["web.b", "web.c", "web.d"]
+    );
+    assert!(changed_keys(&old, &old, "", &[]).is_empty());
+}
+
+#[test]
+fn changed_keys_compares_whole_sections() {
+    let old = table("[db]\nhost = \"a\"");
+    let new = table("[db]\nhost = \"b\"");
+    assert_eq!(changed_keys(&old, &new, "", &[]), vec!["db"]);
+}
+
+#[test]
+fn unchanged_config_changes_nothing() {
+    let plan = plan_for(
+        &config_file(""),
+        &config_file(""),
+        &running(),
+        ForgeKind::Gitlab,
+    );
+    assert!(plan.report.applied.is_empty());
+    assert!(plan.report.restart_required.is_empty());
+    assert!(plan.new_log_level.is_none());
+    assert!(plan.new_gitlab.is_none());
+    assert_eq!(plan.runtime.gitlab, Some(gitlab()));
+}
+
+#[test]
+fn runtime_settings_are_applied() {
+    let plan = plan_for(
+        &config_file(""),
+        &config_file("log_level = \"DEBUG\"\nworker_threads = 2"),
+        &running(),
+        ForgeKind::Gitlab,
+    );
+    assert_eq!(plan.report.applied, vec!["log_level", "worker_threads"]);
+    assert!(plan.report.restart_required.is_empty());
+    assert_eq!(plan.new_log_level, Some(LevelFilter::DEBUG));
+    assert_eq!(plan.runtime.log_level, LevelFilter::DEBUG);
+    assert_eq!(plan.runtime.worker_threads, 2);
+}
+
+#[test]
+fn other_settings_need_a_restart() {
+    let new = config_file("consistency_check_interval_hours = 2")
+        .replace("127.0.0.1:8080", "127.0.0.1:9090")
+        .replace("host = \"localhost\"", "host = \"db.example.org\"");
+    let plan = plan_for(&config_file(""), &new, &running(), ForgeKind::Gitlab);
+    assert!(plan.report.applied.is_empty());
+    assert_eq!(
+        plan.report.restart_required,
+        vec!["consistency_check_interval_hours", "db", "web.site_addr"]
+    );
+}
+
+#[test]
+fn invalid_log_level_is_an_error() {
+    let (_, loaded) = parse_config(&config_file("")).unwrap();
+    let (value, table) = parse_config(&config_file("log_level = \"LOUD\"")).unwrap();
+    assert!(plan_reload(&loaded, &table, value, &running(), ForgeKind::Gitlab, true).is_err());
+}
+
+#[test]
+fn changed_gitlab_settings_replace_the_forge() {
+    let new = config_file("").replace(
+        "project_name = \"manuscripts\"",
+        "project_name = \"manuscripts\"\nwebhook_secret = \"hook\"",
+    );
+    let plan = plan_for(&config_file(""), &new, &running(), ForgeKind::Gitlab);
+    let expected = GitlabConfig {
+        webhook_secret: Some("hook".to_string()),
+        ..gitlab()
+    };
+    assert_eq!(plan.report.applied, vec!["gitlab"]);
+    assert!(plan.report.restart_required.is_empty());
+    assert_eq!(plan.new_gitlab, Some(expected.clone()));
+    assert_eq!(plan.runtime.gitlab, Some(expected));
+}
+
+#[test]
+fn changed_gitlab_address_needs_a_restart_for_logging_in() {
+    let new = config_file("").replace(
+        "addr = \"gitlab.example.org\"",
+        "addr = \"git.example.org\"",
+    );
+    let plan = plan_for(&config_file(""), &new, &running(), ForgeKind::Gitlab);
+    assert_eq!(plan.report.applied, vec!["gitlab"]);
+    assert_eq!(
+        plan.report.restart_required,
+        vec!["gitlab.addr (for logging in)"]
+    );
+
+    // without logging in with gitlab, the new address is all there is to it
+    let (_, loaded) = parse_config(&config_file("")).unwrap();
+    let (value, table) = parse_config(&new).unwrap();
+    let plan = plan_reload(&loaded, &table, value, &running(), ForgeKind::Gitlab, false).unwrap();
+    assert!(plan.report.restart_required.is_empty());
+}
+
+#[test]
+fn changed_accepted_branch_in_gitlab_section_needs_a_restart() {
+    let new = config_file("").replace(
+        "project_name = \"manuscripts\"",
+        "project_name = \"manuscripts\"\naccepted_branch = \"main\"",
+    );
+    let plan = plan_for(&config_file(""), &new, &running(), ForgeKind::Gitlab);
+    assert_eq!(plan.report.applied, vec!["gitlab"]);
+    assert_eq!(plan.report.restart_required, vec!["gitlab.accepted_branch"]);
+}
+
+#[test]
+fn removed_gitlab_section_keeps_the_old_settings() {
+    let new = config_file("");
+    let new = &new[..new.find("[gitlab]").unwrap()];
+    let plan = plan_for(&config_file(""), new, &running(), ForgeKind::Gitlab);
+    assert!(plan.report.applied.is_empty());
+    assert_eq!(plan.report.restart_required, vec!["gitlab"]);
+    assert!(plan.new_gitlab.is_none());
+    assert_eq!(plan.runtime.gitlab, Some(gitlab()));
+}
+
+#[test]
+fn gitlab_settings_are_ignored_for_other_forges() {
+    let loaded = config_file("forge = \"local\"");
+    let new = loaded.replace("group_name = \"bible/critic\"", "group_name = \"bible\"");
+    let old = RuntimeSettings {
+        gitlab: None,
+        ..running()
+    };
+    let plan = plan_for(&loaded, &new, &old, ForgeKind::Local);
+    assert!(plan.report.applied.is_empty());
+    assert!(plan.report.restart_required.is_empty());
+    assert!(plan.new_gitlab.is_none());
+    assert!(plan.runtime.gitlab.is_none());
+}
+
+/// `config_file` serving https with the certificate in `dir`
+fn tls_config_file(dir: &str) -> String {
+    config_file("").replace(
+        "public_addr = \"critic.example.org\"",
+        &format!(
+            "public_addr = \"critic.example.org\"\ntls = {{ cert = \"{dir}/cert.pem\", key = \"{dir}/key.pem\" }}"
+        ),
+    )
+}
+
+fn tls(dir: &str) -> TlsConfig {
+    TlsConfig {
+        cert: format!("{dir}/cert.pem").into(),
+        key: format!("{dir}/key.pem").into(),
+    }
+}
+
+#[test]
+fn certificate_is_reloaded() {
+    let old = RuntimeSettings {
+        tls: Some(tls("/etc/ssl/old")),
+        ..running()
+    };
+    let plan = plan_for(
+        &tls_config_file("/etc/ssl/old"),
+        &tls_config_file("/etc/ssl/new"),
+        &old,
+        ForgeKind::Gitlab,
+    );
+    assert_eq!(plan.report.applied, vec!["web.tls (certificate reloaded)"]);
+    assert!(plan.report.restart_required.is_empty());
+    assert_eq!(plan.runtime.tls, Some(tls("/etc/ssl/new")));
+}
+
+#[test]
+fn switching_tls_on_or_off_needs_a_restart() {
+    let plan = plan_for(
+        &config_file(""),
+        &tls_config_file("/etc/ssl"),
+        &running(),
+        ForgeKind::Gitlab,
+    );
+    assert!(plan.report.applied.is_empty());
+    assert_eq!(plan.report.restart_required, vec!["web.tls"]);
+    assert!(plan.runtime.tls.is_none());
+
+    let old = RuntimeSettings {
+        tls: Some(tls("/etc/ssl")),
+        ..running()
+    };
+    let plan = plan_for(
+        &tls_config_file("/etc/ssl"),
+        &config_file(""),
+        &old,
+        ForgeKind::Gitlab,
+    );
+    assert_eq!(plan.report.restart_required, vec!["web.tls"]);
+    assert_eq!(plan.runtime.tls, Some(tls("/etc/ssl")));
+}
+
+#[test]
+fn report_lists_applied_and_restart_settings() {
+    let report = ReloadReport {
+        applied: vec!["log_level".to_string(), "gitlab".to_string()],
+        restart_required: vec!["db".to_string()],
+    };
+    assert_eq!(
+        report.to_string(),
+        "Applied changed settings: log_level, gitlab. These changed settings need a restart: db."
+    );
+    assert_eq!(
+        ReloadReport::default().to_string(),
+        "No changed setting was applied."
+    );
+}
@@ -0,0 +1,80 @@
+//! Tests for reading the config
+
+use super::{accepted_branch, default_accepted_branch, parse_config, ConfigError, GitlabConfig};
+
+fn gitlab(accepted_branch: Option<&str>) -> GitlabConfig {
+    GitlabConfig {
+        addr: "gitlab.example.org".to_string(),
+        group_name: "bible/critic".to_string(),
+        project_name: "manuscripts".to_string(),
+        access_token: None,
+        webhook_secret: None,
+        accepted_branch: accepted_branch.map(str::to_string),
+    }
+}
+
+#[test]
+fn accepted_branch_defaults() {
+    assert_eq!(
+        accepted_branch(None, None).unwrap(),
+        default_accepted_branch()
+    );
+    assert_eq!(
+        accepted_branch(None, Some(&gitlab(None))).unwrap(),
+        default_accepted_branch()
+    );
+}
+
+#[test]
+fn accepted_branch_is_read_from_the_top_level() {
+    assert_eq!(
+        accepted_branch(Some("main".to_string()), Some(&gitlab(None))).unwrap(),
+        "main"
+    );
+    assert_eq!(
+        accepted_branch(Some("main".to_string()), Some(&gitlab(Some("main")))).unwrap(),
+        "main"
+    );
+}
+
+#[test]
+fn accepted_branch_is_still_read_from_the_gitlab_section() {
+    assert_eq!(
+        accepted_branch(None, Some(&gitlab(Some("accepted")))).unwrap(),
+        "accepted"
+    );
+    let (value, _) = parse_config(
+        r#"
+data_directory = "/var/lib/critic"
+
+[db]
+user = "critic"
+password = "secret"
+host = "localhost"
+database = "critic"
+
+[web]
+site_addr = "127.0.0.1:8080"
+public_addr = "critic.example.org"
+
+[gitlab]
+addr = "gitlab.example.org"
+group_name = "bible/critic"
+project_name = "manuscripts"
+accepted_branch = "accepted"
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        accepted_branch(value.accepted_branch, value.gitlab.as_ref()).unwrap(),
+        "accepted"
+    );
+}
+
+#[test]
+fn conflicting_accepted_branches_are_rejected() {
+    assert!(matches!(
+        accepted_branch(Some("main".to_string()), Some(&gitlab(Some("accepted")))),
+        Err(ConfigError::AcceptedBranchConflict)
+    ));
+}
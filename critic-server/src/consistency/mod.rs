@@ -0,0 +1,304 @@
+//! Check the files on disk against each other and against the database
+//!
+//! Every transcription (published or not) and every accepted transcription is run through the
+//! complete TEI pipeline, its header is compared with the manuscript in the database and its verse
+//! anchors are looked up in the verse map. Pages without an image are flagged as well.
+//!
+//! The checker runs periodically (see [`run_consistency_checks`]) and can be started from the
+//! command line. The last report is written into the data directory, where the admin area reads
+//! it from.
+
+use std::{path::Path, sync::Arc, time::SystemTime};
+
+use critic_format::streamed::Block;
+use critic_shared::{
+    anchor::anchor_verse,
+    consistency::{ConsistencyIssue, ConsistencyProblem, ConsistencyReport, ConsistencySubject},
+    urls::{CONSISTENCY_REPORT_LOCATION, IMAGE_BASE_LOCATION},
+    ManuscriptMeta,
+};
+
+use crate::{
+    collation::AnchorMap,
+    config::Config,
+    db::{get_manuscript, get_manuscripts_by_name, get_verse_map, DBError},
+    signal_handler::InShutdown,
+    tei::{page_meta, read_tei_file, reconciled_path, transcription_directory},
+};
+
+#[derive(Debug)]
+pub enum ConsistencyError {
+    DB(DBError),
+    /// A directory cannot be listed or the report cannot be read or written
+    Io(std::io::Error),
+    /// The report cannot be (de-)serialized
+    Report(serde_json::Error),
+}
+impl core::fmt::Display for ConsistencyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::DB(e) => {
+                write!(f, "{e}")
+            }
+            Self::Io(e) => {
+                write!(f, "IO error while checking consistency: {e}")
+            }
+            Self::Report(e) => {
+                write!(f, "Unable to (de-)serialize the consistency report: {e}")
+            }
+        }
+    }
+}
+impl core::error::Error for ConsistencyError {}
+impl From<DBError> for ConsistencyError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+
+/// The path of the report on disk
+fn report_path(data_directory: &str) -> String {
+    format!("{data_directory}{CONSISTENCY_REPORT_LOCATION}")
+}
+
+/// The current time as shown in the report
+fn now() -> String {
+    let now = time::OffsetDateTime::now_utc();
+    format!("{} {:02}:{:02} UTC", now.date(), now.hour(), now.minute())
+}
+
+/// Does the page have an image, minified or not?
+fn has_image(data_directory: &str, msname: &str, pagename: &str) -> bool {
+    let base_path = format!("{data_directory}{IMAGE_BASE_LOCATION}/{msname}/{pagename}");
+    ["original.webp", "original"]
+        .iter()
+        .any(|name| Path::new(&format!("{base_path}/{name}")).is_file())
+}
+
+/// The transcription files of a page with their path
+fn transcription_files(
+    data_directory: &str,
+    msname: &str,
+    pagename: &str,
+) -> Result<Vec<(ConsistencySubject, String)>, ConsistencyError> {
+    let mut res = vec![];
+    let directory = transcription_directory(data_directory, msname, pagename);
+    if Path::new(&directory).is_dir() {
+        for entry in std::fs::read_dir(&directory).map_err(ConsistencyError::Io)? {
+            let path = entry.map_err(ConsistencyError::Io)?.path();
+            let Some(username) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".tei.xml"))
+            else {
+                continue;
+            };
+            res.push((
+                ConsistencySubject::Transcription(username.to_string()),
+                path.to_string_lossy().to_string(),
+            ));
+        }
+    };
+    res.sort_by(|a, b| a.1.cmp(&b.1));
+    let reconciled = reconciled_path(data_directory, msname, pagename);
+    if Path::new(&reconciled).is_file() {
+        res.push((ConsistencySubject::Accepted, reconciled));
+    };
+    Ok(res)
+}
+
+/// The fields in which the TEI header differs from what the database says
+fn metadata_problems(
+    header: &critic_format::normalized::Meta,
+    expected: &critic_format::normalized::Meta,
+) -> Vec<ConsistencyProblem> {
+    let fields = [
+        ("name", Some(&header.name), Some(&expected.name)),
+        (
+            "page number",
+            Some(&header.page_nr),
+            Some(&expected.page_nr),
+        ),
+        ("title", Some(&header.title), Some(&expected.title)),
+        (
+            "institution",
+            header.institution.as_ref(),
+            expected.institution.as_ref(),
+        ),
+        (
+            "collection",
+            header.collection.as_ref(),
+            expected.collection.as_ref(),
+        ),
+        (
+            "hand description",
+            header.hand_desc.as_ref(),
+            expected.hand_desc.as_ref(),
+        ),
+        (
+            "script description",
+            header.script_desc.as_ref(),
+            expected.script_desc.as_ref(),
+        ),
+    ];
+    fields
+        .into_iter()
+        .filter(|(_, header, database)| header != database)
+        .map(
+            |(field, header, database)| ConsistencyProblem::MetadataMismatch {
+                field: field.to_string(),
+                header: header.cloned(),
+                database: database.cloned(),
+            },
+        )
+        .collect()
+}
+
+/// Check a single transcription file of a page of `ms`
+fn check_file(
+    path: &str,
+    ms: &ManuscriptMeta,
+    pagename: &str,
+    anchors: &AnchorMap,
+) -> Vec<ConsistencyProblem> {
+    let transcription = match read_tei_file(path) {
+        Ok(x) => x,
+        Err(e) => return vec![ConsistencyProblem::InvalidTei(e.to_string())],
+    };
+    let mut res = metadata_problems(&transcription.meta, &page_meta(ms, pagename));
+    for block in &transcription.content {
+        if let Block::Anchor(anchor) = block {
+            if anchor_verse(&anchor.anchor_id).is_some()
+                && anchors.verse_id(&anchor.anchor_id).is_none()
+            {
+                res.push(ConsistencyProblem::UnknownAnchor(anchor.anchor_id.clone()));
+            };
+        };
+    }
+    res
+}
+
+/// Check all pages of all manuscripts
+pub async fn check_consistency(config: &Config) -> Result<ConsistencyReport, ConsistencyError> {
+    let anchors = AnchorMap::new(get_verse_map(&config.db).await?);
+    let mut report = ConsistencyReport {
+        checked_at: now(),
+        ..Default::default()
+    };
+    for meta in get_manuscripts_by_name(&config.db, None).await? {
+        let ms = get_manuscript(&config.db, &meta.title).await?;
+        for page in ms.pages {
+            report.pages_checked += 1;
+            let issue = |subject, problem| ConsistencyIssue {
+                msname: ms.meta.title.clone(),
+                pagename: page.name.clone(),
+                subject,
+                problem,
+            };
+            if !has_image(&config.data_directory, &ms.meta.title, &page.name) {
+                report.issues.push(issue(
+                    ConsistencySubject::Page,
+                    ConsistencyProblem::MissingImage,
+                ));
+            };
+            for (subject, path) in
+                transcription_files(&config.data_directory, &ms.meta.title, &page.name)?
+            {
+                report.files_checked += 1;
+                report.issues.extend(
+                    check_file(&path, &ms.meta, &page.name, &anchors)
+                        .into_iter()
+                        .map(|problem| issue(subject.clone(), problem)),
+                );
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Write the report into the data directory, replacing the last one
+pub fn write_report(
+    data_directory: &str,
+    report: &ConsistencyReport,
+) -> Result<(), ConsistencyError> {
+    let content = serde_json::to_string(report).map_err(ConsistencyError::Report)?;
+    std::fs::write(report_path(data_directory), content).map_err(ConsistencyError::Io)
+}
+
+/// Read the last report from the data directory, None if the checker never ran
+pub fn read_report(data_directory: &str) -> Result<Option<ConsistencyReport>, ConsistencyError> {
+    match std::fs::read_to_string(report_path(data_directory)) {
+        Ok(content) => serde_json::from_str(&content)
+            .map(Some)
+            .map_err(ConsistencyError::Report),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(ConsistencyError::Io(e)),
+    }
+}
+
+/// Check the consistency and write the report
+pub async fn check_and_report(config: &Config) -> Result<ConsistencyReport, ConsistencyError> {
+    let report = check_consistency(config).await?;
+    write_report(&config.data_directory, &report)?;
+    Ok(report)
+}
+
+/// How long to wait until the next check is due
+///
+/// The age of the last report counts, so that restarting critic does not trigger a new check.
+fn next_check_in(config: &Config) -> tokio::time::Duration {
+    let interval = tokio::time::Duration::from_secs(
+        config
+            .consistency_check_interval_hours
+            .saturating_mul(60 * 60),
+    );
+    let age = std::fs::metadata(report_path(&config.data_directory))
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+    match age {
+        Some(age) => interval.saturating_sub(age),
+        None => tokio::time::Duration::ZERO,
+    }
+}
+
+/// Run the consistency checker every `consistency_check_interval_hours`
+pub async fn run_consistency_checks(
+    config: Arc<Config>,
+    mut watcher: tokio::sync::watch::Receiver<InShutdown>,
+) {
+    loop {
+        // wait until the next check is due, or cancel the service if we are in shutdown
+        tokio::select! {
+            _ = watcher.changed() => {
+                tracing::debug!("Shutting down consistency checker now.");
+                return;
+            }
+            _ = tokio::time::sleep(next_check_in(&config)) => {}
+        };
+        match check_and_report(&config).await {
+            Ok(report) => {
+                tracing::info!(
+                    "Consistency check found {} issues in {} pages.",
+                    report.issues.len(),
+                    report.pages_checked
+                );
+            }
+            Err(e) => {
+                tracing::warn!("Failed to check consistency: {e}");
+                // do not retry immediately, the data directory or the DB may have a general
+                // problem
+                tokio::select! {
+                    _ = watcher.changed() => {
+                        tracing::debug!("Shutting down consistency checker now.");
+                        return;
+                    }
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(60 * 60)) => {}
+                };
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,265 @@
+//! Tests for the consistency checker
+
+use std::path::PathBuf;
+
+use critic_format::streamed::{Block, BlockType, FromTypeLangAndContent, Paragraph};
+use critic_shared::{
+    anchor::verse_anchor,
+    consistency::{ConsistencyProblem, ConsistencyReport, ConsistencySubject},
+    ManuscriptMeta, VersificationScheme,
+};
+
+use super::{
+    check_file, has_image, metadata_problems, read_report, transcription_files, write_report,
+};
+use crate::{
+    collation::AnchorMap,
+    db::VerseMapEntry,
+    tei::{page_meta, reconciled_path, transcription_path, write_tei},
+};
+
+/// A temporary data directory, removed on drop
+struct TestDir(PathBuf);
+impl TestDir {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("critic-consistency-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    fn path(&self) -> String {
+        self.0.to_string_lossy().to_string()
+    }
+}
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Write `content` to `path`, creating its directory
+fn write(path: &str, content: &str) {
+    std::fs::create_dir_all(std::path::Path::new(path).parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+fn ms() -> ManuscriptMeta {
+    ManuscriptMeta {
+        id: 1,
+        title: "Codex A".to_string(),
+        institution: Some("National Library".to_string()),
+        collection: None,
+        hand_desc: None,
+        script_desc: None,
+    }
+}
+
+fn scheme() -> VersificationScheme {
+    VersificationScheme {
+        id: 1,
+        full_name: "Present".to_string(),
+        shorthand: "P".to_string(),
+    }
+}
+
+/// A verse map containing only Gen 1:1
+fn anchors() -> AnchorMap {
+    AnchorMap::new(vec![VerseMapEntry {
+        verse_id: 1,
+        shorthand: "P".to_string(),
+        verse_nr: "Gen 1:1".to_string(),
+    }])
+}
+
+fn text(content: &str) -> Block {
+    Block::Text(Paragraph {
+        lang: "hbo-Hebr".to_string(),
+        content: content.to_string(),
+    })
+}
+
+/// A TEI file with the header `meta` and the blocks `content`
+fn tei(meta: critic_format::normalized::Meta, content: Vec<Block>) -> String {
+    write_tei(critic_format::streamed::Manuscript { meta, content }).unwrap()
+}
+
+#[test]
+fn matching_metadata_is_no_problem() {
+    let meta = page_meta(&ms(), "1r");
+    assert!(metadata_problems(&meta, &meta).is_empty());
+}
+
+#[test]
+fn every_mismatching_field_is_reported() {
+    let expected = page_meta(&ms(), "1r");
+    let mut header = page_meta(&ms(), "1r");
+    header.page_nr = "1v".to_string();
+    header.institution = None;
+    header.hand_desc = Some("One hand".to_string());
+    assert_eq!(
+        metadata_problems(&header, &expected),
+        vec![
+            ConsistencyProblem::MetadataMismatch {
+                field: "page number".to_string(),
+                header: Some("1v".to_string()),
+                database: Some("1r".to_string()),
+            },
+            ConsistencyProblem::MetadataMismatch {
+                field: "institution".to_string(),
+                header: None,
+                database: Some("National Library".to_string()),
+            },
+            ConsistencyProblem::MetadataMismatch {
+                field: "hand description".to_string(),
+                header: Some("One hand".to_string()),
+                database: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn consistent_files_have_no_problems() {
+    let dir = TestDir::new("consistent");
+    let path = transcription_path(&dir.path(), "Codex A", "1r", "alice");
+    write(
+        &path,
+        &tei(
+            page_meta(&ms(), "1r"),
+            vec![verse_anchor(&scheme(), "Gen 1:1"), text("בראשית")],
+        ),
+    );
+    assert!(check_file(&path, &ms(), "1r", &anchors()).is_empty());
+}
+
+#[test]
+fn files_of_other_pages_are_reported() {
+    let dir = TestDir::new("other-page");
+    let path = transcription_path(&dir.path(), "Codex A", "1r", "alice");
+    write(&path, &tei(page_meta(&ms(), "2r"), vec![text("בראשית")]));
+    assert_eq!(
+        check_file(&path, &ms(), "1r", &anchors()),
+        vec![ConsistencyProblem::MetadataMismatch {
+            field: "page number".to_string(),
+            header: Some("2r".to_string()),
+            database: Some("1r".to_string()),
+        }]
+    );
+}
+
+#[test]
+fn unknown_verse_anchors_are_reported() {
+    let dir = TestDir::new("unknown-anchor");
+    let path = transcription_path(&dir.path(), "Codex A", "1r", "alice");
+    let mut other_anchor =
+        Block::from_type_lang_and_content(BlockType::Anchor, String::default(), String::default());
+    if let Block::Anchor(ref mut anchor) = other_anchor {
+        anchor.anchor_id = "A_note_1".to_string();
+    };
+    write(
+        &path,
+        &tei(
+            page_meta(&ms(), "1r"),
+            vec![
+                verse_anchor(&scheme(), "Gen 1:1"),
+                text("בראשית"),
+                verse_anchor(&scheme(), "Gen 1:99"),
+                other_anchor,
+                text("ברא"),
+            ],
+        ),
+    );
+    // only verse anchors are looked up in the verse map
+    assert_eq!(
+        check_file(&path, &ms(), "1r", &anchors()),
+        vec![ConsistencyProblem::UnknownAnchor(
+            "A_V_P_Gen 1:99".to_string()
+        )]
+    );
+}
+
+#[test]
+fn unreadable_files_are_invalid_tei() {
+    let dir = TestDir::new("invalid");
+    let path = transcription_path(&dir.path(), "Codex A", "1r", "alice");
+    write(&path, "<TEI><teiHeader>");
+    assert!(matches!(
+        check_file(&path, &ms(), "1r", &anchors()).as_slice(),
+        [ConsistencyProblem::InvalidTei(_)]
+    ));
+}
+
+#[test]
+fn pages_without_image_are_found() {
+    let dir = TestDir::new("images");
+    assert!(!has_image(&dir.path(), "Codex A", "1r"));
+
+    // before minification, only the original upload exists
+    write(&format!("{}/images/Codex A/1r/original", dir.path()), "");
+    assert!(has_image(&dir.path(), "Codex A", "1r"));
+
+    write(
+        &format!("{}/images/Codex A/1v/original.webp", dir.path()),
+        "",
+    );
+    assert!(has_image(&dir.path(), "Codex A", "1v"));
+
+    // previews alone are not enough
+    write(
+        &format!("{}/images/Codex A/2r/preview.webp", dir.path()),
+        "",
+    );
+    assert!(!has_image(&dir.path(), "Codex A", "2r"));
+}
+
+#[test]
+fn transcriptions_are_listed_before_the_accepted_one() {
+    let dir = TestDir::new("files");
+    let data_directory = dir.path();
+    assert!(transcription_files(&data_directory, "Codex A", "1r")
+        .unwrap()
+        .is_empty());
+
+    for username in ["bob", "alice"] {
+        write(
+            &transcription_path(&data_directory, "Codex A", "1r", username),
+            "",
+        );
+    }
+    write(
+        &transcription_path(&data_directory, "Codex A", "1r", "notes").replace(".tei.xml", ".txt"),
+        "",
+    );
+    let reconciled = reconciled_path(&data_directory, "Codex A", "1r");
+    write(&reconciled, "");
+
+    let subjects = transcription_files(&data_directory, "Codex A", "1r")
+        .unwrap()
+        .into_iter()
+        .map(|(subject, _)| subject)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        subjects,
+        vec![
+            ConsistencySubject::Transcription("alice".to_string()),
+            ConsistencySubject::Transcription("bob".to_string()),
+            ConsistencySubject::Accepted,
+        ]
+    );
+}
+
+#[test]
+fn reports_are_read_back() {
+    let dir = TestDir::new("report");
+    assert_eq!(read_report(&dir.path()).unwrap(), None);
+    let report = ConsistencyReport {
+        checked_at: "2026-10-18 12:00 UTC".to_string(),
+        pages_checked: 2,
+        files_checked: 1,
+        issues: vec![],
+    };
+    write_report(&dir.path(), &report).unwrap();
+    assert_eq!(read_report(&dir.path()).unwrap(), Some(report));
+}
@@ -2,9 +2,15 @@
 
 use sqlx::{prelude::FromRow, query_as, Pool, Postgres};
 
-use critic_shared::{ManuscriptMeta, PageMeta, VersificationScheme};
+use critic_shared::{
+    reconciliation::ReconciliationStatus, ManuscriptMeta, PageMeta, ReferenceEdition,
+    TranscriptionMeta, VersificationScheme,
+};
 
-use crate::auth::{AuthenticatedUser, NormalizedTokenResponse, UserInfo};
+use crate::{
+    auth::{oidc::OidcUser, AuthenticatedUser, NormalizedTokenResponse, UserInfo},
+    gitlab::GitlabUserRole,
+};
 
 // include tests
 #[cfg(test)]
@@ -42,6 +48,26 @@ pub enum DBError {
     CannotGetPage(sqlx::Error),
     PageAlreadyExists,
     CannotUpdateManuscript(sqlx::Error),
+    /// The page (msname, pagename) we looked for does not exist
+    PageDoesNotExist(String, String),
+    CannotGetReferenceEdition(sqlx::Error),
+    CannotInsertReferenceEdition(sqlx::Error),
+    CannotGetReferenceVerses(sqlx::Error),
+    CannotInsertReferenceVerse(sqlx::Error),
+    /// The verse number does not exist in the versification scheme
+    VerseDoesNotExist(String),
+    CannotInsertOrUpdateTranscription(sqlx::Error),
+    CannotGetTranscriptions(sqlx::Error),
+    CannotGetVerseMap(sqlx::Error),
+    CannotGetReconciliations(sqlx::Error),
+    CannotUpdateReconciliation(sqlx::Error),
+    CannotMarkPageAccepted(sqlx::Error),
+    /// Failed to build or apply the scratch schema of a rebuild
+    CannotRebuild(sqlx::Error),
+    /// The user we looked for has never logged in
+    UserDoesNotExist(String),
+    /// The username is already used by a user who logs in with a different login backend
+    UsernameTaken(String),
 }
 impl core::fmt::Display for DBError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -97,6 +123,60 @@ impl core::fmt::Display for DBError {
             Self::CannotUpdateManuscript(e) => {
                 write!(f, "Unable to update manuscript metadata: {e}")
             }
+            Self::PageDoesNotExist(msname, pagename) => {
+                write!(
+                    f,
+                    "The page {pagename} does not exist in manuscript {msname}"
+                )
+            }
+            Self::CannotGetReferenceEdition(e) => {
+                write!(f, "Unable to get reference edition: {e}")
+            }
+            Self::CannotInsertReferenceEdition(e) => {
+                write!(f, "Unable to insert reference edition: {e}")
+            }
+            Self::CannotGetReferenceVerses(e) => {
+                write!(f, "Unable to get verses of reference edition: {e}")
+            }
+            Self::CannotInsertReferenceVerse(e) => {
+                write!(f, "Unable to insert verse into reference edition: {e}")
+            }
+            Self::VerseDoesNotExist(verse_nr) => {
+                write!(
+                    f,
+                    "The verse {verse_nr} does not exist in this versification scheme"
+                )
+            }
+            Self::CannotInsertOrUpdateTranscription(e) => {
+                write!(f, "Unable to insert or update transcription: {e}")
+            }
+            Self::CannotGetVerseMap(e) => {
+                write!(f, "Unable to get verse map: {e}")
+            }
+            Self::CannotGetTranscriptions(e) => {
+                write!(f, "Unable to get transcriptions: {e}")
+            }
+            Self::CannotGetReconciliations(e) => {
+                write!(f, "Unable to get reconciliations: {e}")
+            }
+            Self::CannotUpdateReconciliation(e) => {
+                write!(f, "Unable to insert or update reconciliation: {e}")
+            }
+            Self::CannotMarkPageAccepted(e) => {
+                write!(f, "Unable to mark page as accepted: {e}")
+            }
+            Self::CannotRebuild(e) => {
+                write!(f, "Unable to rebuild the database: {e}")
+            }
+            Self::UserDoesNotExist(username) => {
+                write!(f, "The user {username} has never logged in to critic")
+            }
+            Self::UsernameTaken(username) => {
+                write!(
+                    f,
+                    "The username {username} is already used by someone logging in differently"
+                )
+            }
         }
     }
 }
@@ -112,6 +192,7 @@ pub async fn insert_or_update_user_session(
         .await
         .map_err(DBError::CannotStartTransaction)?;
 
+    // users logging in with another backend keep their username
     let authenticated_user = query_as!(
         AuthenticatedUser,
         "insert into user_session (id, username, access_token, refresh_token, expires_at)
@@ -121,6 +202,7 @@ pub async fn insert_or_update_user_session(
             refresh_token = excluded.refresh_token,
             expires_at = excluded.expires_at,
             id = excluded.id
+            where user_session.login = 'forge'
             returning *",
         user_info.id,
         user_info.username,
@@ -128,9 +210,10 @@ pub async fn insert_or_update_user_session(
         token_res.refresh_token,
         token_res.expires_at,
     )
-    .fetch_one(&mut *tx)
+    .fetch_optional(&mut *tx)
     .await
-    .map_err(DBError::CannotInsertOrUpdateUsersession)?;
+    .map_err(DBError::CannotInsertOrUpdateUsersession)?
+    .ok_or(DBError::UsernameTaken(user_info.username))?;
 
     tx.commit()
         .await
@@ -139,6 +222,75 @@ pub async fn insert_or_update_user_session(
     Ok(authenticated_user)
 }
 
+/// Insert or update the session of a user who is not known to the forge
+///
+/// `login` is the login backend, `role` the role given by it. New users get a negative id.
+pub async fn insert_or_update_external_user_session(
+    pool: &Pool<Postgres>,
+    login: &str,
+    username: &str,
+    role: &GitlabUserRole,
+    token_res: NormalizedTokenResponse,
+) -> Result<AuthenticatedUser, DBError> {
+    query_as!(
+        AuthenticatedUser,
+        "insert into user_session (id, username, access_token, refresh_token, expires_at, login, role)
+            values (nextval('external_user_id'), $1, $2, $3, $4, $5, $6)
+            on conflict(username) do update
+            set access_token = excluded.access_token,
+            refresh_token = excluded.refresh_token,
+            expires_at = excluded.expires_at,
+            role = excluded.role
+            where user_session.login = excluded.login
+            returning *",
+        username,
+        token_res.access_token,
+        token_res.refresh_token,
+        token_res.expires_at,
+        login,
+        i32::from(role),
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(DBError::CannotInsertOrUpdateUsersession)?
+    .ok_or(DBError::UsernameTaken(username.to_string()))
+}
+
+/// Insert or update the session of a user who logged in with the OpenID provider
+///
+/// The user is known by issuer and subject, the username is derived from them and never changes.
+pub async fn insert_or_update_oidc_user_session(
+    pool: &Pool<Postgres>,
+    oidc_user: OidcUser,
+) -> Result<AuthenticatedUser, DBError> {
+    let username = oidc_user.username();
+    query_as!(
+        AuthenticatedUser,
+        "insert into user_session (id, username, access_token, refresh_token, expires_at, login, role, issuer, subject)
+            values (nextval('external_user_id'), $1, $2, $3, $4, 'oidc', $5, $6, $7)
+            on conflict(username) do update
+            set access_token = excluded.access_token,
+            refresh_token = excluded.refresh_token,
+            expires_at = excluded.expires_at,
+            role = excluded.role
+            where user_session.login = 'oidc'
+            and user_session.issuer = excluded.issuer
+            and user_session.subject = excluded.subject
+            returning *",
+        username,
+        oidc_user.tokens.access_token,
+        oidc_user.tokens.refresh_token,
+        oidc_user.tokens.expires_at,
+        i32::from(&oidc_user.role),
+        oidc_user.issuer,
+        oidc_user.subject,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(DBError::CannotInsertOrUpdateUsersession)?
+    .ok_or(DBError::UsernameTaken(username))
+}
+
 async fn get_manuscript_meta(
     pool: &Pool<Postgres>,
     msname: &str,
@@ -350,3 +502,602 @@ pub async fn update_ms_meta(pool: &Pool<Postgres>, data: &ManuscriptMeta) -> Res
         .map(|_| {})
         .map_err(DBError::CannotUpdateManuscript)
 }
+
+/// Get a single page of a manuscript
+pub async fn get_page(
+    pool: &Pool<Postgres>,
+    msname: &str,
+    pagename: &str,
+) -> Result<PageMeta, DBError> {
+    sqlx::query_as!(
+        PageMeta,
+        "SELECT page.id, manuscript.id as manuscript_id, page.name, page.verse_start, page.verse_end
+            FROM manuscript
+            INNER JOIN page on page.manuscript = manuscript.id
+            WHERE manuscript.title = $1 AND page.name = $2;",
+        msname,
+        pagename,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(DBError::CannotGetPage)?
+    .ok_or(DBError::PageDoesNotExist(
+        msname.to_string(),
+        pagename.to_string(),
+    ))
+}
+
+pub async fn get_reference_editions(
+    pool: &Pool<Postgres>,
+) -> Result<Vec<ReferenceEdition>, DBError> {
+    query_as!(ReferenceEdition, "SELECT * FROM reference_edition;")
+        .fetch_all(pool)
+        .await
+        .map_err(DBError::CannotGetReferenceEdition)
+}
+
+/// A single verse of a reference edition
+#[derive(FromRow, Debug, PartialEq, Clone)]
+pub struct ReferenceVerse {
+    /// the non-semantic verse id
+    pub verse_id: i64,
+    /// the verse number in the requested versification scheme, if the verse is mapped there
+    pub verse_nr: Option<String>,
+    pub content: String,
+}
+
+/// Get the verses of a reference edition with ids in `verse_start..=verse_end`, in verse order
+///
+/// Verse numbers are given in the versification scheme `scheme_id`.
+pub async fn get_reference_verses(
+    pool: &Pool<Postgres>,
+    edition_id: i64,
+    scheme_id: i64,
+    verse_start: i64,
+    verse_end: i64,
+) -> Result<Vec<ReferenceVerse>, DBError> {
+    query_as!(
+        ReferenceVerse,
+        r#"SELECT reference_verse.verse_id, verse_map.verse_nr as "verse_nr?", reference_verse.content
+            FROM reference_verse
+            LEFT JOIN verse_map
+                ON verse_map.verse_id = reference_verse.verse_id
+                AND verse_map.versification_scheme = $2
+            WHERE reference_verse.edition = $1
+                AND reference_verse.verse_id BETWEEN $3 AND $4
+            ORDER BY reference_verse.verse_id;"#,
+        edition_id,
+        scheme_id,
+        verse_start,
+        verse_end,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DBError::CannotGetReferenceVerses)
+}
+
+/// Add verses to a reference edition, creating the edition if it does not exist yet
+///
+/// `verses` are given as (verse number in `scheme_id`, content). Existing verses are overwritten.
+/// Either all verses are added or none.
+pub async fn add_reference_verses(
+    pool: &Pool<Postgres>,
+    edition_name: &str,
+    lang: &str,
+    scheme_id: i64,
+    verses: Vec<(String, String)>,
+) -> Result<(), DBError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+
+    let edition_id = sqlx::query!(
+        "INSERT INTO reference_edition (name, lang) VALUES ($1, $2)
+            ON CONFLICT (name) DO UPDATE SET lang = excluded.lang
+            RETURNING id;",
+        edition_name,
+        lang,
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(DBError::CannotInsertReferenceEdition)?
+    .id;
+
+    for (verse_nr, content) in verses {
+        let verse_id = sqlx::query!(
+            "SELECT verse_id FROM verse_map WHERE versification_scheme = $1 AND verse_nr = $2;",
+            scheme_id,
+            verse_nr,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(DBError::CannotGetReferenceVerses)?
+        .ok_or(DBError::VerseDoesNotExist(verse_nr))?
+        .verse_id;
+
+        sqlx::query!(
+            "INSERT INTO reference_verse (edition, verse_id, content) VALUES ($1, $2, $3)
+                ON CONFLICT (edition, verse_id) DO UPDATE SET content = excluded.content;",
+            edition_id,
+            verse_id,
+            content,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DBError::CannotInsertReferenceVerse)?;
+    }
+
+    tx.commit().await.map_err(DBError::CannotCommitTransaction)
+}
+
+/// Get the names of all users that have ever logged in
+pub async fn get_usernames(pool: &Pool<Postgres>) -> Result<Vec<String>, DBError> {
+    Ok(
+        sqlx::query!("SELECT username FROM user_session ORDER BY username;")
+            .fetch_all(pool)
+            .await
+            .map_err(DBError::CannotGetUsersession)?
+            .into_iter()
+            .map(|row| row.username)
+            .collect(),
+    )
+}
+
+/// Record that `username` has a transcription for the page `page_id`
+///
+/// If the user already has a transcription for this page, only the published flag is updated.
+pub async fn insert_or_update_transcription(
+    pool: &Pool<Postgres>,
+    page_id: i64,
+    username: &str,
+    published: bool,
+) -> Result<(), DBError> {
+    let user_exists = sqlx::query!(
+        "SELECT username FROM user_session WHERE username = $1;",
+        username
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(DBError::CannotGetUsersession)?
+    .is_some();
+    if !user_exists {
+        return Err(DBError::UserDoesNotExist(username.to_string()));
+    };
+
+    sqlx::query!(
+        "INSERT INTO transcription (page, username, published) VALUES ($1, $2, $3)
+            ON CONFLICT (page, username) DO UPDATE SET published = excluded.published;",
+        page_id,
+        username,
+        published,
+    )
+    .execute(pool)
+    .await
+    .map(|_| {})
+    .map_err(DBError::CannotInsertOrUpdateTranscription)
+}
+
+/// Add an unpublished transcription of `username` for a page, keeping an existing one as it is
+pub async fn ensure_transcription(
+    pool: &Pool<Postgres>,
+    page_id: i64,
+    username: &str,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "INSERT INTO transcription (page, username, published) VALUES ($1, $2, false)
+            ON CONFLICT (page, username) DO NOTHING;",
+        page_id,
+        username,
+    )
+    .execute(pool)
+    .await
+    .map(|_| {})
+    .map_err(DBError::CannotInsertOrUpdateTranscription)
+}
+
+/// Get all transcriptions of a page
+pub async fn get_transcriptions(
+    pool: &Pool<Postgres>,
+    page_id: i64,
+) -> Result<Vec<TranscriptionMeta>, DBError> {
+    query_as!(
+        TranscriptionMeta,
+        "SELECT * FROM transcription WHERE page = $1 ORDER BY username;",
+        page_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DBError::CannotGetTranscriptions)
+}
+
+/// A published transcription of a user, with the names of its page and manuscript
+#[derive(FromRow, Debug, PartialEq, Clone)]
+pub struct UserTranscription {
+    pub id: i64,
+    pub msname: String,
+    pub pagename: String,
+}
+
+/// All published transcriptions of a user, in the order they were started
+pub async fn get_published_transcriptions_of_user(
+    pool: &Pool<Postgres>,
+    username: &str,
+) -> Result<Vec<UserTranscription>, DBError> {
+    query_as!(
+        UserTranscription,
+        "SELECT transcription.id, manuscript.title AS msname, page.name AS pagename
+            FROM transcription
+            INNER JOIN page ON page.id = transcription.page
+            INNER JOIN manuscript ON manuscript.id = page.manuscript
+            WHERE transcription.username = $1 AND transcription.published
+            ORDER BY transcription.id;",
+        username
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DBError::CannotGetTranscriptions)
+}
+
+/// A verse number in one versification scheme
+#[derive(FromRow, Debug, PartialEq, Clone)]
+pub struct VerseMapEntry {
+    /// the non-semantic verse id
+    pub verse_id: i64,
+    /// shorthand of the versification scheme
+    pub shorthand: String,
+    /// the verse number in the scheme, e.g. Gen 5:17
+    pub verse_nr: String,
+}
+
+/// Get the verse numbers of all verses in all versification schemes
+pub async fn get_verse_map(pool: &Pool<Postgres>) -> Result<Vec<VerseMapEntry>, DBError> {
+    query_as!(
+        VerseMapEntry,
+        "SELECT verse_map.verse_id, versification_scheme.shorthand, verse_map.verse_nr
+            FROM verse_map
+            INNER JOIN versification_scheme ON versification_scheme.id = verse_map.versification_scheme
+            ORDER BY verse_map.verse_id;"
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DBError::CannotGetVerseMap)
+}
+
+/// A page with enough published transcriptions to be reconciled that is not done yet
+#[derive(FromRow, Debug, PartialEq, Clone)]
+pub struct OutstandingPage {
+    pub msname: String,
+    pub pagename: String,
+    /// the number of published transcriptions
+    pub witnesses: i64,
+    /// the user working on the open reconciliation, if there is one
+    pub assignee: Option<String>,
+    /// the iid of the merge request of the open reconciliation in gitlab
+    pub merge_request: Option<i64>,
+}
+
+/// Get all pages with at least `min_witnesses` published transcriptions that are not accepted yet
+/// or have an open reconciliation
+///
+/// A page with several open reconciliations is returned once for each of them.
+pub async fn get_outstanding_pages(
+    pool: &Pool<Postgres>,
+    min_witnesses: i64,
+) -> Result<Vec<OutstandingPage>, DBError> {
+    query_as!(
+        OutstandingPage,
+        r#"SELECT manuscript.title AS msname, page.name AS pagename,
+                COUNT(transcription.id) AS "witnesses!",
+                open_reconciliation.username AS "assignee?",
+                open_reconciliation.merge_request AS "merge_request?"
+            FROM page
+            INNER JOIN manuscript ON manuscript.id = page.manuscript
+            INNER JOIN transcription ON transcription.page = page.id AND transcription.published
+            LEFT JOIN reconciliation AS open_reconciliation
+                ON open_reconciliation.page = page.id AND open_reconciliation.status = 'open'
+            GROUP BY manuscript.title, page.id, page.name, open_reconciliation.id
+            HAVING COUNT(transcription.id) >= $1
+                AND (open_reconciliation.id IS NOT NULL OR NOT page.accepted)
+            ORDER BY manuscript.title, page.id;"#,
+        min_witnesses
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DBError::CannotGetReconciliations)
+}
+
+/// Record an open reconciliation of the page `page_id` by `username` on `branch`
+///
+/// An existing reconciliation on this branch is reopened. Its merge request is only replaced if
+/// `merge_request` is set.
+pub async fn insert_or_update_open_reconciliation(
+    pool: &Pool<Postgres>,
+    page_id: i64,
+    username: &str,
+    branch: &str,
+    merge_request: Option<i64>,
+) -> Result<(), DBError> {
+    let user_exists = sqlx::query!(
+        "SELECT username FROM user_session WHERE username = $1;",
+        username
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(DBError::CannotGetUsersession)?
+    .is_some();
+    if !user_exists {
+        return Err(DBError::UserDoesNotExist(username.to_string()));
+    };
+
+    sqlx::query!(
+        "INSERT INTO reconciliation (page, username, branch, merge_request, status)
+            VALUES ($1, $2, $3, $4, 'open')
+            ON CONFLICT (page, username, branch) DO UPDATE SET
+                status = 'open',
+                merge_request = COALESCE(excluded.merge_request, reconciliation.merge_request);",
+        page_id,
+        username,
+        branch,
+        merge_request,
+    )
+    .execute(pool)
+    .await
+    .map_err(DBError::CannotUpdateReconciliation)?;
+    Ok(())
+}
+
+/// The merge request of the open reconciliation on `branch`
+///
+/// None if there is no open reconciliation on this branch, Some(None) if no merge request was
+/// opened for it yet.
+pub async fn get_open_reconciliation_merge_request(
+    pool: &Pool<Postgres>,
+    branch: &str,
+) -> Result<Option<Option<i64>>, DBError> {
+    Ok(sqlx::query!(
+        "SELECT merge_request FROM reconciliation WHERE branch = $1 AND status = 'open';",
+        branch
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(DBError::CannotGetReconciliations)?
+    .map(|row| row.merge_request))
+}
+
+/// Set the status of the open reconciliations on `branch`
+pub async fn set_reconciliation_status(
+    pool: &Pool<Postgres>,
+    branch: &str,
+    status: ReconciliationStatus,
+) -> Result<(), DBError> {
+    sqlx::query!(
+        "UPDATE reconciliation SET status = $2 WHERE branch = $1 AND status = 'open';",
+        branch,
+        status.as_str(),
+    )
+    .execute(pool)
+    .await
+    .map_err(DBError::CannotUpdateReconciliation)?;
+    Ok(())
+}
+
+/// Mark that the page `page_id` has an accepted transcription
+pub async fn mark_page_accepted(pool: &Pool<Postgres>, page_id: i64) -> Result<(), DBError> {
+    sqlx::query!("UPDATE page SET accepted = true WHERE id = $1;", page_id)
+        .execute(pool)
+        .await
+        .map_err(DBError::CannotMarkPageAccepted)?;
+    Ok(())
+}
+
+/// The schema a rebuild from the repository is built into before it is applied
+const REBUILD_SCHEMA: &str = "critic_rebuild";
+
+/// The differences between the database and a rebuild from the repository
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct RebuildDiff {
+    /// manuscripts that do not exist in the database yet
+    pub added_manuscripts: Vec<String>,
+    /// manuscripts whose metadata differs
+    pub updated_manuscripts: Vec<String>,
+    /// pages (manuscript, page) that do not exist in the database yet
+    pub added_pages: Vec<(String, String)>,
+    /// pages that are accepted in the repository, but not in the database
+    pub accepted_pages: Vec<(String, String)>,
+    /// pages that are accepted in the database, but not in the repository
+    pub unaccepted_pages: Vec<(String, String)>,
+}
+
+/// Build manuscripts, pages and their accepted state as in the repository into a scratch schema,
+/// and compare them with the live tables
+///
+/// `manuscripts` holds the metadata of all manuscripts in the repository (the id is ignored),
+/// `pages` all pages (manuscript title, page name) with an accepted transcription.
+///
+/// Only the scratch schema is written. It is kept for [`apply_rebuild`] until [`drop_rebuild`] is
+/// called.
+pub async fn stage_rebuild(
+    pool: &Pool<Postgres>,
+    manuscripts: &[ManuscriptMeta],
+    pages: &[(String, String)],
+) -> Result<RebuildDiff, DBError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+
+    // the scratch schema
+    for statement in [
+        format!("DROP SCHEMA IF EXISTS {REBUILD_SCHEMA} CASCADE;"),
+        format!("CREATE SCHEMA {REBUILD_SCHEMA};"),
+        format!(
+            "CREATE TABLE {REBUILD_SCHEMA}.manuscript (
+                title TEXT PRIMARY KEY,
+                institution TEXT,
+                collection TEXT,
+                hand_desc TEXT,
+                script_desc TEXT
+            );"
+        ),
+        format!(
+            "CREATE TABLE {REBUILD_SCHEMA}.page (
+                manuscript TEXT NOT NULL REFERENCES {REBUILD_SCHEMA}.manuscript(title),
+                name TEXT NOT NULL,
+                PRIMARY KEY (manuscript, name)
+            );"
+        ),
+    ] {
+        sqlx::query(&statement)
+            .execute(&mut *tx)
+            .await
+            .map_err(DBError::CannotRebuild)?;
+    }
+    for ms in manuscripts {
+        sqlx::query(&format!(
+            "INSERT INTO {REBUILD_SCHEMA}.manuscript
+                (title, institution, collection, hand_desc, script_desc)
+                VALUES ($1, $2, $3, $4, $5);"
+        ))
+        .bind(&ms.title)
+        .bind(&ms.institution)
+        .bind(&ms.collection)
+        .bind(&ms.hand_desc)
+        .bind(&ms.script_desc)
+        .execute(&mut *tx)
+        .await
+        .map_err(DBError::CannotRebuild)?;
+    }
+    for (msname, pagename) in pages {
+        sqlx::query(&format!(
+            "INSERT INTO {REBUILD_SCHEMA}.page (manuscript, name) VALUES ($1, $2);"
+        ))
+        .bind(msname)
+        .bind(pagename)
+        .execute(&mut *tx)
+        .await
+        .map_err(DBError::CannotRebuild)?;
+    }
+
+    // compare with the live tables
+    let titles = |query: String| sqlx::query_scalar::<_, String>(&query);
+    let page_names = |query: String| sqlx::query_as::<_, (String, String)>(&query);
+    let diff = RebuildDiff {
+        added_manuscripts: titles(format!(
+            "SELECT rebuilt.title FROM {REBUILD_SCHEMA}.manuscript AS rebuilt
+                WHERE NOT EXISTS (SELECT 1 FROM manuscript WHERE manuscript.title = rebuilt.title)
+                ORDER BY rebuilt.title;"
+        ))
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(DBError::CannotRebuild)?,
+        updated_manuscripts: titles(format!(
+            "SELECT rebuilt.title FROM {REBUILD_SCHEMA}.manuscript AS rebuilt
+                INNER JOIN manuscript ON manuscript.title = rebuilt.title
+                WHERE (rebuilt.institution, rebuilt.collection, rebuilt.hand_desc, rebuilt.script_desc)
+                    IS DISTINCT FROM
+                    (manuscript.institution, manuscript.collection, manuscript.hand_desc, manuscript.script_desc)
+                ORDER BY rebuilt.title;"
+        ))
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(DBError::CannotRebuild)?,
+        added_pages: page_names(format!(
+            "SELECT rebuilt.manuscript, rebuilt.name FROM {REBUILD_SCHEMA}.page AS rebuilt
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM page
+                    INNER JOIN manuscript ON manuscript.id = page.manuscript
+                    WHERE manuscript.title = rebuilt.manuscript AND page.name = rebuilt.name
+                )
+                ORDER BY rebuilt.manuscript, rebuilt.name;"
+        ))
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(DBError::CannotRebuild)?,
+        accepted_pages: page_names(format!(
+            "SELECT rebuilt.manuscript, rebuilt.name FROM {REBUILD_SCHEMA}.page AS rebuilt
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM page
+                    INNER JOIN manuscript ON manuscript.id = page.manuscript
+                    WHERE manuscript.title = rebuilt.manuscript AND page.name = rebuilt.name
+                        AND page.accepted
+                )
+                ORDER BY rebuilt.manuscript, rebuilt.name;"
+        ))
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(DBError::CannotRebuild)?,
+        unaccepted_pages: page_names(format!(
+            "SELECT manuscript.title, page.name FROM page
+                INNER JOIN manuscript ON manuscript.id = page.manuscript
+                WHERE page.accepted AND NOT EXISTS (
+                    SELECT 1 FROM {REBUILD_SCHEMA}.page AS rebuilt
+                    WHERE rebuilt.manuscript = manuscript.title AND rebuilt.name = page.name
+                )
+                ORDER BY manuscript.title, page.name;"
+        ))
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(DBError::CannotRebuild)?,
+    };
+
+    tx.commit()
+        .await
+        .map_err(DBError::CannotCommitTransaction)?;
+    Ok(diff)
+}
+
+/// Apply a rebuild staged by [`stage_rebuild`] to the live tables
+///
+/// Data that does not live in the repository (verse ranges, images, transcriptions, ...) is kept;
+/// manuscripts and pages missing from the repository are only marked as not accepted. The changes
+/// only take effect when the returned transaction is committed, so that the caller can still
+/// abandon them.
+pub async fn apply_rebuild(
+    pool: &Pool<Postgres>,
+) -> Result<sqlx::Transaction<'static, Postgres>, DBError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(DBError::CannotStartTransaction)?;
+    for statement in [
+        format!(
+            "INSERT INTO manuscript (title, institution, collection, hand_desc, script_desc)
+                SELECT title, institution, collection, hand_desc, script_desc
+                FROM {REBUILD_SCHEMA}.manuscript
+                ON CONFLICT (title) DO UPDATE SET
+                    institution = excluded.institution,
+                    collection = excluded.collection,
+                    hand_desc = excluded.hand_desc,
+                    script_desc = excluded.script_desc;"
+        ),
+        format!(
+            "INSERT INTO page (manuscript, name)
+                SELECT manuscript.id, rebuilt.name FROM {REBUILD_SCHEMA}.page AS rebuilt
+                INNER JOIN manuscript ON manuscript.title = rebuilt.manuscript
+                ON CONFLICT (manuscript, name) DO NOTHING;"
+        ),
+        format!(
+            "UPDATE page SET accepted = EXISTS (
+                SELECT 1 FROM {REBUILD_SCHEMA}.page AS rebuilt
+                INNER JOIN manuscript ON manuscript.title = rebuilt.manuscript
+                WHERE manuscript.id = page.manuscript AND rebuilt.name = page.name
+            );"
+        ),
+    ] {
+        sqlx::query(&statement)
+            .execute(&mut *tx)
+            .await
+            .map_err(DBError::CannotRebuild)?;
+    }
+    Ok(tx)
+}
+
+/// Remove the scratch schema of [`stage_rebuild`]
+pub async fn drop_rebuild(pool: &Pool<Postgres>) -> Result<(), DBError> {
+    sqlx::query(&format!("DROP SCHEMA IF EXISTS {REBUILD_SCHEMA} CASCADE;"))
+        .execute(pool)
+        .await
+        .map_err(DBError::CannotRebuild)?;
+    Ok(())
+}
@@ -0,0 +1,327 @@
+//! TEI critical apparatus export (parallel segmentation)
+//!
+//! The collation of a verse range is written as one `<ab>` per verse containing the base text.
+//! Every variation location becomes an `<app>` with the reading of the base witness as `<lem>`
+//! and the other readings as `<rdg>`. All witnesses are listed in `<listWit>` in the header.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use axum::{extract::Query, http::header, response::IntoResponse, Extension};
+use critic_shared::{
+    collation::{Token, TokenKind, VariantReading, VariantTable, VariantVerse},
+    ManuscriptMeta,
+};
+use quick_xml::{
+    events::{BytesDecl, BytesStart, BytesText, Event},
+    Writer,
+};
+use serde::Deserialize;
+
+use super::{
+    attachment,
+    tei::{end, start, start_with_attributes, text_element, XmlWriter, TEI_NAMESPACE},
+    ExportError,
+};
+use crate::{collation::collate_range, config::Config, db::get_manuscripts_by_name};
+
+/// The xml:id for a siglum
+///
+/// Characters that are not allowed in xml:ids are replaced by `_`, so different sigla may end up
+/// with the same id. Use [`WitnessIds`] to get unique ones.
+pub fn witness_id(siglum: &str) -> String {
+    let mut res = siglum
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if !res.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        res.insert(0, '_');
+    };
+    res
+}
+
+/// Unique xml:ids for the witnesses of a table
+///
+/// A siglum gets its [`witness_id`] unless an earlier siglum already has it. In that case, the
+/// first free id with a numeric suffix (`_2`, `_3`, ...) is used.
+#[derive(Debug, Clone, Default)]
+pub struct WitnessIds(HashMap<String, String>);
+impl WitnessIds {
+    pub fn new<'a>(sigla: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut ids = HashMap::new();
+        let mut taken = HashSet::new();
+        for siglum in sigla {
+            if ids.contains_key(siglum) {
+                continue;
+            };
+            let plain = witness_id(siglum);
+            let mut id = plain.clone();
+            let mut suffix = 2;
+            while !taken.insert(id.clone()) {
+                id = format!("{plain}_{suffix}");
+                suffix += 1;
+            }
+            ids.insert(siglum.clone(), id);
+        }
+        Self(ids)
+    }
+
+    /// The id of `siglum`, its plain [`witness_id`] if it is not one of the witnesses
+    pub fn get(&self, siglum: &str) -> String {
+        self.0
+            .get(siglum)
+            .cloned()
+            .unwrap_or_else(|| witness_id(siglum))
+    }
+
+    /// The value for a `wit` attribute pointing to all `sigla`
+    fn wit_attribute(&self, sigla: &[String]) -> String {
+        sigla
+            .iter()
+            .map(|siglum| format!("#{}", self.get(siglum)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+fn text(writer: &mut XmlWriter, content: &str) -> Result<(), ExportError> {
+    writer
+        .write_event(Event::Text(BytesText::new(content)))
+        .map_err(ExportError::Write)
+}
+
+fn empty(
+    writer: &mut XmlWriter,
+    name: &str,
+    attributes: &[(&str, &str)],
+) -> Result<(), ExportError> {
+    writer
+        .write_event(Event::Empty(
+            BytesStart::new(name).with_attributes(attributes.iter().copied()),
+        ))
+        .map_err(ExportError::Write)
+}
+
+/// Write tokens separated by spaces
+fn write_tokens(writer: &mut XmlWriter, tokens: &[Token]) -> Result<(), ExportError> {
+    for (idx, token) in tokens.iter().enumerate() {
+        if idx != 0 {
+            text(writer, " ")?;
+        };
+        match &token.kind {
+            TokenKind::Word | TokenKind::Corrected { .. } => text(writer, &token.text)?,
+            TokenKind::Uncertain => text_element(writer, "unclear", &token.text)?,
+            TokenKind::Lacuna => empty(writer, "gap", &[("reason", "lost")])?,
+        };
+    }
+    Ok(())
+}
+
+/// Write a `<lem>` or `<rdg>`
+fn write_reading(
+    writer: &mut XmlWriter,
+    ids: &WitnessIds,
+    name: &str,
+    reading: &VariantReading,
+) -> Result<(), ExportError> {
+    let wit = ids.wit_attribute(&reading.sigla);
+    if reading.tokens.is_empty() {
+        return empty(writer, name, &[("wit", wit.as_str())]);
+    };
+    start_with_attributes(writer, name, &[("wit", wit.as_str())])?;
+    write_tokens(writer, &reading.tokens)?;
+    end(writer, name)
+}
+
+fn write_verse(
+    writer: &mut XmlWriter,
+    ids: &WitnessIds,
+    verse: &VariantVerse,
+) -> Result<(), ExportError> {
+    start_with_attributes(writer, "ab", &[("n", verse.verse_nr.as_str())])?;
+    if !verse.missing.is_empty() {
+        let wit = ids.wit_attribute(&verse.missing);
+        start_with_attributes(
+            writer,
+            "witDetail",
+            &[("wit", wit.as_str()), ("type", "missing")],
+        )?;
+        text(writer, "verse not extant")?;
+        end(writer, "witDetail")?;
+    };
+    let mut idx = 0;
+    for location in &verse.locations {
+        if location.start > idx {
+            write_tokens(writer, &verse.base[idx..location.start])?;
+            text(writer, " ")?;
+        };
+        start(writer, "app")?;
+        let mut readings = location.readings.iter();
+        if let Some(lemma) = readings.next() {
+            write_reading(writer, ids, "lem", lemma)?;
+        };
+        for reading in readings {
+            write_reading(writer, ids, "rdg", reading)?;
+        }
+        end(writer, "app")?;
+        if location.end < verse.base.len() {
+            text(writer, " ")?;
+        };
+        idx = location.end;
+    }
+    if idx < verse.base.len() {
+        write_tokens(writer, &verse.base[idx..])?;
+    };
+    end(writer, "ab")
+}
+
+/// Write `<witness>` for a manuscript
+fn write_witness(
+    writer: &mut XmlWriter,
+    id: &str,
+    meta: &ManuscriptMeta,
+) -> Result<(), ExportError> {
+    start_with_attributes(writer, "witness", &[("xml:id", id)])?;
+    let description = [
+        Some(&meta.title),
+        meta.institution.as_ref(),
+        meta.collection.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(String::as_str)
+    .collect::<Vec<_>>()
+    .join(", ");
+    text(writer, &description)?;
+    end(writer, "witness")
+}
+
+/// Build a TEI document with a parallel-segmentation apparatus from a variant table
+///
+/// `manuscripts` are the metadata of (at least) all witnesses in the table.
+pub fn apparatus_tei(
+    table: &VariantTable,
+    manuscripts: &[ManuscriptMeta],
+    title: &str,
+) -> Result<String, ExportError> {
+    let mut writer = Writer::new(Vec::new());
+    writer
+        .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+        .map_err(ExportError::Write)?;
+    start_with_attributes(&mut writer, "TEI", &[("xmlns", TEI_NAMESPACE)])?;
+
+    start(&mut writer, "teiHeader")?;
+    start(&mut writer, "fileDesc")?;
+    start(&mut writer, "titleStmt")?;
+    text_element(&mut writer, "title", title)?;
+    end(&mut writer, "titleStmt")?;
+    start(&mut writer, "publicationStmt")?;
+    text_element(&mut writer, "p", "Exported from critic.")?;
+    end(&mut writer, "publicationStmt")?;
+    start(&mut writer, "sourceDesc")?;
+    start(&mut writer, "listWit")?;
+    let sigla = std::iter::once(&table.base).chain(&table.witnesses);
+    let ids = WitnessIds::new(sigla.clone());
+    for siglum in sigla {
+        let id = ids.get(siglum);
+        match manuscripts.iter().find(|ms| &ms.title == siglum) {
+            Some(meta) => write_witness(&mut writer, &id, meta)?,
+            None => {
+                start_with_attributes(&mut writer, "witness", &[("xml:id", id.as_str())])?;
+                text(&mut writer, siglum)?;
+                end(&mut writer, "witness")?;
+            }
+        };
+    }
+    end(&mut writer, "listWit")?;
+    end(&mut writer, "sourceDesc")?;
+    end(&mut writer, "fileDesc")?;
+    start(&mut writer, "encodingDesc")?;
+    empty(
+        &mut writer,
+        "variantEncoding",
+        &[
+            ("method", "parallel-segmentation"),
+            ("location", "internal"),
+        ],
+    )?;
+    end(&mut writer, "encodingDesc")?;
+    end(&mut writer, "teiHeader")?;
+
+    start(&mut writer, "text")?;
+    start(&mut writer, "body")?;
+    for verse in &table.verses {
+        write_verse(&mut writer, &ids, verse)?;
+    }
+    end(&mut writer, "body")?;
+    end(&mut writer, "text")?;
+    end(&mut writer, "TEI")?;
+    Ok(String::from_utf8(writer.into_inner()).expect("only strings are written"))
+}
+
+/// Query parameters for the apparatus export
+#[derive(Debug, Deserialize)]
+pub struct ApparatusExportQuery {
+    /// shorthand of the versification scheme `start` and `end` are given in
+    scheme: String,
+    /// the first verse to export
+    start: String,
+    /// the last verse to export
+    end: String,
+    /// the manuscript used as base (lemma), the first one covering the verses if not set
+    base: Option<String>,
+}
+
+async fn apparatus_document(
+    config: &Config,
+    query: &ApparatusExportQuery,
+) -> Result<String, ExportError> {
+    let table = collate_range(
+        config,
+        &query.scheme,
+        &query.start,
+        &query.end,
+        query.base.clone().filter(|base| !base.is_empty()),
+    )
+    .await?;
+    let manuscripts = get_manuscripts_by_name(&config.db, None)
+        .await
+        .map_err(ExportError::DB)?;
+    apparatus_tei(
+        &table,
+        &manuscripts,
+        &format!("Collation of {} - {}", query.start, query.end),
+    )
+}
+
+/// Export the collation of a verse range as a TEI critical apparatus
+pub async fn apparatus_export(
+    Extension(config): Extension<Arc<Config>>,
+    Query(query): Query<ApparatusExportQuery>,
+) -> impl IntoResponse {
+    match apparatus_document(&config, &query).await {
+        Ok(tei) => (
+            [
+                (header::CONTENT_TYPE, "application/tei+xml".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    attachment(&format!("apparatus_{}-{}.tei.xml", query.start, query.end)),
+                ),
+            ],
+            tei,
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,108 @@
+//! Tests for the critical apparatus export
+
+use critic_shared::{
+    collation::{Token, TokenKind, VariantLocation, VariantReading, VariantTable, VariantVerse},
+    ManuscriptMeta,
+};
+
+use super::{apparatus_tei, witness_id, WitnessIds};
+
+fn words(text: &str) -> Vec<Token> {
+    text.split_whitespace()
+        .map(|word| Token {
+            text: word.to_string(),
+            kind: TokenKind::Word,
+        })
+        .collect()
+}
+
+fn reading(text: &str, sigla: &[&str]) -> VariantReading {
+    VariantReading {
+        tokens: words(text),
+        sigla: sigla.iter().map(|s| s.to_string()).collect(),
+    }
+}
+
+fn meta(title: &str) -> ManuscriptMeta {
+    ManuscriptMeta {
+        id: 1,
+        title: title.to_string(),
+        institution: Some("National Library".to_string()),
+        collection: None,
+        hand_desc: None,
+        script_desc: None,
+    }
+}
+
+/// A table of three witnesses where `A 1` and `A/1` disagree with the base in the second word
+fn table() -> VariantTable {
+    VariantTable {
+        base: "L".to_string(),
+        witnesses: vec!["A 1".to_string(), "A/1".to_string()],
+        verses: vec![VariantVerse {
+            verse_nr: "Gen 1:1".to_string(),
+            base: words("בראשית ברא אלהים"),
+            locations: vec![VariantLocation {
+                start: 1,
+                end: 2,
+                readings: vec![
+                    reading("ברא", &["L"]),
+                    reading("עשה", &["A 1"]),
+                    reading("", &["A/1"]),
+                ],
+            }],
+            missing: vec![],
+        }],
+    }
+}
+
+#[test]
+fn witness_ids_are_valid_xml_ids() {
+    assert_eq!(witness_id("Leningradensis"), "Leningradensis");
+    assert_eq!(witness_id("A 1"), "A_1");
+    assert_eq!(witness_id("4Q1"), "_4Q1");
+    assert_eq!(witness_id("Ms. Heb-2"), "Ms._Heb-2");
+}
+
+#[test]
+fn colliding_witness_ids_get_a_suffix() {
+    let sigla = ["A 1", "A/1", "A_1", "A 1", "A?1"].map(String::from);
+    let ids = WitnessIds::new(&sigla);
+    assert_eq!(ids.get("A 1"), "A_1");
+    assert_eq!(ids.get("A/1"), "A_1_2");
+    assert_eq!(ids.get("A_1"), "A_1_3");
+    assert_eq!(ids.get("A?1"), "A_1_4");
+    // unknown sigla get their plain id
+    assert_eq!(ids.get("B 2"), "B_2");
+}
+
+#[test]
+fn suffixes_skip_ids_taken_by_other_sigla() {
+    let sigla = ["A_1_2", "A 1", "A/1"].map(String::from);
+    let ids = WitnessIds::new(&sigla);
+    assert_eq!(ids.get("A_1_2"), "A_1_2");
+    assert_eq!(ids.get("A 1"), "A_1");
+    assert_eq!(ids.get("A/1"), "A_1_3");
+}
+
+#[test]
+fn readings_point_to_distinct_witnesses() {
+    let tei = apparatus_tei(&table(), &[meta("L")], "Collation").unwrap();
+    assert!(tei.contains(r#"<witness xml:id="L">L, National Library</witness>"#));
+    assert!(tei.contains(r#"<witness xml:id="A_1">A 1</witness>"#));
+    assert!(tei.contains(r#"<witness xml:id="A_1_2">A/1</witness>"#));
+    assert!(tei.contains(
+        r##"<ab n="Gen 1:1">בראשית <app><lem wit="#L">ברא</lem><rdg wit="#A_1">עשה</rdg><rdg wit="#A_1_2"/></app> אלהים</ab>"##
+    ));
+}
+
+#[test]
+fn missing_witnesses_are_noted_per_verse() {
+    let mut table = table();
+    table.verses[0].locations.clear();
+    table.verses[0].missing = vec!["A/1".to_string()];
+    let tei = apparatus_tei(&table, &[], "Collation").unwrap();
+    assert!(tei.contains(
+        r##"<ab n="Gen 1:1"><witDetail wit="#A_1_2" type="missing">verse not extant</witDetail>בראשית ברא אלהים</ab>"##
+    ));
+}
@@ -0,0 +1,326 @@
+//! Shared parts of the exports for Bible software (OSIS and USFM)
+//!
+//! The accepted text of a manuscript is split into verses at its verse anchors. Every verse is
+//! then numbered in the versification scheme chosen for the export, and its verse number
+//! (`<book> <chapter>:<verse>`, e.g. `1 Sam 3:4`) is split into book, chapter and verse.
+//!
+//! Both formats know less markup than our transcriptions. [`BibleExportOptions`] chooses for each
+//! kind of markup whether it is mapped to the closest construct of the format or dropped.
+
+use critic_format::streamed::Block;
+use critic_shared::ManuscriptMeta;
+use serde::Deserialize;
+
+use super::{manuscript_transcriptions, ExportError, TranscriptionChoice};
+use crate::{
+    collation::{load_anchor_map, AnchorMap},
+    config::Config,
+};
+
+/// A book of the Hebrew Bible with its identifiers in OSIS and USFM
+#[derive(Debug, PartialEq, Eq)]
+pub struct Book {
+    /// the OSIS book id, e.g. `1Sam`
+    pub osis: &'static str,
+    /// the USFM book code, e.g. `1SA`
+    pub usfm: &'static str,
+    /// the english name, e.g. `1 Samuel`
+    pub name: &'static str,
+}
+
+const fn book(osis: &'static str, usfm: &'static str, name: &'static str) -> Book {
+    Book { osis, usfm, name }
+}
+
+/// All books that can be exported, in canonical order
+pub const BOOKS: [Book; 39] = [
+    book("Gen", "GEN", "Genesis"),
+    book("Exod", "EXO", "Exodus"),
+    book("Lev", "LEV", "Leviticus"),
+    book("Num", "NUM", "Numbers"),
+    book("Deut", "DEU", "Deuteronomy"),
+    book("Josh", "JOS", "Joshua"),
+    book("Judg", "JDG", "Judges"),
+    book("Ruth", "RUT", "Ruth"),
+    book("1Sam", "1SA", "1 Samuel"),
+    book("2Sam", "2SA", "2 Samuel"),
+    book("1Kgs", "1KI", "1 Kings"),
+    book("2Kgs", "2KI", "2 Kings"),
+    book("1Chr", "1CH", "1 Chronicles"),
+    book("2Chr", "2CH", "2 Chronicles"),
+    book("Ezra", "EZR", "Ezra"),
+    book("Neh", "NEH", "Nehemiah"),
+    book("Esth", "EST", "Esther"),
+    book("Job", "JOB", "Job"),
+    book("Ps", "PSA", "Psalms"),
+    book("Prov", "PRO", "Proverbs"),
+    book("Eccl", "ECC", "Ecclesiastes"),
+    book("Song", "SNG", "Song of Songs"),
+    book("Isa", "ISA", "Isaiah"),
+    book("Jer", "JER", "Jeremiah"),
+    book("Lam", "LAM", "Lamentations"),
+    book("Ezek", "EZK", "Ezekiel"),
+    book("Dan", "DAN", "Daniel"),
+    book("Hos", "HOS", "Hosea"),
+    book("Joel", "JOL", "Joel"),
+    book("Amos", "AMO", "Amos"),
+    book("Obad", "OBA", "Obadiah"),
+    book("Jonah", "JON", "Jonah"),
+    book("Mic", "MIC", "Micah"),
+    book("Nah", "NAM", "Nahum"),
+    book("Hab", "HAB", "Habakkuk"),
+    book("Zeph", "ZEP", "Zephaniah"),
+    book("Hag", "HAG", "Haggai"),
+    book("Zech", "ZEC", "Zechariah"),
+    book("Mal", "MAL", "Malachi"),
+];
+
+/// Find a book by its OSIS id, USFM code or english name
+///
+/// Case and spaces are ignored, so `1 Sam`, `1sa` and `1 Samuel` all find 1 Samuel.
+pub fn find_book(name: &str) -> Option<&'static Book> {
+    let normalize = |s: &str| {
+        s.chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_lowercase()
+    };
+    let name = normalize(name);
+    BOOKS.iter().find(|book| {
+        normalize(book.osis) == name || normalize(book.usfm) == name || normalize(book.name) == name
+    })
+}
+
+/// Whether markup the target format has no direct equivalent for is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkupMode {
+    /// map it to the closest construct of the format (notes, attributes, ...)
+    #[default]
+    Map,
+    /// drop the markup, keeping only the text
+    Drop,
+}
+
+/// The options for the OSIS and USFM export
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BibleExportOptions {
+    /// shorthand of the versification scheme to number the verses in
+    pub scheme: String,
+    /// only export this book (OSIS id, USFM code or english name)
+    pub book: Option<String>,
+    /// Abbreviations are dropped to their expansion
+    #[serde(default)]
+    pub abbreviations: MarkupMode,
+    /// Corrections are dropped to their last version
+    #[serde(default)]
+    pub corrections: MarkupMode,
+    #[serde(default)]
+    pub uncertain: MarkupMode,
+    #[serde(default)]
+    pub lacunae: MarkupMode,
+}
+
+/// A piece of the text of a verse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Text(String),
+    Abbreviation {
+        surface: String,
+        expansion: String,
+    },
+    Uncertain(String),
+    /// the text after correction and the text before
+    Correction {
+        text: String,
+        original: String,
+    },
+    /// a lacuna, described by its extent, e.g. `3 character`
+    Lacuna(String),
+}
+
+/// A single verse with its text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BibleVerse {
+    pub book: &'static Book,
+    pub chapter: String,
+    pub verse: String,
+    pub segments: Vec<Segment>,
+}
+
+/// Split a verse number like `1 Sam 3:4` into book, chapter and verse
+fn parse_verse_nr(verse_nr: &str) -> Result<(&'static Book, String, String), ExportError> {
+    let unreadable = || ExportError::UnreadableVerseNumber(verse_nr.to_string());
+    let (book, reference) = verse_nr.trim().rsplit_once(' ').ok_or_else(unreadable)?;
+    let (chapter, verse) = reference.split_once(':').ok_or_else(unreadable)?;
+    let book = find_book(book).ok_or_else(unreadable)?;
+    Ok((book, chapter.to_string(), verse.to_string()))
+}
+
+/// The segments a block turns into
+///
+/// Breaks and spaces become a single space, other anchors are dropped.
+fn block_segments(block: Block, options: &BibleExportOptions) -> Option<Segment> {
+    let segment = match block {
+        Block::Text(paragraph) => Segment::Text(paragraph.content),
+        Block::Break(_) | Block::Space(_) => Segment::Text(" ".to_string()),
+        Block::Anchor(_) => return None,
+        Block::Abbreviation(abbreviation) => match options.abbreviations {
+            MarkupMode::Map => Segment::Abbreviation {
+                surface: abbreviation.surface,
+                expansion: abbreviation.expansion,
+            },
+            MarkupMode::Drop => Segment::Text(abbreviation.expansion),
+        },
+        Block::Uncertain(uncertain) => match options.uncertain {
+            MarkupMode::Map => Segment::Uncertain(uncertain.content),
+            MarkupMode::Drop => Segment::Text(uncertain.content),
+        },
+        Block::Correction(correction) => {
+            let mut versions = correction.versions.into_iter();
+            let original = versions.next().map(|v| v.content).unwrap_or_default();
+            let text = versions.next_back().map_or(original.clone(), |v| v.content);
+            match options.corrections {
+                MarkupMode::Map if text != original => Segment::Correction { text, original },
+                _ => Segment::Text(text),
+            }
+        }
+        Block::Lacuna(lacuna) => match options.lacunae {
+            MarkupMode::Map => Segment::Lacuna(format!("{} {}", lacuna.n, lacuna.unit.name())),
+            MarkupMode::Drop => return None,
+        },
+    };
+    Some(segment)
+}
+
+/// Split the blocks of a manuscript into verses numbered in the scheme chosen in `options`
+///
+/// The verses keep the order of the manuscript. Text before the first verse anchor is dropped,
+/// anchors of verses without a number in the chosen scheme do not start a new verse.
+pub fn bible_verses(
+    blocks: impl IntoIterator<Item = Block>,
+    anchors: &AnchorMap,
+    options: &BibleExportOptions,
+) -> Result<Vec<BibleVerse>, ExportError> {
+    let only_book = match &options.book {
+        Some(name) => Some(find_book(name).ok_or(ExportError::UnknownBook(name.clone()))?),
+        None => None,
+    };
+    let mut res: Vec<BibleVerse> = vec![];
+    // whether the current verse is exported at all
+    let mut current = false;
+    for block in blocks {
+        if let Block::Anchor(anchor) = &block {
+            if let Some(verse_nr) = anchors
+                .verse_id(&anchor.anchor_id)
+                .and_then(|verse_id| anchors.verse_nr(verse_id, &options.scheme))
+            {
+                let (book, chapter, verse) = parse_verse_nr(verse_nr)?;
+                current = only_book.is_none_or(|only| only == book);
+                if current {
+                    res.push(BibleVerse {
+                        book,
+                        chapter,
+                        verse,
+                        segments: vec![],
+                    });
+                };
+                continue;
+            };
+        };
+        if !current {
+            continue;
+        };
+        if let (Some(segment), Some(verse)) = (block_segments(block, options), res.last_mut()) {
+            verse.segments.push(segment);
+        };
+    }
+    for verse in &mut res {
+        tidy_segments(&mut verse.segments);
+    }
+    Ok(res)
+}
+
+/// The verses of the chosen transcriptions of a manuscript
+pub async fn manuscript_verses(
+    config: &Config,
+    msname: &str,
+    choice: &TranscriptionChoice,
+    options: &BibleExportOptions,
+) -> Result<(ManuscriptMeta, Vec<BibleVerse>), ExportError> {
+    let anchors = load_anchor_map(config).await?;
+    let (meta, pages) = manuscript_transcriptions(config, msname, choice).await?;
+    let verses = bible_verses(
+        pages.into_iter().flat_map(|(_, ms)| ms.content),
+        &anchors,
+        options,
+    )?;
+    Ok((meta, verses))
+}
+
+/// Merge neighbouring text segments and normalize their whitespace
+///
+/// Whitespace at the start and end of the verse is removed.
+fn tidy_segments(segments: &mut Vec<Segment>) {
+    let mut res: Vec<Segment> = vec![];
+    for segment in segments.drain(..) {
+        match (res.last_mut(), segment) {
+            (Some(Segment::Text(last)), Segment::Text(text)) => last.push_str(&text),
+            (_, segment) => res.push(segment),
+        }
+    }
+    for segment in &mut res {
+        if let Segment::Text(text) = segment {
+            *text = collapse_whitespace(text);
+        };
+    }
+    if let Some(Segment::Text(text)) = res.first_mut() {
+        *text = text.trim_start().to_string();
+    };
+    if let Some(Segment::Text(text)) = res.last_mut() {
+        *text = text.trim_end().to_string();
+    };
+    res.retain(|segment| !matches!(segment, Segment::Text(text) if text.is_empty()));
+    *segments = res;
+}
+
+/// Collapse runs of whitespace into single spaces
+fn collapse_whitespace(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut last_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !last_space {
+                res.push(' ');
+            };
+            last_space = true;
+        } else {
+            res.push(c);
+            last_space = false;
+        }
+    }
+    res
+}
+
+/// Verses grouped by book, then by chapter, keeping their order
+pub fn group_verses(verses: &[BibleVerse]) -> Vec<(&'static Book, Vec<(&str, Vec<&BibleVerse>)>)> {
+    let mut res: Vec<(&'static Book, Vec<(&str, Vec<&BibleVerse>)>)> = vec![];
+    for verse in verses {
+        if res.last().is_none_or(|(book, _)| *book != verse.book) {
+            res.push((verse.book, vec![]));
+        };
+        let Some((_, chapters)) = res.last_mut() else {
+            continue;
+        };
+        if chapters
+            .last()
+            .is_none_or(|(chapter, _)| *chapter != verse.chapter)
+        {
+            chapters.push((&verse.chapter, vec![]));
+        };
+        if let Some((_, chapter_verses)) = chapters.last_mut() {
+            chapter_verses.push(verse);
+        };
+    }
+    res
+}
@@ -0,0 +1,85 @@
+//! Export of the distances between manuscripts over a verse range
+
+use std::sync::Arc;
+
+use axum::{extract::Query, http::header, response::IntoResponse, Extension, Json};
+use critic_shared::collation::Orthography;
+use serde::Deserialize;
+
+use super::attachment;
+use crate::{
+    collation::{
+        collate_range,
+        distance::{distance_csv, distance_matrix, neighbor_joining},
+    },
+    config::Config,
+};
+
+/// The format of the distance export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceFormat {
+    /// the distance matrix as CSV
+    #[default]
+    Csv,
+    /// the distance matrix with the counts it is computed from as JSON
+    Json,
+    /// a neighbor-joining tree in Newick format
+    Newick,
+}
+
+/// Query parameters for the distance export
+#[derive(Debug, Deserialize)]
+pub struct DistanceExportQuery {
+    /// shorthand of the versification scheme `start` and `end` are given in
+    scheme: String,
+    /// the first verse to compare
+    start: String,
+    /// the last verse to compare
+    end: String,
+    /// the spelling differences to ignore
+    #[serde(default)]
+    orthography: Orthography,
+    #[serde(default)]
+    format: DistanceFormat,
+}
+
+/// Export the distances between all manuscripts covering a verse range
+pub async fn distance_export(
+    Extension(config): Extension<Arc<Config>>,
+    Query(query): Query<DistanceExportQuery>,
+) -> impl IntoResponse {
+    let table = match collate_range(&config, &query.scheme, &query.start, &query.end, None).await {
+        Ok(table) => table,
+        Err(e) => return e.into_response(),
+    };
+    let matrix = distance_matrix(&table, query.orthography);
+    let disposition = |extension: &str| {
+        (
+            header::CONTENT_DISPOSITION,
+            attachment(&format!(
+                "distances_{}-{}.{extension}",
+                query.start, query.end
+            )),
+        )
+    };
+    match query.format {
+        DistanceFormat::Csv => (
+            [
+                (header::CONTENT_TYPE, "text/csv".to_string()),
+                disposition("csv"),
+            ],
+            distance_csv(&matrix),
+        )
+            .into_response(),
+        DistanceFormat::Json => ([disposition("json")], Json(matrix)).into_response(),
+        DistanceFormat::Newick => (
+            [
+                (header::CONTENT_TYPE, "text/plain".to_string()),
+                disposition("nwk"),
+            ],
+            neighbor_joining(&matrix),
+        )
+            .into_response(),
+    }
+}
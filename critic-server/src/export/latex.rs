@@ -0,0 +1,221 @@
+//! LaTeX export for print editions, following the conventions of reledmac
+//!
+//! The base witness is printed as main text, the readings of the other witnesses go into the
+//! first apparatus (`\Afootnote`). Lacunae, corrections and uncertain readings are marked with
+//! macros defined in the preamble, so that they can be restyled for each edition.
+//!
+//! The text is set right-to-left in Hebrew with polyglossia, so the document has to be compiled
+//! with XeLaTeX and needs the Hebrew font named in the preamble.
+
+use std::sync::Arc;
+
+use axum::{extract::Query, http::header, response::IntoResponse, Extension};
+use serde::Deserialize;
+
+use super::{attachment, ExportError};
+use crate::{
+    collation::{
+        collate, load_anchor_map, load_witness, AnchorMap, CollatedVerse, Token, TokenKind,
+    },
+    config::Config,
+    db::DBError,
+};
+
+/// The preamble with reledmac, Hebrew right-to-left typesetting and the macros for our sigla
+const PREAMBLE: &str = r"% compile with xelatex
+\documentclass{article}
+\usepackage{fontspec}
+% reledmac has to be loaded before polyglossia, which loads bidi for right-to-left text
+\usepackage{reledmac}
+\usepackage{polyglossia}
+\setmainlanguage{hebrew}
+\setotherlanguage{english}
+% replace with any installed font covering Hebrew letters and points
+\newfontfamily\hebrewfont[Script=Hebrew]{Noto Serif Hebrew}
+% sigla and editorial remarks are set left-to-right with \textenglish
+% a lacuna in the manuscript
+\newcommand{\criticlac}{\textenglish{\textit{lac.}}}
+% an uncertain reading
+\newcommand{\criticunc}[1]{#1\textsuperscript{?}}
+% a corrected reading: {text after correction}{text before correction}
+\newcommand{\criticcorr}[2]{#1\textsuperscript{c}}
+\begin{document}
+\beginnumbering
+";
+
+const POSTAMBLE: &str = r"\endnumbering
+\end{document}
+";
+
+/// Escape the characters with special meaning in LaTeX
+pub fn escape_latex(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => res.push_str(r"\textbackslash{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                res.push('\\');
+                res.push(c);
+            }
+            '^' => res.push_str(r"\textasciicircum{}"),
+            '~' => res.push_str(r"\textasciitilde{}"),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+fn token_latex(token: &Token) -> String {
+    match &token.kind {
+        TokenKind::Word => escape_latex(&token.text),
+        TokenKind::Uncertain => format!(r"\criticunc{{{}}}", escape_latex(&token.text)),
+        TokenKind::Corrected { original } => format!(
+            r"\criticcorr{{{}}}{{{}}}",
+            escape_latex(&token.text),
+            escape_latex(original)
+        ),
+        TokenKind::Lacuna => r"\criticlac{}".to_string(),
+    }
+}
+
+fn tokens_latex(tokens: &[Token]) -> String {
+    tokens.iter().map(token_latex).collect::<Vec<_>>().join(" ")
+}
+
+fn sigla_latex(sigla: &[String]) -> String {
+    format!(
+        r"\textenglish{{\textit{{{}}}}}",
+        sigla
+            .iter()
+            .map(|s| escape_latex(s))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// A single verse as a reledmac paragraph
+fn verse_latex(verse: &CollatedVerse, label: &str) -> String {
+    let mut parts = vec![];
+    let label = format!(r"\textenglish{{\textbf{{{}}}}}", escape_latex(label));
+    if verse.missing.is_empty() {
+        parts.push(label);
+    } else {
+        parts.push(format!(
+            r"\edtext{{{label}}}{{\Afootnote{{\textenglish{{\textit{{deest}}}} {}}}}}",
+            sigla_latex(&verse.missing)
+        ));
+    };
+
+    let mut idx = 0;
+    for unit in &verse.units {
+        if unit.start > idx {
+            parts.push(tokens_latex(&verse.base[idx..unit.start]));
+        };
+        let readings = unit
+            .readings
+            .iter()
+            .map(|reading| {
+                let text = if reading.tokens.is_empty() {
+                    r"\textenglish{\textit{om.}}".to_string()
+                } else {
+                    tokens_latex(&reading.tokens)
+                };
+                format!("{text} {}", sigla_latex(&reading.sigla))
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        let lemma = tokens_latex(&verse.base[unit.start..unit.end]);
+        if lemma.is_empty() {
+            // the base witness has no text here at all
+            parts.push(format!(
+                r"\edtext{{}}{{\lemma{{\textenglish{{\textit{{add.}}}}}}\Afootnote{{{readings}}}}}"
+            ));
+        } else {
+            parts.push(format!(r"\edtext{{{lemma}}}{{\Afootnote{{{readings}}}}}"));
+        };
+        idx = unit.end;
+    }
+    if idx < verse.base.len() {
+        parts.push(tokens_latex(&verse.base[idx..]));
+    };
+    format!("\\pstart\n{}\n\\pend\n", parts.join(" "))
+}
+
+/// Render collated verses as a LaTeX document using reledmac
+///
+/// Verses are labeled with their verse number in the scheme `shorthand`.
+pub fn reledmac(verses: &[CollatedVerse], anchors: &AnchorMap, shorthand: &str) -> String {
+    let mut res = PREAMBLE.to_string();
+    for verse in verses {
+        let label = anchors
+            .verse_nr(verse.verse_id, shorthand)
+            .map_or(verse.verse_id.to_string(), str::to_string);
+        res.push_str(&verse_latex(verse, &label));
+    }
+    res.push_str(POSTAMBLE);
+    res
+}
+
+/// Query parameters for the LaTeX export
+#[derive(Debug, Deserialize)]
+pub struct LatexExportQuery {
+    /// the manuscript used as main text
+    base: String,
+    /// the other manuscripts to collate, separated by commas
+    witnesses: String,
+    /// shorthand of the versification scheme `start` and `end` are given in
+    scheme: String,
+    /// the first verse to export
+    start: String,
+    /// the last verse to export
+    end: String,
+}
+
+async fn latex_document(config: &Config, query: &LatexExportQuery) -> Result<String, ExportError> {
+    let anchors = load_anchor_map(config).await?;
+    let verse_id = |verse_nr: &str| {
+        anchors
+            .verse_id_by_number(&query.scheme, verse_nr)
+            .ok_or(ExportError::DB(DBError::VerseDoesNotExist(
+                verse_nr.to_string(),
+            )))
+    };
+    let (verse_start, verse_end) = (verse_id(&query.start)?, verse_id(&query.end)?);
+
+    let base = load_witness(config, &query.base, &anchors).await?;
+    let mut witnesses = vec![];
+    for msname in query
+        .witnesses
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty() && *name != query.base)
+    {
+        witnesses.push(load_witness(config, msname, &anchors).await?);
+    }
+    let collated = collate(&base, &witnesses, verse_start, verse_end);
+    Ok(reledmac(&collated, &anchors, &query.scheme))
+}
+
+/// Export a verse range of collated witnesses as LaTeX for reledmac
+pub async fn latex_export(
+    Extension(config): Extension<Arc<Config>>,
+    Query(query): Query<LatexExportQuery>,
+) -> impl IntoResponse {
+    match latex_document(&config, &query).await {
+        Ok(latex) => (
+            [
+                (header::CONTENT_TYPE, "application/x-tex".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    attachment(&format!("{}_{}-{}.tex", query.base, query.start, query.end)),
+                ),
+            ],
+            latex,
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test;
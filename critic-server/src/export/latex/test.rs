@@ -0,0 +1,160 @@
+//! Tests for the LaTeX export
+
+use critic_shared::collation::{Token, TokenKind};
+
+use super::{escape_latex, reledmac, verse_latex, POSTAMBLE, PREAMBLE};
+use crate::{
+    collation::{AnchorMap, CollatedVerse, Reading, VariantUnit},
+    db::VerseMapEntry,
+};
+
+fn word(text: &str) -> Token {
+    Token {
+        text: text.to_string(),
+        kind: TokenKind::Word,
+    }
+}
+
+fn words(text: &str) -> Vec<Token> {
+    text.split_whitespace().map(word).collect()
+}
+
+fn reading(text: &str, sigla: &[&str]) -> Reading {
+    Reading {
+        tokens: words(text),
+        sigla: sigla.iter().map(|s| s.to_string()).collect(),
+    }
+}
+
+fn verse(base: &str, units: Vec<VariantUnit>, missing: &[&str]) -> CollatedVerse {
+    CollatedVerse {
+        verse_id: 1,
+        base: words(base),
+        units,
+        missing: missing.iter().map(|s| s.to_string()).collect(),
+    }
+}
+
+#[test]
+fn special_characters_are_escaped() {
+    assert_eq!(escape_latex("plain בראשית"), "plain בראשית");
+    assert_eq!(
+        escape_latex(r"50% of $x_1 & {y} #2"),
+        r"50\% of \$x\_1 \& \{y\} \#2"
+    );
+    assert_eq!(
+        escape_latex(r"a\b^c~d"),
+        r"a\textbackslash{}b\textasciicircum{}c\textasciitilde{}d"
+    );
+}
+
+#[test]
+fn verses_without_variants_are_plain_paragraphs() {
+    assert_eq!(
+        verse_latex(&verse("בראשית ברא אלהים", vec![], &[]), "Gen 1:1"),
+        "\\pstart\n\\textenglish{\\textbf{Gen 1:1}} בראשית ברא אלהים\n\\pend\n"
+    );
+}
+
+#[test]
+fn variants_go_into_the_first_apparatus() {
+    let latex = verse_latex(
+        &verse(
+            "בראשית ברא אלהים",
+            vec![VariantUnit {
+                start: 1,
+                end: 2,
+                readings: vec![reading("עשה", &["B", "C"])],
+            }],
+            &[],
+        ),
+        "Gen 1:1",
+    );
+    assert_eq!(
+        latex,
+        "\\pstart\n\\textenglish{\\textbf{Gen 1:1}} בראשית \
+         \\edtext{ברא}{\\Afootnote{עשה \\textenglish{\\textit{B, C}}}} אלהים\n\\pend\n"
+    );
+}
+
+#[test]
+fn omissions_and_additions_are_named() {
+    let omission = verse_latex(
+        &verse(
+            "בראשית ברא",
+            vec![VariantUnit {
+                start: 1,
+                end: 2,
+                readings: vec![reading("", &["B"])],
+            }],
+            &[],
+        ),
+        "1",
+    );
+    assert!(omission.contains(r"\edtext{ברא}{\Afootnote{\textenglish{\textit{om.}} "));
+
+    let addition = verse_latex(
+        &verse(
+            "בראשית",
+            vec![VariantUnit {
+                start: 1,
+                end: 1,
+                readings: vec![reading("ברא", &["B"])],
+            }],
+            &[],
+        ),
+        "1",
+    );
+    assert!(addition.contains(
+        r"\edtext{}{\lemma{\textenglish{\textit{add.}}}\Afootnote{ברא \textenglish{\textit{B}}}}"
+    ));
+}
+
+#[test]
+fn missing_witnesses_are_noted_at_the_label() {
+    let latex = verse_latex(&verse("בראשית", vec![], &["B", "C_1"]), "Gen 1:1");
+    assert!(latex.starts_with(
+        r"\pstart
+\edtext{\textenglish{\textbf{Gen 1:1}}}{\Afootnote{\textenglish{\textit{deest}} \textenglish{\textit{B, C\_1}}}}"
+    ));
+}
+
+#[test]
+fn special_tokens_use_the_preamble_macros() {
+    let mut collated = verse("", vec![], &[]);
+    collated.base = vec![
+        Token {
+            text: "ברא".to_string(),
+            kind: TokenKind::Uncertain,
+        },
+        Token {
+            text: "אלהים".to_string(),
+            kind: TokenKind::Corrected {
+                original: "אלוהים".to_string(),
+            },
+        },
+        Token {
+            text: String::new(),
+            kind: TokenKind::Lacuna,
+        },
+    ];
+    let latex = verse_latex(&collated, "1");
+    assert!(latex.contains(r"\criticunc{ברא} \criticcorr{אלהים}{אלוהים} \criticlac{}"));
+}
+
+#[test]
+fn documents_are_labeled_in_the_requested_scheme() {
+    let anchors = AnchorMap::new(vec![VerseMapEntry {
+        verse_id: 1,
+        shorthand: "P".to_string(),
+        verse_nr: "Gen 1:1".to_string(),
+    }]);
+    let mut second = verse("ויאמר", vec![], &[]);
+    second.verse_id = 2;
+    let document = reledmac(&[verse("בראשית", vec![], &[]), second], &anchors, "P");
+    assert!(document.starts_with(PREAMBLE));
+    assert!(document.ends_with(POSTAMBLE));
+    assert!(document.contains(r"\textenglish{\textbf{Gen 1:1}} בראשית"));
+    // verses without a number in the scheme fall back to their id
+    assert!(document.contains(r"\textenglish{\textbf{2}} ויאמר"));
+}
@@ -0,0 +1,292 @@
+//! Exporting transcriptions into other formats
+//!
+//! All exports work on the streamed representation of the stored TEI files.
+
+use axum::response::{IntoResponse, Response};
+use critic_shared::{ManuscriptMeta, PageMeta};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::{
+    config::Config,
+    db::{get_manuscript, get_page, get_transcriptions, DBError},
+    tei::{read_tei_file, read_transcription, reconciled_path, TeiError},
+};
+
+pub mod apparatus;
+pub mod bible;
+pub mod distance;
+pub mod latex;
+pub mod osis;
+pub mod tei;
+pub mod text;
+pub mod usfm;
+
+/// The router handling all exports
+pub fn export_router() -> axum::Router {
+    axum::Router::new()
+        .route(
+            &format!(
+                "{}/{{msname}}",
+                critic_shared::urls::TEXT_EXPORT_API_ENDPOINT
+            ),
+            axum::routing::get(text::manuscript_text_export),
+        )
+        .route(
+            &format!(
+                "{}/{{msname}}/{{pagename}}",
+                critic_shared::urls::TEXT_EXPORT_API_ENDPOINT
+            ),
+            axum::routing::get(text::page_text_export),
+        )
+        .route(
+            &format!(
+                "{}/{{msname}}",
+                critic_shared::urls::TEI_EXPORT_API_ENDPOINT
+            ),
+            axum::routing::get(tei::manuscript_tei_export),
+        )
+        .route(
+            critic_shared::urls::LATEX_EXPORT_API_ENDPOINT,
+            axum::routing::get(latex::latex_export),
+        )
+        .route(
+            critic_shared::urls::APPARATUS_EXPORT_API_ENDPOINT,
+            axum::routing::get(apparatus::apparatus_export),
+        )
+        .route(
+            critic_shared::urls::DISTANCE_EXPORT_API_ENDPOINT,
+            axum::routing::get(distance::distance_export),
+        )
+        .route(
+            &format!(
+                "{}/{{msname}}",
+                critic_shared::urls::OSIS_EXPORT_API_ENDPOINT
+            ),
+            axum::routing::get(osis::osis_export),
+        )
+        .route(
+            &format!(
+                "{}/{{msname}}",
+                critic_shared::urls::USFM_EXPORT_API_ENDPOINT
+            ),
+            axum::routing::get(usfm::usfm_export),
+        )
+}
+
+/// The value of a Content-Disposition header offering a download as `filename`
+///
+/// Following RFC 6266, the name is given percent-encoded as `filename*` and with all characters
+/// that cannot be sent as-is replaced by `_` as `filename` for older clients.
+pub fn attachment(filename: &str) -> String {
+    let fallback = filename
+        .chars()
+        .map(|c| {
+            if c == ' ' || (c.is_ascii_graphic() && !matches!(c, '"' | '\\')) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!(
+        "attachment; filename=\"{fallback}\"; filename*=UTF-8''{}",
+        urlencoding::encode(filename)
+    )
+}
+
+/// Problems collecting the transcriptions to export
+#[derive(Debug)]
+pub enum ExportError {
+    DB(DBError),
+    Tei(TeiError),
+    /// The page (msname, pagename) has no transcription that can be exported
+    NoTranscription(String, String),
+    /// The page (msname, pagename) has several published transcriptions and none was chosen
+    AmbiguousTranscription(String, String, Vec<String>),
+    /// Unable to write the exported document
+    Write(std::io::Error),
+    /// The verse number cannot be read as `<book> <chapter>:<verse>`
+    UnreadableVerseNumber(String),
+    /// There is no book with this name
+    UnknownBook(String),
+    /// The export would contain these books, but the format holds a single book per document
+    SeveralBooks(Vec<String>),
+}
+impl core::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::DB(e) => {
+                write!(f, "{e}")
+            }
+            Self::Tei(e) => {
+                write!(f, "{e}")
+            }
+            Self::NoTranscription(msname, pagename) => {
+                write!(
+                    f,
+                    "There is no accepted or published transcription of {msname}/{pagename} to export."
+                )
+            }
+            Self::AmbiguousTranscription(msname, pagename, usernames) => {
+                write!(
+                    f,
+                    "{msname}/{pagename} has several published transcriptions. Choose one of these users: {}",
+                    usernames.join(", ")
+                )
+            }
+            Self::Write(e) => {
+                write!(f, "Unable to write the exported document: {e}")
+            }
+            Self::UnreadableVerseNumber(verse_nr) => {
+                write!(
+                    f,
+                    "The verse number {verse_nr} cannot be read as <book> <chapter>:<verse>."
+                )
+            }
+            Self::UnknownBook(name) => {
+                write!(f, "There is no book called {name}.")
+            }
+            Self::SeveralBooks(books) => {
+                write!(
+                    f,
+                    "The export contains several books ({}), but a document can only hold one. Choose one with the book option.",
+                    books.join(", ")
+                )
+            }
+        }
+    }
+}
+impl core::error::Error for ExportError {}
+impl IntoResponse for ExportError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            Self::DB(DBError::ManuscriptDoesNotExist(_))
+            | Self::DB(DBError::PageDoesNotExist(_, _))
+            | Self::NoTranscription(_, _) => StatusCode::NOT_FOUND,
+            Self::AmbiguousTranscription(_, _, _)
+            | Self::DB(DBError::VerseDoesNotExist(_))
+            | Self::UnreadableVerseNumber(_)
+            | Self::UnknownBook(_)
+            | Self::SeveralBooks(_) => StatusCode::BAD_REQUEST,
+            Self::DB(_) | Self::Tei(_) | Self::Write(_) => {
+                tracing::warn!("Failed to export transcriptions: {self}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Query parameters choosing the transcription to export
+#[derive(Debug, Deserialize, Default)]
+pub struct TranscriptionChoice {
+    /// export the published transcription of this user
+    ///
+    /// If this is None, the accepted (reconciled) transcription is exported. Pages that do not
+    /// have one yet must have exactly one published transcription.
+    pub username: Option<String>,
+}
+
+/// The username of the published transcription of a page to export
+///
+/// Returns None if there is no fitting transcription.
+async fn chosen_username(
+    config: &Config,
+    msname: &str,
+    page: &PageMeta,
+    choice: &TranscriptionChoice,
+) -> Result<Option<String>, ExportError> {
+    let published = get_transcriptions(&config.db, page.id)
+        .await
+        .map_err(ExportError::DB)?
+        .into_iter()
+        .filter(|t| t.published)
+        .map(|t| t.username)
+        .collect::<Vec<_>>();
+    match &choice.username {
+        Some(username) => Ok(published.into_iter().find(|u| u == username)),
+        None => match published.len() {
+            0 => Ok(None),
+            1 => Ok(published.into_iter().next()),
+            _ => Err(ExportError::AmbiguousTranscription(
+                msname.to_string(),
+                page.name.clone(),
+                published,
+            )),
+        },
+    }
+}
+
+/// The chosen transcription of a page, if there is one
+///
+/// Without a chosen user, the accepted transcription is preferred over published ones.
+pub(crate) async fn load_page_transcription(
+    config: &Config,
+    msname: &str,
+    page: &PageMeta,
+    choice: &TranscriptionChoice,
+) -> Result<Option<critic_format::streamed::Manuscript>, ExportError> {
+    if choice.username.is_none() {
+        let path = reconciled_path(&config.data_directory, msname, &page.name);
+        if std::path::Path::new(&path).exists() {
+            return read_tei_file(&path).map(Some).map_err(ExportError::Tei);
+        };
+    };
+    let Some(username) = chosen_username(config, msname, page, choice).await? else {
+        return Ok(None);
+    };
+    read_transcription(&config.data_directory, msname, &page.name, &username)
+        .map(Some)
+        .map_err(ExportError::Tei)
+}
+
+/// The chosen transcription of a single page
+pub async fn page_transcription(
+    config: &Config,
+    msname: &str,
+    pagename: &str,
+    choice: &TranscriptionChoice,
+) -> Result<critic_format::streamed::Manuscript, ExportError> {
+    let page = get_page(&config.db, msname, pagename)
+        .await
+        .map_err(ExportError::DB)?;
+    load_page_transcription(config, msname, &page, choice)
+        .await?
+        .ok_or(ExportError::NoTranscription(
+            msname.to_string(),
+            pagename.to_string(),
+        ))
+}
+
+/// The chosen transcriptions of all pages of a manuscript, in page order
+///
+/// Pages without a fitting transcription are left out.
+pub async fn manuscript_transcriptions(
+    config: &Config,
+    msname: &str,
+    choice: &TranscriptionChoice,
+) -> Result<
+    (
+        ManuscriptMeta,
+        Vec<(PageMeta, critic_format::streamed::Manuscript)>,
+    ),
+    ExportError,
+> {
+    let manuscript = get_manuscript(&config.db, msname)
+        .await
+        .map_err(ExportError::DB)?;
+    let mut pages = manuscript.pages;
+    // pages do not have an explicit position, they are ordered by upload
+    pages.sort_by_key(|page| page.id);
+    let mut res = vec![];
+    for page in pages {
+        if let Some(transcription) = load_page_transcription(config, msname, &page, choice).await? {
+            res.push((page, transcription));
+        };
+    }
+    Ok((manuscript.meta, res))
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,159 @@
+//! OSIS export for SWORD-based tools
+//!
+//! Every book becomes a `<div type="book">` containing `<chapter>` and `<verse>` elements with
+//! osisIDs like `Gen.1.1`. See [`super::bible`] for how the verses are found.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query},
+    http::header,
+    response::IntoResponse,
+    Extension,
+};
+use critic_shared::ManuscriptMeta;
+use quick_xml::{
+    events::{BytesDecl, BytesStart, BytesText, Event},
+    Writer,
+};
+
+use super::{
+    attachment,
+    bible::{group_verses, manuscript_verses, BibleExportOptions, BibleVerse, Segment},
+    tei::{end, start, start_with_attributes, text_element, XmlWriter},
+    ExportError, TranscriptionChoice,
+};
+use crate::config::Config;
+
+const OSIS_NAMESPACE: &str = "http://www.bibletechnologies.net/2003/OSIS/namespace";
+
+fn text(writer: &mut XmlWriter, text: &str) -> Result<(), ExportError> {
+    writer
+        .write_event(Event::Text(BytesText::new(text)))
+        .map_err(ExportError::Write)
+}
+
+/// Write `<name attributes...>content</name>`
+fn element_with_text(
+    writer: &mut XmlWriter,
+    name: &str,
+    attributes: &[(&str, &str)],
+    content: &str,
+) -> Result<(), ExportError> {
+    start_with_attributes(writer, name, attributes)?;
+    text(writer, content)?;
+    end(writer, name)
+}
+
+fn write_segment(writer: &mut XmlWriter, segment: &Segment) -> Result<(), ExportError> {
+    match segment {
+        Segment::Text(content) => text(writer, content),
+        Segment::Abbreviation { surface, expansion } => element_with_text(
+            writer,
+            "abbr",
+            &[("expansion", expansion.as_str())],
+            surface,
+        ),
+        Segment::Uncertain(content) => {
+            element_with_text(writer, "seg", &[("type", "x-uncertain")], content)
+        }
+        Segment::Correction {
+            text: content,
+            original,
+        } => {
+            text(writer, content)?;
+            element_with_text(
+                writer,
+                "note",
+                &[("type", "variant")],
+                &format!("before correction: {original}"),
+            )
+        }
+        Segment::Lacuna(extent) => element_with_text(
+            writer,
+            "note",
+            &[("type", "x-lacuna")],
+            &format!("lacuna: {extent}"),
+        ),
+    }
+}
+
+fn write_verse(writer: &mut XmlWriter, verse: &BibleVerse) -> Result<(), ExportError> {
+    let osis_id = format!("{}.{}.{}", verse.book.osis, verse.chapter, verse.verse);
+    start_with_attributes(writer, "verse", &[("osisID", osis_id.as_str())])?;
+    for segment in &verse.segments {
+        write_segment(writer, segment)?;
+    }
+    end(writer, "verse")
+}
+
+/// Build an OSIS document from the verses of a manuscript
+pub fn osis_document(meta: &ManuscriptMeta, verses: &[BibleVerse]) -> Result<String, ExportError> {
+    // OSIS work names must not contain spaces
+    let work = meta.title.replace(char::is_whitespace, "_");
+    let mut writer = Writer::new(Vec::new());
+    writer
+        .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+        .map_err(ExportError::Write)?;
+    start_with_attributes(&mut writer, "osis", &[("xmlns", OSIS_NAMESPACE)])?;
+    start_with_attributes(
+        &mut writer,
+        "osisText",
+        &[("osisIDWork", work.as_str()), ("osisRefWork", "Bible")],
+    )?;
+
+    start(&mut writer, "header")?;
+    start_with_attributes(&mut writer, "work", &[("osisWork", work.as_str())])?;
+    text_element(&mut writer, "title", &meta.title)?;
+    text_element(&mut writer, "refSystem", "Bible")?;
+    end(&mut writer, "work")?;
+    end(&mut writer, "header")?;
+
+    for (book, chapters) in group_verses(verses) {
+        start_with_attributes(
+            &mut writer,
+            "div",
+            &[("type", "book"), ("osisID", book.osis)],
+        )?;
+        for (chapter, chapter_verses) in chapters {
+            let osis_id = format!("{}.{chapter}", book.osis);
+            start_with_attributes(&mut writer, "chapter", &[("osisID", osis_id.as_str())])?;
+            for verse in chapter_verses {
+                write_verse(&mut writer, verse)?;
+            }
+            end(&mut writer, "chapter")?;
+        }
+        end(&mut writer, "div")?;
+    }
+
+    end(&mut writer, "osisText")?;
+    end(&mut writer, "osis")?;
+    Ok(String::from_utf8(writer.into_inner()).expect("only strings are written"))
+}
+
+/// Export the accepted text of a manuscript as OSIS
+pub async fn osis_export(
+    Extension(config): Extension<Arc<Config>>,
+    Path(msname): Path<String>,
+    Query(choice): Query<TranscriptionChoice>,
+    Query(options): Query<BibleExportOptions>,
+) -> impl IntoResponse {
+    let osis = match manuscript_verses(&config, &msname, &choice, &options).await {
+        Ok((meta, verses)) => osis_document(&meta, &verses),
+        Err(e) => Err(e),
+    };
+    match osis {
+        Ok(osis) => (
+            [
+                (header::CONTENT_TYPE, "application/xml".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    attachment(&format!("{msname}.osis.xml")),
+                ),
+            ],
+            osis,
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
@@ -0,0 +1,242 @@
+//! Whole-manuscript TEI export
+//!
+//! Joins the transcriptions of all pages of a manuscript into a single TEI document. Every page
+//! starts with a `<pb>` carrying the page name and a link to the facsimile, the `teiHeader` is
+//! built from the manuscript metadata.
+
+use std::{path::Path as FilePath, sync::Arc};
+
+use axum::{
+    extract::{Path, Query},
+    http::header,
+    response::IntoResponse,
+    Extension,
+};
+use critic_shared::{
+    urls::{IMAGE_BASE_LOCATION, STATIC_BASE_URL},
+    ManuscriptMeta, PageMeta,
+};
+use quick_xml::{
+    events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event},
+    Reader, Writer,
+};
+
+use super::{attachment, manuscript_transcriptions, ExportError, TranscriptionChoice};
+use crate::{config::Config, tei::write_tei};
+
+pub(super) const TEI_NAMESPACE: &str = "http://www.tei-c.org/ns/1.0";
+
+pub(super) type XmlWriter = Writer<Vec<u8>>;
+
+pub(super) fn start(writer: &mut XmlWriter, name: &str) -> Result<(), ExportError> {
+    writer
+        .write_event(Event::Start(BytesStart::new(name)))
+        .map_err(ExportError::Write)
+}
+
+pub(super) fn end(writer: &mut XmlWriter, name: &str) -> Result<(), ExportError> {
+    writer
+        .write_event(Event::End(BytesEnd::new(name)))
+        .map_err(ExportError::Write)
+}
+
+pub(super) fn start_with_attributes(
+    writer: &mut XmlWriter,
+    name: &str,
+    attributes: &[(&str, &str)],
+) -> Result<(), ExportError> {
+    writer
+        .write_event(Event::Start(
+            BytesStart::new(name).with_attributes(attributes.iter().copied()),
+        ))
+        .map_err(ExportError::Write)
+}
+
+/// Write `<name>text</name>`
+pub(super) fn text_element(
+    writer: &mut XmlWriter,
+    name: &str,
+    text: &str,
+) -> Result<(), ExportError> {
+    start(writer, name)?;
+    writer
+        .write_event(Event::Text(BytesText::new(text)))
+        .map_err(ExportError::Write)?;
+    end(writer, name)
+}
+
+/// Write `<name><p>text</p></name>` if `text` is set
+fn paragraph_element(
+    writer: &mut XmlWriter,
+    name: &str,
+    text: &Option<String>,
+) -> Result<(), ExportError> {
+    if let Some(text) = text {
+        start(writer, name)?;
+        text_element(writer, "p", text)?;
+        end(writer, name)?;
+    };
+    Ok(())
+}
+
+/// Write the teiHeader for the manuscript
+fn write_header(writer: &mut XmlWriter, meta: &ManuscriptMeta) -> Result<(), ExportError> {
+    start(writer, "teiHeader")?;
+    start(writer, "fileDesc")?;
+
+    start(writer, "titleStmt")?;
+    text_element(writer, "title", &meta.title)?;
+    end(writer, "titleStmt")?;
+
+    start(writer, "publicationStmt")?;
+    text_element(writer, "p", "Exported from critic.")?;
+    end(writer, "publicationStmt")?;
+
+    start(writer, "sourceDesc")?;
+    start(writer, "msDesc")?;
+    start(writer, "msIdentifier")?;
+    if let Some(institution) = &meta.institution {
+        text_element(writer, "institution", institution)?;
+    };
+    if let Some(collection) = &meta.collection {
+        text_element(writer, "collection", collection)?;
+    };
+    text_element(writer, "idno", &meta.title)?;
+    end(writer, "msIdentifier")?;
+    if meta.hand_desc.is_some() || meta.script_desc.is_some() {
+        start(writer, "physDesc")?;
+        paragraph_element(writer, "handDesc", &meta.hand_desc)?;
+        paragraph_element(writer, "scriptDesc", &meta.script_desc)?;
+        end(writer, "physDesc")?;
+    };
+    end(writer, "msDesc")?;
+    end(writer, "sourceDesc")?;
+
+    end(writer, "fileDesc")?;
+    end(writer, "teiHeader")
+}
+
+/// Copy everything inside the `<body>` of a TEI document to `writer`
+fn copy_body(writer: &mut XmlWriter, tei: &str) -> Result<(), ExportError> {
+    let mut reader = Reader::from_str(tei);
+    // depth of the current element inside body, None outside of body
+    let mut depth: Option<usize> = None;
+    loop {
+        let event = reader.read_event().map_err(|e| {
+            ExportError::Write(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        })?;
+        match (&event, depth) {
+            (Event::Eof, _) => return Ok(()),
+            (Event::Start(e), None) => {
+                if e.local_name().as_ref() == b"body" {
+                    depth = Some(0);
+                };
+            }
+            (Event::End(_), Some(0)) => depth = None,
+            (Event::Start(_), Some(d)) => {
+                depth = Some(d + 1);
+                writer.write_event(event).map_err(ExportError::Write)?;
+            }
+            (Event::End(_), Some(d)) => {
+                depth = Some(d - 1);
+                writer.write_event(event).map_err(ExportError::Write)?;
+            }
+            (_, Some(_)) => writer.write_event(event).map_err(ExportError::Write)?,
+            (_, None) => {}
+        };
+    }
+}
+
+/// The names the original image of a page is stored under, before and after minification
+const ORIGINAL_IMAGE_NAMES: [&str; 2] = ["original.webp", "original"];
+
+/// The absolute url of the original image of a page, None if the page has no image
+pub fn facsimile_url(
+    public_url: &str,
+    data_directory: &str,
+    msname: &str,
+    pagename: &str,
+) -> Option<String> {
+    let directory = format!("{data_directory}{IMAGE_BASE_LOCATION}/{msname}/{pagename}");
+    let name = ORIGINAL_IMAGE_NAMES
+        .into_iter()
+        .find(|name| FilePath::new(&format!("{directory}/{name}")).is_file())?;
+    Some(format!(
+        "{public_url}{STATIC_BASE_URL}{IMAGE_BASE_LOCATION}/{}/{}/{name}",
+        urlencoding::encode(msname),
+        urlencoding::encode(pagename)
+    ))
+}
+
+/// Build a single TEI document from the transcriptions of the pages of a manuscript
+///
+/// `facsimile` gives the link to the image of a page, see [`facsimile_url`].
+pub fn manuscript_tei(
+    meta: &ManuscriptMeta,
+    pages: Vec<(PageMeta, critic_format::streamed::Manuscript)>,
+    facsimile: impl Fn(&PageMeta) -> Option<String>,
+) -> Result<String, ExportError> {
+    let mut writer = Writer::new(Vec::new());
+    writer
+        .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+        .map_err(ExportError::Write)?;
+    writer
+        .write_event(Event::Start(
+            BytesStart::new("TEI").with_attributes([("xmlns", TEI_NAMESPACE)]),
+        ))
+        .map_err(ExportError::Write)?;
+    write_header(&mut writer, meta)?;
+    start(&mut writer, "text")?;
+    start(&mut writer, "body")?;
+    for (page, transcription) in pages {
+        let mut pb = BytesStart::new("pb").with_attributes([("n", page.name.as_str())]);
+        if let Some(url) = facsimile(&page) {
+            pb.push_attribute(("facs", url.as_str()));
+        };
+        writer
+            .write_event(Event::Empty(pb))
+            .map_err(ExportError::Write)?;
+        let tei = write_tei(transcription).map_err(ExportError::Tei)?;
+        copy_body(&mut writer, &tei)?;
+    }
+    end(&mut writer, "body")?;
+    end(&mut writer, "text")?;
+    end(&mut writer, "TEI")?;
+    Ok(String::from_utf8(writer.into_inner()).expect("only strings are written"))
+}
+
+/// Export all pages of a manuscript as a single TEI document
+pub async fn manuscript_tei_export(
+    Extension(config): Extension<Arc<Config>>,
+    Path(msname): Path<String>,
+    Query(choice): Query<TranscriptionChoice>,
+) -> impl IntoResponse {
+    let tei = match manuscript_transcriptions(&config, &msname, &choice).await {
+        Ok((meta, pages)) => manuscript_tei(&meta, pages, |page| {
+            facsimile_url(
+                &config.public_url,
+                &config.data_directory,
+                &msname,
+                &page.name,
+            )
+        }),
+        Err(e) => Err(e),
+    };
+    match tei {
+        Ok(tei) => (
+            [
+                (header::CONTENT_TYPE, "application/tei+xml".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    attachment(&format!("{msname}.tei.xml")),
+                ),
+            ],
+            tei,
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test;
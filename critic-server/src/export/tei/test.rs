@@ -0,0 +1,137 @@
+//! Tests for the whole-manuscript TEI export
+
+use std::path::PathBuf;
+
+use critic_format::streamed::{Block, Paragraph};
+use critic_shared::{ManuscriptMeta, PageMeta};
+use quick_xml::Writer;
+
+use super::{copy_body, facsimile_url, manuscript_tei};
+
+/// A temporary data directory, removed on drop
+struct TestDir(PathBuf);
+impl TestDir {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("critic-tei-export-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    fn path(&self) -> String {
+        self.0.to_string_lossy().to_string()
+    }
+
+    /// Store an (empty) image file for a page
+    fn add_image(&self, msname: &str, pagename: &str, filename: &str) {
+        let directory = self.0.join("images").join(msname).join(pagename);
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join(filename), b"").unwrap();
+    }
+}
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn meta() -> ManuscriptMeta {
+    ManuscriptMeta {
+        id: 1,
+        title: "Codex A".to_string(),
+        institution: Some("National Library".to_string()),
+        collection: None,
+        hand_desc: Some("One hand".to_string()),
+        script_desc: None,
+    }
+}
+
+fn page(name: &str) -> PageMeta {
+    PageMeta {
+        id: 1,
+        manuscript_id: 1,
+        name: name.to_string(),
+        verse_start: None,
+        verse_end: None,
+    }
+}
+
+fn transcription(text: &str) -> critic_format::streamed::Manuscript {
+    critic_format::streamed::Manuscript {
+        meta: critic_format::normalized::Meta {
+            name: "Codex A".to_string(),
+            page_nr: "1r".to_string(),
+            title: "Codex A".to_string(),
+            institution: None,
+            collection: None,
+            hand_desc: None,
+            script_desc: None,
+        },
+        content: vec![Block::Text(Paragraph {
+            lang: "hbo-Hebr".to_string(),
+            content: text.to_string(),
+        })],
+    }
+}
+
+#[test]
+fn facsimiles_link_to_the_stored_image() {
+    let dir = TestDir::new("facsimile");
+    let url =
+        |pagename: &str| facsimile_url("http://localhost:3000", &dir.path(), "Codex A", pagename);
+    assert_eq!(url("1r"), None);
+
+    // before minification, only the original upload exists
+    dir.add_image("Codex A", "1r", "original");
+    assert_eq!(
+        url("1r").unwrap(),
+        "http://localhost:3000/static/images/Codex%20A/1r/original"
+    );
+
+    dir.add_image("Codex A", "1r", "original.webp");
+    assert_eq!(
+        url("1r").unwrap(),
+        "http://localhost:3000/static/images/Codex%20A/1r/original.webp"
+    );
+}
+
+#[test]
+fn only_the_body_is_copied() {
+    let mut writer = Writer::new(Vec::new());
+    copy_body(
+        &mut writer,
+        r#"<TEI><teiHeader><p>header</p></teiHeader><text><body><p>a <unclear>b</unclear></p><lb/></body></text></TEI>"#,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(writer.into_inner()).unwrap(),
+        "<p>a <unclear>b</unclear></p><lb/>"
+    );
+}
+
+#[test]
+fn pages_start_with_page_breaks() {
+    let tei = manuscript_tei(
+        &meta(),
+        vec![
+            (page("1r"), transcription("בראשית")),
+            (page("1v"), transcription("ברא")),
+        ],
+        |page| (page.name == "1r").then(|| "https://example.org/1r".to_string()),
+    )
+    .unwrap();
+    assert!(tei.contains("<title>Codex A</title>"));
+    assert!(tei.contains(
+        "<msIdentifier><institution>National Library</institution><idno>Codex A</idno></msIdentifier>"
+    ));
+    assert!(tei.contains("<physDesc><handDesc><p>One hand</p></handDesc></physDesc>"));
+
+    let first = tei
+        .find(r#"<pb n="1r" facs="https://example.org/1r"/>"#)
+        .unwrap();
+    let first_text = tei.find("בראשית").unwrap();
+    let second = tei.find(r#"<pb n="1v"/>"#).unwrap();
+    let second_text = tei.rfind("ברא").unwrap();
+    assert!(first < first_text && first_text < second && second < second_text);
+}
@@ -0,0 +1,23 @@
+//! Tests for the helpers shared by all exports
+
+use super::attachment;
+
+#[test]
+fn plain_filenames_are_kept() {
+    assert_eq!(
+        attachment("Codex Leningradensis.tei.xml"),
+        "attachment; filename=\"Codex Leningradensis.tei.xml\"; filename*=UTF-8''Codex%20Leningradensis.tei.xml"
+    );
+}
+
+#[test]
+fn other_characters_are_percent_encoded() {
+    assert_eq!(
+        attachment("כתר.tei.xml"),
+        "attachment; filename=\"___.tei.xml\"; filename*=UTF-8''%D7%9B%D7%AA%D7%A8.tei.xml"
+    );
+    assert_eq!(
+        attachment("a\"b\\c\nd.tex"),
+        "attachment; filename=\"a_b_c_d.tex\"; filename*=UTF-8''a%22b%5Cc%0Ad.tex"
+    );
+}
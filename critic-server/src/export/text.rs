@@ -0,0 +1,171 @@
+//! Plain-text export
+//!
+//! Turns the streamed blocks of a transcription into plain text, following a set of normalization
+//! rules chosen with [`TextExportOptions`].
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query},
+    response::IntoResponse,
+    Extension,
+};
+use critic_format::streamed::{Block, BreakType, Correction};
+use critic_shared::anchor::anchor_verse;
+use serde::Deserialize;
+
+use super::{manuscript_transcriptions, page_transcription, TranscriptionChoice};
+use crate::config::Config;
+
+/// How abbreviations appear in the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbbreviationMode {
+    /// as written in the manuscript
+    #[default]
+    AsWritten,
+    /// expanded
+    Expanded,
+}
+
+/// Which version of a correction appears in the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CorrectionMode {
+    /// the first version, usually the original hand
+    First,
+    /// the last version, i.e. the text after all corrections
+    #[default]
+    Last,
+    /// the version of the hand given in [`TextExportOptions::correction_hand`]
+    ///
+    /// Corrections without a version in this hand use the last version.
+    Hand,
+}
+
+/// How lacunae and spaces appear in the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GapMode {
+    /// leave them out completely
+    Omit,
+    /// a short marker: `[...]` for lacunae, a single space for spaces
+    #[default]
+    Marker,
+    /// a marker containing the extent, e.g. `[lacuna: 3 character]`
+    Extent,
+}
+
+/// The normalization rules for the plain-text export
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+pub struct TextExportOptions {
+    #[serde(default)]
+    pub abbreviations: AbbreviationMode,
+    #[serde(default)]
+    pub correction: CorrectionMode,
+    /// the hand to use when `correction` is [`CorrectionMode::Hand`]
+    pub correction_hand: Option<String>,
+    #[serde(default)]
+    pub lacuna: GapMode,
+    #[serde(default)]
+    pub space: GapMode,
+    /// show verse anchors as references, e.g. `[1:3]`
+    #[serde(default)]
+    pub verse_refs: bool,
+}
+
+/// The content of the version of `correction` chosen in `options`
+fn correction_content<'a>(correction: &'a Correction, options: &TextExportOptions) -> &'a str {
+    let version = match options.correction {
+        CorrectionMode::First => correction.versions.first(),
+        CorrectionMode::Last => correction.versions.last(),
+        CorrectionMode::Hand => correction
+            .versions
+            .iter()
+            .find(|v| v.hand.is_some() && v.hand == options.correction_hand)
+            .or(correction.versions.last()),
+    };
+    version.map_or("", |v| v.content.as_str())
+}
+
+/// Render blocks as plain text
+///
+/// Line breaks become newlines, column breaks empty lines.
+pub fn plain_text(blocks: &[Block], options: &TextExportOptions) -> String {
+    let mut res = String::default();
+    for block in blocks {
+        match block {
+            Block::Text(paragraph) => res.push_str(&paragraph.content),
+            Block::Break(BreakType::Line) => res.push('\n'),
+            Block::Break(_) => res.push_str("\n\n"),
+            Block::Lacuna(lacuna) => match options.lacuna {
+                GapMode::Omit => {}
+                GapMode::Marker => res.push_str("[...]"),
+                GapMode::Extent => {
+                    res.push_str(&format!("[lacuna: {} {}]", lacuna.n, lacuna.unit.name()))
+                }
+            },
+            Block::Space(space) => match options.space {
+                GapMode::Omit => {}
+                GapMode::Marker => res.push(' '),
+                GapMode::Extent => res.push_str(&format!(
+                    "[space: {} {}]",
+                    space.quantity,
+                    space.unit.name()
+                )),
+            },
+            Block::Anchor(anchor) => {
+                if options.verse_refs {
+                    if let Some((_, verse_nr)) = anchor_verse(&anchor.anchor_id) {
+                        res.push_str(&format!("[{verse_nr}] "));
+                    };
+                };
+            }
+            Block::Correction(correction) => {
+                res.push_str(correction_content(correction, options));
+            }
+            Block::Uncertain(uncertain) => res.push_str(&uncertain.content),
+            Block::Abbreviation(abbreviation) => match options.abbreviations {
+                AbbreviationMode::AsWritten => res.push_str(&abbreviation.surface),
+                AbbreviationMode::Expanded => res.push_str(&abbreviation.expansion),
+            },
+        }
+    }
+    res
+}
+
+/// Export the transcription of a single page as plain text
+pub async fn page_text_export(
+    Extension(config): Extension<Arc<Config>>,
+    Path((msname, pagename)): Path<(String, String)>,
+    Query(choice): Query<TranscriptionChoice>,
+    Query(options): Query<TextExportOptions>,
+) -> impl IntoResponse {
+    match page_transcription(&config, &msname, &pagename, &choice).await {
+        Ok(transcription) => plain_text(&transcription.content, &options).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Export the transcriptions of all pages of a manuscript as plain text
+///
+/// Pages are separated by empty lines, pages without a published transcription are left out.
+pub async fn manuscript_text_export(
+    Extension(config): Extension<Arc<Config>>,
+    Path(msname): Path<String>,
+    Query(choice): Query<TranscriptionChoice>,
+    Query(options): Query<TextExportOptions>,
+) -> impl IntoResponse {
+    match manuscript_transcriptions(&config, &msname, &choice).await {
+        Ok((_, pages)) => pages
+            .iter()
+            .map(|(_, transcription)| plain_text(&transcription.content, &options))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,165 @@
+//! Tests for the plain-text export
+
+use critic_format::streamed::{
+    Abbreviation, Block, BlockType, BreakType, Correction, FromTypeLangAndContent, Paragraph,
+    Version,
+};
+use critic_shared::{anchor::verse_anchor, VersificationScheme};
+
+use super::{plain_text, AbbreviationMode, CorrectionMode, GapMode, TextExportOptions};
+
+fn text(content: &str) -> Block {
+    Block::Text(Paragraph {
+        lang: "hbo-Hebr".to_string(),
+        content: content.to_string(),
+    })
+}
+
+fn version(hand: Option<&str>, content: &str) -> Version {
+    Version {
+        lang: "hbo-Hebr".to_string(),
+        hand: hand.map(str::to_string),
+        content: content.to_string(),
+    }
+}
+
+/// A correction of the first hand by `B`, which `C` corrected again
+fn correction() -> Block {
+    Block::Correction(Correction {
+        versions: vec![
+            version(None, "השמם"),
+            version(Some("B"), "השמים"),
+            version(Some("C"), "השמימה"),
+        ],
+    })
+}
+
+fn with_correction(correction: CorrectionMode, hand: Option<&str>) -> TextExportOptions {
+    TextExportOptions {
+        correction,
+        correction_hand: hand.map(str::to_string),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn corrections_show_the_first_version() {
+    let options = with_correction(CorrectionMode::First, None);
+    assert_eq!(
+        plain_text(&[text("את "), correction()], &options),
+        "את השמם"
+    );
+}
+
+#[test]
+fn corrections_show_the_last_version_by_default() {
+    assert_eq!(
+        plain_text(&[text("את "), correction()], &TextExportOptions::default()),
+        "את השמימה"
+    );
+}
+
+#[test]
+fn corrections_show_the_version_of_the_chosen_hand() {
+    let options = with_correction(CorrectionMode::Hand, Some("B"));
+    assert_eq!(plain_text(&[correction()], &options), "השמים");
+}
+
+#[test]
+fn corrections_without_the_chosen_hand_fall_back_to_the_last_version() {
+    let other_hand = with_correction(CorrectionMode::Hand, Some("D"));
+    assert_eq!(plain_text(&[correction()], &other_hand), "השמימה");
+    // versions without a hand are never chosen by hand
+    let no_hand = with_correction(CorrectionMode::Hand, None);
+    assert_eq!(plain_text(&[correction()], &no_hand), "השמימה");
+}
+
+#[test]
+fn empty_corrections_add_nothing() {
+    let empty = Block::Correction(Correction { versions: vec![] });
+    for mode in [
+        CorrectionMode::First,
+        CorrectionMode::Last,
+        CorrectionMode::Hand,
+    ] {
+        assert_eq!(
+            plain_text(
+                &[text("a"), empty.clone()],
+                &with_correction(mode, Some("B"))
+            ),
+            "a"
+        );
+    }
+}
+
+#[test]
+fn abbreviations_are_written_or_expanded() {
+    let abbreviation = Block::Abbreviation(Abbreviation {
+        surface_lang: "hbo-Hebr".to_string(),
+        expansion_lang: "hbo-Hebr".to_string(),
+        surface: "אל'".to_string(),
+        expansion: "אלהים".to_string(),
+    });
+    assert_eq!(
+        plain_text(&[abbreviation.clone()], &TextExportOptions::default()),
+        "אל'"
+    );
+    let options = TextExportOptions {
+        abbreviations: AbbreviationMode::Expanded,
+        ..Default::default()
+    };
+    assert_eq!(plain_text(&[abbreviation], &options), "אלהים");
+}
+
+#[test]
+fn breaks_become_newlines() {
+    let blocks = [
+        text("a"),
+        Block::Break(BreakType::Line),
+        text("b"),
+        Block::Break(BreakType::Column),
+        text("c"),
+    ];
+    assert_eq!(
+        plain_text(&blocks, &TextExportOptions::default()),
+        "a\nb\n\nc"
+    );
+}
+
+#[test]
+fn lacunae_follow_the_gap_mode() {
+    let mut lacuna =
+        Block::from_type_lang_and_content(BlockType::Lacuna, String::default(), String::default());
+    let Block::Lacuna(ref mut inner) = lacuna else {
+        panic!("expected a lacuna");
+    };
+    inner.n = 3;
+    let extent = format!("a[lacuna: 3 {}]b", inner.unit.name());
+    let blocks = [text("a"), lacuna, text("b")];
+    let with_lacuna = |lacuna| TextExportOptions {
+        lacuna,
+        ..Default::default()
+    };
+    assert_eq!(plain_text(&blocks, &with_lacuna(GapMode::Omit)), "ab");
+    assert_eq!(
+        plain_text(&blocks, &with_lacuna(GapMode::Marker)),
+        "a[...]b"
+    );
+    assert_eq!(plain_text(&blocks, &with_lacuna(GapMode::Extent)), extent);
+}
+
+#[test]
+fn verse_anchors_are_shown_as_references_on_request() {
+    let scheme = VersificationScheme {
+        id: 1,
+        full_name: "Present".to_string(),
+        shorthand: "P".to_string(),
+    };
+    let blocks = [verse_anchor(&scheme, "Gen 1:1"), text("בראשית")];
+    assert_eq!(plain_text(&blocks, &TextExportOptions::default()), "בראשית");
+    let options = TextExportOptions {
+        verse_refs: true,
+        ..Default::default()
+    };
+    assert_eq!(plain_text(&blocks, &options), "[Gen 1:1] בראשית");
+}
@@ -0,0 +1,126 @@
+//! USFM export for Paratext
+//!
+//! A USFM document starts with the `\id` line of its single book, so manuscripts containing several
+//! books are exported one book at a time with the `book` option. See [`super::bible`] for how the
+//! verses are found.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query},
+    http::header,
+    response::IntoResponse,
+    Extension,
+};
+use critic_shared::ManuscriptMeta;
+
+use super::{
+    attachment,
+    bible::{group_verses, manuscript_verses, BibleExportOptions, BibleVerse, Segment},
+    ExportError, TranscriptionChoice,
+};
+use crate::config::Config;
+
+/// Remove the characters that start markers or separate attributes in USFM
+fn escape_usfm(text: &str) -> String {
+    text.replace(['\\', '|'], "")
+}
+
+/// A footnote for the verse `verse`
+fn footnote(verse: &BibleVerse, content: &str) -> String {
+    format!(
+        "\\f + \\fr {}:{} \\ft {}\\f*",
+        verse.chapter,
+        verse.verse,
+        escape_usfm(content)
+    )
+}
+
+fn segment_usfm(verse: &BibleVerse, segment: &Segment) -> String {
+    match segment {
+        Segment::Text(content) => escape_usfm(content),
+        Segment::Abbreviation { surface, expansion } => format!(
+            "\\w {}|lemma=\"{}\"\\w*",
+            escape_usfm(surface),
+            escape_usfm(expansion).replace('"', "")
+        ),
+        Segment::Uncertain(content) => format!(
+            "{}{}",
+            escape_usfm(content),
+            footnote(verse, "uncertain reading")
+        ),
+        Segment::Correction { text, original } => format!(
+            "{}{}",
+            escape_usfm(text),
+            footnote(verse, &format!("before correction: {original}"))
+        ),
+        Segment::Lacuna(extent) => footnote(verse, &format!("lacuna: {extent}")),
+    }
+}
+
+/// Build a USFM document from the verses of a manuscript
+///
+/// Fails if the verses belong to more than one book, since every book needs its own document.
+pub fn usfm_document(meta: &ManuscriptMeta, verses: &[BibleVerse]) -> Result<String, ExportError> {
+    let books = group_verses(verses);
+    if books.len() > 1 {
+        let mut usfm: Vec<String> = vec![];
+        for (book, _) in &books {
+            if !usfm.iter().any(|code| code == book.usfm) {
+                usfm.push(book.usfm.to_string());
+            };
+        }
+        return Err(ExportError::SeveralBooks(usfm));
+    };
+    let title = escape_usfm(&meta.title);
+    let mut res = String::default();
+    for (book, chapters) in books {
+        res.push_str(&format!(
+            "\\id {} {title}\n\\usfm 3.0\n\\h {}\n\\mt1 {title}\n",
+            book.usfm, book.name
+        ));
+        for (chapter, chapter_verses) in chapters {
+            res.push_str(&format!("\\c {}\n\\p\n", escape_usfm(chapter)));
+            for verse in chapter_verses {
+                res.push_str(&format!("\\v {} ", escape_usfm(&verse.verse)));
+                for segment in &verse.segments {
+                    res.push_str(&segment_usfm(verse, segment));
+                }
+                res.push('\n');
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Export the accepted text of a manuscript as USFM
+pub async fn usfm_export(
+    Extension(config): Extension<Arc<Config>>,
+    Path(msname): Path<String>,
+    Query(choice): Query<TranscriptionChoice>,
+    Query(options): Query<BibleExportOptions>,
+) -> impl IntoResponse {
+    let document = manuscript_verses(&config, &msname, &choice, &options)
+        .await
+        .and_then(|(meta, verses)| usfm_document(&meta, &verses));
+    match document {
+        Ok(document) => (
+            [
+                (
+                    header::CONTENT_TYPE,
+                    "text/plain; charset=utf-8".to_string(),
+                ),
+                (
+                    header::CONTENT_DISPOSITION,
+                    attachment(&format!("{msname}.usfm")),
+                ),
+            ],
+            document,
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,58 @@
+//! Tests for the USFM export
+
+use critic_shared::ManuscriptMeta;
+
+use super::usfm_document;
+use crate::export::{
+    bible::{BibleVerse, Segment, BOOKS},
+    ExportError,
+};
+
+fn meta() -> ManuscriptMeta {
+    ManuscriptMeta {
+        id: 1,
+        title: "Leningradensis".to_string(),
+        institution: None,
+        collection: None,
+        hand_desc: None,
+        script_desc: None,
+    }
+}
+
+fn verse(book: usize, chapter: &str, verse: &str, text: &str) -> BibleVerse {
+    BibleVerse {
+        book: &BOOKS[book],
+        chapter: chapter.to_string(),
+        verse: verse.to_string(),
+        segments: vec![Segment::Text(text.to_string())],
+    }
+}
+
+#[test]
+fn a_single_book_has_one_id_line() {
+    let verses = vec![
+        verse(0, "1", "1", "בראשית ברא"),
+        verse(0, "1", "2", "והארץ היתה"),
+        verse(0, "2", "1", "ויכלו"),
+    ];
+    let document = usfm_document(&meta(), &verses).unwrap();
+    assert_eq!(
+        document,
+        "\\id GEN Leningradensis\n\\usfm 3.0\n\\h Genesis\n\\mt1 Leningradensis\n\
+         \\c 1\n\\p\n\\v 1 בראשית ברא\n\\v 2 והארץ היתה\n\
+         \\c 2\n\\p\n\\v 1 ויכלו\n"
+    );
+    assert_eq!(document.matches("\\id ").count(), 1);
+}
+
+#[test]
+fn several_books_are_rejected() {
+    let verses = vec![
+        verse(0, "50", "26", "וימת יוסף"),
+        verse(1, "1", "1", "ואלה שמות"),
+    ];
+    match usfm_document(&meta(), &verses) {
+        Err(ExportError::SeveralBooks(books)) => assert_eq!(books, vec!["GEN", "EXO"]),
+        other => panic!("expected several books, got {other:?}"),
+    };
+}
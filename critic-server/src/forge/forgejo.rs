@@ -0,0 +1,444 @@
+//! Communicate with the api of forgejo (or gitea)
+//!
+//! Users log in with oauth2, their role is the highest permission of the teams they are in in the
+//! organization owning the repository:
+//!
+//! | team permission | role       |
+//! |-----------------|------------|
+//! | owner           | Owner      |
+//! | admin           | Maintainer |
+//! | write           | Developer  |
+//! | read            | Reporter   |
+//! | none            | NoAccess   |
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use super::{Forge, ForgeError, OauthEndpoints};
+use crate::{
+    auth::{AuthenticatedUser, UserInfo},
+    gitlab::GitlabUserRole,
+};
+
+/// The base URL in forgejo we communicate with - directly after the server name
+const API_BASE_URL: &str = "/api/v1";
+
+#[derive(Debug)]
+pub enum ForgejoApiError {
+    /// Reqwest had problems making the request itself
+    Reqwest(reqwest::Error),
+    /// The user is in no team of the organization
+    UserNotOrgMember(String),
+    /// The status code from forgejos api was not what we assumed
+    BadStatusCode(StatusCode),
+    /// No access token for requests without a logged-in user is configured
+    NoAccessToken,
+    /// The id of a forgejo user does not fit the user ids critic stores
+    UserIdOutOfRange(i64),
+}
+impl From<reqwest::Error> for ForgejoApiError {
+    fn from(value: reqwest::Error) -> Self {
+        Self::Reqwest(value)
+    }
+}
+impl core::fmt::Display for ForgejoApiError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Reqwest(e) => {
+                write!(f, "Unable to complete HTTP request: {e}")
+            }
+            Self::UserNotOrgMember(username) => {
+                write!(
+                    f,
+                    "The user {username} is not in any team of the organization in forgejo."
+                )
+            }
+            Self::BadStatusCode(code) => {
+                write!(f, "Got the following status code: {code} from forgejo API.")
+            }
+            Self::NoAccessToken => {
+                write!(
+                    f,
+                    "No forgejo access token is configured in forgejo.access_token."
+                )
+            }
+            Self::UserIdOutOfRange(id) => {
+                write!(f, "The forgejo user id {id} is too large for critic.")
+            }
+        }
+    }
+}
+impl core::error::Error for ForgejoApiError {}
+
+/// Config partaining to the forgejo instance
+#[derive(Deserialize, Debug, Clone)]
+pub struct ForgejoConfig {
+    /// the address where we can talk to forgejo
+    pub addr: String,
+    /// The organization owning the repository, its teams give users their roles
+    pub organization: String,
+    /// The name of the repository in the organization
+    pub repository: String,
+    /// An access token with read permission for the repository
+    ///
+    /// Used whenever critic talks to forgejo without a logged-in user.
+    pub access_token: Option<String>,
+}
+
+/// The JSON object returned from forgejos get-user endpoint
+#[derive(Deserialize)]
+struct ForgejoUser {
+    id: i64,
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct TeamOrganization {
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct Team {
+    permission: String,
+    organization: Option<TeamOrganization>,
+}
+
+/// A file or directory in the repository tree
+#[derive(Deserialize)]
+struct TreeEntry {
+    path: String,
+    /// `blob` for files, `tree` for directories
+    #[serde(rename = "type")]
+    entry_type: String,
+}
+
+#[derive(Deserialize)]
+struct Tree {
+    tree: Vec<TreeEntry>,
+    /// are there more pages?
+    #[serde(default)]
+    truncated: bool,
+}
+
+#[derive(Deserialize)]
+struct ContentsResponse {
+    sha: String,
+}
+
+/// The part of a created pull request we are interested in
+#[derive(Deserialize)]
+struct CreatedPullRequest {
+    number: i64,
+}
+
+/// The role a team permission gives, None for unknown permissions
+fn permission_role(permission: &str) -> Option<GitlabUserRole> {
+    match permission {
+        "owner" => Some(GitlabUserRole::Owner),
+        "admin" => Some(GitlabUserRole::Maintainer),
+        "write" => Some(GitlabUserRole::Developer),
+        "read" => Some(GitlabUserRole::Reporter),
+        "none" => Some(GitlabUserRole::NoAccess),
+        _ => None,
+    }
+}
+
+/// The highest role the teams of a user give in `organization`
+fn highest_role(teams: &[Team], organization: &str) -> Option<GitlabUserRole> {
+    teams
+        .iter()
+        .filter(|team| {
+            team.organization
+                .as_ref()
+                .is_some_and(|org| org.username == organization)
+        })
+        .filter_map(|team| permission_role(&team.permission))
+        .max()
+}
+
+/// Collect the items of all pages of a paginated endpoint, starting at page 1
+///
+/// The first empty page ends the list.
+async fn all_pages<T, Fut>(
+    mut fetch_page: impl FnMut(u32) -> Fut,
+) -> Result<Vec<T>, ForgejoApiError>
+where
+    Fut: std::future::Future<Output = Result<Vec<T>, ForgejoApiError>>,
+{
+    let mut res = vec![];
+    let mut page = 1_u32;
+    loop {
+        let batch = fetch_page(page).await?;
+        if batch.is_empty() {
+            return Ok(res);
+        };
+        res.extend(batch);
+        page += 1;
+    }
+}
+
+/// Forgejo user ids are 64 bit, critic stores them in 32 bit
+fn user_id(id: i64) -> Result<i32, ForgejoApiError> {
+    i32::try_from(id).map_err(|_| ForgejoApiError::UserIdOutOfRange(id))
+}
+
+/// Url-encode every segment of a path in the repository
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| urlencoding::encode(segment).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Fail with the status code unless the request was successful
+fn check_status(response: reqwest::Response) -> Result<reqwest::Response, ForgejoApiError> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(ForgejoApiError::BadStatusCode(response.status()))
+    }
+}
+
+/// The forgejo (or gitea) instance hosting the repository
+#[derive(Debug, Clone)]
+pub struct ForgejoForge {
+    addr: String,
+    organization: String,
+    repository: String,
+    access_token: Option<String>,
+}
+impl ForgejoForge {
+    pub fn new(config: &ForgejoConfig) -> Self {
+        Self {
+            addr: config.addr.clone(),
+            organization: config.organization.clone(),
+            repository: config.repository.clone(),
+            access_token: config.access_token.clone(),
+        }
+    }
+
+    /// The url of an API endpoint, `path` starting with a /
+    fn api_url(&self, path: &str) -> String {
+        format!("https://{}{API_BASE_URL}{path}", self.addr)
+    }
+
+    /// The url of an API endpoint of the repository, `path` starting with a /
+    fn repo_url(&self, path: &str) -> String {
+        self.api_url(&format!(
+            "/repos/{}/{}{path}",
+            urlencoding::encode(&self.organization),
+            urlencoding::encode(&self.repository)
+        ))
+    }
+
+    /// The configured access token for requests without a logged-in user
+    fn access_token(&self) -> Result<&str, ForgejoApiError> {
+        self.access_token
+            .as_deref()
+            .ok_or(ForgejoApiError::NoAccessToken)
+    }
+
+    /// Get the content of the file at `path` in the repository at `git_ref`
+    ///
+    /// This uses the access token from the config, not the token of a user.
+    pub async fn get_repository_file(
+        &self,
+        path: &str,
+        git_ref: &str,
+    ) -> Result<String, ForgejoApiError> {
+        let response = reqwest::Client::new()
+            .get(self.repo_url(&format!("/raw/{}", encode_path(path))))
+            .query(&[("ref", git_ref)])
+            .bearer_auth(self.access_token()?)
+            .send()
+            .await?;
+        Ok(check_status(response)?.text().await?)
+    }
+
+    /// Get the paths of all files in the repository at `git_ref`
+    ///
+    /// This uses the access token from the config, not the token of a user.
+    pub async fn list_repository_files(
+        &self,
+        git_ref: &str,
+    ) -> Result<Vec<String>, ForgejoApiError> {
+        let access_token = self.access_token()?;
+        let request_url = self.repo_url(&format!("/git/trees/{}", urlencoding::encode(git_ref)));
+        let client = reqwest::Client::new();
+        let mut res = vec![];
+        let mut page = 1_u32;
+        loop {
+            let response = client
+                .get(&request_url)
+                .query(&[("recursive", "true"), ("page", &page.to_string())])
+                .bearer_auth(access_token)
+                .send()
+                .await?;
+            let tree = check_status(response)?.json::<Tree>().await?;
+            res.extend(
+                tree.tree
+                    .into_iter()
+                    .filter(|entry| entry.entry_type == "blob")
+                    .map(|entry| entry.path),
+            );
+            if !tree.truncated {
+                return Ok(res);
+            };
+            page += 1;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Forge for ForgejoForge {
+    fn oauth_endpoints(&self) -> Option<OauthEndpoints> {
+        Some(OauthEndpoints {
+            auth_url: format!("https://{}/login/oauth/authorize", self.addr),
+            token_url: format!("https://{}/login/oauth/access_token", self.addr),
+            scopes: vec![
+                "read:user".to_string(),
+                "read:organization".to_string(),
+                "write:repository".to_string(),
+            ],
+        })
+    }
+
+    async fn user_info(&self, access_token: &str) -> Result<UserInfo, ForgeError> {
+        let response = reqwest::Client::new()
+            .get(self.api_url("/user"))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(ForgejoApiError::Reqwest)?;
+        let user = check_status(response)?
+            .json::<ForgejoUser>()
+            .await
+            .map_err(ForgejoApiError::Reqwest)?;
+        Ok(UserInfo {
+            id: user_id(user.id)?,
+            username: user.login,
+        })
+    }
+
+    async fn user_role(&self, user: &AuthenticatedUser) -> Result<GitlabUserRole, ForgeError> {
+        let client = reqwest::Client::new();
+        let teams = all_pages(|page| {
+            let request = client
+                .get(self.api_url("/user/teams"))
+                .query(&[("limit", "50"), ("page", &page.to_string())])
+                .bearer_auth(&user.access_token);
+            async move {
+                let response = request.send().await?;
+                Ok::<_, ForgejoApiError>(check_status(response)?.json::<Vec<Team>>().await?)
+            }
+        })
+        .await?;
+        highest_role(&teams, &self.organization)
+            .ok_or_else(|| ForgejoApiError::UserNotOrgMember(user.username.clone()).into())
+    }
+
+    async fn read_file(&self, path: &str, git_ref: &str) -> Result<String, ForgeError> {
+        Ok(self.get_repository_file(path, git_ref).await?)
+    }
+
+    async fn list_files(&self, git_ref: &str) -> Result<Vec<String>, ForgeError> {
+        Ok(self.list_repository_files(git_ref).await?)
+    }
+
+    async fn create_branch(
+        &self,
+        user: &AuthenticatedUser,
+        branch: &str,
+        from: &str,
+    ) -> Result<(), ForgeError> {
+        let response = reqwest::Client::new()
+            .post(self.repo_url("/branches"))
+            .bearer_auth(&user.access_token)
+            .json(&serde_json::json!({
+                "new_branch_name": branch,
+                "old_ref_name": from,
+            }))
+            .send()
+            .await
+            .map_err(ForgejoApiError::Reqwest)?;
+        check_status(response)?;
+        Ok(())
+    }
+
+    async fn commit_file(
+        &self,
+        user: &AuthenticatedUser,
+        branch: &str,
+        path: &str,
+        content: &str,
+        message: &str,
+    ) -> Result<(), ForgeError> {
+        let client = reqwest::Client::new();
+        let url = self.repo_url(&format!("/contents/{}", encode_path(path)));
+        // updating a file needs the sha of its current version
+        let existing = client
+            .get(&url)
+            .query(&[("ref", branch)])
+            .bearer_auth(&user.access_token)
+            .send()
+            .await
+            .map_err(ForgejoApiError::Reqwest)?;
+        let request = match existing.status() {
+            StatusCode::OK => {
+                let sha = existing
+                    .json::<ContentsResponse>()
+                    .await
+                    .map_err(ForgejoApiError::Reqwest)?
+                    .sha;
+                client.put(&url).json(&serde_json::json!({
+                    "branch": branch,
+                    "message": message,
+                    "content": BASE64.encode(content),
+                    "sha": sha,
+                }))
+            }
+            StatusCode::NOT_FOUND => client.post(&url).json(&serde_json::json!({
+                "branch": branch,
+                "message": message,
+                "content": BASE64.encode(content),
+            })),
+            c => return Err(ForgejoApiError::BadStatusCode(c).into()),
+        };
+        let response = request
+            .bearer_auth(&user.access_token)
+            .send()
+            .await
+            .map_err(ForgejoApiError::Reqwest)?;
+        check_status(response)?;
+        Ok(())
+    }
+
+    async fn open_merge_request(
+        &self,
+        user: &AuthenticatedUser,
+        source_branch: &str,
+        target_branch: &str,
+        title: &str,
+    ) -> Result<i64, ForgeError> {
+        let response = reqwest::Client::new()
+            .post(self.repo_url("/pulls"))
+            .bearer_auth(&user.access_token)
+            .json(&serde_json::json!({
+                "head": source_branch,
+                "base": target_branch,
+                "title": title,
+            }))
+            .send()
+            .await
+            .map_err(ForgejoApiError::Reqwest)?;
+        Ok(check_status(response)?
+            .json::<CreatedPullRequest>()
+            .await
+            .map_err(ForgejoApiError::Reqwest)?
+            .number)
+    }
+}
+
+#[cfg(test)]
+mod test;
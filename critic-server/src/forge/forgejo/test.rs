@@ -0,0 +1,104 @@
+//! Tests for the forgejo forge
+
+use std::cell::RefCell;
+
+use super::{
+    all_pages, encode_path, highest_role, permission_role, user_id, ForgejoApiError, Team,
+};
+use crate::gitlab::GitlabUserRole;
+
+/// Teams as returned by `/user/teams`, trimmed to the fields we read
+const TEAMS: &str = r#"[
+    {"id": 1, "name": "Owners", "permission": "owner", "organization": {"id": 3, "username": "other-project"}},
+    {"id": 2, "name": "Readers", "permission": "read", "organization": {"id": 4, "username": "critic"}},
+    {"id": 5, "name": "Editors", "permission": "write", "organization": {"id": 4, "username": "critic"}},
+    {"id": 6, "name": "Ghosts", "permission": "admin", "organization": null}
+]"#;
+
+fn teams() -> Vec<Team> {
+    serde_json::from_str(TEAMS).unwrap()
+}
+
+#[test]
+fn team_permissions_map_to_roles() {
+    assert_eq!(permission_role("owner"), Some(GitlabUserRole::Owner));
+    assert_eq!(permission_role("admin"), Some(GitlabUserRole::Maintainer));
+    assert_eq!(permission_role("write"), Some(GitlabUserRole::Developer));
+    assert_eq!(permission_role("read"), Some(GitlabUserRole::Reporter));
+    assert_eq!(permission_role("none"), Some(GitlabUserRole::NoAccess));
+    assert_eq!(permission_role("Owner"), None);
+    assert_eq!(permission_role(""), None);
+}
+
+#[test]
+fn the_highest_role_in_the_organization_counts() {
+    assert_eq!(
+        highest_role(&teams(), "critic"),
+        Some(GitlabUserRole::Developer)
+    );
+    assert_eq!(
+        highest_role(&teams(), "other-project"),
+        Some(GitlabUserRole::Owner)
+    );
+    assert_eq!(highest_role(&teams(), "elsewhere"), None);
+    assert_eq!(highest_role(&[], "critic"), None);
+}
+
+#[test]
+fn paths_are_encoded_per_segment() {
+    assert_eq!(encode_path("README.md"), "README.md");
+    assert_eq!(
+        encode_path("Codex A/1r 50%.tei.xml"),
+        "Codex%20A/1r%2050%25.tei.xml"
+    );
+    assert_eq!(
+        encode_path("כתב/דף?#.xml"),
+        "%D7%9B%D7%AA%D7%91/%D7%93%D7%A3%3F%23.xml"
+    );
+}
+
+#[test]
+fn user_ids_must_fit_critic() {
+    assert_eq!(user_id(1).unwrap(), 1);
+    assert_eq!(user_id(i64::from(i32::MAX)).unwrap(), i32::MAX);
+    assert!(matches!(
+        user_id(i64::from(i32::MAX) + 1),
+        Err(ForgejoApiError::UserIdOutOfRange(2_147_483_648))
+    ));
+    assert!(matches!(
+        user_id(i64::MIN),
+        Err(ForgejoApiError::UserIdOutOfRange(i64::MIN))
+    ));
+}
+
+#[tokio::test]
+async fn pages_are_fetched_until_an_empty_one() {
+    let requested = RefCell::new(vec![]);
+    let items = all_pages(|page| {
+        requested.borrow_mut().push(page);
+        async move {
+            Ok::<_, ForgejoApiError>(match page {
+                1 => vec![1, 2],
+                2 => vec![3],
+                _ => vec![],
+            })
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(items, vec![1, 2, 3]);
+    assert_eq!(requested.into_inner(), vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn failing_pages_fail_the_whole_list() {
+    let res = all_pages(|page| async move {
+        if page == 2 {
+            Err(ForgejoApiError::NoAccessToken)
+        } else {
+            Ok(vec![page])
+        }
+    })
+    .await;
+    assert!(matches!(res, Err(ForgejoApiError::NoAccessToken)));
+}
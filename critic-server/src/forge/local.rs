@@ -0,0 +1,413 @@
+//! A forge that is only a bare git repository on the local disk
+//!
+//! This lets critic run without gitlab, e.g. for development, tests or small projects. There is
+//! no identity provider: the users and their roles are listed in the config, and the access token
+//! of a user is their username. Merge requests are recorded as `refs/merge-requests/<id>/head`
+//! (the same refs gitlab uses), the merge itself is done with plain git.
+//!
+//! The repository is changed with git plumbing commands, so it never needs a working tree. Refs
+//! and paths are always passed after `--end-of-options`, so that they are never taken as options.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use serde::Deserialize;
+
+use super::{Forge, ForgeError, OauthEndpoints};
+use crate::{
+    auth::{AuthenticatedUser, UserInfo},
+    gitlab::GitlabUserRole,
+};
+
+/// Counter to give concurrent commits their own temporary index
+static INDEX_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+pub enum LocalGitError {
+    /// git cannot be run
+    Spawn(std::io::Error),
+    /// git ran, but failed
+    Git { command: String, stderr: String },
+    /// A file in the repository is not valid UTF-8
+    NotUtf8(String),
+    /// The user is not configured for the local forge
+    UnknownUser(String),
+}
+impl core::fmt::Display for LocalGitError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Spawn(e) => {
+                write!(f, "Unable to run git: {e}")
+            }
+            Self::Git { command, stderr } => {
+                write!(f, "git {command} failed: {}", stderr.trim())
+            }
+            Self::NotUtf8(path) => {
+                write!(f, "The file {path} in the repository is not valid UTF-8")
+            }
+            Self::UnknownUser(username) => {
+                write!(
+                    f,
+                    "The user {username} is not configured for the local forge"
+                )
+            }
+        }
+    }
+}
+impl core::error::Error for LocalGitError {}
+
+/// A user known to the local forge
+#[derive(Deserialize, Debug, Clone)]
+pub struct LocalUser {
+    pub id: i32,
+    pub username: String,
+    pub role: GitlabUserRole,
+}
+
+/// Config for the local forge
+#[derive(Deserialize, Debug, Clone)]
+pub struct LocalForgeConfig {
+    /// the bare repository (created with `git init --bare`)
+    pub repository: PathBuf,
+    #[serde(default)]
+    pub users: Vec<LocalUser>,
+}
+
+/// Run git on the bare repository `repo`, returning its stdout
+fn git(
+    repo: &Path,
+    args: &[&str],
+    env: &[(&str, &str)],
+    stdin: Option<&[u8]>,
+) -> Result<Vec<u8>, LocalGitError> {
+    let mut child = Command::new("git")
+        .arg("--git-dir")
+        .arg(repo)
+        .args(args)
+        .envs(env.iter().copied())
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(LocalGitError::Spawn)?;
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        std::io::Write::write_all(&mut pipe, input).map_err(LocalGitError::Spawn)?;
+    };
+    let output = child.wait_with_output().map_err(LocalGitError::Spawn)?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(LocalGitError::Git {
+            command: args.join(" "),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+}
+
+/// Run git and return its stdout as a single trimmed line
+fn git_line(repo: &Path, args: &[&str], env: &[(&str, &str)]) -> Result<String, LocalGitError> {
+    Ok(String::from_utf8_lossy(&git(repo, args, env, None)?)
+        .trim()
+        .to_string())
+}
+
+/// Commit `content` as `path` onto `branch` without a working tree
+fn commit_file_blocking(
+    repo: &Path,
+    username: &str,
+    branch: &str,
+    path: &str,
+    content: &str,
+    message: &str,
+) -> Result<(), LocalGitError> {
+    let branch_ref = format!("refs/heads/{branch}");
+    let parent = git_line(
+        repo,
+        &[
+            "rev-parse",
+            "--verify",
+            "--end-of-options",
+            &format!("{branch_ref}^{{commit}}"),
+        ],
+        &[],
+    )?;
+    let blob = String::from_utf8_lossy(&git(
+        repo,
+        &["hash-object", "-w", "--stdin"],
+        &[],
+        Some(content.as_bytes()),
+    )?)
+    .trim()
+    .to_string();
+
+    // build the new tree in a temporary index, so that concurrent commits do not interfere
+    let index = std::env::temp_dir().join(format!(
+        "critic-index-{}-{}",
+        std::process::id(),
+        INDEX_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let index_path = index.to_string_lossy().to_string();
+    let index_env = [("GIT_INDEX_FILE", index_path.as_str())];
+    let tree = git(
+        repo,
+        &["read-tree", "--end-of-options", &parent],
+        &index_env,
+        None,
+    )
+    .and_then(|_| {
+        git(
+            repo,
+            &[
+                "update-index",
+                "--add",
+                "--cacheinfo",
+                &format!("100644,{blob},{path}"),
+            ],
+            &index_env,
+            None,
+        )
+    })
+    .and_then(|_| git_line(repo, &["write-tree"], &index_env));
+    // the index is not needed anymore, whether the tree could be written or not
+    let _ = std::fs::remove_file(&index);
+    let tree = tree?;
+
+    let email = format!("{username}@localhost");
+    let commit = git_line(
+        repo,
+        &["commit-tree", &tree, "-p", &parent, "-m", message],
+        &[
+            ("GIT_AUTHOR_NAME", username),
+            ("GIT_AUTHOR_EMAIL", &email),
+            ("GIT_COMMITTER_NAME", username),
+            ("GIT_COMMITTER_EMAIL", &email),
+        ],
+    )?;
+    // fails if the branch moved in the meantime
+    git(
+        repo,
+        &[
+            "update-ref",
+            "--end-of-options",
+            &branch_ref,
+            &commit,
+            &parent,
+        ],
+        &[],
+        None,
+    )?;
+    Ok(())
+}
+
+/// The next free id for a merge request
+fn next_merge_request_id(repo: &Path) -> Result<i64, LocalGitError> {
+    let refs = git_line(
+        repo,
+        &[
+            "for-each-ref",
+            "--format=%(refname)",
+            "refs/merge-requests/",
+        ],
+        &[],
+    )?;
+    Ok(refs
+        .lines()
+        .filter_map(|r| r.strip_prefix("refs/merge-requests/")?.split('/').next())
+        .filter_map(|id| id.parse::<i64>().ok())
+        .max()
+        .unwrap_or(0)
+        + 1)
+}
+
+/// A bare git repository on the local disk
+#[derive(Debug, Clone)]
+pub struct LocalForge {
+    repository: PathBuf,
+    users: Vec<LocalUser>,
+}
+impl LocalForge {
+    pub fn new(config: LocalForgeConfig) -> Self {
+        Self {
+            repository: config.repository,
+            users: config.users,
+        }
+    }
+
+    fn user(&self, username: &str) -> Result<&LocalUser, LocalGitError> {
+        self.users
+            .iter()
+            .find(|user| user.username == username)
+            .ok_or(LocalGitError::UnknownUser(username.to_string()))
+    }
+
+    /// Run `f` on the repository without blocking the runtime
+    async fn blocking<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Path) -> Result<T, LocalGitError> + Send + 'static,
+    ) -> Result<T, ForgeError> {
+        let repository = self.repository.clone();
+        tokio::task::spawn_blocking(move || f(&repository))
+            .await
+            .map_err(|e| LocalGitError::Spawn(std::io::Error::other(e)))?
+            .map_err(ForgeError::Local)
+    }
+}
+
+#[async_trait::async_trait]
+impl Forge for LocalForge {
+    fn oauth_endpoints(&self) -> Option<OauthEndpoints> {
+        None
+    }
+
+    async fn user_info(&self, access_token: &str) -> Result<UserInfo, ForgeError> {
+        let user = self.user(access_token)?;
+        Ok(UserInfo {
+            id: user.id,
+            username: user.username.clone(),
+        })
+    }
+
+    async fn user_role(&self, user: &AuthenticatedUser) -> Result<GitlabUserRole, ForgeError> {
+        Ok(self.user(&user.username)?.role)
+    }
+
+    /// Commits are made in the name of the configured users, whichever way they logged in
+    fn can_act_for(&self, user: &AuthenticatedUser) -> bool {
+        self.user(&user.username).is_ok()
+    }
+
+    async fn read_file(&self, path: &str, git_ref: &str) -> Result<String, ForgeError> {
+        let path = path.to_string();
+        let object = format!("{git_ref}:{path}");
+        self.blocking(move |repo| {
+            String::from_utf8(git(
+                repo,
+                &["show", "--end-of-options", &object],
+                &[],
+                None,
+            )?)
+            .map_err(|_| LocalGitError::NotUtf8(path))
+        })
+        .await
+    }
+
+    async fn list_files(&self, git_ref: &str) -> Result<Vec<String>, ForgeError> {
+        let git_ref = git_ref.to_string();
+        self.blocking(move |repo| {
+            let output = git(
+                repo,
+                &[
+                    "ls-tree",
+                    "-r",
+                    "-z",
+                    "--name-only",
+                    "--end-of-options",
+                    &git_ref,
+                ],
+                &[],
+                None,
+            )?;
+            Ok(output
+                .split(|b| *b == 0)
+                .filter(|path| !path.is_empty())
+                .map(|path| String::from_utf8_lossy(path).to_string())
+                .collect())
+        })
+        .await
+    }
+
+    async fn create_branch(
+        &self,
+        user: &AuthenticatedUser,
+        branch: &str,
+        from: &str,
+    ) -> Result<(), ForgeError> {
+        self.user(&user.username)?;
+        let (branch, from) = (branch.to_string(), from.to_string());
+        self.blocking(move |repo| {
+            git(
+                repo,
+                &["branch", "--end-of-options", &branch, &from],
+                &[],
+                None,
+            )
+            .map(|_| ())
+        })
+        .await
+    }
+
+    async fn commit_file(
+        &self,
+        user: &AuthenticatedUser,
+        branch: &str,
+        path: &str,
+        content: &str,
+        message: &str,
+    ) -> Result<(), ForgeError> {
+        let username = self.user(&user.username)?.username.clone();
+        let (branch, path, content, message) = (
+            branch.to_string(),
+            path.to_string(),
+            content.to_string(),
+            message.to_string(),
+        );
+        self.blocking(move |repo| {
+            commit_file_blocking(repo, &username, &branch, &path, &content, &message)
+        })
+        .await
+    }
+
+    async fn open_merge_request(
+        &self,
+        user: &AuthenticatedUser,
+        source_branch: &str,
+        target_branch: &str,
+        title: &str,
+    ) -> Result<i64, ForgeError> {
+        self.user(&user.username)?;
+        let source = format!("refs/heads/{source_branch}");
+        let target = format!("refs/heads/{target_branch}");
+        let message = format!("{title} (into {target_branch})");
+        self.blocking(move |repo| {
+            git(
+                repo,
+                &["rev-parse", "--verify", "--end-of-options", &target],
+                &[],
+                None,
+            )?;
+            let head = git_line(
+                repo,
+                &["rev-parse", "--verify", "--end-of-options", &source],
+                &[],
+            )?;
+            let id = next_merge_request_id(repo)?;
+            git(
+                repo,
+                &[
+                    "update-ref",
+                    "--create-reflog",
+                    "-m",
+                    &message,
+                    "--end-of-options",
+                    &format!("refs/merge-requests/{id}/head"),
+                    &head,
+                    "",
+                ],
+                &[],
+                None,
+            )?;
+            Ok(id)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,235 @@
+//! Tests for the local forge, on bare repositories in the temporary directory
+
+use std::path::PathBuf;
+
+use super::{git, git_line, LocalForge, LocalForgeConfig, LocalGitError, LocalUser};
+use crate::{
+    auth::AuthenticatedUser,
+    forge::{Forge, ForgeError},
+    gitlab::GitlabUserRole,
+};
+
+/// The accepted transcription the test repositories start with
+const ACCEPTED: &str = "<TEI>accepted</TEI>";
+
+/// A bare repository that is deleted at the end of the test
+struct TestRepo(PathBuf);
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A bare repository with `ms/1r.tei.xml` on `main`, and a forge on it
+fn forge(name: &str) -> (TestRepo, LocalForge) {
+    let repo =
+        std::env::temp_dir().join(format!("critic-local-forge-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&repo);
+    std::fs::create_dir_all(&repo).unwrap();
+    let test_repo = TestRepo(repo.clone());
+    git(&repo, &["init", "--bare", "--quiet"], &[], None).unwrap();
+
+    let identity = [
+        ("GIT_AUTHOR_NAME", "critic"),
+        ("GIT_AUTHOR_EMAIL", "critic@localhost"),
+        ("GIT_COMMITTER_NAME", "critic"),
+        ("GIT_COMMITTER_EMAIL", "critic@localhost"),
+    ];
+    let empty_tree = git_line(&repo, &["mktree"], &[]).unwrap();
+    let root = git_line(
+        &repo,
+        &["commit-tree", &empty_tree, "-m", "init"],
+        &identity,
+    )
+    .unwrap();
+    git(&repo, &["update-ref", "refs/heads/main", &root], &[], None).unwrap();
+    super::commit_file_blocking(&repo, "critic", "main", "ms/1r.tei.xml", ACCEPTED, "accept")
+        .unwrap();
+
+    let forge = LocalForge::new(LocalForgeConfig {
+        repository: repo,
+        users: vec![
+            LocalUser {
+                id: 1,
+                username: "alice".to_string(),
+                role: GitlabUserRole::Developer,
+            },
+            LocalUser {
+                id: 2,
+                username: "bob".to_string(),
+                role: GitlabUserRole::Maintainer,
+            },
+        ],
+    });
+    (test_repo, forge)
+}
+
+fn user(username: &str) -> AuthenticatedUser {
+    AuthenticatedUser {
+        id: 1,
+        username: username.to_string(),
+        access_token: username.to_string(),
+        refresh_token: String::default(),
+        expires_at: time::OffsetDateTime::now_utc(),
+        login: "forge".to_string(),
+        role: None,
+        issuer: None,
+        subject: None,
+    }
+}
+
+#[tokio::test]
+async fn users_come_from_the_config() {
+    let (_repo, forge) = forge("users");
+    let info = forge.user_info("bob").await.unwrap();
+    assert_eq!((info.id, info.username.as_str()), (2, "bob"));
+    assert_eq!(
+        forge.user_role(&user("alice")).await.unwrap(),
+        GitlabUserRole::Developer
+    );
+    assert!(matches!(
+        forge.user_info("mallory").await,
+        Err(ForgeError::Local(LocalGitError::UnknownUser(_)))
+    ));
+    assert!(forge.oauth_endpoints().is_none());
+}
+
+#[test]
+fn configured_users_can_commit_whichever_way_they_logged_in() {
+    let (_repo, forge) = forge("act-for");
+    let mut alice = user("alice");
+    alice.login = "dev".to_string();
+    assert!(forge.can_act_for(&alice));
+    assert!(forge.can_act_for(&user("bob")));
+    assert!(!forge.can_act_for(&user("mallory")));
+}
+
+#[tokio::test]
+async fn files_are_read_from_a_ref() {
+    let (_repo, forge) = forge("read");
+    assert_eq!(
+        forge.read_file("ms/1r.tei.xml", "main").await.unwrap(),
+        ACCEPTED
+    );
+    assert_eq!(
+        forge.list_files("main").await.unwrap(),
+        vec!["ms/1r.tei.xml"]
+    );
+    assert!(forge.read_file("ms/1v.tei.xml", "main").await.is_err());
+}
+
+#[tokio::test]
+async fn reconciliation_is_committed_on_its_branch() {
+    let (repo, forge) = forge("reconcile");
+    let alice = user("alice");
+    let branch = "rec/ms/1r/alice";
+    forge.create_branch(&alice, branch, "main").await.unwrap();
+    forge
+        .commit_file(
+            &alice,
+            branch,
+            "ms/1r.tei.xml",
+            "<TEI>new</TEI>",
+            "Reconcile ms 1r",
+        )
+        .await
+        .unwrap();
+    forge
+        .commit_file(
+            &alice,
+            branch,
+            "ms/1v.tei.xml",
+            "<TEI>1v</TEI>",
+            "Reconcile ms 1v",
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        forge.read_file("ms/1r.tei.xml", branch).await.unwrap(),
+        "<TEI>new</TEI>"
+    );
+    assert_eq!(
+        forge.read_file("ms/1r.tei.xml", "main").await.unwrap(),
+        ACCEPTED
+    );
+    assert_eq!(
+        forge.list_files(branch).await.unwrap(),
+        vec!["ms/1r.tei.xml", "ms/1v.tei.xml"]
+    );
+    let author = git_line(&repo.0, &["log", "-1", "--format=%an <%ae>", branch], &[]).unwrap();
+    assert_eq!(author, "alice <alice@localhost>");
+}
+
+#[tokio::test]
+async fn merge_requests_are_recorded_as_refs() {
+    let (repo, forge) = forge("merge-request");
+    let alice = user("alice");
+    forge
+        .create_branch(&alice, "rec/ms/1r/alice", "main")
+        .await
+        .unwrap();
+    forge
+        .create_branch(&alice, "rec/ms/1r/bob", "main")
+        .await
+        .unwrap();
+
+    let first = forge
+        .open_merge_request(&alice, "rec/ms/1r/alice", "main", "Reconcile ms 1r")
+        .await
+        .unwrap();
+    let second = forge
+        .open_merge_request(&alice, "rec/ms/1r/bob", "main", "Reconcile ms 1r")
+        .await
+        .unwrap();
+    assert_eq!((first, second), (1, 2));
+    assert_eq!(
+        git_line(&repo.0, &["rev-parse", "refs/merge-requests/1/head"], &[]).unwrap(),
+        git_line(&repo.0, &["rev-parse", "refs/heads/rec/ms/1r/alice"], &[]).unwrap()
+    );
+    assert!(forge
+        .open_merge_request(&alice, "rec/ms/1r/alice", "missing", "Reconcile ms 1r")
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn unknown_users_cannot_change_the_repository() {
+    let (_repo, forge) = forge("unknown-user");
+    let mallory = user("mallory");
+    assert!(forge
+        .create_branch(&mallory, "rec/ms/1r/mallory", "main")
+        .await
+        .is_err());
+    assert!(forge
+        .commit_file(&mallory, "main", "ms/1r.tei.xml", "<TEI/>", "overwrite")
+        .await
+        .is_err());
+    assert_eq!(
+        forge.read_file("ms/1r.tei.xml", "main").await.unwrap(),
+        ACCEPTED
+    );
+}
+
+#[tokio::test]
+async fn refs_are_never_options() {
+    let (_repo, forge) = forge("options");
+    let alice = user("alice");
+    assert!(forge
+        .create_branch(&alice, "--force", "main")
+        .await
+        .is_err());
+    assert!(forge
+        .create_branch(&alice, "rec/ms/1r/alice", "--help")
+        .await
+        .is_err());
+    assert!(forge.list_files("--help").await.is_err());
+    assert!(forge
+        .read_file("ms/1r.tei.xml", "--output=x")
+        .await
+        .is_err());
+    assert_eq!(
+        forge.list_files("main").await.unwrap(),
+        vec!["ms/1r.tei.xml"]
+    );
+}
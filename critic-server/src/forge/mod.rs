@@ -0,0 +1,415 @@
+//! The forge hosting the repository of accepted transcriptions and knowing the users
+//!
+//! critic talks to its forge through the [`Forge`] trait: to find out who a user is and which role
+//! they have, and to create branches, commits and merge requests in the repository. Which forge is
+//! used is selected in the config (`forge = "gitlab"`, `forge = "forgejo"` or `forge = "local"`).
+//!
+//! The layout of the repository is the same for every forge: the accepted transcription of a page
+//! lives at `<manuscript>/<page>.tei.xml` and reconciliations are done on branches called
+//! `rec/<manuscript>/<page>/<user>`.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use crate::{
+    auth::{AuthenticatedUser, UserInfo},
+    config::Config,
+    gitlab::{GitlabApiError, GitlabUserRole},
+};
+
+pub mod forgejo;
+pub mod local;
+
+/// Problems talking to the forge
+#[derive(Debug)]
+pub enum ForgeError {
+    Gitlab(GitlabApiError),
+    Forgejo(forgejo::ForgejoApiError),
+    Local(local::LocalGitError),
+}
+impl core::fmt::Display for ForgeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Gitlab(e) => {
+                write!(f, "{e}")
+            }
+            Self::Forgejo(e) => {
+                write!(f, "{e}")
+            }
+            Self::Local(e) => {
+                write!(f, "{e}")
+            }
+        }
+    }
+}
+impl core::error::Error for ForgeError {}
+impl From<GitlabApiError> for ForgeError {
+    fn from(value: GitlabApiError) -> Self {
+        Self::Gitlab(value)
+    }
+}
+impl From<forgejo::ForgejoApiError> for ForgeError {
+    fn from(value: forgejo::ForgejoApiError) -> Self {
+        Self::Forgejo(value)
+    }
+}
+impl From<local::LocalGitError> for ForgeError {
+    fn from(value: local::LocalGitError) -> Self {
+        Self::Local(value)
+    }
+}
+
+/// Where users log in to a forge with oauth2
+#[derive(Debug, Clone)]
+pub struct OauthEndpoints {
+    pub auth_url: String,
+    pub token_url: String,
+    /// the scopes critic needs to act on behalf of the user
+    pub scopes: Vec<String>,
+}
+
+/// Everything critic needs from a forge
+///
+/// Operations changing the repository are done on behalf of `user`, so that branches, commits
+/// and merge requests are attributed to them.
+#[async_trait::async_trait]
+pub trait Forge: core::fmt::Debug + Send + Sync {
+    /// The oauth2 endpoints of the forge, None if users cannot log in with it
+    fn oauth_endpoints(&self) -> Option<OauthEndpoints>;
+
+    /// The user an access token (obtained by logging in) belongs to
+    async fn user_info(&self, access_token: &str) -> Result<UserInfo, ForgeError>;
+
+    /// The role a user has in the project
+    async fn user_role(&self, user: &AuthenticatedUser) -> Result<GitlabUserRole, ForgeError>;
+
+    /// Can the repository be changed on behalf of `user`?
+    ///
+    /// Forges acting with the access token of the user can only do so for users who logged in with
+    /// the forge, the token of any other login means nothing to them.
+    fn can_act_for(&self, user: &AuthenticatedUser) -> bool {
+        user.login == "forge"
+    }
+
+    /// The content of the file at `path` in the repository at `git_ref` (a branch or commit)
+    async fn read_file(&self, path: &str, git_ref: &str) -> Result<String, ForgeError>;
+
+    /// The paths of all files in the repository at `git_ref`, separated by `/`
+    async fn list_files(&self, git_ref: &str) -> Result<Vec<String>, ForgeError>;
+
+    /// The paths of the files added or modified between the commits `from` and `to`
+    ///
+    /// Forges that cannot compare commits list every file at `to`.
+    async fn changed_files(&self, from: &str, to: &str) -> Result<Vec<String>, ForgeError> {
+        let _ = from;
+        self.list_files(to).await
+    }
+
+    /// Create `branch`, starting at `from` (a branch or commit)
+    async fn create_branch(
+        &self,
+        user: &AuthenticatedUser,
+        branch: &str,
+        from: &str,
+    ) -> Result<(), ForgeError>;
+
+    /// Commit `content` as the file at `path` onto `branch`, creating the file if necessary
+    async fn commit_file(
+        &self,
+        user: &AuthenticatedUser,
+        branch: &str,
+        path: &str,
+        content: &str,
+        message: &str,
+    ) -> Result<(), ForgeError>;
+
+    /// Open a merge request from `source_branch` into `target_branch`, returning its id in the
+    /// project
+    async fn open_merge_request(
+        &self,
+        user: &AuthenticatedUser,
+        source_branch: &str,
+        target_branch: &str,
+        title: &str,
+    ) -> Result<i64, ForgeError>;
+}
+
+/// A forge that can be replaced while critic is running, when the config is reloaded
+///
+/// Every call goes to the forge that is current when the call starts.
+#[derive(Debug)]
+pub struct ReloadableForge {
+    current: RwLock<Arc<dyn Forge>>,
+}
+impl ReloadableForge {
+    pub fn new(forge: Arc<dyn Forge>) -> Self {
+        Self {
+            current: RwLock::new(forge),
+        }
+    }
+
+    /// Use `forge` for all calls from now on
+    pub fn replace(&self, forge: Arc<dyn Forge>) {
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = forge;
+    }
+
+    fn current(&self) -> Arc<dyn Forge> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl Forge for ReloadableForge {
+    fn oauth_endpoints(&self) -> Option<OauthEndpoints> {
+        self.current().oauth_endpoints()
+    }
+
+    async fn user_info(&self, access_token: &str) -> Result<UserInfo, ForgeError> {
+        self.current().user_info(access_token).await
+    }
+
+    async fn user_role(&self, user: &AuthenticatedUser) -> Result<GitlabUserRole, ForgeError> {
+        self.current().user_role(user).await
+    }
+
+    fn can_act_for(&self, user: &AuthenticatedUser) -> bool {
+        self.current().can_act_for(user)
+    }
+
+    async fn read_file(&self, path: &str, git_ref: &str) -> Result<String, ForgeError> {
+        self.current().read_file(path, git_ref).await
+    }
+
+    async fn list_files(&self, git_ref: &str) -> Result<Vec<String>, ForgeError> {
+        self.current().list_files(git_ref).await
+    }
+
+    async fn changed_files(&self, from: &str, to: &str) -> Result<Vec<String>, ForgeError> {
+        self.current().changed_files(from, to).await
+    }
+
+    async fn create_branch(
+        &self,
+        user: &AuthenticatedUser,
+        branch: &str,
+        from: &str,
+    ) -> Result<(), ForgeError> {
+        self.current().create_branch(user, branch, from).await
+    }
+
+    async fn commit_file(
+        &self,
+        user: &AuthenticatedUser,
+        branch: &str,
+        path: &str,
+        content: &str,
+        message: &str,
+    ) -> Result<(), ForgeError> {
+        self.current()
+            .commit_file(user, branch, path, content, message)
+            .await
+    }
+
+    async fn open_merge_request(
+        &self,
+        user: &AuthenticatedUser,
+        source_branch: &str,
+        target_branch: &str,
+        title: &str,
+    ) -> Result<i64, ForgeError> {
+        self.current()
+            .open_merge_request(user, source_branch, target_branch, title)
+            .await
+    }
+}
+
+/// Get the role of a user in the project
+///
+/// Users who did not log in with the forge have the role they got at login.
+pub async fn get_user_role(
+    config: Arc<Config>,
+    user: &AuthenticatedUser,
+) -> Result<GitlabUserRole, ForgeError> {
+    match user.role {
+        Some(role) => Ok(GitlabUserRole::try_from(role).unwrap_or(GitlabUserRole::NoAccess)),
+        None => config.forge.user_role(user).await,
+    }
+}
+
+/// The prefix of all branches holding a reconciliation
+const RECONCILIATION_BRANCH_PREFIX: &str = "rec/";
+
+/// Encode a part of a branch name, so that every manuscript, page and username gives a valid ref
+///
+/// Everything except ASCII letters, digits, `-` and `_` is percent-encoded, e.g. `oidc:a.b` becomes
+/// `oidc%3Aa%2Eb`. This keeps out everything git does not allow in refs (spaces, `:`, `..`, ...).
+fn encode_ref_component(component: &str) -> String {
+    let mut res = String::with_capacity(component.len());
+    for byte in component.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            res.push(char::from(byte));
+        } else {
+            res.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    res
+}
+
+/// The branch `username` reconciles a page on: `rec/<manuscript>/<page>/<user>`
+///
+/// The parts are encoded with [`encode_ref_component`].
+pub fn reconciliation_branch(msname: &str, pagename: &str, username: &str) -> String {
+    format!(
+        "{RECONCILIATION_BRANCH_PREFIX}{}/{}/{}",
+        encode_ref_component(msname),
+        encode_ref_component(pagename),
+        encode_ref_component(username)
+    )
+}
+
+/// The manuscript, page and user of a reconciliation branch
+///
+/// None if this is not a reconciliation branch.
+pub fn parse_reconciliation_branch(branch: &str) -> Option<(String, String, String)> {
+    let mut parts = branch
+        .strip_prefix(RECONCILIATION_BRANCH_PREFIX)?
+        .split('/');
+    let (msname, pagename, username) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None;
+    };
+    let decode = |part: &str| urlencoding::decode(part).ok().map(|part| part.into_owned());
+    Some((decode(msname)?, decode(pagename)?, decode(username)?))
+}
+
+/// The path of the accepted transcription of a page in the repository
+pub fn repository_path(msname: &str, pagename: &str) -> String {
+    format!("{msname}/{pagename}.tei.xml")
+}
+
+/// The manuscript and page of a transcription in the repository
+///
+/// None if the path is not a transcription.
+pub fn parse_repository_path(path: &str) -> Option<(&str, &str)> {
+    let (msname, pagename) = path.strip_suffix(".tei.xml")?.split_once('/')?;
+    (!pagename.contains('/')).then_some((msname, pagename))
+}
+
+/// Problems reading files from the repository
+#[derive(Debug)]
+pub enum RepositoryError {
+    Forge(ForgeError),
+    LocalClone(std::io::Error),
+}
+impl core::fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Forge(e) => {
+                write!(f, "Unable to read the repository from the forge: {e}")
+            }
+            Self::LocalClone(e) => {
+                write!(f, "Unable to read the local clone of the repository: {e}")
+            }
+        }
+    }
+}
+impl core::error::Error for RepositoryError {}
+
+/// Where the files of the repository are read from
+#[derive(Debug, Clone)]
+pub enum RepositorySource {
+    /// the forge configured for critic
+    Forge,
+    /// a local clone of the repository, checked out at the state to read
+    ///
+    /// The git ref passed to the functions reading from the repository is ignored.
+    LocalClone(PathBuf),
+}
+impl RepositorySource {
+    /// Read the file at `path` in the repository at `git_ref`
+    pub async fn read(
+        &self,
+        config: &Config,
+        path: &str,
+        git_ref: &str,
+    ) -> Result<String, RepositoryError> {
+        match self {
+            Self::Forge => config
+                .forge
+                .read_file(path, git_ref)
+                .await
+                .map_err(RepositoryError::Forge),
+            Self::LocalClone(dir) => {
+                std::fs::read_to_string(dir.join(path)).map_err(RepositoryError::LocalClone)
+            }
+        }
+    }
+
+    /// The paths of all files in the repository at `git_ref`, separated by `/`
+    pub async fn list(
+        &self,
+        config: &Config,
+        git_ref: &str,
+    ) -> Result<Vec<String>, RepositoryError> {
+        match self {
+            Self::Forge => config
+                .forge
+                .list_files(git_ref)
+                .await
+                .map_err(RepositoryError::Forge),
+            Self::LocalClone(dir) => {
+                let mut res = vec![];
+                list_local_files(dir, "", &mut res).map_err(RepositoryError::LocalClone)?;
+                res.sort();
+                Ok(res)
+            }
+        }
+    }
+
+    /// The paths of the files added or modified between the commits `from` and `to`
+    ///
+    /// A local clone has no history to compare, all its files count as changed.
+    pub async fn changed(
+        &self,
+        config: &Config,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<String>, RepositoryError> {
+        match self {
+            Self::Forge => config
+                .forge
+                .changed_files(from, to)
+                .await
+                .map_err(RepositoryError::Forge),
+            Self::LocalClone(_) => self.list(config, to).await,
+        }
+    }
+}
+
+/// Add the paths of all files below `dir` to `res`, prefixed with `prefix`
+///
+/// The `.git` directory is skipped.
+fn list_local_files(dir: &Path, prefix: &str, res: &mut Vec<String>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == ".git" {
+            continue;
+        };
+        let path = format!("{prefix}{name}");
+        if entry.file_type()?.is_dir() {
+            list_local_files(&entry.path(), &format!("{path}/"), res)?;
+        } else {
+            res.push(path);
+        };
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test;
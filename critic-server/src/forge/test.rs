@@ -0,0 +1,83 @@
+//! Tests for the naming of branches and files in the repository
+
+use super::{
+    parse_reconciliation_branch, parse_repository_path, reconciliation_branch, repository_path,
+};
+
+/// Does git accept `branch` as the name of a branch?
+fn valid_branch(branch: &str) -> bool {
+    std::process::Command::new("git")
+        .args(["check-ref-format", "--branch", branch])
+        .output()
+        .expect("git can be run")
+        .status
+        .success()
+}
+
+#[test]
+fn plain_names_stay_readable() {
+    let branch = reconciliation_branch("Leningradensis", "1r", "alice");
+    assert_eq!(branch, "rec/Leningradensis/1r/alice");
+    assert_eq!(
+        parse_reconciliation_branch(&branch),
+        Some((
+            "Leningradensis".to_string(),
+            "1r".to_string(),
+            "alice".to_string()
+        ))
+    );
+}
+
+#[test]
+fn spaced_titles_and_oidc_users_give_valid_branches() {
+    let branch = reconciliation_branch("Codex Aleppo", "12v", "oidc:f81d4fae-7dec");
+    assert_eq!(branch, "rec/Codex%20Aleppo/12v/oidc%3Af81d4fae-7dec");
+    assert!(valid_branch(&branch), "{branch}");
+    assert_eq!(
+        parse_reconciliation_branch(&branch),
+        Some((
+            "Codex Aleppo".to_string(),
+            "12v".to_string(),
+            "oidc:f81d4fae-7dec".to_string()
+        ))
+    );
+}
+
+#[test]
+fn branches_are_valid_for_any_name() {
+    for (msname, pagename, username) in [
+        ("..", ".lock", "a@{b"),
+        ("ms/with/slashes", "page~1^2", "user?*[x]"),
+        ("כתר ארם צובה", "1r.", "oidc:..%2Falice"),
+    ] {
+        let branch = reconciliation_branch(msname, pagename, username);
+        assert!(valid_branch(&branch), "{branch}");
+        assert_eq!(
+            parse_reconciliation_branch(&branch),
+            Some((
+                msname.to_string(),
+                pagename.to_string(),
+                username.to_string()
+            ))
+        );
+    }
+}
+
+#[test]
+fn other_branches_are_not_reconciliations() {
+    assert_eq!(parse_reconciliation_branch("main"), None);
+    assert_eq!(parse_reconciliation_branch("rec/Leningradensis/1r"), None);
+    assert_eq!(
+        parse_reconciliation_branch("rec/Leningradensis/1r/alice/x"),
+        None
+    );
+}
+
+#[test]
+fn repository_paths_round_trip() {
+    let path = repository_path("Codex Aleppo", "12v");
+    assert_eq!(path, "Codex Aleppo/12v.tei.xml");
+    assert_eq!(parse_repository_path(&path), Some(("Codex Aleppo", "12v")));
+    assert_eq!(parse_repository_path("README.md"), None);
+    assert_eq!(parse_repository_path("a/b/c.tei.xml"), None);
+}
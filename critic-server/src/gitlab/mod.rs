@@ -1,11 +1,18 @@
 //! Communicate with gitlabs api
 
-use std::{cmp::Ordering, sync::Arc};
+use std::cmp::Ordering;
 
+use axum::http::header::USER_AGENT;
 use reqwest::StatusCode;
 use serde::Deserialize;
 
-use crate::{auth::AuthenticatedUser, config::Config};
+use crate::{
+    auth::{AuthenticatedUser, UserInfo},
+    config::GitlabConfig,
+    forge::{Forge, ForgeError, OauthEndpoints},
+};
+
+pub mod webhook;
 
 /// The base URL in gitlab we communicate with - directly after the server name
 const API_BASE_URL: &str = "/api/v4";
@@ -20,6 +27,8 @@ pub enum GitlabApiError {
     UserNotGroupMember(i32),
     /// The status code from gitlabs api was what we assumed
     BadStatusCode(StatusCode),
+    /// No access token for requests without a logged-in user is configured
+    NoAccessToken,
 }
 impl From<reqwest::Error> for GitlabApiError {
     fn from(value: reqwest::Error) -> Self {
@@ -44,11 +53,22 @@ impl core::fmt::Display for GitlabApiError {
             Self::BadStatusCode(code) => {
                 write!(f, "Got the following status code: {code} from gitlab API.")
             }
+            Self::NoAccessToken => {
+                write!(
+                    f,
+                    "No gitlab access token is configured in gitlab.access_token."
+                )
+            }
         }
     }
 }
 impl core::error::Error for GitlabApiError {}
 
+/// The role of a user in the project
+///
+/// These are the access levels of gitlab, other forges map their roles onto them.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum GitlabUserRole {
     NoAccess,
     Minimal,
@@ -115,29 +135,340 @@ struct GroupMember {
     access_level: i32,
 }
 
-pub async fn get_user_role(
-    config: Arc<Config>,
-    user: &AuthenticatedUser,
-) -> Result<GitlabUserRole, GitlabApiError> {
-    let encoded_group_name = urlencoding::encode(&config.gitlab.group_name);
-    let request_url = format!(
-        "https://{}/{API_BASE_URL}/groups/{}/members/{}",
-        config.gitlab.addr, encoded_group_name, user.id
-    );
-    let response = reqwest::Client::new()
-        .get(request_url)
-        .bearer_auth(user.access_token.clone())
-        .send()
-        .await?;
-    match response.status() {
-        StatusCode::NOT_FOUND => Err(GitlabApiError::UserNotGroupMember(user.id)),
-        StatusCode::OK => {
-            let response = response.json::<GroupMember>().await?;
-            Ok(response
-                .access_level
-                .try_into()
-                .map_err(|_| GitlabApiError::UserRoleDoesNotExist(response.access_level))?)
+/// A file or directory in the repository tree
+#[derive(Deserialize)]
+struct TreeEntry {
+    path: String,
+    /// `blob` for files, `tree` for directories
+    #[serde(rename = "type")]
+    entry_type: String,
+}
+
+/// A changed file in the comparison of two commits
+#[derive(Deserialize)]
+struct CompareDiff {
+    new_path: String,
+    deleted_file: bool,
+}
+
+/// The comparison of two commits
+#[derive(Deserialize)]
+struct Comparison {
+    diffs: Vec<CompareDiff>,
+}
+impl Comparison {
+    /// The paths of all files that still exist after the compared changes
+    fn changed_paths(self) -> Vec<String> {
+        self.diffs
+            .into_iter()
+            .filter(|diff| !diff.deleted_file)
+            .map(|diff| diff.new_path)
+            .collect()
+    }
+}
+
+/// The part of a created merge request we are interested in
+#[derive(Deserialize)]
+struct CreatedMergeRequest {
+    iid: i64,
+}
+
+/// Fail with the status code unless the request was successful
+fn check_status(response: reqwest::Response) -> Result<reqwest::Response, GitlabApiError> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(GitlabApiError::BadStatusCode(response.status()))
+    }
+}
+
+/// The gitlab instance hosting the project
+#[derive(Debug, Clone)]
+pub struct GitlabForge {
+    addr: String,
+    group_name: String,
+    project_name: String,
+    access_token: Option<String>,
+}
+impl GitlabForge {
+    pub fn new(config: &GitlabConfig) -> Self {
+        Self {
+            addr: config.addr.clone(),
+            group_name: config.group_name.clone(),
+            project_name: config.project_name.clone(),
+            access_token: config.access_token.clone(),
         }
-        c => Err(GitlabApiError::BadStatusCode(c)),
+    }
+
+    /// The url of an API endpoint, `path` starting with a /
+    fn api_url(&self, path: &str) -> String {
+        format!("https://{}{API_BASE_URL}{path}", self.addr)
+    }
+
+    /// The url of an API endpoint of the project, `path` starting with a /
+    ///
+    /// Gitlab identifies the project by its full path in the group, url-encoded as a whole.
+    fn project_url(&self, path: &str) -> String {
+        // the project name is configured url-encoded, but must only be encoded once here
+        let project_name = urlencoding::decode(&self.project_name)
+            .map(|name| name.into_owned())
+            .unwrap_or_else(|_| self.project_name.clone());
+        let project = format!("{}/{project_name}", self.group_name);
+        self.api_url(&format!(
+            "/projects/{}{path}",
+            urlencoding::encode(&project)
+        ))
+    }
+
+    /// The url of a file in the repository
+    fn file_url(&self, path: &str) -> String {
+        self.project_url(&format!("/repository/files/{}", urlencoding::encode(path)))
+    }
+
+    /// The configured access token for requests without a logged-in user
+    fn access_token(&self) -> Result<&str, GitlabApiError> {
+        self.access_token
+            .as_deref()
+            .ok_or(GitlabApiError::NoAccessToken)
+    }
+
+    /// Get the content of the file at `path` in the repository at `git_ref` (a branch or commit)
+    ///
+    /// This uses the access token from the config, not the token of a user.
+    pub async fn get_repository_file(
+        &self,
+        path: &str,
+        git_ref: &str,
+    ) -> Result<String, GitlabApiError> {
+        let response = reqwest::Client::new()
+            .get(format!("{}/raw", self.file_url(path)))
+            .query(&[("ref", git_ref)])
+            .header("PRIVATE-TOKEN", self.access_token()?)
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK => Ok(response.text().await?),
+            c => Err(GitlabApiError::BadStatusCode(c)),
+        }
+    }
+
+    /// Get the paths of all files in the repository at `git_ref`
+    ///
+    /// This uses the access token from the config, not the token of a user.
+    pub async fn list_repository_files(
+        &self,
+        git_ref: &str,
+    ) -> Result<Vec<String>, GitlabApiError> {
+        let access_token = self.access_token()?;
+        let request_url = self.project_url("/repository/tree");
+        let client = reqwest::Client::new();
+        let mut res = vec![];
+        let mut page = "1".to_string();
+        loop {
+            let response = client
+                .get(&request_url)
+                .query(&[
+                    ("ref", git_ref),
+                    ("recursive", "true"),
+                    ("per_page", "100"),
+                    ("page", &page),
+                ])
+                .header("PRIVATE-TOKEN", access_token)
+                .send()
+                .await?;
+            if response.status() != StatusCode::OK {
+                return Err(GitlabApiError::BadStatusCode(response.status()));
+            };
+            // gitlab leaves this header empty on the last page
+            let next_page = response
+                .headers()
+                .get("x-next-page")
+                .and_then(|v| v.to_str().ok())
+                .filter(|v| !v.is_empty())
+                .map(str::to_string);
+            res.extend(
+                response
+                    .json::<Vec<TreeEntry>>()
+                    .await?
+                    .into_iter()
+                    .filter(|entry| entry.entry_type == "blob")
+                    .map(|entry| entry.path),
+            );
+            match next_page {
+                Some(next) => page = next,
+                None => return Ok(res),
+            };
+        }
+    }
+
+    /// Get the paths of the files added or modified between the commits `from` and `to`
+    ///
+    /// This uses the access token from the config, not the token of a user.
+    pub async fn compare_repository_files(
+        &self,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<String>, GitlabApiError> {
+        let response = reqwest::Client::new()
+            .get(self.project_url("/repository/compare"))
+            .query(&[("from", from), ("to", to)])
+            .header("PRIVATE-TOKEN", self.access_token()?)
+            .send()
+            .await?;
+        Ok(check_status(response)?
+            .json::<Comparison>()
+            .await?
+            .changed_paths())
+    }
+}
+
+#[async_trait::async_trait]
+impl Forge for GitlabForge {
+    fn oauth_endpoints(&self) -> Option<OauthEndpoints> {
+        Some(OauthEndpoints {
+            auth_url: format!("https://{}/oauth/authorize", self.addr),
+            token_url: format!("https://{}/oauth/token", self.addr),
+            scopes: vec!["api".to_string()],
+        })
+    }
+
+    async fn user_info(&self, access_token: &str) -> Result<UserInfo, ForgeError> {
+        let response = reqwest::Client::new()
+            .get(self.api_url("/user"))
+            .header(USER_AGENT.as_str(), "axum-login") // See: https://docs.github.com/en/rest/overview/resources-in-the-rest-api?apiVersion=2022-11-28#user-agent-required
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(GitlabApiError::Reqwest)?;
+        Ok(check_status(response)?
+            .json::<UserInfo>()
+            .await
+            .map_err(GitlabApiError::Reqwest)?)
+    }
+
+    async fn user_role(&self, user: &AuthenticatedUser) -> Result<GitlabUserRole, ForgeError> {
+        let encoded_group_name = urlencoding::encode(&self.group_name);
+        let request_url = self.api_url(&format!(
+            "/groups/{}/members/{}",
+            encoded_group_name, user.id
+        ));
+        let response = reqwest::Client::new()
+            .get(request_url)
+            .bearer_auth(user.access_token.clone())
+            .send()
+            .await
+            .map_err(GitlabApiError::Reqwest)?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Err(GitlabApiError::UserNotGroupMember(user.id).into()),
+            StatusCode::OK => {
+                let response = response
+                    .json::<GroupMember>()
+                    .await
+                    .map_err(GitlabApiError::Reqwest)?;
+                Ok(response
+                    .access_level
+                    .try_into()
+                    .map_err(|_| GitlabApiError::UserRoleDoesNotExist(response.access_level))?)
+            }
+            c => Err(GitlabApiError::BadStatusCode(c).into()),
+        }
+    }
+
+    async fn read_file(&self, path: &str, git_ref: &str) -> Result<String, ForgeError> {
+        Ok(self.get_repository_file(path, git_ref).await?)
+    }
+
+    async fn list_files(&self, git_ref: &str) -> Result<Vec<String>, ForgeError> {
+        Ok(self.list_repository_files(git_ref).await?)
+    }
+
+    async fn changed_files(&self, from: &str, to: &str) -> Result<Vec<String>, ForgeError> {
+        Ok(self.compare_repository_files(from, to).await?)
+    }
+
+    async fn create_branch(
+        &self,
+        user: &AuthenticatedUser,
+        branch: &str,
+        from: &str,
+    ) -> Result<(), ForgeError> {
+        let response = reqwest::Client::new()
+            .post(self.project_url("/repository/branches"))
+            .query(&[("branch", branch), ("ref", from)])
+            .bearer_auth(&user.access_token)
+            .send()
+            .await
+            .map_err(GitlabApiError::Reqwest)?;
+        check_status(response)?;
+        Ok(())
+    }
+
+    async fn commit_file(
+        &self,
+        user: &AuthenticatedUser,
+        branch: &str,
+        path: &str,
+        content: &str,
+        message: &str,
+    ) -> Result<(), ForgeError> {
+        let client = reqwest::Client::new();
+        // gitlab needs to know whether the file is new
+        let existing = client
+            .head(self.file_url(path))
+            .query(&[("ref", branch)])
+            .bearer_auth(&user.access_token)
+            .send()
+            .await
+            .map_err(GitlabApiError::Reqwest)?;
+        let action = match existing.status() {
+            StatusCode::OK => "update",
+            StatusCode::NOT_FOUND => "create",
+            c => return Err(GitlabApiError::BadStatusCode(c).into()),
+        };
+        let response = client
+            .post(self.project_url("/repository/commits"))
+            .bearer_auth(&user.access_token)
+            .json(&serde_json::json!({
+                "branch": branch,
+                "commit_message": message,
+                "actions": [{
+                    "action": action,
+                    "file_path": path,
+                    "content": content,
+                }],
+            }))
+            .send()
+            .await
+            .map_err(GitlabApiError::Reqwest)?;
+        check_status(response)?;
+        Ok(())
+    }
+
+    async fn open_merge_request(
+        &self,
+        user: &AuthenticatedUser,
+        source_branch: &str,
+        target_branch: &str,
+        title: &str,
+    ) -> Result<i64, ForgeError> {
+        let response = reqwest::Client::new()
+            .post(self.project_url("/merge_requests"))
+            .bearer_auth(&user.access_token)
+            .json(&serde_json::json!({
+                "source_branch": source_branch,
+                "target_branch": target_branch,
+                "title": title,
+            }))
+            .send()
+            .await
+            .map_err(GitlabApiError::Reqwest)?;
+        Ok(check_status(response)?
+            .json::<CreatedMergeRequest>()
+            .await
+            .map_err(GitlabApiError::Reqwest)?
+            .iid)
     }
 }
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,57 @@
+//! Tests for talking to gitlab
+
+use super::{Comparison, GitlabForge};
+use crate::config::GitlabConfig;
+
+fn forge(project_name: &str) -> GitlabForge {
+    GitlabForge::new(&GitlabConfig {
+        addr: "gitlab.example.org".to_string(),
+        group_name: "bible/critic".to_string(),
+        project_name: project_name.to_string(),
+        access_token: None,
+        webhook_secret: None,
+        accepted_branch: None,
+    })
+}
+
+#[test]
+fn project_url_contains_the_encoded_project_path() {
+    assert_eq!(
+        forge("manuscripts").project_url("/repository/tree"),
+        "https://gitlab.example.org/api/v4/projects/bible%2Fcritic%2Fmanuscripts/repository/tree"
+    );
+}
+
+#[test]
+fn project_url_encodes_the_project_name_once() {
+    assert_eq!(
+        forge("dead%20sea").project_url(""),
+        forge("dead sea").project_url("")
+    );
+    assert_eq!(
+        forge("dead sea").file_url("ms/1r.tei.xml"),
+        "https://gitlab.example.org/api/v4/projects/bible%2Fcritic%2Fdead%20sea/repository/files/ms%2F1r.tei.xml"
+    );
+}
+
+#[test]
+fn comparisons_list_the_files_that_still_exist() {
+    let comparison: Comparison = serde_json::from_str(
+        r#"{
+            "commit": {"id": "c0ffee00112233445566778899aabbccddeeff00"},
+            "commits": [],
+            "diffs": [
+                {"old_path": "Codex A/1r.tei.xml", "new_path": "Codex A/1r.tei.xml", "a_mode": "100644", "b_mode": "100644", "new_file": false, "renamed_file": false, "deleted_file": false, "diff": ""},
+                {"old_path": "Codex A/2r.tei.xml", "new_path": "Codex A/2r.tei.xml", "a_mode": "100644", "b_mode": "0", "new_file": false, "renamed_file": false, "deleted_file": true, "diff": ""},
+                {"old_path": "Codex A/3r.tei.xml", "new_path": "Codex A/3v.tei.xml", "a_mode": "100644", "b_mode": "100644", "new_file": false, "renamed_file": true, "deleted_file": false, "diff": ""}
+            ],
+            "compare_timeout": false,
+            "compare_same_ref": false
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        comparison.changed_paths(),
+        vec!["Codex A/1r.tei.xml", "Codex A/3v.tei.xml"]
+    );
+}
@@ -0,0 +1,420 @@
+//! Webhooks from gitlab keeping reconciliations in sync with their branches and merge requests
+//!
+//! Merge request events track the state of the merge request of a reconciliation branch. When it
+//! is merged into the accepted branch, the page is marked as accepted and the merged file is
+//! re-imported into the reconciled directory. Push events open reconciliations for new branches,
+//! close them when their branch is deleted and re-import every transcription touched on the
+//! accepted branch, so that changes made directly in gitlab are picked up as well.
+//!
+//! Recorded payloads (the request bodies gitlab sent) can be replayed with [`replay_payloads`].
+
+use std::{collections::BTreeSet, path::Path, sync::Arc};
+
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use critic_shared::reconciliation::ReconciliationStatus;
+use serde::Deserialize;
+
+use crate::{
+    config::Config,
+    db::{
+        get_page, insert_or_update_open_reconciliation, mark_page_accepted,
+        set_reconciliation_status, DBError,
+    },
+    forge::{
+        parse_reconciliation_branch, parse_repository_path, repository_path, RepositoryError,
+        RepositorySource,
+    },
+    tei::{parse_tei, reconciled_path, TeiError},
+};
+
+/// The header gitlab puts the webhook secret into
+const TOKEN_HEADER: &str = "X-Gitlab-Token";
+
+#[derive(Debug)]
+pub enum WebhookError {
+    /// The payload is not a webhook event we understand
+    Payload(serde_json::Error),
+    DB(DBError),
+    Repository(RepositoryError),
+    /// The merged file is not a valid transcription
+    Tei(TeiError),
+    /// The merged file cannot be written or a recorded payload cannot be read
+    Io(std::io::Error),
+}
+impl core::fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Payload(e) => {
+                write!(f, "Unable to parse webhook payload: {e}")
+            }
+            Self::DB(e) => {
+                write!(f, "Database error while handling webhook: {e}")
+            }
+            Self::Repository(e) => {
+                write!(f, "Unable to get the merged file: {e}")
+            }
+            Self::Tei(e) => {
+                write!(f, "The merged file is not a valid transcription: {e}")
+            }
+            Self::Io(e) => {
+                write!(f, "IO error while handling webhook: {e}")
+            }
+        }
+    }
+}
+impl core::error::Error for WebhookError {}
+impl From<DBError> for WebhookError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+
+/// The attributes of a merge request we are interested in
+#[derive(Debug, Deserialize)]
+pub struct MergeRequestAttributes {
+    /// the id of the merge request in the project
+    pub iid: i64,
+    pub source_branch: String,
+    pub target_branch: String,
+    /// opened, closed, locked or merged
+    pub state: String,
+    pub merge_commit_sha: Option<String>,
+}
+
+/// A commit in a push event
+#[derive(Debug, Deserialize)]
+pub struct PushCommit {
+    #[serde(default)]
+    pub added: Vec<String>,
+    #[serde(default)]
+    pub modified: Vec<String>,
+}
+
+/// A push to a branch (or tag)
+#[derive(Debug, Deserialize)]
+pub struct PushEvent {
+    /// the full ref, e.g. `refs/heads/main`
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    /// the commit before the push, all zeros if the branch was created
+    pub before: String,
+    /// the commit after the push, all zeros if the branch was deleted
+    pub after: String,
+    /// gitlab sends at most 20 commits, this is the number of all pushed commits
+    #[serde(default)]
+    pub total_commits_count: usize,
+    #[serde(default)]
+    pub commits: Vec<PushCommit>,
+}
+
+/// Is `sha` the null commit gitlab sends for created and deleted branches?
+fn is_null_commit(sha: &str) -> bool {
+    sha.chars().all(|c| c == '0')
+}
+
+/// A webhook event sent by gitlab
+#[derive(Debug, Deserialize)]
+#[serde(tag = "object_kind", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    MergeRequest {
+        object_attributes: MergeRequestAttributes,
+    },
+    Push(PushEvent),
+    /// any other event is ignored
+    #[serde(other)]
+    Other,
+}
+
+/// Copy the accepted transcription of a page from the repository into the reconciled directory
+/// and mark the page as accepted
+async fn import_accepted(
+    config: &Config,
+    source: &RepositorySource,
+    msname: &str,
+    pagename: &str,
+    git_ref: &str,
+) -> Result<(), WebhookError> {
+    let page = get_page(&config.db, msname, pagename).await?;
+    let content = source
+        .read(config, &repository_path(msname, pagename), git_ref)
+        .await
+        .map_err(WebhookError::Repository)?;
+    // only accept files the editor can represent
+    parse_tei(&content).map_err(WebhookError::Tei)?;
+    let path = reconciled_path(&config.data_directory, msname, pagename);
+    if let Some(dir) = Path::new(&path).parent() {
+        std::fs::create_dir_all(dir).map_err(WebhookError::Io)?;
+    };
+    std::fs::write(&path, content).map_err(WebhookError::Io)?;
+    mark_page_accepted(&config.db, page.id).await?;
+    tracing::info!("Imported the accepted transcription of {msname} {pagename}");
+    Ok(())
+}
+
+/// What critic does for a webhook event
+#[derive(Debug, PartialEq)]
+pub enum WebhookAction {
+    /// the event does not concern critic
+    Ignore,
+    /// open the reconciliation on `branch`, or note its merge request
+    Open {
+        msname: String,
+        pagename: String,
+        username: String,
+        branch: String,
+        merge_request: Option<i64>,
+    },
+    /// the reconciliation on `branch` was abandoned
+    Close { branch: String },
+    /// the reconciliation on `branch` was merged into the accepted branch at `git_ref`
+    Accept {
+        msname: String,
+        pagename: String,
+        branch: String,
+        git_ref: String,
+    },
+    /// re-import the accepted transcriptions changed by a push to the accepted branch
+    ImportPushed {
+        before: String,
+        after: String,
+        /// the added and modified files, None if the payload does not list all pushed commits
+        files: Option<Vec<String>>,
+    },
+}
+
+fn plan_merge_request(mr: MergeRequestAttributes, accepted_branch: &str) -> WebhookAction {
+    let Some((msname, pagename, username)) = parse_reconciliation_branch(&mr.source_branch) else {
+        tracing::debug!("Ignoring merge request from {}", mr.source_branch);
+        return WebhookAction::Ignore;
+    };
+    match mr.state.as_str() {
+        "merged" if mr.target_branch == accepted_branch => WebhookAction::Accept {
+            msname,
+            pagename,
+            git_ref: mr.merge_commit_sha.unwrap_or(mr.target_branch),
+            branch: mr.source_branch,
+        },
+        "merged" => {
+            tracing::info!(
+                "Ignoring merge of {} into {}, which is not the accepted branch",
+                mr.source_branch,
+                mr.target_branch
+            );
+            WebhookAction::Ignore
+        }
+        "closed" => WebhookAction::Close {
+            branch: mr.source_branch,
+        },
+        _ => WebhookAction::Open {
+            msname,
+            pagename,
+            username,
+            branch: mr.source_branch,
+            merge_request: Some(mr.iid),
+        },
+    }
+}
+
+fn plan_push(push: PushEvent, accepted_branch: &str) -> WebhookAction {
+    let Some(branch) = push.git_ref.strip_prefix("refs/heads/") else {
+        // tags are not interesting to us
+        return WebhookAction::Ignore;
+    };
+    let deleted = is_null_commit(&push.after);
+    if let Some((msname, pagename, username)) = parse_reconciliation_branch(branch) {
+        if deleted {
+            WebhookAction::Close {
+                branch: branch.to_string(),
+            }
+        } else {
+            WebhookAction::Open {
+                msname,
+                pagename,
+                username,
+                branch: branch.to_string(),
+                merge_request: None,
+            }
+        }
+    } else if branch == accepted_branch && !deleted {
+        let files = (push.total_commits_count <= push.commits.len()).then(|| {
+            push.commits
+                .iter()
+                .flat_map(|commit| commit.added.iter().chain(&commit.modified))
+                .cloned()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect()
+        });
+        WebhookAction::ImportPushed {
+            before: push.before,
+            after: push.after,
+            files,
+        }
+    } else {
+        WebhookAction::Ignore
+    }
+}
+
+/// What critic does for `event`, given the name of the accepted branch
+pub fn plan_event(event: WebhookEvent, accepted_branch: &str) -> WebhookAction {
+    match event {
+        WebhookEvent::MergeRequest { object_attributes } => {
+            plan_merge_request(object_attributes, accepted_branch)
+        }
+        WebhookEvent::Push(push) => plan_push(push, accepted_branch),
+        WebhookEvent::Other => WebhookAction::Ignore,
+    }
+}
+
+/// Carry out what a webhook event asks for
+async fn run_action(
+    config: &Config,
+    source: &RepositorySource,
+    action: WebhookAction,
+) -> Result<(), WebhookError> {
+    match action {
+        WebhookAction::Ignore => Ok(()),
+        WebhookAction::Open {
+            msname,
+            pagename,
+            username,
+            branch,
+            merge_request,
+        } => {
+            let page = get_page(&config.db, &msname, &pagename).await?;
+            Ok(insert_or_update_open_reconciliation(
+                &config.db,
+                page.id,
+                &username,
+                &branch,
+                merge_request,
+            )
+            .await?)
+        }
+        WebhookAction::Close { branch } => {
+            Ok(
+                set_reconciliation_status(&config.db, &branch, ReconciliationStatus::Closed)
+                    .await?,
+            )
+        }
+        WebhookAction::Accept {
+            msname,
+            pagename,
+            branch,
+            git_ref,
+        } => {
+            set_reconciliation_status(&config.db, &branch, ReconciliationStatus::Accepted).await?;
+            import_accepted(config, source, &msname, &pagename, &git_ref).await
+        }
+        WebhookAction::ImportPushed {
+            before,
+            after,
+            files,
+        } => {
+            let files = match files {
+                Some(files) => files,
+                // a created branch has nothing to compare with
+                None if is_null_commit(&before) => source
+                    .list(config, &after)
+                    .await
+                    .map_err(WebhookError::Repository)?,
+                None => source
+                    .changed(config, &before, &after)
+                    .await
+                    .map_err(WebhookError::Repository)?,
+            };
+            let touched = files.iter().map(String::as_str).collect::<BTreeSet<_>>();
+            for (msname, pagename) in touched.into_iter().filter_map(parse_repository_path) {
+                import_accepted(config, source, msname, pagename, &after).await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Handle a single webhook payload (the body of the request gitlab sends)
+pub async fn handle_payload(
+    config: &Config,
+    source: &RepositorySource,
+    payload: &[u8],
+) -> Result<(), WebhookError> {
+    let event = serde_json::from_slice(payload).map_err(WebhookError::Payload)?;
+    run_action(config, source, plan_event(event, &config.accepted_branch)).await
+}
+
+/// Replay all recorded payloads (`*.json`) in `directory` in the order of their file names
+///
+/// Stops at the first payload that cannot be handled.
+pub async fn replay_payloads(
+    config: &Config,
+    source: &RepositorySource,
+    directory: &Path,
+) -> Result<usize, WebhookError> {
+    let mut paths = std::fs::read_dir(directory)
+        .map_err(WebhookError::Io)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(WebhookError::Io)?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+    paths.sort();
+    for path in &paths {
+        tracing::info!("Replaying webhook payload {}", path.display());
+        let payload = std::fs::read(path).map_err(WebhookError::Io)?;
+        handle_payload(config, source, &payload).await?;
+    }
+    Ok(paths.len())
+}
+
+/// Compare two secrets in time independent of their content
+fn secrets_match(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Receive a webhook from gitlab
+pub async fn gitlab_webhook(
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(secret) = config
+        .runtime()
+        .gitlab
+        .and_then(|gitlab| gitlab.webhook_secret)
+    else {
+        tracing::warn!("Rejecting gitlab webhook: gitlab.webhook_secret is not configured.");
+        return StatusCode::FORBIDDEN.into_response();
+    };
+    let token = headers.get(TOKEN_HEADER).map(|v| v.as_bytes());
+    if !token.is_some_and(|token| secrets_match(token, secret.as_bytes())) {
+        tracing::warn!("Rejecting gitlab webhook with a wrong secret.");
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    match handle_payload(&config, &RepositorySource::Forge, &body).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(e @ WebhookError::Payload(_)) => {
+            (StatusCode::BAD_REQUEST, e.to_string()).into_response()
+        }
+        Err(e) => {
+            tracing::warn!("Failed to handle gitlab webhook: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+/// The routes receiving webhooks
+///
+/// These must not require a login, the requests are verified by their secret instead.
+pub fn webhook_router() -> axum::Router {
+    axum::Router::new().route(
+        critic_shared::urls::GITLAB_WEBHOOK_API_ENDPOINT,
+        axum::routing::post(gitlab_webhook),
+    )
+}
+
+#[cfg(test)]
+mod test;
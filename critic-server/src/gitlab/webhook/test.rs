@@ -0,0 +1,190 @@
+//! Tests for the gitlab webhooks, using payloads recorded from gitlab
+
+use super::{plan_event, secrets_match, WebhookAction, WebhookEvent};
+
+const ACCEPTED: &str = "accepted";
+
+fn event(payload: &str) -> WebhookEvent {
+    serde_json::from_str(payload).unwrap()
+}
+
+fn plan(payload: &str) -> WebhookAction {
+    plan_event(event(payload), ACCEPTED)
+}
+
+fn open(merge_request: Option<i64>) -> WebhookAction {
+    WebhookAction::Open {
+        msname: "Codex A".to_string(),
+        pagename: "1r".to_string(),
+        username: "alice".to_string(),
+        branch: "rec/Codex%20A/1r/alice".to_string(),
+        merge_request,
+    }
+}
+
+#[test]
+fn merge_request_payloads_are_parsed() {
+    let WebhookEvent::MergeRequest { object_attributes } =
+        event(include_str!("payloads/merge_request_merged.json"))
+    else {
+        panic!("expected a merge request event");
+    };
+    assert_eq!(object_attributes.iid, 4);
+    assert_eq!(object_attributes.source_branch, "rec/Codex%20A/1r/alice");
+    assert_eq!(object_attributes.target_branch, "accepted");
+    assert_eq!(object_attributes.state, "merged");
+    assert_eq!(
+        object_attributes.merge_commit_sha.as_deref(),
+        Some("a4b1c2d3e4f5061728394a5b6c7d8e9f00112233")
+    );
+}
+
+#[test]
+fn push_payloads_are_parsed() {
+    let WebhookEvent::Push(push) = event(include_str!("payloads/push_accepted.json")) else {
+        panic!("expected a push event");
+    };
+    assert_eq!(push.git_ref, "refs/heads/accepted");
+    assert_eq!(push.before, "a4b1c2d3e4f5061728394a5b6c7d8e9f00112233");
+    assert_eq!(push.after, "c0ffee00112233445566778899aabbccddeeff00");
+    assert_eq!(push.total_commits_count, 2);
+    assert_eq!(push.commits.len(), 2);
+    assert_eq!(
+        push.commits[1].added,
+        vec!["Codex A/1v.tei.xml", "notes/README.md"]
+    );
+}
+
+#[test]
+fn other_events_are_parsed_as_other() {
+    assert!(matches!(
+        event(include_str!("payloads/tag_push.json")),
+        WebhookEvent::Other
+    ));
+    assert!(matches!(
+        event(r#"{"object_kind": "pipeline", "object_attributes": {"id": 1}}"#),
+        WebhookEvent::Other
+    ));
+    assert!(serde_json::from_str::<WebhookEvent>(r#"{"ref": "refs/heads/main"}"#).is_err());
+}
+
+#[test]
+fn opened_merge_requests_are_noted() {
+    assert_eq!(
+        plan(include_str!("payloads/merge_request_opened.json")),
+        open(Some(4))
+    );
+}
+
+#[test]
+fn merged_merge_requests_are_accepted_at_the_merge_commit() {
+    assert_eq!(
+        plan(include_str!("payloads/merge_request_merged.json")),
+        WebhookAction::Accept {
+            msname: "Codex A".to_string(),
+            pagename: "1r".to_string(),
+            branch: "rec/Codex%20A/1r/alice".to_string(),
+            git_ref: "a4b1c2d3e4f5061728394a5b6c7d8e9f00112233".to_string(),
+        }
+    );
+}
+
+#[test]
+fn merges_into_other_branches_are_ignored() {
+    assert_eq!(
+        plan_event(
+            event(include_str!("payloads/merge_request_merged.json")),
+            "main"
+        ),
+        WebhookAction::Ignore
+    );
+}
+
+#[test]
+fn closed_merge_requests_close_the_reconciliation() {
+    assert_eq!(
+        plan(include_str!("payloads/merge_request_closed.json")),
+        WebhookAction::Close {
+            branch: "rec/Codex%20A/1r/alice".to_string()
+        }
+    );
+}
+
+#[test]
+fn merge_requests_of_other_branches_are_ignored() {
+    assert_eq!(
+        plan(include_str!("payloads/merge_request_other_branch.json")),
+        WebhookAction::Ignore
+    );
+}
+
+#[test]
+fn pushed_reconciliation_branches_are_opened_and_closed() {
+    assert_eq!(
+        plan(include_str!("payloads/push_branch_created.json")),
+        open(None)
+    );
+    assert_eq!(
+        plan(include_str!("payloads/push_branch_deleted.json")),
+        WebhookAction::Close {
+            branch: "rec/Codex%20A/1r/alice".to_string()
+        }
+    );
+}
+
+#[test]
+fn pushes_to_the_accepted_branch_import_the_listed_files() {
+    assert_eq!(
+        plan(include_str!("payloads/push_accepted.json")),
+        WebhookAction::ImportPushed {
+            before: "a4b1c2d3e4f5061728394a5b6c7d8e9f00112233".to_string(),
+            after: "c0ffee00112233445566778899aabbccddeeff00".to_string(),
+            files: Some(vec![
+                "Codex A/1r.tei.xml".to_string(),
+                "Codex A/1v.tei.xml".to_string(),
+                "notes/README.md".to_string(),
+            ]),
+        }
+    );
+}
+
+#[test]
+fn truncated_pushes_leave_the_files_to_a_comparison() {
+    assert_eq!(
+        plan(include_str!("payloads/push_accepted_truncated.json")),
+        WebhookAction::ImportPushed {
+            before: "a4b1c2d3e4f5061728394a5b6c7d8e9f00112233".to_string(),
+            after: "c0ffee00112233445566778899aabbccddeeff00".to_string(),
+            files: None,
+        }
+    );
+}
+
+#[test]
+fn tags_and_other_branches_are_ignored() {
+    assert_eq!(
+        plan(include_str!("payloads/tag_push.json")),
+        WebhookAction::Ignore
+    );
+    // the tag ref also keeps tags out when they are sent as push events
+    let tag = include_str!("payloads/tag_push.json").replace("\"tag_push\"", "\"push\"");
+    assert_eq!(plan(&tag), WebhookAction::Ignore);
+    assert_eq!(
+        plan_event(event(include_str!("payloads/push_accepted.json")), "main"),
+        WebhookAction::Ignore
+    );
+    // deleting the accepted branch imports nothing
+    let deleted = include_str!("payloads/push_accepted.json")
+        .replace("c0ffee00112233445566778899aabbccddeeff00", &"0".repeat(40));
+    assert_eq!(plan(&deleted), WebhookAction::Ignore);
+}
+
+#[test]
+fn secrets_must_match_exactly() {
+    assert!(secrets_match(b"hook-secret", b"hook-secret"));
+    assert!(secrets_match(b"", b""));
+    assert!(!secrets_match(b"hook-secret", b"hook-secreT"));
+    assert!(!secrets_match(b"hook-secret", b"hook-secret2"));
+    assert!(!secrets_match(b"hook", b"hook-secret"));
+    assert!(!secrets_match(b"", b"hook-secret"));
+}
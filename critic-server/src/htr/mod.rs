@@ -0,0 +1,357 @@
+//! Import the output of handwritten text recognition (HTR) or OCR as a base transcription
+//!
+//! Both PAGE XML and ALTO are supported. Text regions (PAGE) and text blocks (ALTO) become
+//! columns, lines become Text blocks separated by line breaks. Words recognised with a confidence
+//! below a threshold become Uncertain blocks, with the confidence in `cert`.
+
+use critic_format::streamed::{Block, BlockType, BreakType, FromTypeLangAndContent, Paragraph};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use crate::tei::line_and_column;
+
+/// The agent set on Uncertain blocks created for low-confidence words
+const HTR_AGENT: &str = "htr";
+
+/// Problems reading HTR/OCR output
+#[derive(Debug)]
+pub enum HtrError {
+    /// The file is not well-formed XML
+    Syntax {
+        line: usize,
+        column: usize,
+        error: quick_xml::Error,
+    },
+    /// The root element is neither PAGE XML nor ALTO
+    UnknownFormat(String),
+    /// The file does not contain any recognised text
+    NoText,
+}
+impl core::fmt::Display for HtrError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Syntax {
+                line,
+                column,
+                error,
+            } => {
+                write!(
+                    f,
+                    "XML syntax error at line {line}, column {column}: {error}"
+                )
+            }
+            Self::UnknownFormat(root) => {
+                write!(
+                    f,
+                    "Unknown root element {root}. Expected PAGE XML (PcGts) or ALTO (alto)."
+                )
+            }
+            Self::NoText => {
+                write!(f, "The file does not contain any recognised text.")
+            }
+        }
+    }
+}
+impl core::error::Error for HtrError {}
+
+/// A single recognised word
+#[derive(Debug, Clone, PartialEq)]
+struct Word {
+    text: String,
+    /// confidence between 0 and 1, if given
+    conf: Option<f64>,
+}
+
+/// A text region (PAGE) or text block (ALTO), containing lines of words
+type Region = Vec<Vec<Word>>;
+
+/// The local name (without namespace prefix) of an element
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).to_string()
+}
+
+/// The (unescaped) value of the attribute `name`, if present
+fn attribute(e: &BytesStart, name: &str) -> Option<String> {
+    e.try_get_attribute(name)
+        .ok()
+        .flatten()
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.to_string())
+}
+
+/// The confidence given in the attribute `name`, if present and valid
+fn confidence(e: &BytesStart, name: &str) -> Option<f64> {
+    attribute(e, name).and_then(|v| v.trim().parse::<f64>().ok())
+}
+
+/// Split a text into words, all with the same confidence
+fn split_words(text: &str, conf: Option<f64>) -> Vec<Word> {
+    text.split_whitespace()
+        .map(|w| Word {
+            text: w.to_string(),
+            conf,
+        })
+        .collect()
+}
+
+fn syntax_error(content: &str, reader: &Reader<&[u8]>, error: quick_xml::Error) -> HtrError {
+    let (line, column) = line_and_column(content, reader.error_position() as usize);
+    HtrError::Syntax {
+        line,
+        column,
+        error,
+    }
+}
+
+/// Add a finished line to the last region, creating a region if there is none yet
+fn push_line(regions: &mut Vec<Region>, line: Vec<Word>) {
+    if regions.is_empty() {
+        regions.push(vec![]);
+    };
+    if let Some(region) = regions.last_mut() {
+        region.push(line);
+    };
+}
+
+/// Parse PAGE XML
+///
+/// If a line has Words, their first TextEquiv is used, otherwise the first TextEquiv of the
+/// line is split into words.
+fn parse_page_xml(content: &str) -> Result<Vec<Region>, HtrError> {
+    let mut reader = Reader::from_str(content);
+    let mut regions: Vec<Region> = vec![];
+    // names of all currently open elements
+    let mut stack: Vec<String> = vec![];
+    let mut line_words: Vec<Word> = vec![];
+    let mut line_equiv: Option<Word> = None;
+    let mut word_equiv: Option<Word> = None;
+    // the TextEquiv we are currently in
+    let mut equiv: Option<Word> = None;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = local_name(&e);
+                match name.as_str() {
+                    "TextRegion" => regions.push(vec![]),
+                    "TextLine" => {
+                        line_words.clear();
+                        line_equiv = None;
+                    }
+                    "Word" => word_equiv = None,
+                    "TextEquiv" => {
+                        equiv = Some(Word {
+                            text: String::default(),
+                            conf: confidence(&e, "conf"),
+                        })
+                    }
+                    _ => {}
+                };
+                stack.push(name);
+            }
+            Ok(Event::Text(e)) => {
+                if let (Some("Unicode"), Some(word)) =
+                    (stack.last().map(String::as_str), equiv.as_mut())
+                {
+                    match reader.decoder().decode(&e) {
+                        Ok(text) => word.text.push_str(&text),
+                        Err(error) => {
+                            return Err(syntax_error(content, &reader, error.into()));
+                        }
+                    };
+                };
+            }
+            // entity and character references inside the text, e.g. &amp;
+            Ok(Event::GeneralRef(e)) => {
+                if let (Some("Unicode"), Some(word)) =
+                    (stack.last().map(String::as_str), equiv.as_mut())
+                {
+                    if let Ok(Some(c)) = e.resolve_char_ref() {
+                        word.text.push(c);
+                    } else if let Some(resolved) = reader
+                        .decoder()
+                        .decode(&e)
+                        .ok()
+                        .and_then(|name| quick_xml::escape::resolve_predefined_entity(&name))
+                    {
+                        word.text.push_str(resolved);
+                    };
+                };
+            }
+            Ok(Event::End(_)) => {
+                let Some(name) = stack.pop() else {
+                    continue;
+                };
+                match name.as_str() {
+                    "TextEquiv" => {
+                        let Some(word) = equiv.take() else {
+                            continue;
+                        };
+                        match stack.last().map(String::as_str) {
+                            Some("Word") if word_equiv.is_none() => word_equiv = Some(word),
+                            Some("TextLine") if line_equiv.is_none() => line_equiv = Some(word),
+                            _ => {}
+                        };
+                    }
+                    "Word" => {
+                        if let Some(word) = word_equiv.take() {
+                            line_words.extend(split_words(&word.text, word.conf));
+                        };
+                    }
+                    "TextLine" => {
+                        let mut words = std::mem::take(&mut line_words);
+                        if words.is_empty() {
+                            if let Some(line) = line_equiv.take() {
+                                words = split_words(&line.text, line.conf);
+                            };
+                        };
+                        push_line(&mut regions, words);
+                    }
+                    _ => {}
+                };
+            }
+            Ok(Event::Eof) => return Ok(regions),
+            Ok(_) => {}
+            Err(error) => return Err(syntax_error(content, &reader, error)),
+        }
+    }
+}
+
+/// Handle a (start or empty) element in ALTO
+fn alto_element(e: &BytesStart, regions: &mut Vec<Region>, line: &mut Vec<Word>) {
+    match local_name(e).as_str() {
+        "TextBlock" => regions.push(vec![]),
+        "TextLine" => line.clear(),
+        "String" => {
+            if let Some(text) = attribute(e, "CONTENT") {
+                line.extend(split_words(&text, confidence(e, "WC")));
+            };
+        }
+        // the hyphen at the end of a line belongs to the last word
+        "HYP" => {
+            if let (Some(hyphen), Some(last)) = (attribute(e, "CONTENT"), line.last_mut()) {
+                last.text.push_str(&hyphen);
+            };
+        }
+        _ => {}
+    }
+}
+
+/// Parse ALTO
+fn parse_alto(content: &str) -> Result<Vec<Region>, HtrError> {
+    let mut reader = Reader::from_str(content);
+    let mut regions: Vec<Region> = vec![];
+    let mut line: Vec<Word> = vec![];
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                alto_element(&e, &mut regions, &mut line);
+            }
+            Ok(Event::End(e)) => {
+                if e.local_name().as_ref() == b"TextLine" {
+                    push_line(&mut regions, std::mem::take(&mut line));
+                };
+            }
+            Ok(Event::Eof) => return Ok(regions),
+            Ok(_) => {}
+            Err(error) => return Err(syntax_error(content, &reader, error)),
+        }
+    }
+}
+
+/// The local name of the root element of `content`
+fn root_element(content: &str) -> Result<String, HtrError> {
+    let mut reader = Reader::from_str(content);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => return Ok(local_name(&e)),
+            Ok(Event::Eof) => return Err(HtrError::NoText),
+            Ok(_) => {}
+            Err(error) => return Err(syntax_error(content, &reader, error)),
+        }
+    }
+}
+
+/// Turn the recognised regions into blocks
+///
+/// Regions are separated by column breaks, lines by line breaks. Empty lines and regions are
+/// dropped.
+fn regions_to_blocks(regions: Vec<Region>, lang: &str, min_confidence: f64) -> Vec<Block> {
+    let mut res = vec![];
+    let regions = regions
+        .into_iter()
+        .map(|region| {
+            region
+                .into_iter()
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .filter(|region| !region.is_empty());
+    for (region_idx, region) in regions.enumerate() {
+        if region_idx != 0 {
+            res.push(Block::Break(BreakType::Column));
+        };
+        for (line_idx, line) in region.into_iter().enumerate() {
+            if line_idx != 0 {
+                res.push(Block::Break(BreakType::Line));
+            };
+            // confident words are collected into a single Text block
+            let mut text = String::default();
+            for (word_idx, word) in line.into_iter().enumerate() {
+                if word_idx != 0 {
+                    text.push(' ');
+                };
+                match word.conf {
+                    Some(conf) if conf < min_confidence => {
+                        if !text.is_empty() {
+                            res.push(Block::Text(Paragraph {
+                                lang: lang.to_string(),
+                                content: std::mem::take(&mut text),
+                            }));
+                        };
+                        let mut block = Block::from_type_lang_and_content(
+                            BlockType::Uncertain,
+                            lang.to_string(),
+                            word.text,
+                        );
+                        if let Block::Uncertain(ref mut uncertain) = block {
+                            uncertain.cert = Some(format!("{conf:.2}"));
+                            uncertain.agent = HTR_AGENT.to_string();
+                        };
+                        res.push(block);
+                    }
+                    _ => text.push_str(&word.text),
+                };
+            }
+            if !text.is_empty() {
+                res.push(Block::Text(Paragraph {
+                    lang: lang.to_string(),
+                    content: text,
+                }));
+            };
+        }
+    }
+    res
+}
+
+/// Convert PAGE XML or ALTO into blocks for a new transcription
+///
+/// The format is detected from the root element. Words with a confidence below
+/// `min_confidence` become Uncertain blocks.
+pub fn htr_blocks(content: &str, lang: &str, min_confidence: f64) -> Result<Vec<Block>, HtrError> {
+    let regions = match root_element(content)?.as_str() {
+        "PcGts" => parse_page_xml(content)?,
+        "alto" => parse_alto(content)?,
+        other => return Err(HtrError::UnknownFormat(other.to_string())),
+    };
+    let blocks = regions_to_blocks(regions, lang, min_confidence);
+    if blocks.is_empty() {
+        Err(HtrError::NoText)
+    } else {
+        Ok(blocks)
+    }
+}
+
+#[cfg(test)]
+mod test;
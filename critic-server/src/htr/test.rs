@@ -0,0 +1,201 @@
+//! Tests for the HTR import, using files recorded from Transkribus (PAGE XML) and eScriptorium (ALTO)
+
+use critic_format::streamed::{Block, BlockType, BreakType, FromTypeLangAndContent, Paragraph};
+
+use super::{htr_blocks, parse_alto, parse_page_xml, regions_to_blocks, HtrError, Region, Word};
+
+const PAGE_XML: &str = include_str!("fixtures/transkribus.page.xml");
+const ALTO: &str = include_str!("fixtures/escriptorium.alto.xml");
+const LANG: &str = "hbo-Hebr";
+
+fn word(text: &str, conf: Option<f64>) -> Word {
+    Word {
+        text: text.to_string(),
+        conf,
+    }
+}
+
+fn text(content: &str) -> Block {
+    Block::Text(Paragraph {
+        lang: LANG.to_string(),
+        content: content.to_string(),
+    })
+}
+
+fn uncertain(content: &str, cert: &str) -> Block {
+    let mut block = Block::from_type_lang_and_content(
+        BlockType::Uncertain,
+        LANG.to_string(),
+        content.to_string(),
+    );
+    if let Block::Uncertain(ref mut uncertain) = block {
+        uncertain.cert = Some(cert.to_string());
+        uncertain.agent = "htr".to_string();
+    };
+    block
+}
+
+#[test]
+fn page_xml_regions_and_lines_are_read() {
+    let regions = parse_page_xml(PAGE_XML).unwrap();
+    let expected: Vec<Region> = vec![
+        vec![
+            // the words of a line take precedence over the text of the line
+            vec![
+                word("בראשית", Some(0.97)),
+                word("ברא", Some(0.42)),
+                word("אלהים", Some(0.91)),
+            ],
+            // without words, the text of the line is split
+            vec![
+                word("את", Some(0.88)),
+                word("השמים", Some(0.88)),
+                word("&", Some(0.88)),
+                word("את", Some(0.88)),
+            ],
+            vec![],
+        ],
+        vec![vec![word("הארץ", Some(0.35))]],
+        vec![],
+    ];
+    assert_eq!(regions, expected);
+}
+
+#[test]
+fn alto_blocks_and_lines_are_read() {
+    let regions = parse_alto(ALTO).unwrap();
+    let expected: Vec<Region> = vec![
+        vec![
+            vec![
+                word("ויאמר", Some(0.96)),
+                word("אלהים", Some(0.58)),
+                word("יהי", Some(0.55)),
+                // the hyphen is part of the last word
+                word("או-", Some(0.93)),
+            ],
+            vec![],
+            vec![word("ר", None), word("ויהי", None)],
+        ],
+        vec![vec![word("אור", Some(0.99))]],
+    ];
+    assert_eq!(regions, expected);
+}
+
+#[test]
+fn lines_without_region_get_one() {
+    let regions = parse_page_xml(
+        r#"<PcGts><Page><TextLine><TextEquiv><Unicode>אור</Unicode></TextEquiv></TextLine></Page></PcGts>"#,
+    )
+    .unwrap();
+    assert_eq!(regions, vec![vec![vec![word("אור", None)]]]);
+}
+
+#[test]
+fn uncertain_words_split_the_line() {
+    assert_eq!(
+        htr_blocks(PAGE_XML, LANG, 0.5).unwrap(),
+        vec![
+            text("בראשית "),
+            uncertain("ברא", "0.42"),
+            text(" אלהים"),
+            Block::Break(BreakType::Line),
+            text("את השמים & את"),
+            Block::Break(BreakType::Column),
+            uncertain("הארץ", "0.35"),
+        ]
+    );
+    // consecutive uncertain words stay separated by a space, words without confidence are kept
+    assert_eq!(
+        htr_blocks(ALTO, LANG, 0.6).unwrap(),
+        vec![
+            text("ויאמר "),
+            uncertain("אלהים", "0.58"),
+            text(" "),
+            uncertain("יהי", "0.55"),
+            text(" או-"),
+            Block::Break(BreakType::Line),
+            text("ר ויהי"),
+            Block::Break(BreakType::Column),
+            text("אור"),
+        ]
+    );
+}
+
+#[test]
+fn without_threshold_all_words_are_text() {
+    assert_eq!(
+        htr_blocks(PAGE_XML, LANG, 0.0).unwrap(),
+        vec![
+            text("בראשית ברא אלהים"),
+            Block::Break(BreakType::Line),
+            text("את השמים & את"),
+            Block::Break(BreakType::Column),
+            text("הארץ"),
+        ]
+    );
+}
+
+#[test]
+fn the_threshold_itself_is_confident() {
+    assert_eq!(
+        regions_to_blocks(
+            vec![vec![vec![word("אור", Some(0.5)), word("יהי", Some(0.499))]]],
+            LANG,
+            0.5
+        ),
+        vec![text("אור "), uncertain("יהי", "0.50")]
+    );
+}
+
+#[test]
+fn empty_lines_and_regions_are_dropped() {
+    assert!(regions_to_blocks(vec![vec![], vec![vec![]]], LANG, 0.5).is_empty());
+    assert_eq!(
+        regions_to_blocks(
+            vec![
+                vec![vec![]],
+                vec![vec![], vec![word("אור", None)], vec![]],
+                vec![],
+                vec![vec![word("יהי", None)]],
+            ],
+            LANG,
+            0.5
+        ),
+        vec![text("אור"), Block::Break(BreakType::Column), text("יהי"),]
+    );
+}
+
+#[test]
+fn the_format_is_detected_from_the_root_element() {
+    let prefixed = r#"<pc:PcGts xmlns:pc="http://schema.primaresearch.org/PAGE/gts/pagecontent/2013-07-15"><pc:Page><pc:TextRegion><pc:TextLine><pc:TextEquiv><pc:Unicode>אור</pc:Unicode></pc:TextEquiv></pc:TextLine></pc:TextRegion></pc:Page></pc:PcGts>"#;
+    assert_eq!(htr_blocks(prefixed, LANG, 0.5).unwrap(), vec![text("אור")]);
+    assert!(matches!(
+        htr_blocks(r#"<?xml version="1.0"?><TEI/>"#, LANG, 0.5),
+        Err(HtrError::UnknownFormat(root)) if root == "TEI"
+    ));
+}
+
+#[test]
+fn files_without_text_are_rejected() {
+    assert!(matches!(htr_blocks("", LANG, 0.5), Err(HtrError::NoText)));
+    assert!(matches!(
+        htr_blocks("<PcGts><Page><TextRegion/></Page></PcGts>", LANG, 0.5),
+        Err(HtrError::NoText)
+    ));
+    assert!(matches!(
+        htr_blocks(
+            "<alto><Layout><TextBlock><TextLine></TextLine></TextBlock></Layout></alto>",
+            LANG,
+            0.5
+        ),
+        Err(HtrError::NoText)
+    ));
+}
+
+#[test]
+fn syntax_errors_have_a_position() {
+    assert!(matches!(
+        htr_blocks("<alto>\n<Layout></alto>", LANG, 0.5),
+        Err(HtrError::Syntax { line: 2, .. })
+    ));
+}
@@ -2,11 +2,24 @@
 //!
 //! Also contains some axum routes that are static or directly linked to external APIs (like the
 //! oauth flow).
+pub mod agreement;
 pub mod auth;
+pub mod cli;
+pub mod collation;
 pub mod config;
+pub mod consistency;
 pub mod db;
+pub mod export;
+pub mod forge;
 pub mod gitlab;
+pub mod htr;
 pub mod minification;
+pub mod rebuild;
+pub mod reconciliation;
+pub mod reference;
+pub mod session;
 pub mod signal_handler;
 pub mod static_files;
+pub mod tei;
+pub mod tls;
 pub mod upload;
@@ -116,12 +116,25 @@ pub async fn run_minification(
     mut watcher: tokio::sync::watch::Receiver<InShutdown>,
 ) {
     tracing::debug!("Starting the minification service");
+    // the number of threads the pool was built for and the pool (None if building it failed)
+    let mut pool: Option<(u8, Option<Arc<rayon::ThreadPool>>)> = None;
     loop {
-        let wait_till_next_minification = match get_page_to_minify(
-            &config.db,
-            config.worker_threads,
-        )
-        .await
+        // read for every batch, so that a reloaded config applies to the next one
+        let worker_threads = config.runtime().worker_threads;
+        if pool.as_ref().map(|(threads, _)| *threads) != Some(worker_threads) {
+            let new_pool = match rayon::ThreadPoolBuilder::new()
+                .num_threads(worker_threads.into())
+                .build()
+            {
+                Ok(new_pool) => Some(Arc::new(new_pool)),
+                Err(e) => {
+                    tracing::warn!("Failed to create a thread pool for minification, using the global one: {e}");
+                    None
+                }
+            };
+            pool = Some((worker_threads, new_pool));
+        };
+        let wait_till_next_minification = match get_page_to_minify(&config.db, worker_threads).await
         {
             Ok(pages) => {
                 if pages.is_empty() {
@@ -129,23 +142,30 @@ pub async fn run_minification(
                     tokio::time::Duration::from_secs(1)
                 } else {
                     let config_arc = config.clone();
+                    let pool = pool.as_ref().and_then(|(_, pool)| pool.clone());
                     // attempt the minifications in parallel, without blocking this thread
                     let minify_results: Vec<(Result<(), MinificationError>, String, PageMeta)> =
                         tokio::task::spawn_blocking(move || {
-                            pages
-                                .into_par_iter()
-                                .map(|(msname, page_to_minify)| {
-                                    (
-                                        minify_page(
-                                            &config_arc.data_directory,
-                                            &msname,
-                                            &page_to_minify,
-                                        ),
-                                        msname,
-                                        page_to_minify,
-                                    )
-                                })
-                                .collect::<Vec<_>>()
+                            let minify_all = || {
+                                pages
+                                    .into_par_iter()
+                                    .map(|(msname, page_to_minify)| {
+                                        (
+                                            minify_page(
+                                                &config_arc.data_directory,
+                                                &msname,
+                                                &page_to_minify,
+                                            ),
+                                            msname,
+                                            page_to_minify,
+                                        )
+                                    })
+                                    .collect::<Vec<_>>()
+                            };
+                            match pool {
+                                Some(pool) => pool.install(minify_all),
+                                None => minify_all(),
+                            }
                         })
                         .await
                         .unwrap();
@@ -0,0 +1,315 @@
+//! Rebuild the database from the repository in the forge
+//!
+//! The repository is authoritative for the accepted transcriptions and, through their TEI
+//! headers, for the metadata of the manuscripts. A rebuild reads every transcription in the
+//! accepted branch and checks that it can be parsed. It then stages both sides: manuscripts,
+//! pages and their accepted state in a scratch schema (see [`stage_rebuild`]), and the
+//! transcriptions in a directory next to the reconciled directory. Only if all of this succeeds
+//! are the changes applied: the reconciled directory is swapped first, and the database changes
+//! are committed only after the swap succeeded. If the commit fails, the swap is undone.
+
+use std::{collections::BTreeMap, path::Path};
+
+use critic_shared::{urls::RECONCILED_BASE_LOCATION, ManuscriptMeta};
+
+use crate::{
+    config::Config,
+    db::{apply_rebuild, drop_rebuild, stage_rebuild, DBError, RebuildDiff},
+    forge::{parse_repository_path, RepositoryError, RepositorySource},
+    tei::{parse_tei, TeiError},
+};
+
+#[derive(Debug)]
+pub enum RebuildError {
+    Repository(RepositoryError),
+    /// Some transcriptions in the repository cannot be parsed
+    InvalidFiles(Vec<(String, TeiError)>),
+    /// The pages of a manuscript disagree on the metadata of the manuscript
+    InconsistentMetadata(String),
+    DB(DBError),
+    /// The reconciled directory cannot be replaced
+    Io(std::io::Error),
+}
+impl core::fmt::Display for RebuildError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Repository(e) => {
+                write!(f, "{e}")
+            }
+            Self::InvalidFiles(files) => {
+                write!(f, "{} transcriptions cannot be parsed:", files.len())?;
+                for (path, e) in files {
+                    write!(f, "\n{path}: {e}")?;
+                }
+                Ok(())
+            }
+            Self::InconsistentMetadata(msname) => {
+                write!(
+                    f,
+                    "The pages of the manuscript {msname} have different metadata in their TEI headers"
+                )
+            }
+            Self::DB(e) => {
+                write!(f, "{e}")
+            }
+            Self::Io(e) => {
+                write!(f, "Unable to replace the reconciled transcriptions: {e}")
+            }
+        }
+    }
+}
+impl core::error::Error for RebuildError {}
+
+/// What a rebuild changed (or would change in a dry run)
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct RebuildReport {
+    /// the changes to the database
+    pub db: RebuildDiff,
+    /// accepted transcriptions (manuscript, page) whose file differs from the reconciled directory
+    pub changed_files: Vec<(String, String)>,
+    /// was this a dry run, i.e. nothing was applied?
+    pub dry_run: bool,
+}
+impl core::fmt::Display for RebuildReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let verb = if self.dry_run { "would be" } else { "were" };
+        let section = |f: &mut core::fmt::Formatter, what: &str, entries: Vec<String>| {
+            if entries.is_empty() {
+                Ok(())
+            } else {
+                writeln!(f, "{} {what} {verb} changed:", entries.len())?;
+                entries.iter().try_for_each(|e| writeln!(f, "  {e}"))
+            }
+        };
+        let pages = |pages: &[(String, String)]| {
+            pages
+                .iter()
+                .map(|(msname, pagename)| format!("{msname} {pagename}"))
+                .collect::<Vec<_>>()
+        };
+        section(f, "manuscripts (added)", self.db.added_manuscripts.clone())?;
+        section(
+            f,
+            "manuscripts (metadata)",
+            self.db.updated_manuscripts.clone(),
+        )?;
+        section(f, "pages (added)", pages(&self.db.added_pages))?;
+        section(f, "pages (now accepted)", pages(&self.db.accepted_pages))?;
+        section(
+            f,
+            "pages (no longer accepted)",
+            pages(&self.db.unaccepted_pages),
+        )?;
+        section(f, "accepted transcriptions", pages(&self.changed_files))?;
+        if self.db == RebuildDiff::default() && self.changed_files.is_empty() {
+            writeln!(f, "The database matches the repository.")?;
+        };
+        Ok(())
+    }
+}
+
+/// The metadata of a manuscript as given in the TEI header of one of its pages
+fn header_meta(msname: &str, meta: critic_format::normalized::Meta) -> ManuscriptMeta {
+    ManuscriptMeta {
+        id: 0,
+        title: msname.to_string(),
+        institution: meta.institution,
+        collection: meta.collection,
+        hand_desc: meta.hand_desc,
+        script_desc: meta.script_desc,
+    }
+}
+
+/// Write the transcriptions (manuscript, page, content) into `staging_dir`, returning those that
+/// differ from the files in `reconciled_dir`
+///
+/// With `dry_run`, nothing is written.
+fn stage_files(
+    files: &[(String, String, String)],
+    reconciled_dir: &str,
+    staging_dir: &str,
+    dry_run: bool,
+) -> std::io::Result<Vec<(String, String)>> {
+    if Path::new(staging_dir).exists() {
+        std::fs::remove_dir_all(staging_dir)?;
+    };
+    if !dry_run {
+        // also when the repository contains no transcriptions
+        std::fs::create_dir_all(staging_dir)?;
+    };
+    let mut changed = vec![];
+    for (msname, pagename, content) in files {
+        let relative = format!("{msname}/{pagename}.tei.xml");
+        let current = std::fs::read_to_string(format!("{reconciled_dir}/{relative}")).ok();
+        if current.as_ref() != Some(content) {
+            changed.push((msname.clone(), pagename.clone()));
+        };
+        if !dry_run {
+            std::fs::create_dir_all(format!("{staging_dir}/{msname}"))?;
+            std::fs::write(format!("{staging_dir}/{relative}"), content)?;
+        };
+    }
+    Ok(changed)
+}
+
+/// Where [`swap_directories`] keeps the replaced reconciled directory
+fn replaced_directory(reconciled_dir: &str) -> String {
+    format!("{reconciled_dir}.old")
+}
+
+/// Replace `reconciled_dir` with `staging_dir`, keeping the replaced directory until
+/// [`remove_replaced_directory`] or [`restore_directories`]
+fn swap_directories(reconciled_dir: &str, staging_dir: &str) -> std::io::Result<()> {
+    let old_dir = replaced_directory(reconciled_dir);
+    if Path::new(&old_dir).exists() {
+        std::fs::remove_dir_all(&old_dir)?;
+    };
+    if Path::new(reconciled_dir).exists() {
+        std::fs::rename(reconciled_dir, &old_dir)?;
+    };
+    if let Err(e) = std::fs::rename(staging_dir, reconciled_dir) {
+        if Path::new(&old_dir).exists() {
+            std::fs::rename(&old_dir, reconciled_dir)?;
+        };
+        return Err(e);
+    };
+    Ok(())
+}
+
+/// Undo [`swap_directories`], moving the staged files back to `staging_dir`
+fn restore_directories(reconciled_dir: &str, staging_dir: &str) -> std::io::Result<()> {
+    std::fs::rename(reconciled_dir, staging_dir)?;
+    let old_dir = replaced_directory(reconciled_dir);
+    if Path::new(&old_dir).exists() {
+        std::fs::rename(&old_dir, reconciled_dir)?;
+    };
+    Ok(())
+}
+
+/// Remove the directory replaced by [`swap_directories`]
+fn remove_replaced_directory(reconciled_dir: &str) -> std::io::Result<()> {
+    let old_dir = replaced_directory(reconciled_dir);
+    if Path::new(&old_dir).exists() {
+        std::fs::remove_dir_all(&old_dir)?;
+    };
+    Ok(())
+}
+
+/// Apply a staged rebuild: swap in the staged files, then commit the database changes
+///
+/// If the swap fails, the database changes are rolled back. If the commit fails, the swap is
+/// undone.
+async fn apply_staged(
+    config: &Config,
+    reconciled_dir: &str,
+    staging_dir: &str,
+) -> Result<(), RebuildError> {
+    let tx = apply_rebuild(&config.db).await.map_err(RebuildError::DB)?;
+    if let Err(e) = swap_directories(reconciled_dir, staging_dir) {
+        if let Err(e) = tx.rollback().await {
+            tracing::warn!("Unable to roll back the rebuild of the database: {e}");
+        };
+        return Err(RebuildError::Io(e));
+    };
+    if let Err(e) = tx.commit().await {
+        if let Err(e) = restore_directories(reconciled_dir, staging_dir) {
+            tracing::error!(
+                "Unable to restore the reconciled transcriptions from {} after the rebuild of the database failed: {e}",
+                replaced_directory(reconciled_dir)
+            );
+        };
+        return Err(RebuildError::DB(DBError::CannotCommitTransaction(e)));
+    };
+    if let Err(e) = remove_replaced_directory(reconciled_dir) {
+        tracing::warn!("Unable to remove the replaced reconciled transcriptions: {e}");
+    };
+    Ok(())
+}
+
+/// Rebuild the database from the accepted branch of the repository
+///
+/// With `dry_run`, the rebuild is only built and compared, but nothing is changed.
+pub async fn rebuild(
+    config: &Config,
+    source: &RepositorySource,
+    dry_run: bool,
+) -> Result<RebuildReport, RebuildError> {
+    let git_ref = &config.accepted_branch;
+    let paths = source
+        .list(config, git_ref)
+        .await
+        .map_err(RebuildError::Repository)?;
+
+    // read and check all transcriptions before touching anything
+    let mut manuscripts = BTreeMap::<String, ManuscriptMeta>::new();
+    let mut files = vec![];
+    let mut invalid = vec![];
+    for path in &paths {
+        let Some((msname, pagename)) = parse_repository_path(path) else {
+            continue;
+        };
+        let content = source
+            .read(config, path, git_ref)
+            .await
+            .map_err(RebuildError::Repository)?;
+        let ms = match parse_tei(&content) {
+            Ok(ms) => ms,
+            Err(e) => {
+                invalid.push((path.clone(), e));
+                continue;
+            }
+        };
+        let meta = header_meta(msname, ms.meta);
+        match manuscripts.get(msname) {
+            Some(existing) if *existing != meta => {
+                return Err(RebuildError::InconsistentMetadata(msname.to_string()));
+            }
+            Some(_) => {}
+            None => {
+                manuscripts.insert(msname.to_string(), meta);
+            }
+        };
+        files.push((msname.to_string(), pagename.to_string(), content));
+    }
+    if !invalid.is_empty() {
+        return Err(RebuildError::InvalidFiles(invalid));
+    };
+
+    // stage the new reconciled directory next to the current one
+    let reconciled_dir = format!("{}{RECONCILED_BASE_LOCATION}", config.data_directory);
+    let staging_dir = format!("{reconciled_dir}.rebuild");
+    let changed_files =
+        stage_files(&files, &reconciled_dir, &staging_dir, dry_run).map_err(RebuildError::Io)?;
+
+    let pages = files
+        .into_iter()
+        .map(|(msname, pagename, _)| (msname, pagename))
+        .collect::<Vec<_>>();
+    let staged = stage_rebuild(
+        &config.db,
+        &manuscripts.into_values().collect::<Vec<_>>(),
+        &pages,
+    )
+    .await
+    .map_err(RebuildError::DB);
+    let applied = match staged {
+        Ok(diff) if dry_run => Ok(diff),
+        Ok(diff) => apply_staged(config, &reconciled_dir, &staging_dir)
+            .await
+            .map(|()| diff),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = drop_rebuild(&config.db).await {
+        tracing::warn!("Unable to remove the scratch schema of the rebuild: {e}");
+    };
+    let diff = applied?;
+
+    Ok(RebuildReport {
+        db: diff,
+        changed_files,
+        dry_run,
+    })
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,180 @@
+//! Tests for staging and swapping the reconciled transcriptions of a rebuild
+
+use std::path::PathBuf;
+
+use super::{remove_replaced_directory, restore_directories, stage_files, swap_directories};
+
+/// A data directory in the temporary directory that is deleted at the end of the test
+struct TestDir(PathBuf);
+impl TestDir {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("critic-rebuild-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    /// The reconciled and the staging directory in this directory
+    fn dirs(&self) -> (String, String) {
+        let reconciled = self.0.join("reconciled").to_string_lossy().to_string();
+        let staging = format!("{reconciled}.rebuild");
+        (reconciled, staging)
+    }
+}
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn write(dir: &str, relative: &str, content: &str) {
+    let path = format!("{dir}/{relative}");
+    std::fs::create_dir_all(std::path::Path::new(&path).parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+fn read(dir: &str, relative: &str) -> Option<String> {
+    std::fs::read_to_string(format!("{dir}/{relative}")).ok()
+}
+
+fn files() -> Vec<(String, String, String)> {
+    [
+        ("ms", "1r", "<TEI>1r</TEI>"),
+        ("ms", "1v", "<TEI>1v new</TEI>"),
+    ]
+    .into_iter()
+    .map(|(msname, pagename, content)| {
+        (
+            msname.to_string(),
+            pagename.to_string(),
+            content.to_string(),
+        )
+    })
+    .collect()
+}
+
+#[test]
+fn staging_reports_changed_files() {
+    let test_dir = TestDir::new("stage");
+    let (reconciled, staging) = test_dir.dirs();
+    write(&reconciled, "ms/1r.tei.xml", "<TEI>1r</TEI>");
+    write(&reconciled, "ms/1v.tei.xml", "<TEI>1v</TEI>");
+
+    let changed = stage_files(&files(), &reconciled, &staging, false).unwrap();
+    assert_eq!(changed, vec![("ms".to_string(), "1v".to_string())]);
+    assert_eq!(read(&staging, "ms/1r.tei.xml").unwrap(), "<TEI>1r</TEI>");
+    assert_eq!(
+        read(&staging, "ms/1v.tei.xml").unwrap(),
+        "<TEI>1v new</TEI>"
+    );
+    // the reconciled directory is left alone
+    assert_eq!(read(&reconciled, "ms/1v.tei.xml").unwrap(), "<TEI>1v</TEI>");
+}
+
+#[test]
+fn staging_replaces_an_old_staging_directory() {
+    let test_dir = TestDir::new("restage");
+    let (reconciled, staging) = test_dir.dirs();
+    write(&staging, "old/1r.tei.xml", "<TEI>left over</TEI>");
+
+    let changed = stage_files(&files(), &reconciled, &staging, false).unwrap();
+    assert_eq!(changed.len(), 2);
+    assert_eq!(read(&staging, "old/1r.tei.xml"), None);
+    assert!(read(&staging, "ms/1r.tei.xml").is_some());
+}
+
+#[test]
+fn staging_an_empty_repository_creates_the_directory() {
+    let test_dir = TestDir::new("empty");
+    let (reconciled, staging) = test_dir.dirs();
+    assert!(stage_files(&[], &reconciled, &staging, false)
+        .unwrap()
+        .is_empty());
+    assert!(std::path::Path::new(&staging).is_dir());
+}
+
+#[test]
+fn dry_runs_do_not_write() {
+    let test_dir = TestDir::new("dry-run");
+    let (reconciled, staging) = test_dir.dirs();
+    write(&reconciled, "ms/1r.tei.xml", "<TEI>1r</TEI>");
+
+    let changed = stage_files(&files(), &reconciled, &staging, true).unwrap();
+    assert_eq!(changed, vec![("ms".to_string(), "1v".to_string())]);
+    assert!(!std::path::Path::new(&staging).exists());
+}
+
+#[test]
+fn swapping_keeps_the_replaced_directory_until_it_is_removed() {
+    let test_dir = TestDir::new("swap");
+    let (reconciled, staging) = test_dir.dirs();
+    write(&reconciled, "ms/1r.tei.xml", "<TEI>old</TEI>");
+    write(&staging, "ms/1r.tei.xml", "<TEI>new</TEI>");
+
+    swap_directories(&reconciled, &staging).unwrap();
+    assert_eq!(
+        read(&reconciled, "ms/1r.tei.xml").unwrap(),
+        "<TEI>new</TEI>"
+    );
+    assert!(!std::path::Path::new(&staging).exists());
+    assert_eq!(
+        read(&format!("{reconciled}.old"), "ms/1r.tei.xml").unwrap(),
+        "<TEI>old</TEI>"
+    );
+
+    remove_replaced_directory(&reconciled).unwrap();
+    assert!(!std::path::Path::new(&format!("{reconciled}.old")).exists());
+    assert_eq!(
+        read(&reconciled, "ms/1r.tei.xml").unwrap(),
+        "<TEI>new</TEI>"
+    );
+}
+
+#[test]
+fn restoring_undoes_the_swap() {
+    let test_dir = TestDir::new("restore");
+    let (reconciled, staging) = test_dir.dirs();
+    write(&reconciled, "ms/1r.tei.xml", "<TEI>old</TEI>");
+    write(&staging, "ms/1r.tei.xml", "<TEI>new</TEI>");
+
+    swap_directories(&reconciled, &staging).unwrap();
+    restore_directories(&reconciled, &staging).unwrap();
+    assert_eq!(
+        read(&reconciled, "ms/1r.tei.xml").unwrap(),
+        "<TEI>old</TEI>"
+    );
+    assert_eq!(read(&staging, "ms/1r.tei.xml").unwrap(), "<TEI>new</TEI>");
+    assert!(!std::path::Path::new(&format!("{reconciled}.old")).exists());
+}
+
+#[test]
+fn swapping_without_a_reconciled_directory() {
+    let test_dir = TestDir::new("first");
+    let (reconciled, staging) = test_dir.dirs();
+    write(&staging, "ms/1r.tei.xml", "<TEI>new</TEI>");
+
+    swap_directories(&reconciled, &staging).unwrap();
+    assert_eq!(
+        read(&reconciled, "ms/1r.tei.xml").unwrap(),
+        "<TEI>new</TEI>"
+    );
+    restore_directories(&reconciled, &staging).unwrap();
+    assert!(!std::path::Path::new(&reconciled).exists());
+    assert!(std::path::Path::new(&staging).is_dir());
+}
+
+#[test]
+fn a_failed_swap_keeps_the_reconciled_directory() {
+    let test_dir = TestDir::new("failed-swap");
+    let (reconciled, staging) = test_dir.dirs();
+    write(&reconciled, "ms/1r.tei.xml", "<TEI>old</TEI>");
+
+    // there is nothing staged to swap in
+    assert!(swap_directories(&reconciled, &staging).is_err());
+    assert_eq!(
+        read(&reconciled, "ms/1r.tei.xml").unwrap(),
+        "<TEI>old</TEI>"
+    );
+    assert!(!std::path::Path::new(&format!("{reconciled}.old")).exists());
+}
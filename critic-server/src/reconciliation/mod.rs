@@ -0,0 +1,166 @@
+//! Reconciliation of the published transcriptions of a page into one accepted transcription
+//!
+//! A reconciliation is worked on in its own branch in the forge and becomes the accepted
+//! transcription of its page when its merge request is merged.
+//!
+//! A reconciler reconciles a page in their own transcription of it and then submits that
+//! transcription with [`submit_reconciliation`].
+
+use critic_shared::reconciliation::OutstandingReconciliation;
+
+use crate::{
+    agreement::page_agreement,
+    auth::AuthenticatedUser,
+    config::Config,
+    db::{
+        get_open_reconciliation_merge_request, get_outstanding_pages, get_page,
+        insert_or_update_open_reconciliation, DBError,
+    },
+    forge::{reconciliation_branch, repository_path, Forge, ForgeError},
+    tei::transcription_path,
+};
+
+/// Problems submitting a reconciliation
+#[derive(Debug)]
+pub enum ReconciliationError {
+    DB(DBError),
+    /// The forge cannot act for the user, who logged in with this login backend
+    NotAForgeUser(String),
+    /// The transcription of the reconciler cannot be read
+    Transcription(std::io::Error),
+    Forge(ForgeError),
+}
+impl core::fmt::Display for ReconciliationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::DB(e) => {
+                write!(f, "{e}")
+            }
+            Self::NotAForgeUser(login) => {
+                write!(
+                    f,
+                    "Reconciliations are submitted to the forge in your name, which is not possible \
+                    after logging in with {login}. Log in with the forge to submit one."
+                )
+            }
+            Self::Transcription(e) => {
+                write!(f, "Unable to read your transcription of this page: {e}")
+            }
+            Self::Forge(e) => {
+                write!(f, "Unable to submit the reconciliation to the forge: {e}")
+            }
+        }
+    }
+}
+impl core::error::Error for ReconciliationError {}
+impl From<DBError> for ReconciliationError {
+    fn from(value: DBError) -> Self {
+        Self::DB(value)
+    }
+}
+impl From<ForgeError> for ReconciliationError {
+    fn from(value: ForgeError) -> Self {
+        Self::Forge(value)
+    }
+}
+
+/// All pages with at least `min_witnesses` published transcriptions that still need to be
+/// reconciled or whose reconciliation is still open
+pub async fn outstanding_reconciliations(
+    config: &Config,
+    min_witnesses: i64,
+) -> Result<Vec<OutstandingReconciliation>, DBError> {
+    let mut res = vec![];
+    for page in get_outstanding_pages(&config.db, min_witnesses).await? {
+        // a page whose agreement cannot be computed is still outstanding
+        let (disagreement, agreement_error) =
+            match page_agreement(config, &page.msname, &page.pagename).await {
+                Ok(agreement) => (agreement.character_agreement.map(|a| 1.0 - a), None),
+                Err(e) => {
+                    tracing::warn!(
+                        "Unable to compute the agreement of {} {}: {e}",
+                        page.msname,
+                        page.pagename
+                    );
+                    (None, Some(e.to_string()))
+                }
+            };
+        res.push(OutstandingReconciliation {
+            msname: page.msname,
+            pagename: page.pagename,
+            witnesses: page.witnesses,
+            disagreement,
+            agreement_error,
+            assignee: page.assignee,
+            merge_request: page.merge_request,
+        });
+    }
+    Ok(res)
+}
+
+/// Submit the transcription `user` made of a page as their reconciliation of it
+///
+/// The transcription is committed onto the reconciliation branch of the user, which is created
+/// from the accepted branch unless the user already has an open reconciliation of this page. A
+/// merge request into the accepted branch is opened unless there is one already. Returns the id
+/// of the merge request, whose state is then followed through the webhook.
+pub async fn submit_reconciliation(
+    config: &Config,
+    user: &AuthenticatedUser,
+    msname: &str,
+    pagename: &str,
+) -> Result<i64, ReconciliationError> {
+    if !config.forge.can_act_for(user) {
+        return Err(ReconciliationError::NotAForgeUser(user.login.clone()));
+    };
+    let page = get_page(&config.db, msname, pagename).await?;
+    let content = std::fs::read_to_string(transcription_path(
+        &config.data_directory,
+        msname,
+        pagename,
+        &user.username,
+    ))
+    .map_err(ReconciliationError::Transcription)?;
+    let branch = reconciliation_branch(msname, pagename, &user.username);
+    let title = format!("Reconcile {msname} {pagename}");
+
+    let open = get_open_reconciliation_merge_request(&config.db, &branch).await?;
+    if open.is_none() {
+        config
+            .forge
+            .create_branch(user, &branch, &config.accepted_branch)
+            .await?;
+    };
+    config
+        .forge
+        .commit_file(
+            user,
+            &branch,
+            &repository_path(msname, pagename),
+            &content,
+            &title,
+        )
+        .await?;
+    let merge_request = match open.flatten() {
+        Some(merge_request) => merge_request,
+        None => {
+            config
+                .forge
+                .open_merge_request(user, &branch, &config.accepted_branch, &title)
+                .await?
+        }
+    };
+    insert_or_update_open_reconciliation(
+        &config.db,
+        page.id,
+        &user.username,
+        &branch,
+        Some(merge_request),
+    )
+    .await?;
+    tracing::info!(
+        "{} submitted the reconciliation of {msname} {pagename} (merge request {merge_request})",
+        user.username
+    );
+    Ok(merge_request)
+}
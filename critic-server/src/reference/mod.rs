@@ -0,0 +1,80 @@
+//! Reference editions: stored base texts used to seed new transcriptions
+//!
+//! Reference editions are imported as plain text files with one verse per line:
+//! `<verse number><TAB><text>`, e.g. `Gen 1:1\tבראשית ברא אלהים`.
+//! The verse numbers are interpreted in one versification scheme chosen during the import.
+
+use critic_format::streamed::{Block, Paragraph};
+use critic_shared::{anchor::verse_anchor, VersificationScheme};
+
+use crate::db::ReferenceVerse;
+
+/// Problems with the content of a reference edition file
+#[derive(Debug)]
+pub enum ReferenceParseError {
+    /// The line with this (1-based) number has no tab separating verse number and text
+    MissingSeparator(usize),
+    /// The line with this (1-based) number has an empty verse number
+    EmptyVerseNumber(usize),
+}
+impl core::fmt::Display for ReferenceParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::MissingSeparator(line) => {
+                write!(
+                    f,
+                    "Line {line} does not contain a tab between verse number and text."
+                )
+            }
+            Self::EmptyVerseNumber(line) => {
+                write!(f, "Line {line} has an empty verse number.")
+            }
+        }
+    }
+}
+impl core::error::Error for ReferenceParseError {}
+
+/// Parse the content of a reference edition file into (verse number, text)
+///
+/// Empty lines are ignored.
+pub fn parse_reference_file(content: &str) -> Result<Vec<(String, String)>, ReferenceParseError> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            let (verse_nr, text) = line
+                .split_once('\t')
+                .ok_or(ReferenceParseError::MissingSeparator(idx + 1))?;
+            let verse_nr = verse_nr.trim();
+            if verse_nr.is_empty() {
+                return Err(ReferenceParseError::EmptyVerseNumber(idx + 1));
+            };
+            Ok((verse_nr.to_string(), text.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Turn the verses of a reference edition into blocks for a new transcription
+///
+/// Each verse becomes an Anchor (if it is mapped in `scheme`) followed by its Text.
+pub fn reference_blocks(
+    verses: Vec<ReferenceVerse>,
+    scheme: &VersificationScheme,
+    lang: &str,
+) -> Vec<Block> {
+    let mut res = vec![];
+    for verse in verses {
+        if let Some(verse_nr) = verse.verse_nr {
+            res.push(verse_anchor(scheme, &verse_nr));
+        };
+        res.push(Block::Text(Paragraph {
+            lang: lang.to_string(),
+            content: verse.content,
+        }));
+    }
+    res
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,102 @@
+//! Tests for reference editions
+
+use critic_format::streamed::{Block, Paragraph};
+use critic_shared::{anchor::verse_anchor, VersificationScheme};
+
+use super::{parse_reference_file, reference_blocks, ReferenceParseError};
+use crate::db::ReferenceVerse;
+
+fn scheme() -> VersificationScheme {
+    VersificationScheme {
+        id: 1,
+        full_name: "Present".to_string(),
+        shorthand: "P".to_string(),
+    }
+}
+
+fn verse(verse_id: i64, verse_nr: Option<&str>, content: &str) -> ReferenceVerse {
+    ReferenceVerse {
+        verse_id,
+        verse_nr: verse_nr.map(str::to_string),
+        content: content.to_string(),
+    }
+}
+
+fn text(lang: &str, content: &str) -> Block {
+    Block::Text(Paragraph {
+        lang: lang.to_string(),
+        content: content.to_string(),
+    })
+}
+
+#[test]
+fn lines_are_split_at_the_first_tab() {
+    assert_eq!(
+        parse_reference_file("Gen 1:1\tבראשית ברא אלהים\nGen 1:2\tוהארץ\tהיתה תהו\n").unwrap(),
+        vec![
+            ("Gen 1:1".to_string(), "בראשית ברא אלהים".to_string()),
+            ("Gen 1:2".to_string(), "והארץ\tהיתה תהו".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn whitespace_and_empty_lines_are_ignored() {
+    assert_eq!(
+        parse_reference_file("\n  Gen 1:1 \t בראשית \r\n\n \t\nGen 1:2\t\n").unwrap(),
+        vec![
+            ("Gen 1:1".to_string(), "בראשית".to_string()),
+            ("Gen 1:2".to_string(), String::new()),
+        ]
+    );
+    assert!(parse_reference_file("").unwrap().is_empty());
+}
+
+#[test]
+fn broken_lines_are_reported_with_their_number() {
+    assert!(matches!(
+        parse_reference_file("Gen 1:1\tבראשית\n\nGen 1:2 והארץ"),
+        Err(ReferenceParseError::MissingSeparator(3))
+    ));
+    assert!(matches!(
+        parse_reference_file("Gen 1:1\tבראשית\n \tוהארץ"),
+        Err(ReferenceParseError::EmptyVerseNumber(2))
+    ));
+}
+
+#[test]
+fn verses_become_anchors_and_text() {
+    assert_eq!(
+        reference_blocks(
+            vec![
+                verse(1, Some("Gen 1:1"), "בראשית ברא אלהים"),
+                verse(2, Some("Gen 1:2"), "והארץ היתה תהו"),
+            ],
+            &scheme(),
+            "hbo-Hebr",
+        ),
+        vec![
+            verse_anchor(&scheme(), "Gen 1:1"),
+            text("hbo-Hebr", "בראשית ברא אלהים"),
+            verse_anchor(&scheme(), "Gen 1:2"),
+            text("hbo-Hebr", "והארץ היתה תהו"),
+        ]
+    );
+}
+
+#[test]
+fn verses_missing_from_the_scheme_get_no_anchor() {
+    assert_eq!(
+        reference_blocks(
+            vec![verse(1, None, "בראשית"), verse(2, Some("Gen 1:2"), "והארץ")],
+            &scheme(),
+            "arc-Hebr",
+        ),
+        vec![
+            text("arc-Hebr", "בראשית"),
+            verse_anchor(&scheme(), "Gen 1:2"),
+            text("arc-Hebr", "והארץ"),
+        ]
+    );
+    assert!(reference_blocks(vec![], &scheme(), "hbo-Hebr").is_empty());
+}
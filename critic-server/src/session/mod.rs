@@ -0,0 +1,131 @@
+//! Where the sessions of logged-in users are stored
+//!
+//! Sessions are kept in postgres (next to everything else) by default, so that restarting critic
+//! neither logs everyone out nor breaks a login flow that is in progress. A local sqlite file or
+//! (for development) memory can be selected in the `[sessions]` section of the config.
+//!
+//! Expired sessions are deleted periodically by [`run_session_cleanup`].
+
+use std::sync::Arc;
+
+use axum_login::tower_sessions::{
+    session::{Id, Record},
+    session_store::{self, ExpiredDeletion},
+    MemoryStore, SessionStore,
+};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+use tower_sessions_sqlx_store::{PostgresStore, SqliteStore};
+
+use crate::{config::Config, signal_handler::InShutdown};
+
+/// How often expired sessions are deleted
+const CLEANUP_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(15 * 60);
+
+/// Config for the session store
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(tag = "store", rename_all = "snake_case")]
+pub enum SessionConfig {
+    /// the postgres database critic uses anyway
+    #[default]
+    Postgres,
+    /// a sqlite file at `path`, created if it does not exist
+    Sqlite { path: String },
+    /// memory only: every restart logs everyone out
+    Memory,
+}
+
+/// The session store selected in the config
+#[derive(Debug, Clone)]
+pub enum CriticSessionStore {
+    Postgres(PostgresStore),
+    Sqlite(SqliteStore),
+    Memory(MemoryStore),
+}
+impl CriticSessionStore {
+    /// Open the store and create its tables if necessary
+    pub async fn try_new(config: &SessionConfig, db: &Pool<Postgres>) -> Result<Self, sqlx::Error> {
+        match config {
+            SessionConfig::Postgres => {
+                let store = PostgresStore::new(db.clone());
+                store.migrate().await?;
+                Ok(Self::Postgres(store))
+            }
+            SessionConfig::Sqlite { path } => {
+                let options = sqlx::sqlite::SqliteConnectOptions::new()
+                    .filename(path)
+                    .create_if_missing(true);
+                let pool = sqlx::sqlite::SqlitePool::connect_with(options).await?;
+                let store = SqliteStore::new(pool);
+                store.migrate().await?;
+                Ok(Self::Sqlite(store))
+            }
+            SessionConfig::Memory => Ok(Self::Memory(MemoryStore::default())),
+        }
+    }
+
+    /// Delete all expired sessions
+    ///
+    /// The memory store already ignores expired sessions when loading them.
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        match self {
+            Self::Postgres(store) => store.delete_expired().await,
+            Self::Sqlite(store) => store.delete_expired().await,
+            Self::Memory(_) => Ok(()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for CriticSessionStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        match self {
+            Self::Postgres(store) => store.create(record).await,
+            Self::Sqlite(store) => store.create(record).await,
+            Self::Memory(store) => store.create(record).await,
+        }
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        match self {
+            Self::Postgres(store) => store.save(record).await,
+            Self::Sqlite(store) => store.save(record).await,
+            Self::Memory(store) => store.save(record).await,
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        match self {
+            Self::Postgres(store) => store.load(session_id).await,
+            Self::Sqlite(store) => store.load(session_id).await,
+            Self::Memory(store) => store.load(session_id).await,
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        match self {
+            Self::Postgres(store) => store.delete(session_id).await,
+            Self::Sqlite(store) => store.delete(session_id).await,
+            Self::Memory(store) => store.delete(session_id).await,
+        }
+    }
+}
+
+/// Delete expired sessions every [`CLEANUP_INTERVAL`]
+pub async fn run_session_cleanup(
+    config: Arc<Config>,
+    mut watcher: tokio::sync::watch::Receiver<InShutdown>,
+) {
+    loop {
+        tokio::select! {
+            _ = watcher.changed() => {
+                tracing::debug!("Shutting down session cleanup now.");
+                return;
+            }
+            _ = tokio::time::sleep(CLEANUP_INTERVAL) => {}
+        };
+        if let Err(e) = config.session_store.delete_expired().await {
+            tracing::warn!("Failed to delete expired sessions: {e}");
+        };
+    }
+}
@@ -1,3 +1,7 @@
+use std::sync::Arc;
+
+use crate::config::Config;
+
 /// Is the entire app currently trying to shut down?
 ///
 /// This will be synced via a global [`tokio::sync::watch`].
@@ -6,7 +10,9 @@ pub enum InShutdown {
     No,
 }
 
+/// Shut down on SIGTERM, SIGINT and Ctrl-c, reload the config on SIGHUP
 pub async fn signal_handler(
+    config: Arc<Config>,
     mut watcher: tokio::sync::watch::Receiver<InShutdown>,
     shutdown_tx: tokio::sync::watch::Sender<InShutdown>,
 ) -> Result<(), std::io::Error> {
@@ -36,36 +42,50 @@ pub async fn signal_handler(
             return Err(e);
         }
     };
-    // wait for a shutdown signal
-    tokio::select! {
-        // shutdown the signal handler when some other process signals a shutdown
-        _ = watcher.changed() => {}
-        _ = sigterm.recv() => {
-            tracing::info!("Got SIGTERM. Shuting down.");
-            shutdown_tx.send_replace(InShutdown::Yes);
-        }
-        _ = sighup.recv() => {
-            tracing::info!("Got SIGHUP. Shuting down.");
-            shutdown_tx.send_replace(InShutdown::Yes);
-        }
-        _ = sigint.recv() => {
-            tracing::info!("Got SIGINT. Shuting down.");
-            shutdown_tx.send_replace(InShutdown::Yes);
-        }
-        x = tokio::signal::ctrl_c() =>  {
-            match x {
-                Ok(()) => {
-                    tracing::info!("Received Ctrl-c. Shutting down.");
-                    shutdown_tx.send_replace(InShutdown::Yes);
-                }
-                Err(err) => {
-                    tracing::error!("Unable to listen for shutdown signal: {}", err);
-                    // we also shut down in case of error
-                    shutdown_tx.send_replace(InShutdown::Yes);
+    // wait for a shutdown signal, reloading the config whenever SIGHUP arrives
+    loop {
+        tokio::select! {
+            // shutdown the signal handler when some other process signals a shutdown
+            _ = watcher.changed() => {}
+            _ = sigterm.recv() => {
+                tracing::info!("Got SIGTERM. Shuting down.");
+                shutdown_tx.send_replace(InShutdown::Yes);
+            }
+            _ = sighup.recv() => {
+                tracing::info!("Got SIGHUP. Reloading the config.");
+                match config.reload() {
+                    Ok(report) if report.restart_required.is_empty() => {
+                        tracing::info!("Reloaded the config. {report}");
+                    }
+                    Ok(report) => {
+                        tracing::warn!("Reloaded the config. {report}");
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to reload the config, nothing was changed: {e}");
+                    }
+                };
+                continue;
+            }
+            _ = sigint.recv() => {
+                tracing::info!("Got SIGINT. Shuting down.");
+                shutdown_tx.send_replace(InShutdown::Yes);
+            }
+            x = tokio::signal::ctrl_c() =>  {
+                match x {
+                    Ok(()) => {
+                        tracing::info!("Received Ctrl-c. Shutting down.");
+                        shutdown_tx.send_replace(InShutdown::Yes);
+                    }
+                    Err(err) => {
+                        tracing::error!("Unable to listen for shutdown signal: {}", err);
+                        // we also shut down in case of error
+                        shutdown_tx.send_replace(InShutdown::Yes);
+                    }
                 }
             }
-        }
-    };
+        };
+        break;
+    }
 
     Ok(())
 }
@@ -4,16 +4,24 @@
 //! - images
 
 use axum::routing::get_service;
-use critic_shared::urls::IMAGE_BASE_LOCATION;
+use critic_shared::urls::{
+    IMAGE_BASE_LOCATION, RECONCILED_BASE_LOCATION, TRANSCRIPTION_BASE_LOCATION,
+};
 use tower_http::services::ServeDir;
 
 /// Creates the following directory structure if it does not exist
 /// <data_directory>
 ///     /files
+///     /transcript
+///     /reconciled
 /// If any of the intermediate paths already exist as files, this fails
 fn create_data_directory_layout(data_directory: &str) -> Result<(), std::io::Error> {
     // the directory for manuscript images
     std::fs::create_dir_all(format!("{data_directory}{IMAGE_BASE_LOCATION}"))?;
+    // the directory for TEI transcriptions
+    std::fs::create_dir_all(format!("{data_directory}{TRANSCRIPTION_BASE_LOCATION}"))?;
+    // the directory for accepted transcriptions
+    std::fs::create_dir_all(format!("{data_directory}{RECONCILED_BASE_LOCATION}"))?;
     Ok(())
 }
 
@@ -0,0 +1,187 @@
+//! Reading and writing transcriptions as TEI
+//!
+//! Every TEI file is run through the complete pipeline schema -> normalized -> streamed when
+//! reading, so that only files that the editor can represent are accepted.
+
+use critic_format::{denorm::NormalizationError, destream::StreamError};
+use quick_xml::{events::Event, Reader};
+
+/// Problems reading or writing a TEI file
+#[derive(Debug)]
+pub enum TeiError {
+    /// The file is not well-formed XML
+    Syntax {
+        line: usize,
+        column: usize,
+        error: quick_xml::Error,
+    },
+    /// The file is XML, but does not follow our TEI schema
+    Schema(quick_xml::de::DeError),
+    /// The file follows our TEI schema, but cannot be normalized
+    Normalization(NormalizationError),
+    /// The normalized file cannot be represented as a stream of blocks
+    Stream(StreamError),
+    /// The data cannot be serialized
+    Serialize(quick_xml::se::SeError),
+    /// The file cannot be read
+    Io(std::io::Error),
+}
+impl core::fmt::Display for TeiError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Syntax {
+                line,
+                column,
+                error,
+            } => {
+                write!(
+                    f,
+                    "XML syntax error at line {line}, column {column}: {error}"
+                )
+            }
+            Self::Schema(e) => {
+                write!(
+                    f,
+                    "The file does not follow the TEI schema used by critic: {e}"
+                )
+            }
+            Self::Normalization(e) => {
+                write!(f, "Unable to normalize the TEI: {e}")
+            }
+            Self::Stream(e) => {
+                write!(f, "Unable to convert the TEI into blocks: {e}")
+            }
+            Self::Serialize(e) => {
+                write!(f, "Unable to serialize the TEI: {e}")
+            }
+            Self::Io(e) => {
+                write!(f, "Unable to read the TEI file: {e}")
+            }
+        }
+    }
+}
+impl core::error::Error for TeiError {}
+
+/// 1-based line and column of the byte at `offset` in `content`
+pub(crate) fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(content.len());
+    while !content.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit_once('\n')
+        .map_or(before, |(_, rest)| rest)
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+/// Check that `content` is well-formed XML, reporting the position of the first error
+fn check_syntax(content: &str) -> Result<(), TeiError> {
+    let mut reader = Reader::from_str(content);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Eof) => return Ok(()),
+            Ok(_) => {}
+            Err(error) => {
+                let (line, column) = line_and_column(content, reader.error_position() as usize);
+                return Err(TeiError::Syntax {
+                    line,
+                    column,
+                    error,
+                });
+            }
+        }
+    }
+}
+
+/// Parse a TEI document into the streamed representation the editor works on
+pub fn parse_tei(content: &str) -> Result<critic_format::streamed::Manuscript, TeiError> {
+    check_syntax(content)?;
+    let ds: critic_format::schema::Tei =
+        quick_xml::de::from_str(content).map_err(TeiError::Schema)?;
+    let normalized: critic_format::normalized::Manuscript =
+        ds.try_into().map_err(TeiError::Normalization)?;
+    normalized.try_into().map_err(TeiError::Stream)
+}
+
+/// Serialize the streamed representation into a TEI document
+pub fn write_tei(ms: critic_format::streamed::Manuscript) -> Result<String, TeiError> {
+    let destreamed: critic_format::normalized::Manuscript =
+        ms.try_into().map_err(TeiError::Stream)?;
+    let denormed: critic_format::schema::Tei =
+        destreamed.try_into().map_err(TeiError::Normalization)?;
+    quick_xml::se::to_string_with_root("TEI", &denormed).map_err(TeiError::Serialize)
+}
+
+/// The path of the TEI file for a transcription on disk
+pub fn transcription_path(
+    data_directory: &str,
+    msname: &str,
+    pagename: &str,
+    username: &str,
+) -> String {
+    format!(
+        "{}/{username}.tei.xml",
+        transcription_directory(data_directory, msname, pagename)
+    )
+}
+
+/// Read and parse a TEI file from disk
+pub fn read_tei_file(path: &str) -> Result<critic_format::streamed::Manuscript, TeiError> {
+    let content = std::fs::read_to_string(path).map_err(TeiError::Io)?;
+    parse_tei(&content)
+}
+
+/// Read the transcription of `username` for a page from disk
+pub fn read_transcription(
+    data_directory: &str,
+    msname: &str,
+    pagename: &str,
+    username: &str,
+) -> Result<critic_format::streamed::Manuscript, TeiError> {
+    read_tei_file(&transcription_path(
+        data_directory,
+        msname,
+        pagename,
+        username,
+    ))
+}
+
+/// The path of the accepted (reconciled) transcription of a page on disk
+pub fn reconciled_path(data_directory: &str, msname: &str, pagename: &str) -> String {
+    format!(
+        "{data_directory}{}/{msname}/{pagename}.tei.xml",
+        critic_shared::urls::RECONCILED_BASE_LOCATION
+    )
+}
+
+/// The directory holding all transcriptions for a page
+pub fn transcription_directory(data_directory: &str, msname: &str, pagename: &str) -> String {
+    format!(
+        "{data_directory}{}/{msname}/{pagename}",
+        critic_shared::urls::TRANSCRIPTION_BASE_LOCATION
+    )
+}
+
+/// The TEI header metadata for a page of a manuscript
+pub fn page_meta(
+    ms: &critic_shared::ManuscriptMeta,
+    pagename: &str,
+) -> critic_format::normalized::Meta {
+    critic_format::normalized::Meta {
+        name: ms.title.clone(),
+        page_nr: pagename.to_string(),
+        title: ms.title.clone(),
+        institution: ms.institution.clone(),
+        collection: ms.collection.clone(),
+        hand_desc: ms.hand_desc.clone(),
+        script_desc: ms.script_desc.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test;
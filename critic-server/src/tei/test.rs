@@ -0,0 +1,85 @@
+//! Tests for reading and writing TEI
+
+use critic_format::streamed::{Block, BreakType, Paragraph};
+use critic_shared::ManuscriptMeta;
+
+use super::{line_and_column, page_meta, parse_tei, write_tei, TeiError};
+
+fn ms() -> ManuscriptMeta {
+    ManuscriptMeta {
+        id: 1,
+        title: "Codex A".to_string(),
+        institution: Some("National Library".to_string()),
+        collection: None,
+        hand_desc: None,
+        script_desc: None,
+    }
+}
+
+fn text(content: &str) -> Block {
+    Block::Text(Paragraph {
+        lang: "hbo-Hebr".to_string(),
+        content: content.to_string(),
+    })
+}
+
+fn blocks() -> Vec<Block> {
+    vec![
+        text("בראשית ברא"),
+        Block::Break(BreakType::Line),
+        text("אלהים"),
+    ]
+}
+
+#[test]
+fn written_transcriptions_are_read_back() {
+    let tei = write_tei(critic_format::streamed::Manuscript {
+        meta: page_meta(&ms(), "1r"),
+        content: blocks(),
+    })
+    .unwrap();
+    let parsed = parse_tei(&tei).unwrap();
+    assert_eq!(parsed.content, blocks());
+    assert_eq!(parsed.meta.page_nr, "1r");
+    assert_eq!(parsed.meta.title, "Codex A");
+    assert_eq!(parsed.meta.institution.as_deref(), Some("National Library"));
+}
+
+#[test]
+fn syntax_errors_report_their_line() {
+    let result = parse_tei("<TEI>\n  <teiHeader></text>\n</TEI>");
+    assert!(
+        matches!(result, Err(TeiError::Syntax { line: 2, .. })),
+        "{result:?}"
+    );
+}
+
+#[test]
+fn xml_outside_the_schema_is_rejected() {
+    let result = parse_tei("<?xml version=\"1.0\"?>\n<html><body/></html>");
+    assert!(matches!(result, Err(TeiError::Schema(_))), "{result:?}");
+}
+
+#[test]
+fn positions_are_one_based() {
+    let content = "<TEI>\n  <text/>\n</TEI>";
+    assert_eq!(line_and_column(content, 0), (1, 1));
+    // the newline belongs to the line it ends
+    assert_eq!(line_and_column(content, 5), (1, 6));
+    assert_eq!(line_and_column(content, 6), (2, 1));
+    assert_eq!(line_and_column(content, 8), (2, 3));
+    assert_eq!(line_and_column(content, content.len()), (3, 7));
+}
+
+#[test]
+fn columns_count_chars_not_bytes() {
+    // every Hebrew letter takes two bytes
+    let content = "<p>אב</p>\n<p>ג</p>";
+    assert_eq!(line_and_column(content, 3), (1, 4));
+    assert_eq!(line_and_column(content, 7), (1, 6));
+    // offsets inside a char count as that char
+    assert_eq!(line_and_column(content, 4), (1, 4));
+    assert_eq!(line_and_column(content, 6), (1, 5));
+    // offsets past the end are clamped
+    assert_eq!(line_and_column(content, 1000), (2, 9));
+}
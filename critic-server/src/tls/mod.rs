@@ -0,0 +1,97 @@
+//! Serving critic over https without a reverse proxy in front
+//!
+//! TLS is enabled by setting `tls = { cert = "...", key = "..." }` in the `[web]` section. The
+//! certificate is reloaded without a restart when the files change (e.g. after a renewal by
+//! certbot), when critic receives SIGUSR1 or when the config is reloaded with SIGHUP, which may
+//! also point to other files.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use axum_server::tls_rustls::RustlsConfig;
+use serde::Deserialize;
+
+use crate::{config::Config, signal_handler::InShutdown};
+
+/// How often the certificate files are checked for changes
+const CHECK_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(60);
+
+/// Where the certificate and its key are
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TlsConfig {
+    /// the certificate chain in PEM format
+    pub cert: PathBuf,
+    /// the private key in PEM format
+    pub key: PathBuf,
+}
+impl TlsConfig {
+    /// Load the certificate and key for serving
+    pub async fn load(&self) -> std::io::Result<RustlsConfig> {
+        RustlsConfig::from_pem_file(&self.cert, &self.key).await
+    }
+
+    /// Replace the certificate and key used by `rustls_config` with the ones on disk
+    ///
+    /// The old certificate stays in use if the new one cannot be loaded.
+    pub async fn reload(&self, rustls_config: &RustlsConfig) -> std::io::Result<()> {
+        rustls_config
+            .reload_from_pem_file(&self.cert, &self.key)
+            .await
+    }
+
+    /// When the certificate and key were last changed
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        Some((modified(&self.cert)?, modified(&self.key)?))
+    }
+}
+
+/// Reload the certificate whenever its files change, SIGUSR1 is received or the config is reloaded
+pub async fn run_certificate_reload(
+    config: Arc<Config>,
+    rustls_config: RustlsConfig,
+    mut watcher: tokio::sync::watch::Receiver<InShutdown>,
+) {
+    let mut sigusr1 = match tokio::signal::unix::signal(
+        tokio::signal::unix::SignalKind::user_defined1(),
+    ) {
+        Ok(x) => Some(x),
+        Err(e) => {
+            tracing::warn!("Failed to install SIGUSR1 listener, certificates are only reloaded when their files change or the config is reloaded: {e}");
+            None
+        }
+    };
+    let mut last_modified = config.runtime().tls.and_then(|tls| tls.modified());
+    loop {
+        let forced = tokio::select! {
+            _ = watcher.changed() => {
+                tracing::debug!("Shutting down certificate reload now.");
+                return;
+            }
+            Some(_) = async { sigusr1.as_mut()?.recv().await } => true,
+            _ = config.tls_reload.notified() => true,
+            _ = tokio::time::sleep(CHECK_INTERVAL) => false,
+        };
+        // TLS cannot be switched off at runtime, so this is always set
+        let Some(tls) = config.runtime().tls else {
+            continue;
+        };
+        let modified = tls.modified();
+        if !forced && modified == last_modified {
+            continue;
+        };
+        match tls.reload(&rustls_config).await {
+            Ok(()) => {
+                tracing::info!("Reloaded the TLS certificate.");
+                last_modified = modified;
+            }
+            Err(e) => {
+                // the files may be in the middle of being replaced, so this is retried
+                tracing::warn!("Failed to reload the TLS certificate, keeping the old one: {e}");
+            }
+        };
+    }
+}
@@ -1,22 +1,31 @@
 //! Endpoints for uploading stuff to the server
 
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use axum::{
-    extract::{DefaultBodyLimit, Multipart, Path},
+    extract::{DefaultBodyLimit, Multipart, Path, Query},
     response::IntoResponse,
     Extension, Json,
 };
 use critic_shared::{
-    urls::IMAGE_BASE_LOCATION, FileTransferResponse, ALLOWED_IMAGE_EXTENSIONS, MAX_BODY_SIZE,
+    urls::IMAGE_BASE_LOCATION, FileTransferResponse, ALLOWED_IMAGE_EXTENSIONS,
+    HTR_DEFAULT_MIN_CONFIDENCE, MAX_BODY_SIZE,
 };
 use reqwest::StatusCode;
+use serde::Deserialize;
 
 use crate::{
     auth::AuthSession,
     config::Config,
-    db::add_page,
-    gitlab::{get_user_role, GitlabUserRole},
+    db::{
+        add_page, add_reference_verses, get_manuscript, get_versification_schemes,
+        insert_or_update_transcription,
+    },
+    forge::get_user_role,
+    gitlab::GitlabUserRole,
+    htr::htr_blocks,
+    reference::parse_reference_file,
+    tei::{page_meta, parse_tei, transcription_directory, transcription_path, write_tei},
 };
 
 /// The router handling all file uploads
@@ -29,6 +38,31 @@ pub fn upload_router() -> axum::Router {
             ),
             axum::routing::post(page_upload),
         )
+        .route(
+            &format!(
+                "{}/{{edition}}",
+                critic_shared::urls::REFERENCE_UPLOAD_API_ENDPOINT
+            ),
+            axum::routing::post(reference_upload),
+        )
+        .route(
+            &format!(
+                "{}/{{msname}}",
+                critic_shared::urls::TRANSCRIPTION_UPLOAD_API_ENDPOINT
+            ),
+            axum::routing::post(manuscript_transcription_upload),
+        )
+        .route(
+            &format!(
+                "{}/{{msname}}/{{pagename}}",
+                critic_shared::urls::TRANSCRIPTION_UPLOAD_API_ENDPOINT
+            ),
+            axum::routing::post(transcription_upload),
+        )
+        .route(
+            critic_shared::urls::HTR_IMPORT_API_ENDPOINT,
+            axum::routing::post(htr_import),
+        )
         .layer(DefaultBodyLimit::max(MAX_BODY_SIZE))
 }
 
@@ -141,3 +175,362 @@ pub async fn page_upload(
         StatusCode::UNAUTHORIZED.into_response()
     }
 }
+
+/// Query parameters for uploading a reference edition
+#[derive(Debug, Deserialize)]
+pub struct ReferenceUploadQuery {
+    /// language of the text in the edition, e.g. hbo-Hebr
+    lang: String,
+    /// shorthand of the versification scheme the verse numbers are given in
+    scheme: String,
+}
+
+/// Upload (parts of) a reference edition
+///
+/// Each file contains one verse per line, see [`crate::reference`] for the format.
+pub async fn reference_upload(
+    Extension(config): Extension<Arc<Config>>,
+    Path(edition): Path<String>,
+    Query(query): Query<ReferenceUploadQuery>,
+    auth_session: AuthSession,
+    mut mpart: Multipart,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let user_role = match get_user_role(config.clone(), &user).await {
+        Ok(x) => x,
+        Err(e) => {
+            tracing::warn!("Unable to get the user role for {}: {e}", user.username);
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    if user_role < GitlabUserRole::Maintainer {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let scheme = match get_versification_schemes(&config.db).await {
+        Ok(schemes) => schemes
+            .into_iter()
+            .find(|scheme| scheme.shorthand == query.scheme),
+        Err(e) => {
+            tracing::warn!("Unable to get versification schemes: {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    let Some(scheme) = scheme else {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Unknown versification scheme: {}", query.scheme),
+        )
+            .into_response();
+    };
+
+    let mut results = FileTransferResponse::new();
+    loop {
+        match mpart.next_field().await {
+            Ok(Some(field)) => {
+                let content = match field.text().await {
+                    Ok(x) => x,
+                    Err(e) => {
+                        results.push_err(format!("Unable to read file as text: {e}."));
+                        continue;
+                    }
+                };
+                let verses = match parse_reference_file(&content) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        results.push_err(e.to_string());
+                        continue;
+                    }
+                };
+                let verse_count = verses.len();
+                if let Err(e) =
+                    add_reference_verses(&config.db, &edition, &query.lang, scheme.id, verses).await
+                {
+                    tracing::warn!("Failed to add verses to reference edition {edition}: {e}");
+                    results.push_err(format!("Failed to add verses to the db: {e}."));
+                    continue;
+                };
+                tracing::info!(
+                    "{} added {verse_count} verses to reference edition {edition}.",
+                    user.username
+                );
+                results.push_ok();
+            }
+            Ok(None) => {
+                break;
+            }
+            Err(e) => {
+                tracing::warn!("Failed reading one of the multipart fields: {e}");
+                tracing::warn!("logged in user: {}", user.username);
+            }
+        };
+    }
+    (
+        if results.err.iter().all(|e| e.is_none()) {
+            StatusCode::OK
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        },
+        Json(results),
+    )
+        .into_response()
+}
+
+/// Query parameters for importing transcriptions
+#[derive(Debug, Deserialize)]
+pub struct TranscriptionUploadQuery {
+    /// the user the imported transcriptions are attributed to
+    username: String,
+    /// whether the imported transcriptions should be published immediately
+    #[serde(default)]
+    published: bool,
+}
+
+/// The page a transcription file is imported for: its name up to the first dot
+///
+/// `folio3-recto.tei.xml` is imported as the transcription of the page `folio3-recto`.
+fn page_name_of(file_name: &str) -> &str {
+    file_name
+        .split_once('.')
+        .map_or(file_name, |(base_name, _)| base_name)
+}
+
+/// Import existing TEI files as transcriptions for a page
+///
+/// Because a user only has one transcription per page, only one file can be imported at a time.
+/// See [`manuscript_transcription_upload`] to import several pages at once.
+pub async fn transcription_upload(
+    Extension(config): Extension<Arc<Config>>,
+    Path((msname, pagename)): Path<(String, String)>,
+    Query(query): Query<TranscriptionUploadQuery>,
+    auth_session: AuthSession,
+    mpart: Multipart,
+) -> impl IntoResponse {
+    import_transcriptions(config, msname, Some(pagename), query, auth_session, mpart).await
+}
+
+/// Import existing TEI files as transcriptions for several pages of a manuscript
+///
+/// Every file is imported as the transcription of the page named like the file, see
+/// [`page_name_of`].
+pub async fn manuscript_transcription_upload(
+    Extension(config): Extension<Arc<Config>>,
+    Path(msname): Path<String>,
+    Query(query): Query<TranscriptionUploadQuery>,
+    auth_session: AuthSession,
+    mpart: Multipart,
+) -> impl IntoResponse {
+    import_transcriptions(config, msname, None, query, auth_session, mpart).await
+}
+
+/// Import every uploaded TEI file as transcription of `pagename`, or of the page named like the
+/// file if `pagename` is None
+///
+/// Every file is run through the complete schema -> normalized -> streamed pipeline, so only files
+/// that can be opened in the editor are saved. Errors are reported per file, including the
+/// location of XML syntax errors.
+async fn import_transcriptions(
+    config: Arc<Config>,
+    msname: String,
+    pagename: Option<String>,
+    query: TranscriptionUploadQuery,
+    auth_session: AuthSession,
+    mut mpart: Multipart,
+) -> axum::response::Response {
+    let Some(user) = auth_session.user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let user_role = match get_user_role(config.clone(), &user).await {
+        Ok(x) => x,
+        Err(e) => {
+            tracing::warn!("Unable to get the user role for {}: {e}", user.username);
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+    if user_role < GitlabUserRole::Maintainer {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let manuscript = match get_manuscript(&config.db, &msname).await {
+        Ok(x) => x,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+        }
+    };
+    if let Some(pagename) = &pagename {
+        if !manuscript.pages.iter().any(|page| &page.name == pagename) {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("The page {pagename} does not exist in manuscript {msname}"),
+            )
+                .into_response();
+        };
+    };
+
+    let mut results = FileTransferResponse::new();
+    // the pages a transcription was imported for
+    let mut imported = HashSet::new();
+    loop {
+        match mpart.next_field().await {
+            Ok(Some(field)) => {
+                let file_name = field.file_name().unwrap_or("(unnamed)").to_string();
+                let pagename = pagename
+                    .clone()
+                    .unwrap_or_else(|| page_name_of(&file_name).to_string());
+                let Some(page) = manuscript.pages.iter().find(|page| page.name == pagename) else {
+                    results.push_err(format!(
+                        "{file_name}: The page {pagename} does not exist in manuscript {msname}."
+                    ));
+                    continue;
+                };
+                let content = match field.text().await {
+                    Ok(x) => x,
+                    Err(e) => {
+                        results.push_err(format!("{file_name}: Unable to read file as text: {e}."));
+                        continue;
+                    }
+                };
+                let mut streamed = match parse_tei(&content) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        results.push_err(format!("{file_name}: {e}"));
+                        continue;
+                    }
+                };
+                if imported.contains(&pagename) {
+                    results.push_err(format!(
+                        "{file_name}: {} can only have one transcription for the page {pagename}.",
+                        query.username
+                    ));
+                    continue;
+                };
+                // the header is always taken from the manuscript, not from the imported file
+                streamed.meta = page_meta(&manuscript.meta, &pagename);
+                let tei = match write_tei(streamed) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        results.push_err(format!("{file_name}: {e}"));
+                        continue;
+                    }
+                };
+
+                if let Err(e) = insert_or_update_transcription(
+                    &config.db,
+                    page.id,
+                    &query.username,
+                    query.published,
+                )
+                .await
+                {
+                    tracing::warn!(
+                        "Failed to insert transcription for {msname}/{pagename} by {}: {e}",
+                        query.username
+                    );
+                    results.push_err(format!(
+                        "{file_name}: Failed to insert transcription into the db: {e}."
+                    ));
+                    continue;
+                };
+                if let Err(e) = std::fs::create_dir_all(transcription_directory(
+                    &config.data_directory,
+                    &msname,
+                    &pagename,
+                )) {
+                    results.push_err(format!(
+                        "{file_name}: Failed to create directory to put transcription into: {e}."
+                    ));
+                    continue;
+                };
+                if let Err(e) = std::fs::write(
+                    transcription_path(&config.data_directory, &msname, &pagename, &query.username),
+                    tei,
+                ) {
+                    tracing::warn!("Unable to write transcription to file: {e}");
+                    results.push_err(format!(
+                        "{file_name}: Failed to write transcription to file."
+                    ));
+                    continue;
+                };
+                tracing::info!(
+                    "{} imported {file_name} as transcription of {msname}/{pagename} by {}.",
+                    user.username,
+                    query.username
+                );
+                imported.insert(pagename);
+                results.push_ok();
+            }
+            Ok(None) => {
+                break;
+            }
+            Err(e) => {
+                tracing::warn!("Failed reading one of the multipart fields: {e}");
+                tracing::warn!("logged in user: {}", user.username);
+            }
+        };
+    }
+    (
+        if results.err.iter().all(|e| e.is_none()) {
+            StatusCode::OK
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        },
+        Json(results),
+    )
+        .into_response()
+}
+
+/// Query parameters for converting HTR/OCR output
+#[derive(Debug, Deserialize)]
+pub struct HtrImportQuery {
+    /// language of the recognised text, e.g. hbo-Hebr
+    lang: String,
+    /// words with a lower confidence become Uncertain blocks
+    min_confidence: Option<f64>,
+}
+
+/// Convert a single PAGE XML or ALTO file into blocks for the editor
+///
+/// Nothing is saved on the server: the blocks are returned as json, so that the editor can open
+/// them as a draft.
+pub async fn htr_import(
+    Query(query): Query<HtrImportQuery>,
+    auth_session: AuthSession,
+    mut mpart: Multipart,
+) -> impl IntoResponse {
+    if auth_session.user.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let field = match mpart.next_field().await {
+        Ok(Some(field)) => field,
+        Ok(None) => {
+            return (StatusCode::BAD_REQUEST, "No file was uploaded.").into_response();
+        }
+        Err(e) => {
+            tracing::warn!("Failed reading one of the multipart fields: {e}");
+            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+        }
+    };
+    let content = match field.text().await {
+        Ok(x) => x,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("Unable to read file as text: {e}."),
+            )
+                .into_response();
+        }
+    };
+    match htr_blocks(
+        &content,
+        &query.lang,
+        query.min_confidence.unwrap_or(HTR_DEFAULT_MIN_CONFIDENCE),
+    ) {
+        Ok(blocks) => (StatusCode::OK, Json(blocks)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,11 @@
+//! Tests for uploading files
+
+use super::page_name_of;
+
+#[test]
+fn transcriptions_are_imported_for_the_page_named_like_the_file() {
+    assert_eq!(page_name_of("folio3-recto.tei.xml"), "folio3-recto");
+    assert_eq!(page_name_of("1r.xml"), "1r");
+    assert_eq!(page_name_of("1r"), "1r");
+    assert_eq!(page_name_of(".xml"), "");
+}
@@ -0,0 +1,42 @@
+//! Types for the agreement between independent transcriptions of a page
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// A place where two transcriptions of a page differ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Disagreement {
+    /// the two users whose transcriptions differ
+    pub users: (String, String),
+    /// what differs, e.g. `extent: 3 character / 5 character`
+    pub description: String,
+}
+
+/// The agreement between all published transcriptions of a page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageAgreement {
+    pub msname: String,
+    pub pagename: String,
+    /// the users with a published transcription of this page
+    pub usernames: Vec<String>,
+    /// the share of blocks the transcriptions agree on (0 to 1), averaged over all pairs
+    ///
+    /// None if there are less than two transcriptions.
+    pub block_agreement: Option<f64>,
+    /// the character similarity of the transcriptions' text (0 to 1), averaged over all pairs
+    pub character_agreement: Option<f64>,
+    /// the disagreements, grouped by block type
+    pub disagreements: BTreeMap<String, Vec<Disagreement>>,
+}
+
+/// The accuracy of a user's transcription of a page against its reconciled transcription
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccuracyPoint {
+    pub msname: String,
+    pub pagename: String,
+    /// the share of blocks agreeing with the reconciled transcription (0 to 1)
+    pub block_accuracy: f64,
+    /// the character similarity with the reconciled transcription (0 to 1)
+    pub character_accuracy: f64,
+}
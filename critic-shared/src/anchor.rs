@@ -0,0 +1,29 @@
+//! Verse anchors: Anchor blocks marking where a verse starts in a transcription
+//!
+//! A verse anchor has the id `A_V_<scheme shorthand>_<verse number>`, e.g. `A_V_P_Gen 1:1`, and
+//! the full name of its versification scheme as type.
+
+use critic_format::streamed::{Block, BlockType, FromTypeLangAndContent};
+
+use crate::VersificationScheme;
+
+/// An anchor for the verse `verse_nr` in `scheme`
+pub fn verse_anchor(scheme: &VersificationScheme, verse_nr: &str) -> Block {
+    let mut block =
+        Block::from_type_lang_and_content(BlockType::Anchor, String::default(), String::default());
+    if let Block::Anchor(ref mut anchor) = block {
+        anchor.anchor_id = format!("A_V_{}_{verse_nr}", scheme.shorthand);
+        anchor.anchor_type = scheme.full_name.clone();
+    };
+    block
+}
+
+/// The (scheme shorthand, verse number) a verse anchor id refers to
+///
+/// This is the inverse of [`verse_anchor`]. Returns None for anchors that are not verse anchors.
+pub fn anchor_verse(anchor_id: &str) -> Option<(&str, &str)> {
+    anchor_id.strip_prefix("A_V_")?.split_once('_')
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,38 @@
+//! Tests for verse anchors
+
+use critic_format::streamed::Block;
+
+use super::{anchor_verse, verse_anchor};
+use crate::VersificationScheme;
+
+fn scheme() -> VersificationScheme {
+    VersificationScheme {
+        id: 1,
+        full_name: "Present".to_string(),
+        shorthand: "P".to_string(),
+    }
+}
+
+#[test]
+fn verse_anchors_name_scheme_and_verse() {
+    let Block::Anchor(anchor) = verse_anchor(&scheme(), "Gen 1:1") else {
+        panic!("a verse anchor has to be an Anchor block");
+    };
+    assert_eq!(anchor.anchor_id, "A_V_P_Gen 1:1");
+    assert_eq!(anchor.anchor_type, "Present");
+}
+
+#[test]
+fn anchor_verse_is_the_inverse_of_verse_anchor() {
+    let Block::Anchor(anchor) = verse_anchor(&scheme(), "1 Sam 3:4") else {
+        panic!("a verse anchor has to be an Anchor block");
+    };
+    assert_eq!(anchor_verse(&anchor.anchor_id), Some(("P", "1 Sam 3:4")));
+}
+
+#[test]
+fn other_anchors_are_no_verse_anchors() {
+    assert_eq!(anchor_verse("A_X_1"), None);
+    assert_eq!(anchor_verse("A_V_P"), None);
+    assert_eq!(anchor_verse(""), None);
+}
@@ -0,0 +1,124 @@
+//! Types for the verse-aligned collation of manuscripts
+
+use serde::{Deserialize, Serialize};
+
+/// What kind of reading a token represents
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenKind {
+    /// a normal word
+    Word,
+    /// a word that could not be read with certainty
+    Uncertain,
+    /// a word that is the result of a correction, with the text before correction
+    Corrected { original: String },
+    /// a lacuna (the text is lost)
+    Lacuna,
+}
+
+/// A single word (or lacuna) of a witness
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Token {
+    pub text: String,
+    pub kind: TokenKind,
+}
+impl Token {
+    /// Do these tokens count as the same reading?
+    ///
+    /// Lacunae never agree with anything.
+    pub fn agrees_with(&self, other: &Token) -> bool {
+        self.kind != TokenKind::Lacuna && other.kind != TokenKind::Lacuna && self.text == other.text
+    }
+}
+impl core::fmt::Display for Token {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self.kind {
+            TokenKind::Lacuna => write!(f, "[…]"),
+            _ => write!(f, "{}", self.text),
+        }
+    }
+}
+
+/// The text of a list of tokens, separated by spaces
+pub fn tokens_text(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A reading at a variant location and the witnesses supporting it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantReading {
+    /// the tokens of this reading, empty for omissions
+    pub tokens: Vec<Token>,
+    pub sigla: Vec<String>,
+}
+
+/// A place in a verse where the witnesses disagree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantLocation {
+    /// position of the lemma in the tokens of the base witness
+    pub start: usize,
+    pub end: usize,
+    /// all readings, starting with the reading of the base witness
+    pub readings: Vec<VariantReading>,
+}
+
+/// The variants of a single verse
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantVerse {
+    /// the verse number in the scheme of the table
+    pub verse_nr: String,
+    /// the tokens of the base witness
+    pub base: Vec<Token>,
+    pub locations: Vec<VariantLocation>,
+    /// the witnesses that do not contain this verse
+    pub missing: Vec<String>,
+}
+
+/// The variants of all witnesses for a range of verses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantTable {
+    /// the siglum of the base witness
+    pub base: String,
+    /// the sigla of all other witnesses
+    pub witnesses: Vec<String>,
+    pub verses: Vec<VariantVerse>,
+}
+
+/// Which spelling differences are ignored when comparing readings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orthography {
+    /// readings have to be spelled the same
+    #[default]
+    Exact,
+    /// vocalization, accents, punctuation and case are ignored
+    IgnoreMarks,
+    /// like `IgnoreMarks`, and word-internal ו and י are ignored as well
+    ///
+    /// This makes plene and defective spellings agree, but also words that only differ in a
+    /// consonantal ו or י, like עיר and ער.
+    IgnoreMatresLectionis,
+}
+
+/// Pairwise distances between manuscripts, computed from their readings at variant locations
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DistanceMatrix {
+    /// the manuscripts in the order of the rows and columns
+    pub sigla: Vec<String>,
+    /// the number of variant locations where both manuscripts are extant
+    pub compared: Vec<Vec<usize>>,
+    /// the number of those locations where both manuscripts have the same reading
+    pub agreements: Vec<Vec<usize>>,
+}
+impl DistanceMatrix {
+    /// The share of compared locations where the manuscripts `i` and `j` disagree
+    ///
+    /// None if there is no location to compare them at.
+    pub fn distance(&self, i: usize, j: usize) -> Option<f64> {
+        let compared = self.compared[i][j];
+        (compared != 0).then(|| 1.0 - self.agreements[i][j] as f64 / compared as f64)
+    }
+}
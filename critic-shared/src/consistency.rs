@@ -0,0 +1,113 @@
+//! Types for the report of the consistency checker
+
+use serde::{Deserialize, Serialize};
+
+/// Something that is inconsistent between the files on disk and the database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConsistencyProblem {
+    /// The file cannot be run through schema -> normalized -> streamed
+    InvalidTei(String),
+    /// A field in the TEI header differs from the manuscript in the database
+    MetadataMismatch {
+        field: String,
+        header: Option<String>,
+        database: Option<String>,
+    },
+    /// The file contains a verse anchor that is not in the verse map
+    UnknownAnchor(String),
+    /// The page has no image on disk
+    MissingImage,
+}
+impl core::fmt::Display for ConsistencyProblem {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::InvalidTei(e) => {
+                write!(f, "{e}")
+            }
+            Self::MetadataMismatch {
+                field,
+                header,
+                database,
+            } => {
+                write!(
+                    f,
+                    "{field} is {} in the TEI header, but {} in the database",
+                    header.as_deref().unwrap_or("empty"),
+                    database.as_deref().unwrap_or("empty")
+                )
+            }
+            Self::UnknownAnchor(anchor_id) => {
+                write!(f, "The verse anchor {anchor_id} is not in the verse map")
+            }
+            Self::MissingImage => {
+                write!(f, "The page has no image")
+            }
+        }
+    }
+}
+
+/// What on a page a problem was found in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConsistencySubject {
+    /// the page itself
+    Page,
+    /// the transcription of this user
+    Transcription(String),
+    /// the accepted transcription
+    Accepted,
+}
+impl core::fmt::Display for ConsistencySubject {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Page => {
+                write!(f, "page")
+            }
+            Self::Transcription(username) => {
+                write!(f, "transcription of {username}")
+            }
+            Self::Accepted => {
+                write!(f, "accepted transcription")
+            }
+        }
+    }
+}
+
+/// A problem found for a page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsistencyIssue {
+    pub msname: String,
+    pub pagename: String,
+    pub subject: ConsistencySubject,
+    pub problem: ConsistencyProblem,
+}
+
+/// The result of a complete run of the consistency checker
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ConsistencyReport {
+    /// when the check was run (UTC)
+    pub checked_at: String,
+    /// the number of pages checked
+    pub pages_checked: usize,
+    /// the number of transcription files checked
+    pub files_checked: usize,
+    pub issues: Vec<ConsistencyIssue>,
+}
+impl core::fmt::Display for ConsistencyReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        for issue in &self.issues {
+            writeln!(
+                f,
+                "{} {} ({}): {}",
+                issue.msname, issue.pagename, issue.subject, issue.problem
+            )?;
+        }
+        write!(
+            f,
+            "Checked {} files in {} pages at {}, found {} issues.",
+            self.files_checked,
+            self.pages_checked,
+            self.checked_at,
+            self.issues.len()
+        )
+    }
+}
@@ -0,0 +1,74 @@
+//! Aligning sequences and describing blocks, for comparing transcriptions and witnesses
+
+use critic_format::streamed::{Block, BreakType};
+
+/// The pairs of indices of equal elements in a longest common subsequence of `a` and `b`
+///
+/// `same` decides which elements are equal. The pairs are increasing in both indices.
+pub fn lcs_matches<A, B>(a: &[A], b: &[B], same: impl Fn(&A, &B) -> bool) -> Vec<(usize, usize)> {
+    let (n, m) = (a.len(), b.len());
+    // lengths[i][j]: length of the lcs of a[i..] and b[j..]
+    let mut lengths = vec![vec![0_usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if same(&a[i], &b[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut res = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if same(&a[i], &b[j]) {
+            res.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    res
+}
+
+/// The name of the type of a block
+pub fn block_type_name(block: &Block) -> &'static str {
+    match block {
+        Block::Text(_) => "Text",
+        Block::Break(_) => "Break",
+        Block::Lacuna(_) => "Lacuna",
+        Block::Anchor(_) => "Anchor",
+        Block::Correction(_) => "Correction",
+        Block::Uncertain(_) => "Uncertain",
+        Block::Abbreviation(_) => "Abbreviation",
+        Block::Space(_) => "Space",
+    }
+}
+
+/// The text of a block, or a short description for blocks without text (e.g. `3 character`)
+pub fn block_text(block: &Block) -> String {
+    match block {
+        Block::Text(paragraph) => paragraph.content.clone(),
+        Block::Break(BreakType::Line) => "line break".to_string(),
+        Block::Break(_) => "column break".to_string(),
+        Block::Lacuna(lacuna) => format!("{} {}", lacuna.n, lacuna.unit.name()),
+        Block::Anchor(anchor) => anchor.anchor_id.clone(),
+        Block::Correction(correction) => correction
+            .versions
+            .iter()
+            .map(|v| v.content.as_str())
+            .collect::<Vec<_>>()
+            .join(" -> "),
+        Block::Uncertain(uncertain) => uncertain.content.clone(),
+        Block::Abbreviation(abbreviation) => {
+            format!("{} ({})", abbreviation.surface, abbreviation.expansion)
+        }
+        Block::Space(space) => format!("{} {}", space.quantity, space.unit.name()),
+    }
+}
+
+#[cfg(test)]
+mod test;
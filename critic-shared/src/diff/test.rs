@@ -0,0 +1,79 @@
+//! Tests for aligning sequences and describing blocks
+
+use critic_format::streamed::{Block, BlockType, BreakType, FromTypeLangAndContent, Paragraph};
+
+use super::{block_text, block_type_name, lcs_matches};
+
+fn text(content: &str) -> Block {
+    Block::Text(Paragraph {
+        lang: "hbo-Hebr".to_string(),
+        content: content.to_string(),
+    })
+}
+
+#[test]
+fn lcs_of_empty_sequences() {
+    assert!(lcs_matches::<char, char>(&[], &[], |a, b| a == b).is_empty());
+    assert!(lcs_matches(&['a'], &[], |a: &char, b: &char| a == b).is_empty());
+}
+
+#[test]
+fn lcs_of_equal_sequences_matches_everything() {
+    let a = ['a', 'b', 'c'];
+    assert_eq!(
+        lcs_matches(&a, &a, |a, b| a == b),
+        vec![(0, 0), (1, 1), (2, 2)]
+    );
+}
+
+#[test]
+fn lcs_skips_insertions_and_deletions() {
+    let a = "abcde".chars().collect::<Vec<_>>();
+    let b = "xbcyd".chars().collect::<Vec<_>>();
+    assert_eq!(
+        lcs_matches(&a, &b, |a, b| a == b),
+        vec![(1, 1), (2, 2), (3, 4)]
+    );
+}
+
+#[test]
+fn lcs_uses_the_given_equality() {
+    let a = ["Word", "other"];
+    let b = ["word".to_string(), "OTHER".to_string()];
+    assert_eq!(
+        lcs_matches(&a, &b, |a, b| a.eq_ignore_ascii_case(b)),
+        vec![(0, 0), (1, 1)]
+    );
+    assert!(lcs_matches(&a, &b, |a, b| a == b).is_empty());
+}
+
+#[test]
+fn block_type_names() {
+    assert_eq!(block_type_name(&text("בראשית")), "Text");
+    assert_eq!(block_type_name(&Block::Break(BreakType::Line)), "Break");
+    let uncertain = Block::from_type_lang_and_content(
+        BlockType::Uncertain,
+        "hbo-Hebr".to_string(),
+        "ברא".to_string(),
+    );
+    assert_eq!(block_type_name(&uncertain), "Uncertain");
+}
+
+#[test]
+fn block_texts() {
+    assert_eq!(block_text(&text("בראשית ברא")), "בראשית ברא");
+    assert_eq!(block_text(&Block::Break(BreakType::Line)), "line break");
+    assert_eq!(block_text(&Block::Break(BreakType::Column)), "column break");
+    let uncertain = Block::from_type_lang_and_content(
+        BlockType::Uncertain,
+        "hbo-Hebr".to_string(),
+        "ברא".to_string(),
+    );
+    assert_eq!(block_text(&uncertain), "ברא");
+    let mut anchor =
+        Block::from_type_lang_and_content(BlockType::Anchor, String::default(), String::default());
+    if let Block::Anchor(ref mut a) = anchor {
+        a.anchor_id = "A_V_M_Gen_1_1".to_string();
+    };
+    assert_eq!(block_text(&anchor), "A_V_M_Gen_1_1");
+}
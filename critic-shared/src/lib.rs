@@ -1,5 +1,11 @@
 //! Types and functions shared by App and Server
 
+pub mod agreement;
+pub mod anchor;
+pub mod collation;
+pub mod consistency;
+pub mod diff;
+pub mod reconciliation;
 pub mod urls;
 
 use serde::{Deserialize, Serialize};
@@ -12,6 +18,9 @@ pub const ALLOWED_IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg"];
 ///
 /// Please note changes to this value in the README under `Reverse Proxying critic`
 pub const MAX_BODY_SIZE: usize = 150 * 1024 * 1024;
+/// Words recognised by HTR/OCR with a lower confidence than this become Uncertain blocks by
+/// default
+pub const HTR_DEFAULT_MIN_CONFIDENCE: f64 = 0.8;
 
 /// Response from the backend after file uploads
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
@@ -48,7 +57,7 @@ impl Extend<Option<String>> for FileTransferResponse {
 }
 
 /// The names of a versification scheme
-#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
 pub struct VersificationScheme {
     pub id: i64,
@@ -113,3 +122,27 @@ impl From<ShowHelp> for bool {
         value.0
     }
 }
+
+/// A reference edition (e.g. a public-domain edition of the base text) stored verse by verse
+#[cfg_attr(feature = "ssr", derive(FromRow))]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ReferenceEdition {
+    pub id: i64,
+    /// Short name of this edition, e.g. "WLC"
+    pub name: String,
+    /// The language of the text in this edition, e.g. "hbo-Hebr"
+    pub lang: String,
+}
+
+/// Information on a single transcription of a page (the TEI data itself lives on disk)
+#[cfg_attr(feature = "ssr", derive(FromRow))]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct TranscriptionMeta {
+    pub id: i64,
+    /// the page this transcription is for
+    pub page: i64,
+    /// the user who created this transcription
+    pub username: String,
+    /// does the user wish this transcription to be viewable
+    pub published: bool,
+}
@@ -0,0 +1,43 @@
+//! Types for the reconciliation of published transcriptions into one accepted transcription
+
+use serde::{Deserialize, Serialize};
+
+/// A page that still needs to be reconciled, or whose reconciliation is still in review
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutstandingReconciliation {
+    pub msname: String,
+    pub pagename: String,
+    /// the number of published transcriptions of this page
+    pub witnesses: i64,
+    /// one minus the character agreement of the published transcriptions (0 to 1)
+    ///
+    /// None if there are less than two transcriptions.
+    pub disagreement: Option<f64>,
+    /// why the agreement of this page could not be computed, if it could not
+    pub agreement_error: Option<String>,
+    /// the user working on the open reconciliation of this page, if there is one
+    pub assignee: Option<String>,
+    /// the iid of the merge request of the open reconciliation in gitlab
+    pub merge_request: Option<i64>,
+}
+
+/// The state of a reconciliation, as stored in the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReconciliationStatus {
+    /// in progress or in review
+    Open,
+    /// merged into the accepted branch
+    Accepted,
+    /// abandoned without merging
+    Closed,
+}
+impl ReconciliationStatus {
+    /// The name of this status in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Accepted => "accepted",
+            Self::Closed => "closed",
+        }
+    }
+}
@@ -14,3 +14,45 @@ pub const UPLOAD_BASE_URL: &str = "/upload";
 /// The api endpoint where new manuscript pages should be uploaded to
 /// The manuscriptname these pages belong to will be appended after this string (and a /)
 pub const PAGE_UPLOAD_API_ENDPOINT: &str = "/v1/page";
+/// The api endpoint where reference editions should be uploaded to
+/// The name of the edition will be appended after this string (and a /)
+pub const REFERENCE_UPLOAD_API_ENDPOINT: &str = "/v1/reference";
+/// filesystem-location to put transcriptions into
+/// lives under the data-directory in the fs
+pub const TRANSCRIPTION_BASE_LOCATION: &str = "/transcript";
+/// filesystem-location to put accepted (reconciled) transcriptions into
+/// lives under the data-directory in the fs
+pub const RECONCILED_BASE_LOCATION: &str = "/reconciled";
+/// filesystem-location of the report written by the consistency checker
+/// lives under the data-directory in the fs
+pub const CONSISTENCY_REPORT_LOCATION: &str = "/consistency_report.json";
+/// The api endpoint where TEI transcriptions for a page should be uploaded to
+/// The manuscript name and page name will be appended after this string (separated by /)
+pub const TRANSCRIPTION_UPLOAD_API_ENDPOINT: &str = "/v1/transcription";
+/// The api endpoint where HTR/OCR output (PAGE XML or ALTO) is converted into blocks for the editor
+pub const HTR_IMPORT_API_ENDPOINT: &str = "/v1/htr";
+/// The base url for exporting transcriptions
+pub const EXPORT_BASE_URL: &str = "/export";
+/// The api endpoint for plain-text exports
+/// The manuscript name (and optionally the page name) will be appended after this string
+/// (separated by /)
+pub const TEXT_EXPORT_API_ENDPOINT: &str = "/v1/text";
+/// The api endpoint for exporting a whole manuscript as a single TEI document
+/// The manuscript name will be appended after this string (and a /)
+pub const TEI_EXPORT_API_ENDPOINT: &str = "/v1/tei";
+/// The api endpoint for exporting collated witnesses as LaTeX (reledmac)
+pub const LATEX_EXPORT_API_ENDPOINT: &str = "/v1/latex";
+/// The api endpoint for exporting a manuscript as OSIS XML
+pub const OSIS_EXPORT_API_ENDPOINT: &str = "/v1/osis";
+/// The api endpoint for exporting a manuscript as USFM
+pub const USFM_EXPORT_API_ENDPOINT: &str = "/v1/usfm";
+/// The api endpoint for exporting the collation of a verse range as a TEI critical apparatus
+pub const APPARATUS_EXPORT_API_ENDPOINT: &str = "/v1/apparatus";
+/// The api endpoint for exporting the distances between manuscripts over a verse range
+pub const DISTANCE_EXPORT_API_ENDPOINT: &str = "/v1/distance";
+/// The base url for webhooks from external services
+///
+/// Requests to these endpoints are not authenticated with a login, but with a shared secret.
+pub const WEBHOOK_BASE_URL: &str = "/webhook";
+/// The api endpoint gitlab sends its webhooks (merge request and push events) to
+pub const GITLAB_WEBHOOK_API_ENDPOINT: &str = "/v1/gitlab";
@@ -0,0 +1,104 @@
+//! The report of the last run of the consistency checker
+
+use critic_shared::consistency::{ConsistencyIssue, ConsistencyReport};
+use leptos::prelude::*;
+
+#[server]
+async fn get_consistency_report() -> Result<Option<ConsistencyReport>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    critic_server::consistency::read_report(&config.data_directory)
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Does the search term appear in the manuscript, page or problem?
+fn matches_search(issue: &ConsistencyIssue, search: &str) -> bool {
+    let search = search.to_lowercase();
+    issue.msname.to_lowercase().contains(&search)
+        || issue.pagename.to_lowercase().contains(&search)
+        || issue.subject.to_string().to_lowercase().contains(&search)
+        || issue.problem.to_string().to_lowercase().contains(&search)
+}
+
+/// A row of the report
+#[component]
+fn IssueRow(issue: ConsistencyIssue) -> impl IntoView {
+    view! {
+        <tr class="border-b border-slate-600 odd:bg-slate-800 even:bg-slate-700">
+            <td class="p-2">{issue.msname.clone()}</td>
+            <td class="p-2">
+                <a class="underline" href=format!(
+                    "/admin/manuscripts/{}/{}",
+                    urlencoding::encode(&issue.msname),
+                    urlencoding::encode(&issue.pagename)
+                )>
+                    {issue.pagename.clone()}
+                </a>
+            </td>
+            <td class="p-2">{issue.subject.to_string()}</td>
+            <td class="p-2">{issue.problem.to_string()}</td>
+        </tr>
+    }
+}
+
+#[component]
+pub fn ConsistencyPage() -> impl IntoView {
+    let report = OnceResource::new(get_consistency_report());
+    let search = RwSignal::new(String::default());
+
+    view! {
+        <div class="flex h-full flex-col overflow-y-auto">
+            <div class="flex flex-row justify-center">
+                <h1 class="p-10 text-6xl font-semibold">Consistency Report</h1>
+            </div>
+            <Suspense fallback=|| view! { <p class="m-4">"Loading the consistency report..."</p> }>
+            {move || Suspend::new(async move {
+                match report.await {
+                    Err(e) => view! { <p class="m-4 text-red-500">{e.to_string()}</p> }.into_any(),
+                    Ok(None) => view! {
+                        <p class="m-4">"The consistency checker has not run yet."</p>
+                    }.into_any(),
+                    Ok(Some(report)) => view! {
+                        <div class="mx-4 flex flex-row items-center gap-4 text-xl">
+                            <p>
+                                {format!(
+                                    "Checked {} files in {} pages at {}, found {} issues.",
+                                    report.files_checked,
+                                    report.pages_checked,
+                                    report.checked_at,
+                                    report.issues.len(),
+                                )}
+                            </p>
+                            <label class="ml-auto" for="consistency-search">"Search:"</label>
+                            <input id="consistency-search" class="rounded border border-slate-500 bg-slate-800 p-1"
+                                placeholder="manuscript, page or problem"
+                                on:input:target=move |ev| search.set(ev.target().value())
+                                prop:value=search
+                            />
+                        </div>
+                        <table class="m-4 table-auto text-lg">
+                            <thead>
+                                <tr>
+                                    <th class="p-2 text-left">"Manuscript"</th>
+                                    <th class="p-2 text-left">"Page"</th>
+                                    <th class="p-2 text-left">"In"</th>
+                                    <th class="p-2 text-left">"Problem"</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {move || report
+                                    .issues
+                                    .iter()
+                                    .filter(|issue| matches_search(issue, &search.read()))
+                                    .cloned()
+                                    .map(|issue| view! { <IssueRow issue/> })
+                                    .collect_view()}
+                            </tbody>
+                        </table>
+                    }.into_any(),
+                }
+            })}
+            </Suspense>
+        </div>
+    }
+}
@@ -9,7 +9,10 @@
 
 use critic_components::filetransfer::TransferPage;
 use critic_components::{TEXTAREA_DEFAULT_COLS, TEXTAREA_DEFAULT_ROWS};
-use critic_shared::urls::{IMAGE_BASE_LOCATION, STATIC_BASE_URL};
+use critic_shared::urls::{
+    EXPORT_BASE_URL, IMAGE_BASE_LOCATION, PAGE_UPLOAD_API_ENDPOINT, STATIC_BASE_URL,
+    TEI_EXPORT_API_ENDPOINT, TRANSCRIPTION_UPLOAD_API_ENDPOINT, UPLOAD_BASE_URL,
+};
 use critic_shared::ManuscriptMeta;
 use leptos::either::Either;
 use leptos::prelude::*;
@@ -242,10 +245,13 @@ pub fn Manuscript() -> impl IntoView {
                         Ok(info) => {
                             let show_page_upload = RwSignal::new(false);
                             let msname = info.meta.title.clone();
+                            let tei_export_url = format!("{EXPORT_BASE_URL}{TEI_EXPORT_API_ENDPOINT}/{msname}");
+                            let tei_export_filename = format!("{msname}.tei.xml");
                             Either::Right(
                             view!{
                                 <div id="Manuscript-wrapper" class="h-full flex flex-col w-3/4 overflow-y-auto">
                                 <ManuscriptMeta meta=info.meta />
+                                <ImportTranscription msname=msname.clone() />
                                 // container for the lower half of the screen
                                 <div class="flex flex-row w-full h-0 grow bg-pink-300">
                                     // wrapper around the page upload form - this is show over the
@@ -255,7 +261,7 @@ pub fn Manuscript() -> impl IntoView {
                                         <div class="absolute inset-0 bg-stone-100/60 backdrop-blur-[4px]">
                                             <div class="relative inset-1/12 w-10/12">
                                             <div class="bg-violet-50">
-                                                <TransferPage msname=msname.clone() />
+                                                <TransferPage target_url=format!("{UPLOAD_BASE_URL}{PAGE_UPLOAD_API_ENDPOINT}/{msname}") />
                                             </div>
                                             <div class="flex justify-around">
                                             <button on:click=move |_| {
@@ -279,6 +285,7 @@ pub fn Manuscript() -> impl IntoView {
                                             // show the page upload form
                                             show_page_upload.update(|x| *x ^= true);
                                         }>"Add Pages"</button>
+                                        <a href=tei_export_url download=tei_export_filename>"Download TEI"</a>
                                         // list over all pages
                                         <ul>
                                             {
@@ -370,7 +377,8 @@ pub fn MMetaInput(
 #[server]
 async fn update_ms_metadata(data: ManuscriptMeta, old_title: String) -> Result<(), ServerFnError> {
     use critic_server::auth::AuthSession;
-    use critic_server::gitlab::{get_user_role, GitlabUserRole};
+    use critic_server::forge::get_user_role;
+    use critic_server::gitlab::GitlabUserRole;
     use critic_shared::urls::IMAGE_BASE_LOCATION;
     use leptos_axum::extract;
 
@@ -540,6 +548,7 @@ pub fn Page() -> impl IntoView {
                                 // simple form to change the name or image for this page
                                 <button class="w-2/8 p-1 rounded-xl border bg-slate-300 hover:bg-slate-200">"Edit - TODO"</button>
                             </div>
+                            <ImportTranscription msname=msname.clone() pagename=pagename.clone() />
                             // image preview for this page in the right hand side
                             <img alt={format!("Preview for {msname} - {pagename}")} src={format!("{image_base}/preview.webp")}/>
                         </div>
@@ -553,6 +562,84 @@ pub fn Page() -> impl IntoView {
     }
 }
 
+#[server]
+async fn get_usernames() -> Result<Vec<String>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    critic_server::db::get_usernames(&config.db)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Import existing TEI files as the transcriptions of a user
+///
+/// With a page, a single file is imported for it. Without one, every file is imported for the page
+/// of this manuscript named like the file.
+#[component]
+fn ImportTranscription(
+    msname: String,
+    #[prop(optional)] pagename: Option<String>,
+) -> impl IntoView {
+    let usernames = OnceResource::new(get_usernames());
+    let username = RwSignal::new(String::default());
+    let published = RwSignal::new(false);
+    let (summary, path) = match &pagename {
+        Some(pagename) => (
+            "Import TEI transcription",
+            format!(
+                "{}/{}",
+                urlencoding::encode(&msname),
+                urlencoding::encode(pagename)
+            ),
+        ),
+        None => (
+            "Import TEI transcriptions (one file per page, named like the page)",
+            urlencoding::encode(&msname).into_owned(),
+        ),
+    };
+    let target_url = Signal::derive(move || {
+        format!(
+            "{UPLOAD_BASE_URL}{TRANSCRIPTION_UPLOAD_API_ENDPOINT}/{path}?username={}&published={}",
+            urlencoding::encode(&username.get()),
+            published.get()
+        )
+    });
+
+    view! {
+        <details class="m-2 border border-slate-500 p-2">
+            <summary>{summary}</summary>
+            <div class="flex flex-row justify-start gap-2 p-2">
+                <label for="import-transcription-user">"Transcribed by: "</label>
+                <Suspense fallback=|| view! { "Loading users..." }>
+                {move || Suspend::new(async move {
+                    usernames.await.map(|usernames| view! {
+                        <select id="import-transcription-user"
+                            on:change:target=move |ev| username.set(ev.target().value())
+                        >
+                            <option value="">"(choose a user)"</option>
+                            {usernames.into_iter().map(|name| view! {
+                                <option value=name.clone()>{name.clone()}</option>
+                            }).collect_view()}
+                        </select>
+                    })
+                })}
+                </Suspense>
+                <label>
+                    <input type="checkbox"
+                        prop:checked=move || published.get()
+                        on:change:target=move |ev| published.set(ev.target().checked())
+                    />
+                    "Publish immediately"
+                </label>
+            </div>
+            <Show when=move || !username.read().is_empty()
+                fallback=|| view! { <p class="text-sm">"Choose the user this transcription is attributed to."</p> }>
+                <TransferPage target_url=target_url />
+            </Show>
+        </details>
+    }
+}
+
 #[component]
 pub fn ManuscriptLanding() -> impl IntoView {
     view! {
@@ -4,7 +4,9 @@ use leptos::prelude::*;
 use leptos_router::components::{ParentRoute, Route};
 use leptos_router::path;
 
+mod consistency;
 mod manuscripts;
+mod reference;
 
 #[component]
 pub fn AdminLanding() -> impl IntoView {
@@ -36,6 +38,24 @@ pub fn AdminLanding() -> impl IntoView {
             </div>
             <p class="ml-12 list-disc text-xl">Manage Versification Schemes</p>
           </a>
+          <a href="/admin/references" class="rounded-4xl border-2 border-sky-600 bg-slate-700 p-8 shadow-lg shadow-sky-600 hover:bg-slate-600 hover:shadow-xl">
+            <div class="flex flex-row justify-start">
+              <svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-14">
+                <path stroke-linecap="round" stroke-linejoin="round" d="M12 6.042A8.967 8.967 0 0 0 6 3.75c-1.052 0-2.062.18-3 .512v14.25A8.987 8.987 0 0 1 6 18c2.305 0 4.408.867 6 2.292m0-14.25a8.966 8.966 0 0 1 6-2.292c1.052 0 2.062.18 3 .512v14.25A8.987 8.987 0 0 0 18 18a8.967 8.967 0 0 0-6 2.292m0-14.25v14.25" />
+              </svg>
+              <h2 class="mt-3 mb-4 ml-2 text-4xl font-bold">References</h2>
+            </div>
+            <p class="ml-12 list-disc text-xl">Upload reference editions to start transcriptions from</p>
+          </a>
+          <a href="/admin/consistency" class="rounded-4xl border-2 border-sky-600 bg-slate-700 p-8 shadow-lg shadow-sky-600 hover:bg-slate-600 hover:shadow-xl">
+            <div class="flex flex-row justify-start">
+              <svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="size-14">
+                <path stroke-linecap="round" stroke-linejoin="round" d="M9 12.75 11.25 15 15 9.75m-3-7.036A11.959 11.959 0 0 1 3.598 6 11.99 11.99 0 0 0 3 9.749c0 5.592 3.824 10.29 9 11.623 5.176-1.332 9-6.03 9-11.622 0-1.31-.21-2.571-.598-3.751h-.152c-3.196 0-6.1-1.248-8.25-3.285Z" />
+              </svg>
+              <h2 class="mt-3 mb-4 ml-2 text-4xl font-bold">Consistency</h2>
+            </div>
+            <p class="ml-12 list-disc text-xl">Problems found in transcriptions, metadata and images</p>
+          </a>
         </div>
       </div>
     </div>
@@ -52,6 +72,8 @@ pub fn AdminRouter() -> impl MatchNestedRoutes + Clone {
             </ParentRoute>
             <Route path=path!("") view=manuscripts::ManuscriptLanding/>
         </ParentRoute>
+        <Route path=path!("references") view=reference::ReferencePage/>
+        <Route path=path!("consistency") view=consistency::ConsistencyPage/>
     }
     .into_inner()
 }
@@ -0,0 +1,127 @@
+//! Uploading reference editions, the base texts new transcriptions can be started from
+
+use critic_components::editor::versification_scheme::get_versification_schemes;
+use critic_components::filetransfer::TransferPage;
+use critic_shared::urls::{REFERENCE_UPLOAD_API_ENDPOINT, UPLOAD_BASE_URL};
+use critic_shared::ReferenceEdition;
+use leptos::prelude::*;
+
+#[server]
+async fn list_reference_editions() -> Result<Vec<ReferenceEdition>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    critic_server::db::get_reference_editions(&config.db)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// The editions already in the database
+#[component]
+fn EditionList() -> impl IntoView {
+    let editions = OnceResource::new(list_reference_editions());
+
+    view! {
+        <Suspense fallback=|| view! { <p class="m-4">"Loading reference editions..."</p> }>
+        {move || Suspend::new(async move {
+            match editions.await {
+                Err(e) => view! { <p class="m-4 text-red-500">{e.to_string()}</p> }.into_any(),
+                Ok(editions) if editions.is_empty() => view! {
+                    <p class="m-4">"No reference edition has been uploaded yet."</p>
+                }.into_any(),
+                Ok(editions) => view! {
+                    <table class="m-4 table-auto text-lg">
+                        <thead>
+                            <tr>
+                                <th class="p-2 text-left">"Edition"</th>
+                                <th class="p-2 text-left">"Language"</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {editions.into_iter().map(|edition| view! {
+                                <tr class="border-b border-slate-600 odd:bg-slate-800 even:bg-slate-700">
+                                    <td class="p-2">{edition.name}</td>
+                                    <td class="p-2">{edition.lang}</td>
+                                </tr>
+                            }).collect_view()}
+                        </tbody>
+                    </table>
+                }.into_any(),
+            }
+        })}
+        </Suspense>
+    }
+}
+
+/// Upload the verses of a reference edition
+///
+/// Uploading to an existing edition adds its verses or replaces their text.
+#[component]
+fn UploadEdition() -> impl IntoView {
+    let versification_schemes = OnceResource::new(get_versification_schemes());
+    let name = RwSignal::new(String::default());
+    let lang = RwSignal::new("hbo-Hebr".to_string());
+    let scheme = RwSignal::new(String::default());
+    let target_url = Signal::derive(move || {
+        format!(
+            "{UPLOAD_BASE_URL}{REFERENCE_UPLOAD_API_ENDPOINT}/{}?lang={}&scheme={}",
+            urlencoding::encode(name.read().trim()),
+            urlencoding::encode(lang.read().trim()),
+            urlencoding::encode(&scheme.read())
+        )
+    });
+
+    view! {
+        <div class="m-4 border border-slate-500 p-2">
+            <h2 class="text-2xl">"Upload verses"</h2>
+            <p class="text-sm">
+                "Plain text files with one verse per line: the verse number, a tab and the text of the verse, e.g. "
+                <code>"Gen 1:1<TAB>בראשית ברא אלהים"</code>
+            </p>
+            <div class="flex flex-row justify-start gap-2 p-2">
+                <label for="reference-name">"Edition: "</label>
+                <input id="reference-name" class="rounded border border-slate-500 bg-slate-800 p-1"
+                    placeholder="e.g. WLC"
+                    on:input:target=move |ev| name.set(ev.target().value())
+                    prop:value=name
+                />
+                <label for="reference-lang">"Language: "</label>
+                <input id="reference-lang" class="rounded border border-slate-500 bg-slate-800 p-1"
+                    on:input:target=move |ev| lang.set(ev.target().value())
+                    prop:value=lang
+                />
+                <label for="reference-scheme">"Verse numbers in: "</label>
+                <Suspense fallback=|| view! { "Loading versification schemes..." }>
+                {move || Suspend::new(async move {
+                    versification_schemes.await.map(|schemes| view! {
+                        <select id="reference-scheme"
+                            on:change:target=move |ev| scheme.set(ev.target().value())
+                        >
+                            <option value="">"(choose a versification scheme)"</option>
+                            {schemes.into_iter().map(|versification| view! {
+                                <option value=versification.shorthand>{versification.full_name}</option>
+                            }).collect_view()}
+                        </select>
+                    })
+                })}
+                </Suspense>
+            </div>
+            <Show when=move || !name.read().trim().is_empty() && !lang.read().trim().is_empty() && !scheme.read().is_empty()
+                fallback=|| view! { <p class="text-sm">"Name the edition, its language and the versification scheme of its verse numbers."</p> }>
+                <TransferPage target_url=target_url />
+            </Show>
+        </div>
+    }
+}
+
+#[component]
+pub fn ReferencePage() -> impl IntoView {
+    view! {
+        <div class="flex h-full flex-col overflow-y-auto">
+            <div class="flex flex-row justify-center">
+                <h1 class="p-10 text-6xl font-semibold">Reference Editions</h1>
+            </div>
+            <EditionList />
+            <UploadEdition />
+        </div>
+    }
+}
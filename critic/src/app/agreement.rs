@@ -0,0 +1,241 @@
+//! Agreement between independent transcriptions and accuracy of transcribers
+
+use critic_shared::agreement::{AccuracyPoint, PageAgreement};
+use critic_shared::ManuscriptMeta;
+use leptos::prelude::*;
+
+#[server]
+async fn get_manuscripts() -> Result<Vec<ManuscriptMeta>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    critic_server::db::get_manuscripts_by_name(&config.db, None)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+#[server]
+async fn get_usernames() -> Result<Vec<String>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    critic_server::db::get_usernames(&config.db)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+#[server]
+async fn get_manuscript_agreement(msname: String) -> Result<Vec<PageAgreement>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    critic_server::agreement::manuscript_agreement(&config, &msname)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+#[server]
+async fn get_user_accuracy(username: String) -> Result<Vec<AccuracyPoint>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    critic_server::agreement::user_accuracy(&config, &username)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// A share between 0 and 1 as percentage
+fn percent(share: Option<f64>) -> String {
+    share.map_or("-".to_string(), |s| format!("{:.1} %", 100.0 * s))
+}
+
+/// A row of the agreement table with the disagreements to expand
+#[component]
+fn PageAgreementRow(agreement: PageAgreement) -> impl IntoView {
+    // pages with low agreement probably need another transcriber
+    let class = match agreement.character_agreement {
+        Some(a) if a < 0.9 => "text-red-400",
+        Some(a) if a < 0.97 => "text-orange-300",
+        _ => "text-green-400",
+    };
+    let total = agreement
+        .disagreements
+        .values()
+        .map(Vec::len)
+        .sum::<usize>();
+    view! {
+        <tr class="border-b border-slate-600 align-top odd:bg-slate-800 even:bg-slate-700">
+            <td class="p-2">
+                <a class="underline" href=format!(
+                    "/admin/manuscripts/{}/{}",
+                    urlencoding::encode(&agreement.msname),
+                    urlencoding::encode(&agreement.pagename)
+                )>
+                    {agreement.pagename.clone()}
+                </a>
+            </td>
+            <td class="p-2">{agreement.usernames.join(", ")}</td>
+            <td class="p-2">{percent(agreement.block_agreement)}</td>
+            <td class=format!("p-2 {class}")>{percent(agreement.character_agreement)}</td>
+            <td class="p-2">
+                <details>
+                    <summary>{format!("{total} disagreements")}</summary>
+                    {agreement.disagreements.into_iter().map(|(block_type, disagreements)| view! {
+                        <h3 class="mt-2 font-bold">{format!("{block_type} ({})", disagreements.len())}</h3>
+                        <ul class="ml-4 list-disc text-sm">
+                            {disagreements.into_iter().map(|d| view! {
+                                <li>{format!("{} / {}: {}", d.users.0, d.users.1, d.description)}</li>
+                            }).collect_view()}
+                        </ul>
+                    }).collect_view()}
+                </details>
+            </td>
+        </tr>
+    }
+}
+
+/// The accuracy of a user against the reconciled transcriptions, as bars in the order of work
+#[component]
+fn AccuracyTrend(points: Vec<AccuracyPoint>) -> impl IntoView {
+    if points.is_empty() {
+        return view! {
+            <p class="m-4">"None of the published transcriptions of this user has been reconciled yet."</p>
+        }
+        .into_any();
+    };
+    view! {
+        <div class="m-4 flex h-48 flex-row items-end gap-1 border-b border-slate-500">
+            {points.iter().map(|point| view! {
+                <div class="w-4 bg-sky-600"
+                    style=format!("height: {:.0}%", 100.0 * point.character_accuracy)
+                    title=format!("{} {}: {:.1} %", point.msname, point.pagename, 100.0 * point.character_accuracy)
+                ></div>
+            }).collect_view()}
+        </div>
+        <table class="m-4 table-auto text-lg">
+            <thead>
+                <tr>
+                    <th class="p-2 text-left">"Page"</th>
+                    <th class="p-2 text-left">"Blocks"</th>
+                    <th class="p-2 text-left">"Characters"</th>
+                </tr>
+            </thead>
+            <tbody>
+                {points.into_iter().map(|point| view! {
+                    <tr class="border-b border-slate-600 odd:bg-slate-800 even:bg-slate-700">
+                        <td class="p-2">{format!("{} {}", point.msname, point.pagename)}</td>
+                        <td class="p-2">{percent(Some(point.block_accuracy))}</td>
+                        <td class="p-2">{percent(Some(point.character_accuracy))}</td>
+                    </tr>
+                }).collect_view()}
+            </tbody>
+        </table>
+    }
+    .into_any()
+}
+
+#[component]
+pub fn AgreementPage() -> impl IntoView {
+    let manuscripts = OnceResource::new(get_manuscripts());
+    let usernames = OnceResource::new(get_usernames());
+    let msname = RwSignal::new(String::default());
+    let username = RwSignal::new(String::default());
+    let page_agreements = Resource::new(
+        move || msname.get(),
+        async |msname| {
+            if msname.is_empty() {
+                Ok(vec![])
+            } else {
+                get_manuscript_agreement(msname).await
+            }
+        },
+    );
+    let accuracy = Resource::new(
+        move || username.get(),
+        async |username| {
+            if username.is_empty() {
+                Ok(None)
+            } else {
+                get_user_accuracy(username).await.map(Some)
+            }
+        },
+    );
+
+    let input_classes = "rounded border border-slate-500 bg-slate-800 p-1";
+    view! {
+        <div class="flex h-full flex-col overflow-y-auto">
+            <div class="flex flex-row justify-center">
+                <h1 class="p-10 text-6xl font-semibold">Transcriber Agreement</h1>
+            </div>
+            <div class="mx-4 flex flex-row items-center gap-4 text-xl">
+                <label for="agreement-manuscript">"Manuscript:"</label>
+                <Suspense fallback=|| view! { "Loading manuscripts..." }>
+                {move || Suspend::new(async move {
+                    manuscripts.await.map(|manuscripts| view! {
+                        <select id="agreement-manuscript" class=input_classes
+                            on:change:target=move |ev| msname.set(ev.target().value())
+                        >
+                            <option value="">"(choose a manuscript)"</option>
+                            {manuscripts.into_iter().map(|ms| view! {
+                                <option value=ms.title.clone()>{ms.title.clone()}</option>
+                            }).collect_view()}
+                        </select>
+                    })
+                })}
+                </Suspense>
+            </div>
+            <Suspense fallback=|| view! { <p class="m-4">"Comparing transcriptions..."</p> }>
+            {move || Suspend::new(async move {
+                match page_agreements.await {
+                    Err(e) => view! { <p class="m-4 text-red-500">{e.to_string()}</p> }.into_any(),
+                    Ok(agreements) if agreements.is_empty() => view! {
+                        <p class="m-4">"No page of this manuscript has two published transcriptions yet."</p>
+                    }.into_any(),
+                    Ok(agreements) => view! {
+                        <table class="m-4 table-auto text-lg">
+                            <thead>
+                                <tr>
+                                    <th class="p-2 text-left">"Page"</th>
+                                    <th class="p-2 text-left">"Transcribers"</th>
+                                    <th class="p-2 text-left">"Block agreement"</th>
+                                    <th class="p-2 text-left">"Character agreement"</th>
+                                    <th class="p-2 text-left">"Disagreements"</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {agreements.into_iter().map(|agreement| view! {
+                                    <PageAgreementRow agreement/>
+                                }).collect_view()}
+                            </tbody>
+                        </table>
+                    }.into_any(),
+                }
+            })}
+            </Suspense>
+
+            <h2 class="mx-4 mt-8 text-3xl font-bold">"Accuracy against reconciled transcriptions"</h2>
+            <div class="m-4 flex flex-row items-center gap-4 text-xl">
+                <label for="agreement-user">"User:"</label>
+                <Suspense fallback=|| view! { "Loading users..." }>
+                {move || Suspend::new(async move {
+                    usernames.await.map(|usernames| view! {
+                        <select id="agreement-user" class=input_classes
+                            on:change:target=move |ev| username.set(ev.target().value())
+                        >
+                            <option value="">"(choose a user)"</option>
+                            {usernames.into_iter().map(|name| view! {
+                                <option value=name.clone()>{name.clone()}</option>
+                            }).collect_view()}
+                        </select>
+                    })
+                })}
+                </Suspense>
+            </div>
+            <Suspense fallback=|| view! { <p class="m-4">"Comparing transcriptions..."</p> }>
+            {move || Suspend::new(async move {
+                match accuracy.await {
+                    Err(e) => view! { <p class="m-4 text-red-500">{e.to_string()}</p> }.into_any(),
+                    Ok(None) => ().into_any(),
+                    Ok(Some(points)) => view! { <AccuracyTrend points/> }.into_any(),
+                }
+            })}
+            </Suspense>
+        </div>
+    }
+}
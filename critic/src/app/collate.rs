@@ -0,0 +1,245 @@
+//! Verse-aligned collation of all manuscripts covering a verse range
+
+use critic_components::editor::versification_scheme::get_versification_schemes;
+use critic_shared::collation::{Token, TokenKind, VariantLocation, VariantTable, VariantVerse};
+use critic_shared::urls::{APPARATUS_EXPORT_API_ENDPOINT, EXPORT_BASE_URL};
+use critic_shared::ManuscriptMeta;
+use leptos::prelude::*;
+
+#[server]
+async fn get_manuscripts() -> Result<Vec<ManuscriptMeta>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    critic_server::db::get_manuscripts_by_name(&config.db, None)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Collate all manuscripts covering the verses `start` to `end` in the scheme `shorthand`
+#[server]
+async fn collate_verses(
+    shorthand: String,
+    start: String,
+    end: String,
+    base: Option<String>,
+) -> Result<VariantTable, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    critic_server::collation::collate_range(&config, &shorthand, &start, &end, base)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// A single token, styled by its kind
+#[component]
+fn TokenView(token: Token) -> impl IntoView {
+    let text = token.to_string();
+    match token.kind {
+        TokenKind::Word => view! { <span>{text}</span> }.into_any(),
+        TokenKind::Uncertain => view! {
+            <span class="italic text-orange-300" title="uncertain reading">{text}</span>
+        }
+        .into_any(),
+        TokenKind::Corrected { original } => view! {
+            <span class="underline decoration-dotted" title=format!("before correction: {original}")>{text}</span>
+        }
+        .into_any(),
+        TokenKind::Lacuna => view! {
+            <span class="text-slate-400" title="lacuna">{text}</span>
+        }
+        .into_any(),
+    }
+}
+
+/// A list of tokens separated by spaces, or om. for an empty list
+#[component]
+fn Tokens(tokens: Vec<Token>) -> impl IntoView {
+    if tokens.is_empty() {
+        return view! { <span class="italic text-slate-400">"om."</span> }.into_any();
+    };
+    view! {
+        <span class="inline-flex flex-row flex-wrap gap-x-1">
+            {tokens.into_iter().map(|token| view! { <TokenView token/> }).collect_view()}
+        </span>
+    }
+    .into_any()
+}
+
+/// One row of the variant table
+#[component]
+fn LocationRow(location: VariantLocation) -> impl IntoView {
+    view! {
+        <tr class="border-b border-slate-600 odd:bg-slate-800 even:bg-slate-700">
+            {location.readings.into_iter().enumerate().map(|(idx, reading)| {
+                let class = if idx == 0 { "p-2 font-bold" } else { "p-2" };
+                view! {
+                    <td class=class>
+                        <Tokens tokens=reading.tokens/>
+                        <span class="ml-2 text-sm text-sky-300">{reading.sigla.join(", ")}</span>
+                    </td>
+                }
+            }).collect_view()}
+        </tr>
+    }
+}
+
+/// The base text of a verse with its variant locations below
+#[component]
+fn VerseVariants(verse: VariantVerse) -> impl IntoView {
+    // mark the tokens of the base that are the lemma of a variant location
+    let in_lemma = verse
+        .base
+        .iter()
+        .enumerate()
+        .map(|(idx, _)| {
+            verse
+                .locations
+                .iter()
+                .any(|l| l.start <= idx && idx < l.end)
+        })
+        .collect::<Vec<_>>();
+    let missing = verse.missing.join(", ");
+    view! {
+        <div class="m-4 rounded-xl border border-slate-600 p-4">
+            <h2 class="text-2xl font-bold">{verse.verse_nr}</h2>
+            <p class="mt-2 flex flex-row flex-wrap gap-x-1 text-xl" dir="auto">
+                {verse.base.into_iter().zip(in_lemma).map(|(token, lemma)| {
+                    let class = if lemma { "rounded bg-sky-900" } else { "" };
+                    view! { <span class=class><TokenView token/></span> }
+                }).collect_view()}
+            </p>
+            <Show when={
+                let missing = missing.clone();
+                move || !missing.is_empty()
+            }>
+                <p class="mt-2 text-sm text-slate-400">"Not extant in: "{missing.clone()}</p>
+            </Show>
+            <table class="mt-4 w-full table-auto text-lg" dir="auto">
+                <tbody>
+                    {verse.locations.into_iter().map(|location| view! {
+                        <LocationRow location/>
+                    }).collect_view()}
+                </tbody>
+            </table>
+        </div>
+    }
+}
+
+#[component]
+pub fn CollatePage() -> impl IntoView {
+    let schemes = OnceResource::new(get_versification_schemes());
+    let manuscripts = OnceResource::new(get_manuscripts());
+    let shorthand = RwSignal::new(String::default());
+    let start = RwSignal::new(String::default());
+    let end = RwSignal::new(String::default());
+    let base = RwSignal::new(String::default());
+
+    let collate_action = Action::new(|input: &(String, String, String, Option<String>)| {
+        let (shorthand, start, end, base) = input.clone();
+        async move { collate_verses(shorthand, start, end, base).await }
+    });
+
+    // the input of the last collation, for the download of its TEI apparatus
+    let last_input = RwSignal::new(None::<(String, String, String, Option<String>)>);
+    let apparatus_url = move || {
+        last_input
+            .get()
+            .map(|(shorthand, start, end, base)| {
+                format!(
+                    "{EXPORT_BASE_URL}{APPARATUS_EXPORT_API_ENDPOINT}?scheme={}&start={}&end={}&base={}",
+                    urlencoding::encode(&shorthand),
+                    urlencoding::encode(&start),
+                    urlencoding::encode(&end),
+                    urlencoding::encode(&base.unwrap_or_default())
+                )
+            })
+    };
+
+    let input_classes = "rounded border border-slate-500 bg-slate-800 p-1";
+    view! {
+        <div class="flex h-full flex-col overflow-y-auto">
+            <div class="flex flex-row justify-center">
+                <h1 class="p-10 text-6xl font-semibold">Collate Manuscripts</h1>
+            </div>
+            <form class="flex flex-row flex-wrap items-center justify-center gap-4 text-xl"
+                on:submit=move |ev| {
+                    ev.prevent_default();
+                    let base = base.get();
+                    let input = (
+                        shorthand.get(),
+                        start.get(),
+                        end.get(),
+                        (!base.is_empty()).then_some(base),
+                    );
+                    last_input.set(Some(input.clone()));
+                    collate_action.dispatch(input);
+                }
+            >
+                <label for="collate-scheme">"Scheme:"</label>
+                <Suspense fallback=|| view! { "Loading schemes..." }>
+                {move || Suspend::new(async move {
+                    schemes.await.map(|schemes| view! {
+                        <select id="collate-scheme" class=input_classes
+                            on:change:target=move |ev| shorthand.set(ev.target().value())
+                        >
+                            <option value="">"(choose a scheme)"</option>
+                            {schemes.into_iter().map(|scheme| view! {
+                                <option value=scheme.shorthand.clone()>{scheme.full_name.clone()}</option>
+                            }).collect_view()}
+                        </select>
+                    })
+                })}
+                </Suspense>
+                <label for="collate-start">"From:"</label>
+                <input id="collate-start" class=input_classes placeholder="Gen 1:1"
+                    on:input:target=move |ev| start.set(ev.target().value())
+                    prop:value=start
+                />
+                <label for="collate-end">"To:"</label>
+                <input id="collate-end" class=input_classes placeholder="Gen 1:5"
+                    on:input:target=move |ev| end.set(ev.target().value())
+                    prop:value=end
+                />
+                <label for="collate-base">"Base:"</label>
+                <Suspense fallback=|| view! { "Loading manuscripts..." }>
+                {move || Suspend::new(async move {
+                    manuscripts.await.map(|manuscripts| view! {
+                        <select id="collate-base" class=input_classes
+                            on:change:target=move |ev| base.set(ev.target().value())
+                        >
+                            <option value="">"(first manuscript)"</option>
+                            {manuscripts.into_iter().map(|ms| view! {
+                                <option value=ms.title.clone()>{ms.title.clone()}</option>
+                            }).collect_view()}
+                        </select>
+                    })
+                })}
+                </Suspense>
+                <button type="submit"
+                    class="rounded-xl bg-slate-600 p-2 pl-4 pr-4 font-bold shadow-md shadow-sky-600 hover:bg-slate-500"
+                    disabled=move || shorthand.read().is_empty() || start.read().is_empty() || end.read().is_empty()
+                >"Collate"</button>
+            </form>
+            <Show when=move || collate_action.pending().get()>
+                <p class="m-4 text-center">"Collating..."</p>
+            </Show>
+            {move || collate_action.value().get().map(|result| match result {
+                Err(e) => view! { <p class="m-4 text-center text-red-500">{e.to_string()}</p> }.into_any(),
+                Ok(table) if table.verses.is_empty() => view! {
+                    <p class="m-4 text-center">"No manuscript covers these verses."</p>
+                }.into_any(),
+                Ok(table) => view! {
+                    <p class="m-4 text-center text-lg">
+                        "Base: "<span class="font-bold">{table.base.clone()}</span>
+                        " - collated with: "{table.witnesses.join(", ")}
+                        <a class="ml-4 underline" href=apparatus_url download>"Download TEI apparatus"</a>
+                        <a class="ml-4 underline" href="/collate/distance">"Manuscript distances"</a>
+                    </p>
+                    {table.verses.into_iter().map(|verse| view! {
+                        <VerseVariants verse/>
+                    }).collect_view()}
+                }.into_any(),
+            })}
+        </div>
+    }
+}
@@ -0,0 +1,191 @@
+//! Distances between manuscripts over a verse range, shown as a heatmap
+
+use critic_components::editor::versification_scheme::get_versification_schemes;
+use critic_shared::collation::{DistanceMatrix, Orthography};
+use critic_shared::urls::{DISTANCE_EXPORT_API_ENDPOINT, EXPORT_BASE_URL};
+use leptos::prelude::*;
+
+/// The spelling options with their value in the export query and their label
+const ORTHOGRAPHIES: [(Orthography, &str, &str); 3] = [
+    (Orthography::Exact, "exact", "exact"),
+    (
+        Orthography::IgnoreMarks,
+        "ignore_marks",
+        "ignore vocalization and punctuation",
+    ),
+    (
+        Orthography::IgnoreMatresLectionis,
+        "ignore_matres_lectionis",
+        "also ignore word-internal ו and י",
+    ),
+];
+
+/// The distance matrix and neighbor-joining tree (Newick) for a verse range
+#[server]
+async fn compute_distances(
+    shorthand: String,
+    start: String,
+    end: String,
+    orthography: Orthography,
+) -> Result<(DistanceMatrix, String), ServerFnError> {
+    use critic_server::collation::{collate_range, distance};
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    let table = collate_range(&config, &shorthand, &start, &end, None)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    let matrix = distance::distance_matrix(&table, orthography);
+    let newick = distance::neighbor_joining(&matrix);
+    Ok((matrix, newick))
+}
+
+/// The distance matrix with every cell colored by distance
+#[component]
+fn Heatmap(matrix: DistanceMatrix) -> impl IntoView {
+    let n = matrix.sigla.len();
+    let cell = move |i: usize, j: usize| match matrix.distance(i, j) {
+        Some(distance) => {
+            // close manuscripts are light, distant ones dark
+            let lightness = 85.0 - 70.0 * distance;
+            let text_class = if lightness > 50.0 {
+                "text-black"
+            } else {
+                "text-white"
+            };
+            view! {
+                <td class=format!("p-2 text-center {text_class}")
+                    style=format!("background-color: hsl(200, 80%, {lightness:.0}%)")
+                    title=format!("{} of {} locations agree", matrix.agreements[i][j], matrix.compared[i][j])
+                >{format!("{distance:.2}")}</td>
+            }
+            .into_any()
+        }
+        None => {
+            view! { <td class="p-2 text-center text-slate-500" title="nothing to compare">"-"</td> }
+                .into_any()
+        }
+    };
+    view! {
+        <table class="m-4 table-auto text-lg">
+            <thead>
+                <tr>
+                    <th></th>
+                    {matrix.sigla.iter().map(|siglum| view! { <th class="p-2">{siglum.clone()}</th> }).collect_view()}
+                </tr>
+            </thead>
+            <tbody>
+                {matrix.sigla.iter().enumerate().map(|(i, siglum)| view! {
+                    <tr>
+                        <th class="p-2 text-right">{siglum.clone()}</th>
+                        {(0..n).map(|j| cell(i, j)).collect_view()}
+                    </tr>
+                }).collect_view()}
+            </tbody>
+        </table>
+    }
+}
+
+#[component]
+pub fn DistancePage() -> impl IntoView {
+    let schemes = OnceResource::new(get_versification_schemes());
+    let shorthand = RwSignal::new(String::default());
+    let start = RwSignal::new(String::default());
+    let end = RwSignal::new(String::default());
+    let orthography = RwSignal::new(0);
+
+    let distance_action = Action::new(|input: &(String, String, String, usize)| {
+        let (shorthand, start, end, orthography) = input.clone();
+        async move { compute_distances(shorthand, start, end, ORTHOGRAPHIES[orthography].0).await }
+    });
+    // the input of the last computation, for the downloads
+    let last_input = RwSignal::new(None::<(String, String, String, usize)>);
+    let download_url = move |format: &'static str| {
+        move || {
+            last_input.get().map(|(shorthand, start, end, orthography)| {
+                format!(
+                    "{EXPORT_BASE_URL}{DISTANCE_EXPORT_API_ENDPOINT}?scheme={}&start={}&end={}&orthography={}&format={format}",
+                    urlencoding::encode(&shorthand),
+                    urlencoding::encode(&start),
+                    urlencoding::encode(&end),
+                    ORTHOGRAPHIES[orthography].1
+                )
+            })
+        }
+    };
+
+    let input_classes = "rounded border border-slate-500 bg-slate-800 p-1";
+    view! {
+        <div class="flex h-full flex-col overflow-y-auto">
+            <div class="flex flex-row justify-center">
+                <h1 class="p-10 text-6xl font-semibold">Manuscript Distances</h1>
+            </div>
+            <form class="flex flex-row flex-wrap items-center justify-center gap-4 text-xl"
+                on:submit=move |ev| {
+                    ev.prevent_default();
+                    let input = (shorthand.get(), start.get(), end.get(), orthography.get());
+                    last_input.set(Some(input.clone()));
+                    distance_action.dispatch(input);
+                }
+            >
+                <label for="distance-scheme">"Scheme:"</label>
+                <Suspense fallback=|| view! { "Loading schemes..." }>
+                {move || Suspend::new(async move {
+                    schemes.await.map(|schemes| view! {
+                        <select id="distance-scheme" class=input_classes
+                            on:change:target=move |ev| shorthand.set(ev.target().value())
+                        >
+                            <option value="">"(choose a scheme)"</option>
+                            {schemes.into_iter().map(|scheme| view! {
+                                <option value=scheme.shorthand.clone()>{scheme.full_name.clone()}</option>
+                            }).collect_view()}
+                        </select>
+                    })
+                })}
+                </Suspense>
+                <label for="distance-start">"From:"</label>
+                <input id="distance-start" class=input_classes placeholder="Gen 1:1"
+                    on:input:target=move |ev| start.set(ev.target().value())
+                    prop:value=start
+                />
+                <label for="distance-end">"To:"</label>
+                <input id="distance-end" class=input_classes placeholder="Gen 1:5"
+                    on:input:target=move |ev| end.set(ev.target().value())
+                    prop:value=end
+                />
+                <label for="distance-orthography">"Spelling:"</label>
+                <select id="distance-orthography" class=input_classes
+                    on:change:target=move |ev| orthography.set(ev.target().value().parse().unwrap_or_default())
+                >
+                    {ORTHOGRAPHIES.iter().enumerate().map(|(idx, (_, _, label))| view! {
+                        <option value=idx.to_string() selected=move || orthography.get() == idx>{*label}</option>
+                    }).collect_view()}
+                </select>
+                <button type="submit"
+                    class="rounded-xl bg-slate-600 p-2 pl-4 pr-4 font-bold shadow-md shadow-sky-600 hover:bg-slate-500"
+                    disabled=move || shorthand.read().is_empty() || start.read().is_empty() || end.read().is_empty()
+                >"Compute"</button>
+            </form>
+            <Show when=move || distance_action.pending().get()>
+                <p class="m-4 text-center">"Computing..."</p>
+            </Show>
+            {move || distance_action.value().get().map(|result| match result {
+                Err(e) => view! { <p class="m-4 text-center text-red-500">{e.to_string()}</p> }.into_any(),
+                Ok((matrix, _)) if matrix.sigla.is_empty() => view! {
+                    <p class="m-4 text-center">"No manuscript covers these verses."</p>
+                }.into_any(),
+                Ok((matrix, newick)) => view! {
+                    <div class="flex flex-row justify-center gap-4 text-lg">
+                        <a class="underline" href=download_url("csv") download>"Download CSV"</a>
+                        <a class="underline" href=download_url("json") download>"Download JSON"</a>
+                        <a class="underline" href=download_url("newick") download>"Download Newick tree"</a>
+                    </div>
+                    <div class="flex flex-row justify-center">
+                        <Heatmap matrix/>
+                    </div>
+                    <h2 class="mx-4 text-2xl font-bold">"Neighbor-joining tree"</h2>
+                    <pre class="m-4 whitespace-pre-wrap break-all rounded bg-slate-800 p-2">{newick}</pre>
+                }.into_any(),
+            })}
+        </div>
+    }
+}
@@ -12,6 +12,10 @@ use critic_components::editor::Editor;
 use transcribe::{editor::TranscribeEditor, todo::TranscribeTodoList};
 
 mod admin;
+mod agreement;
+mod collate;
+mod distance;
+mod reconcile;
 mod transcribe;
 
 pub fn shell(options: LeptosOptions) -> impl IntoView {
@@ -44,6 +48,7 @@ fn NavBar() -> impl IntoView {
       <a href="/logo"><img alt="logo" src="/logo.webp"/></a>
       <a class=navbar_button_classes href="/transcribe">Transcribe</a>
       <a class=navbar_button_classes href="/reconcile">Reconcile</a>
+      <a class=navbar_button_classes href="/collate">Collate</a>
       <a class=navbar_button_classes href="/admin">Administer</a>
       <span
         on:click=move |_| {
@@ -91,6 +96,10 @@ pub fn App() -> impl IntoView {
                     <Route path=StaticSegment("") view=HomePage/>
                     <Route path=path!("transcribe") view=TranscribeTodoList/>
                     <Route path=path!("transcribe/:msname/:pagename") view=TranscribeEditor/>
+                    <Route path=path!("reconcile") view=reconcile::ReconcilePage/>
+                    <Route path=path!("reconcile/agreement") view=agreement::AgreementPage/>
+                    <Route path=path!("collate") view=collate::CollatePage/>
+                    <Route path=path!("collate/distance") view=distance::DistancePage/>
                     <ParentRoute path=path!("admin") view=|| {view!{ <Outlet/> }}>
                         <Route path=path!("") view=admin::AdminLanding/>
                         <admin::AdminRouter/>
@@ -0,0 +1,199 @@
+//! Overview of the pages waiting for a reconciliation
+
+use critic_shared::reconciliation::OutstandingReconciliation;
+use leptos::prelude::*;
+
+/// The default number of published transcriptions a page needs to be reconciled
+const DEFAULT_MIN_WITNESSES: i64 = 2;
+
+#[server]
+async fn get_outstanding_reconciliations(
+    min_witnesses: i64,
+) -> Result<Vec<OutstandingReconciliation>, ServerFnError> {
+    let config: std::sync::Arc<critic_server::config::Config> =
+        use_context().ok_or(ServerFnError::new("Unable to get config from context"))?;
+    critic_server::reconciliation::outstanding_reconciliations(&config, min_witnesses)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Submit the own transcription of the logged-in user as their reconciliation of a page
+#[server]
+async fn submit_reconciliation(msname: String, pagename: String) -> Result<i64, ServerFnError> {
+    use critic_server::auth::AuthSession;
+    use critic_server::forge::get_user_role;
+    use critic_server::gitlab::GitlabUserRole;
+    use leptos_axum::extract;
+
+    let auth_session = match extract::<AuthSession>().await {
+        Ok(x) => x,
+        Err(e) => {
+            let msg = format!("Failed to get AuthSession: {e}");
+            tracing::warn!(msg);
+            return Err(ServerFnError::new(msg));
+        }
+    };
+    let config = use_context::<std::sync::Arc<critic_server::config::Config>>()
+        .ok_or(ServerFnError::new("Unable to get config from context"))?;
+    let Some(user) = auth_session.user else {
+        return Err(ServerFnError::new(
+            "Unauthorized: Need to be logged in to submit a reconciliation.",
+        ));
+    };
+    let user_role = match get_user_role(config.clone(), &user).await {
+        Ok(x) => x,
+        Err(e) => {
+            tracing::warn!("Unable to get the user role for {}: {e}", user.username);
+            return Err(ServerFnError::new(e.to_string()));
+        }
+    };
+    if user_role < GitlabUserRole::Developer {
+        return Err(ServerFnError::new(
+            "Unauthorized: Need to be Developer to submit a reconciliation.",
+        ));
+    };
+    critic_server::reconciliation::submit_reconciliation(&config, &user, &msname, &pagename)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+/// Does the search term appear in the manuscript, page or assignee?
+fn matches_search(entry: &OutstandingReconciliation, search: &str) -> bool {
+    let search = search.to_lowercase();
+    entry.msname.to_lowercase().contains(&search)
+        || entry.pagename.to_lowercase().contains(&search)
+        || entry
+            .assignee
+            .as_ref()
+            .is_some_and(|a| a.to_lowercase().contains(&search))
+}
+
+/// Submit the own transcription as the reconciliation of a page
+#[component]
+fn SubmitReconciliationButton(msname: String, pagename: String) -> impl IntoView {
+    let submit = ServerAction::<SubmitReconciliation>::new();
+    view! {
+        <ActionForm action=submit>
+            <input type="hidden" name="msname" value=msname/>
+            <input type="hidden" name="pagename" value=pagename/>
+            <button type="submit" class="underline">"Submit my transcription"</button>
+        </ActionForm>
+        {move || submit.value().get().map(|res| match res {
+            Ok(merge_request) => view! {
+                <p class="text-green-400">{format!("Submitted as !{merge_request}")}</p>
+            }.into_any(),
+            Err(e) => view! { <p class="text-red-500">{e.to_string()}</p> }.into_any(),
+        })}
+    }
+}
+
+/// A row of the overview
+#[component]
+fn OutstandingRow(entry: OutstandingReconciliation) -> impl IntoView {
+    let (disagreement, class) = match entry.disagreement {
+        None if entry.agreement_error.is_some() => ("error".to_string(), "p-2 text-red-500"),
+        Some(d) => (
+            format!("{:.1} %", 100.0 * d),
+            if d > 0.1 {
+                "p-2 text-red-400"
+            } else if d > 0.03 {
+                "p-2 text-orange-300"
+            } else {
+                "p-2 text-green-400"
+            },
+        ),
+        None => ("-".to_string(), "p-2"),
+    };
+    let state = match (&entry.assignee, entry.merge_request) {
+        (Some(_), Some(iid)) => format!("in review (!{iid})"),
+        (Some(_), None) => "in progress".to_string(),
+        _ => "waiting".to_string(),
+    };
+    view! {
+        <tr class="border-b border-slate-600 odd:bg-slate-800 even:bg-slate-700">
+            <td class="p-2">{entry.msname.clone()}</td>
+            <td class="p-2">
+                <a class="underline" href=format!(
+                    "/admin/manuscripts/{}/{}",
+                    urlencoding::encode(&entry.msname),
+                    urlencoding::encode(&entry.pagename)
+                )>
+                    {entry.pagename.clone()}
+                </a>
+            </td>
+            <td class="p-2">{entry.witnesses}</td>
+            <td class=class title=entry.agreement_error.clone()>{disagreement}</td>
+            <td class="p-2">{entry.assignee.unwrap_or("-".to_string())}</td>
+            <td class="p-2">{state}</td>
+            <td class="p-2">
+                <SubmitReconciliationButton msname=entry.msname.clone() pagename=entry.pagename.clone()/>
+            </td>
+        </tr>
+    }
+}
+
+#[component]
+pub fn ReconcilePage() -> impl IntoView {
+    let min_witnesses = RwSignal::new(DEFAULT_MIN_WITNESSES);
+    let search = RwSignal::new(String::default());
+    let outstanding = Resource::new(move || min_witnesses.get(), get_outstanding_reconciliations);
+
+    let input_classes = "rounded border border-slate-500 bg-slate-800 p-1";
+    view! {
+        <div class="flex h-full flex-col overflow-y-auto">
+            <div class="flex flex-row justify-center">
+                <h1 class="p-10 text-6xl font-semibold">Outstanding Reconciliations</h1>
+            </div>
+            <div class="mx-4 flex flex-row items-center gap-4 text-xl">
+                <label for="reconcile-search">"Search:"</label>
+                <input id="reconcile-search" class=input_classes placeholder="manuscript, page or user"
+                    on:input:target=move |ev| search.set(ev.target().value())
+                    prop:value=search
+                />
+                <label for="reconcile-min-witnesses">"Published transcriptions at least:"</label>
+                <input id="reconcile-min-witnesses" class=input_classes type="number" min="1"
+                    on:change:target=move |ev| {
+                        if let Ok(n) = ev.target().value().parse() {
+                            min_witnesses.set(n);
+                        };
+                    }
+                    prop:value=move || min_witnesses.get().to_string()
+                />
+                <a class="ml-auto underline" href="/reconcile/agreement">"Transcriber agreement"</a>
+            </div>
+            <Suspense fallback=|| view! { <p class="m-4">"Loading outstanding reconciliations..."</p> }>
+            {move || Suspend::new(async move {
+                match outstanding.await {
+                    Err(e) => view! { <p class="m-4 text-red-500">{e.to_string()}</p> }.into_any(),
+                    Ok(entries) if entries.is_empty() => view! {
+                        <p class="m-4">"There is nothing to reconcile right now."</p>
+                    }.into_any(),
+                    Ok(entries) => view! {
+                        <table class="m-4 table-auto text-lg">
+                            <thead>
+                                <tr>
+                                    <th class="p-2 text-left">"Manuscript"</th>
+                                    <th class="p-2 text-left">"Page"</th>
+                                    <th class="p-2 text-left">"Witnesses"</th>
+                                    <th class="p-2 text-left">"Disagreement"</th>
+                                    <th class="p-2 text-left">"Assignee"</th>
+                                    <th class="p-2 text-left">"State"</th>
+                                    <th class="p-2 text-left">"Reconcile"</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {move || entries
+                                    .iter()
+                                    .filter(|entry| matches_search(entry, &search.read()))
+                                    .cloned()
+                                    .map(|entry| view! { <OutstandingRow entry/> })
+                                    .collect_view()}
+                            </tbody>
+                        </table>
+                    }.into_any(),
+                }
+            })}
+            </Suspense>
+        </div>
+    }
+}
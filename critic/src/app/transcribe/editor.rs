@@ -2,9 +2,30 @@
 //!
 //! this shows the editor, the publish button, rendering to html and xml and so on
 
+use critic_components::editor::Editor;
 use leptos::prelude::*;
+use leptos_router::hooks::use_params;
+use leptos_router::params::Params;
+
+#[derive(Params, Clone, PartialEq)]
+struct TranscribeParams {
+    msname: Option<String>,
+    pagename: Option<String>,
+}
 
 /// The main component for the transcription editor page
 #[component]
 pub fn TranscribeEditor() -> impl IntoView {
+    let params = use_params::<TranscribeParams>();
+    move || {
+        let (msname, pagename) = params
+            .read()
+            .as_ref()
+            .ok()
+            .map(|p| (p.msname.clone(), p.pagename.clone()))
+            .unwrap_or_default();
+        view! {
+            <Editor default_language="hbo-Hebr".to_string() msname=msname pagename=pagename/>
+        }
+    }
 }
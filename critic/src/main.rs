@@ -26,14 +26,17 @@ pub async fn run_web_server(
     use axum::{Extension, Router};
     use axum_login::{
         login_required,
-        tower_sessions::{Expiry, MemoryStore, SessionManagerLayer},
+        tower_sessions::{Expiry, SessionManagerLayer},
         AuthManagerLayerBuilder,
     };
     use critic::app::*;
     use critic_server::{
-        auth::GitlabOauthBackend, signal_handler::InShutdown, upload::upload_router,
+        auth::LoginBackend, export::export_router, gitlab::webhook::webhook_router,
+        signal_handler::InShutdown, upload::upload_router,
+    };
+    use critic_shared::urls::{
+        EXPORT_BASE_URL, STATIC_BASE_URL, UPLOAD_BASE_URL, WEBHOOK_BASE_URL,
     };
-    use critic_shared::urls::{STATIC_BASE_URL, UPLOAD_BASE_URL};
     use leptos::prelude::*;
     use leptos_axum::{generate_route_list, LeptosRoutes};
     use time::Duration;
@@ -63,12 +66,13 @@ pub async fn run_web_server(
         .with_state(config.leptos_options.clone());
 
     // create the auth layer on top of our application core
-    let session_store = MemoryStore::default();
+    let session_store = config.session_store.clone();
     let session_layer = SessionManagerLayer::new(session_store)
-        .with_secure(false)
+        // cookies only travel over https when critic serves https itself
+        .with_secure(config.runtime().tls.is_some())
         .with_same_site(axum_login::tower_sessions::cookie::SameSite::Lax)
         .with_expiry(Expiry::OnInactivity(Duration::days(1)));
-    let backend = GitlabOauthBackend::new(config.clone());
+    let backend = LoginBackend::new(config.clone());
     let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
 
     let static_router = match critic_server::static_files::image_dir_router(&config.data_directory)
@@ -83,20 +87,51 @@ pub async fn run_web_server(
     };
     let app = app_core
         .nest(UPLOAD_BASE_URL, upload_router())
-        .route_layer(login_required!(GitlabOauthBackend, login_url = "/login"))
+        .nest(EXPORT_BASE_URL, export_router())
+        .route_layer(login_required!(LoginBackend, login_url = "/login"))
         .merge(critic_server::auth::backend::auth_router())
         .layer(auth_layer)
         .nest(STATIC_BASE_URL, static_router)
+        .nest(WEBHOOK_BASE_URL, webhook_router())
         .layer(Extension(config.clone()));
 
     let shutdown_handle = axum_server::Handle::new();
     let shutdown_future = shutdown_signal(shutdown_handle.clone(), watcher.clone());
 
-    // serve the main app on HTTP
-    let web_server_future = axum_server::bind(config.leptos_options.site_addr)
-        .handle(shutdown_handle.clone())
-        .serve(app.clone().into_make_service());
-    tracing::info!("listening on http://{}", &config.leptos_options.site_addr);
+    // serve the main app on HTTPS if TLS is configured, on HTTP otherwise
+    let web_server_future: std::pin::Pin<
+        Box<dyn std::future::Future<Output = std::io::Result<()>> + Send>,
+    > = match config.runtime().tls {
+        Some(tls) => {
+            let rustls_config = match tls.load().await {
+                Ok(x) => x,
+                Err(e) => {
+                    tracing::error!("Cannot load the TLS certificate: {e}. SHUTTING DOWN NOW.");
+                    shutdown_tx.send_replace(InShutdown::Yes);
+                    return;
+                }
+            };
+            tokio::spawn(critic_server::tls::run_certificate_reload(
+                config.clone(),
+                rustls_config.clone(),
+                watcher.clone(),
+            ));
+            tracing::info!("listening on https://{}", &config.leptos_options.site_addr);
+            Box::pin(
+                axum_server::bind_rustls(config.leptos_options.site_addr, rustls_config)
+                    .handle(shutdown_handle.clone())
+                    .serve(app.clone().into_make_service()),
+            )
+        }
+        None => {
+            tracing::info!("listening on http://{}", &config.leptos_options.site_addr);
+            Box::pin(
+                axum_server::bind(config.leptos_options.site_addr)
+                    .handle(shutdown_handle.clone())
+                    .serve(app.clone().into_make_service()),
+            )
+        }
+    };
     // wait until either some other component shuts down or the webserver shuts down
     tokio::select! {
         r = web_server_future => {
@@ -115,9 +150,22 @@ pub async fn run_web_server(
 async fn main() {
     use std::sync::Arc;
 
-    use critic_server::{minification::run_minification, signal_handler::InShutdown};
+    use critic_server::{
+        consistency::run_consistency_checks, minification::run_minification,
+        session::run_session_cleanup, signal_handler::InShutdown,
+    };
     use tracing_subscriber::{fmt::format::FmtSpan, prelude::*, EnvFilter};
 
+    // maintenance commands run instead of the server
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let command = match critic_server::cli::Command::from_args(&args) {
+        Ok(x) => x,
+        Err(usage) => {
+            eprintln!("{usage}");
+            std::process::exit(2);
+        }
+    };
+
     let config = match critic_server::config::Config::try_create().await {
         Ok(x) => x,
         Err(e) => {
@@ -133,19 +181,44 @@ async fn main() {
     critic_server::db::migrate(&config_arc.db).await;
 
     let my_crate_filter = EnvFilter::new("critic");
+    // the log level can be changed by reloading the config
+    let (level_filter, level_handle) =
+        tracing_subscriber::reload::Layer::new(config_arc.runtime().log_level);
     let subscriber = tracing_subscriber::registry().with(my_crate_filter).with(
         tracing_subscriber::fmt::layer()
             .compact()
             .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
             .with_line_number(true)
-            .with_filter(config_arc.log_level),
+            .with_filter(level_filter),
     );
     tracing::subscriber::set_global_default(subscriber).expect("static tracing config");
+    config_arc.set_log_level_setter(critic_server::config::LogLevelSetter::new(move |level| {
+        level_handle
+            .modify(|filter| *filter = level)
+            .map_err(|e| e.to_string())
+    }));
     tracing::debug!("Tracing enabled.");
+    if let Some(dev_login) = &config_arc.dev_login {
+        tracing::warn!(
+            "The development login is enabled: anyone can log in as one of the {} configured users.",
+            dev_login.users.len()
+        );
+    };
+
+    if let Some(command) = command {
+        match command.run(&config_arc).await {
+            Ok(report) => println!("{report}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        };
+        return;
+    };
 
     // setup global rayon threadpool
     rayon::ThreadPoolBuilder::new()
-        .num_threads(config_arc.worker_threads.into())
+        .num_threads(config_arc.runtime().worker_threads.into())
         .build_global()
         .expect("First threadpool initialization.");
 
@@ -153,6 +226,7 @@ async fn main() {
     let (tx, rx) = tokio::sync::watch::channel(InShutdown::No);
     // start the Signal handler
     let signal_handle = tokio::spawn(critic_server::signal_handler::signal_handler(
+        config_arc.clone(),
         rx,
         tx.clone(),
     ));
@@ -161,11 +235,21 @@ async fn main() {
         tx.subscribe(),
         tx.clone(),
     ));
-    let minification_service = tokio::task::spawn(run_minification(config_arc, tx.subscribe()));
+    let minification_service =
+        tokio::task::spawn(run_minification(config_arc.clone(), tx.subscribe()));
+    let consistency_service =
+        tokio::task::spawn(run_consistency_checks(config_arc.clone(), tx.subscribe()));
+    let session_cleanup_service =
+        tokio::task::spawn(run_session_cleanup(config_arc, tx.subscribe()));
 
     // Join the different services
-    let (signal_res, web_res, minification_res) =
-        tokio::join!(signal_handle, web_server, minification_service);
+    let (signal_res, web_res, minification_res, consistency_res, session_cleanup_res) = tokio::join!(
+        signal_handle,
+        web_server,
+        minification_service,
+        consistency_service,
+        session_cleanup_service
+    );
     match signal_res {
         Ok(Ok(())) => {}
         Ok(Err(e)) => {
@@ -181,6 +265,12 @@ async fn main() {
     if let Err(e) = minification_res {
         tracing::error!("Error joining the minificaiton service: {e}");
     };
+    if let Err(e) = consistency_res {
+        tracing::error!("Error joining the consistency checker: {e}");
+    };
+    if let Err(e) = session_cleanup_res {
+        tracing::error!("Error joining the session cleanup: {e}");
+    };
 }
 
 #[cfg(not(feature = "ssr"))]